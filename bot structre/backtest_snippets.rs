@@ -0,0 +1,359 @@
+use crate::data;
+use crate::log_functions;
+use crate::markets::bitskins::{self, ItemStatResult};
+use crate::price_functions::{self, from_thousandths};
+use crate::structs::{ItemSaleStats, Market, Price, TradeHoldPremiums};
+use chrono::{Local, NaiveDate, TimeDelta};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use strum::IntoEnumIterator;
+
+// The built prices are stamped with the time of the run, this only keeps most_profitable from skipping them
+const BACKTEST_PRICE_MAX_AGE_SECS: u64 = 86400;
+
+// The struct that has the parameters of a strategy the backtest runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BacktestStrategy {
+    pub initial_capital: f32,
+    // The minimum profit percentage after the commisions, like minimum_profit_margin of the config
+    pub min_margin: f32,
+    pub trade_hold_premiums: TradeHoldPremiums,
+    pub max_count: MaxCountRule,
+}
+
+impl Default for BacktestStrategy {
+    fn default() -> Self {
+        BacktestStrategy {
+            initial_capital: 1000.0,
+            min_margin: 8.0,
+            trade_hold_premiums: TradeHoldPremiums::default(),
+            max_count: MaxCountRule::Velocity {
+                weekly_volume_ratio: 0.5,
+                default_cap: 2,
+            },
+        }
+    }
+}
+
+// The enum which differentiates the rules of how many of an item the strategy holds at once
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum MaxCountRule {
+    Unlimited,
+    Fixed { max_count: i16 },
+    // The rule of price_functions::velocity_max_count with the given parameters
+    Velocity { weekly_volume_ratio: f32, default_cap: i16 },
+}
+
+impl MaxCountRule {
+    // Returns how many of the item can be held, 0 means unlimited
+    fn max_count(&self, sale_stats: &ItemSaleStats) -> i16 {
+        match self {
+            MaxCountRule::Unlimited => 0,
+            MaxCountRule::Fixed { max_count } => *max_count,
+            MaxCountRule::Velocity { weekly_volume_ratio, default_cap } => {
+                price_functions::velocity_max_count_with(Some(sale_stats), *weekly_volume_ratio, *default_cap)
+            }
+        }
+    }
+}
+
+// The struct that has a recorded day of a series, the stats of the day and optionally the cheapest trade held listings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedDay {
+    #[serde(flatten)]
+    pub stats: ItemStatResult,
+    // The cheapest listings with a (7, 4, 2) day trade hold in thousandths, like Price.price_buy_trade
+    #[serde(default)]
+    pub trade_hold_price_min: Option<(i64, i64, i64)>,
+}
+
+// The struct that has the recorded days of an item on a market, one file of the series directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedSeries {
+    pub name: String,
+    pub market: Market,
+    pub days: Vec<RecordedDay>,
+}
+
+// The struct that has a hypothetical trade of the backtest, sold_date is None while it is held
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BacktestTrade {
+    pub name: String,
+    pub buy_market: Market,
+    pub sell_market: Market,
+    pub trade_hold_days: i32,
+    pub bought_date: NaiveDate,
+    pub buy_price_w_comm: f32,
+    pub sold_date: Option<NaiveDate>,
+    pub sold_price_w_comm: Option<f32>,
+}
+
+// The struct that has the result of a backtest
+// The capital of a day is the cash plus the held items valued at their last sell price after the commision
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BacktestReport {
+    pub trades: Vec<BacktestTrade>,
+    pub capital_curve: Vec<(NaiveDate, f32)>,
+    pub final_capital: f32,
+    pub realized_profit: f32,
+    // The largest drop from a peak of the capital curve, in percent of the peak
+    pub max_drawdown_perc: f32,
+}
+
+/// Loads every .json series file of the directory
+pub fn load_series_dir(dir: &str) -> Result<Vec<RecordedSeries>, String> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| format!("backtest.rs | load_series_dir(dir={}) | Error occured when reading the directory. E: {:?}", dir, e))?;
+
+    let mut res = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|e| format!("backtest.rs | load_series_dir(dir={}) | Error occured when reading a directory entry. E: {:?}", dir, e))?
+            .path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+
+        let file_str = std::fs::read_to_string(&path)
+            .map_err(|e| format!("backtest.rs | load_series_dir(dir={}) | Error occured when reading {:?}. E: {:?}", dir, path, e))?;
+        let series: RecordedSeries = serde_json::from_str(&file_str)
+            .map_err(|e| format!("backtest.rs | load_series_dir(dir={}) | Error occured when parsing {:?}. E: {:?}", dir, path, e))?;
+        res.push(series);
+    }
+
+    Ok(res)
+}
+
+/// Loads the strategy from a toml file, the missing fields get their defaults
+pub fn load_strategy(path: &str) -> Result<BacktestStrategy, String> {
+    let file_str = std::fs::read_to_string(path)
+        .map_err(|e| format!("backtest.rs | load_strategy(path={}) | Error occured when reading the file. E: {:?}", path, e))?;
+
+    toml::from_str(&file_str)
+        .map_err(|e| format!("backtest.rs | load_strategy(path={}) | Error occured when parsing the file. E: {:?}", path, e))
+}
+
+/// Replays the recorded series day by day with the strategy
+///
+/// - The sell stats of a day are calculated with bitskins::sale_stats_from_days from the days up to it
+/// - The buy decision is price_functions::most_profitable_with and the price limit is max_buy_price,
+///   so the backtest runs the same code as the bot
+/// - At most one of an item is bought a day, a held item is sold on the first day after its trade hold
+///   that the sell market had sales, at the lowest price of that day
+/// - The items still held at the end stay open in the report
+pub fn run_backtest(series: &[RecordedSeries], strategy: &BacktestStrategy) -> Result<BacktestReport, String> {
+    let mut res = BacktestReport::default();
+
+    // (item name, market) -> (day -> recorded day), every series is looked up by the day
+    let mut by_item: HashMap<String, HashMap<Market, HashMap<NaiveDate, &RecordedDay>>> = HashMap::new();
+    let mut all_dates: BTreeSet<NaiveDate> = BTreeSet::new();
+    for recorded in series {
+        let days = by_item.entry(recorded.name.clone()).or_default().entry(recorded.market.clone()).or_default();
+        for day in recorded.days.iter() {
+            let date = NaiveDate::parse_from_str(&day.stats.date, "%Y-%m-%d").map_err(|e| format!(
+                "backtest.rs | run_backtest() | Error occured when parsing the date {:?} of {} on {:?}. E: {:?}",
+                day.stats.date, recorded.name, recorded.market, e
+            ))?;
+            days.insert(date, day);
+            all_dates.insert(date);
+        }
+    }
+
+    let mut cash = strategy.initial_capital;
+    let mut last_sell_value: HashMap<String, f32> = HashMap::new();
+    let mut peak = strategy.initial_capital;
+    let now_unix = Local::now().timestamp();
+
+    for today in all_dates.iter().copied() {
+        let mut names: Vec<&String> = by_item.keys().collect();
+        names.sort();
+
+        for name in names {
+            let markets = &by_item[name];
+            let sell_market = Market::MarketCSGO;
+            let sell_days = match markets.get(&sell_market) {
+                Some(sell_days) => sell_days,
+                None => continue,
+            };
+
+            // Sell the held items whose trade hold is over on a day the sell market had sales
+            if let Some(day) = sell_days.get(&today).filter(|day| day.stats.counter > 0) {
+                let sell_price_w_comm = price_after_sell_comm(sell_market.clone(), from_thousandths(day.stats.price_min))?;
+                last_sell_value.insert(name.clone(), sell_price_w_comm);
+
+                for trade in res.trades.iter_mut() {
+                    if trade.name != *name || trade.sold_date.is_some() {
+                        continue;
+                    }
+                    if today < trade.bought_date + TimeDelta::days(trade.trade_hold_days as i64) {
+                        continue;
+                    }
+                    trade.sold_date = Some(today);
+                    trade.sold_price_w_comm = Some(sell_price_w_comm);
+                    cash += sell_price_w_comm;
+                    res.realized_profit += sell_price_w_comm - trade.buy_price_w_comm;
+                }
+            }
+
+            // The sell stats as get_item_sale_stats would have returned them today
+            let seen_days: Vec<ItemStatResult> = sell_days
+                .iter()
+                .filter(|(date, _)| **date <= today)
+                .map(|(_, day)| day.stats.clone())
+                .collect();
+            let sale_stats = bitskins::sale_stats_from_days(seen_days, today)?;
+            if sale_stats.weekly_vwap_w_comm <= 0.0 {
+                continue;
+            }
+
+            let prices = build_prices(markets, today, &sale_stats, now_unix)?;
+            let (buy_market, sell_market, profit_perc, trade_hold_days) =
+                price_functions::most_profitable_with(&prices, name, BACKTEST_PRICE_MAX_AGE_SECS, &strategy.trade_hold_premiums);
+            if profit_perc < strategy.min_margin {
+                continue;
+            }
+
+            let buy_price = match prices.iter().find(|price| price.market == buy_market) {
+                Some(buy_price) => buy_price,
+                None => continue,
+            };
+            let (listed_price, price_w_comm) = match trade_hold_days {
+                2 => (buy_price.price_buy_trade.2, buy_price.price_buy_trade_w_comm.2),
+                4 => (buy_price.price_buy_trade.1, buy_price.price_buy_trade_w_comm.1),
+                7 => (buy_price.price_buy_trade.0, buy_price.price_buy_trade_w_comm.0),
+                _ => (buy_price.price_buy, buy_price.price_buy_w_comm),
+            };
+            if listed_price > price_functions::max_buy_price(sale_stats.weekly_vwap_w_comm, buy_market.clone(), strategy.min_margin) {
+                continue;
+            }
+
+            let held = res.trades.iter().filter(|trade| trade.name == *name && trade.sold_date.is_none()).count() as i16;
+            let max_count = strategy.max_count.max_count(&sale_stats);
+            if max_count > 0 && held >= max_count {
+                continue;
+            }
+            if cash < price_w_comm {
+                continue;
+            }
+
+            cash -= price_w_comm;
+            res.trades.push(BacktestTrade {
+                name: name.clone(),
+                buy_market,
+                sell_market,
+                trade_hold_days,
+                bought_date: today,
+                buy_price_w_comm: price_w_comm,
+                sold_date: None,
+                sold_price_w_comm: None,
+            });
+        }
+
+        // Value the held items at their last sell price, at their cost if they were never seen on the sell market
+        let held_value: f32 = res
+            .trades
+            .iter()
+            .filter(|trade| trade.sold_date.is_none())
+            .map(|trade| *last_sell_value.get(&trade.name).unwrap_or(&trade.buy_price_w_comm))
+            .sum();
+        let capital = cash + held_value;
+        res.capital_curve.push((today, capital));
+
+        peak = peak.max(capital);
+        if peak > 0.0 {
+            res.max_drawdown_perc = res.max_drawdown_perc.max((peak - capital) / peak * 100.0);
+        }
+    }
+
+    res.final_capital = res.capital_curve.last().map(|(_, capital)| *capital).unwrap_or(strategy.initial_capital);
+    Ok(res)
+}
+
+/// The command line entry of the backtest: `backtest <series directory> <strategy.toml> [report.json]`
+///
+/// - Prints the summary and writes the full report as json if a report path is given
+pub fn run_cli(args: &[String]) -> Result<(), String> {
+    let (series_dir, strategy_path) = match (args.first(), args.get(1)) {
+        (Some(series_dir), Some(strategy_path)) => (series_dir, strategy_path),
+        _ => return Err("Usage: backtest <series directory> <strategy.toml> [report.json]".to_string()),
+    };
+
+    let series = load_series_dir(series_dir)?;
+    let strategy = load_strategy(strategy_path)?;
+    let report = run_backtest(&series, &strategy)?;
+
+    let sold = report.trades.iter().filter(|trade| trade.sold_date.is_some()).count();
+    println!(
+        "Trades: {} ({} sold, {} held)\nRealized profit: {:.2}\nFinal capital: {:.2} (started with {:.2})\nMax drawdown: {:.2}%",
+        report.trades.len(),
+        sold,
+        report.trades.len() - sold,
+        report.realized_profit,
+        report.final_capital,
+        strategy.initial_capital,
+        report.max_drawdown_perc
+    );
+
+    if let Some(report_path) = args.get(2) {
+        let report_str = serde_json::to_string_pretty(&report)
+            .map_err(|e| format!("backtest.rs | run_cli() | Error occured when serializing the report. E: {:?}", e))?;
+        std::fs::write(report_path, report_str)
+            .map_err(|e| format!("backtest.rs | run_cli(report_path={}) | Error occured when writing the report. E: {:?}", report_path, e))?;
+        log_functions::log_structured("Info", &format!("backtest.rs | run_cli() | Wrote the backtest report to {}", report_path));
+    }
+
+    Ok(())
+}
+
+// Builds the prices of the markets that have a recorded day today, the sell market gets the sale stats
+fn build_prices(
+    markets: &HashMap<Market, HashMap<NaiveDate, &RecordedDay>>,
+    today: NaiveDate,
+    sale_stats: &ItemSaleStats,
+    now_unix: i64,
+) -> Result<Vec<Price>, String> {
+    let mut prices = Vec::new();
+    for market in Market::iter() {
+        let day = match markets.get(&market).and_then(|days| days.get(&today)) {
+            Some(day) => day,
+            None => continue,
+        };
+
+        let comms = data::get_market_commisions(market.clone(), "")
+            .map_err(|e| format!("backtest.rs | build_prices(market={:?}) | Error occured when getting the commisions. E: {:?}", market, e))?;
+        let with_buy_comm = |price: f32| price * (1.0 + comms.0 as f32 / 100.0);
+
+        let price_buy = from_thousandths(day.stats.price_min);
+        // Without recorded trade held listings they cost the same as the tradable one, the premium never picks them
+        let price_buy_trade = match day.trade_hold_price_min {
+            Some((seven, four, two)) => (from_thousandths(seven), from_thousandths(four), from_thousandths(two)),
+            None => (price_buy, price_buy, price_buy),
+        };
+
+        prices.push(Price {
+            market: market.clone(),
+            commision: comms.1 + comms.2,
+            price_buy_trade,
+            price_buy_trade_w_comm: (with_buy_comm(price_buy_trade.0), with_buy_comm(price_buy_trade.1), with_buy_comm(price_buy_trade.2)),
+            price_buy,
+            price_buy_w_comm: with_buy_comm(price_buy),
+            price_sell: price_buy,
+            price_sell_w_comm: price_after_sell_comm(market.clone(), price_buy)?,
+            sale_stats: (market == Market::MarketCSGO).then(|| sale_stats.clone()),
+            fetched_at_unix: now_unix,
+            volume_24h: Some(day.stats.counter as u32),
+            currency: Default::default(),
+        });
+    }
+
+    Ok(prices)
+}
+
+// The amount received for a sale on the market after its commisions
+fn price_after_sell_comm(market: Market, price: f32) -> Result<f32, String> {
+    let comms = data::get_market_commisions(market.clone(), "")
+        .map_err(|e| format!("backtest.rs | price_after_sell_comm(market={:?}) | Error occured when getting the commisions. E: {:?}", market, e))?;
+    Ok(price * (1.0 - (comms.1 + comms.2) as f32 / 100.0))
+}
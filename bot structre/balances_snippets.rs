@@ -0,0 +1,175 @@
+use crate::bankroll;
+use crate::config;
+use crate::control::SharedControl;
+use crate::currency::{self, Currency};
+use crate::log_functions;
+use crate::markets::{bitskins::BitSkinsBalance, dmarket::DMarketBalance};
+use crate::notifications;
+use crate::structs::Market;
+use chrono::Local;
+use std::collections::HashMap;
+use strum::IntoEnumIterator;
+use tokio::time::{sleep, Duration};
+
+// The struct that has the balance of an account on a market
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Balance {
+    // What can be spent right now
+    pub available: f32,
+    // The funds locked in pending sales and withdrawals
+    pub pending: f32,
+    pub currency: Currency,
+}
+
+impl Balance {
+    pub fn total(&self) -> f32 {
+        self.available + self.pending
+    }
+}
+
+// The enum that contains the reasons a balance can't be read
+#[derive(Debug, Clone, PartialEq)]
+pub enum BalanceError {
+    // The market has no balance endpoint or it isn't implemented yet
+    Unsupported(Market),
+    Failed(String),
+}
+
+impl std::fmt::Display for BalanceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BalanceError::Unsupported(market) => write!(f, "The balance of {:?} is not supported.", market),
+            BalanceError::Failed(err_str) => write!(f, "{}", err_str),
+        }
+    }
+}
+
+impl From<String> for BalanceError {
+    fn from(err_str: String) -> Self {
+        BalanceError::Failed(err_str)
+    }
+}
+
+// Declare the balance of a market, implemented by the market handlers that can read it
+#[allow(async_fn_in_trait)]
+pub trait BalanceProvider {
+    fn market(&self) -> Market;
+    async fn get_balance(&self) -> Result<Balance, BalanceError>;
+}
+
+// The provider of the markets that don't have a balance yet
+pub struct UnsupportedBalance(pub Market);
+
+impl BalanceProvider for UnsupportedBalance {
+    fn market(&self) -> Market {
+        self.0.clone()
+    }
+
+    async fn get_balance(&self) -> Result<Balance, BalanceError> {
+        Err(BalanceError::Unsupported(self.0.clone()))
+    }
+}
+
+// The struct that has the balances of every market that could be read
+#[derive(Debug, Clone, Default)]
+pub struct MultiMarketBalance {
+    pub balances: HashMap<Market, Balance>,
+    pub fetched_at_unix: i64,
+}
+
+impl MultiMarketBalance {
+    // The available and pending funds of every market in dollars
+    // A balance whose currency has no exchange rate is logged and left out
+    pub async fn to_usd(&self) -> HashMap<Market, Balance> {
+        let mut res = HashMap::new();
+        for (market, balance) in self.balances.iter() {
+            let available = currency::convert(balance.available, balance.currency, Currency::USD).await;
+            let pending = currency::convert(balance.pending, balance.currency, Currency::USD).await;
+            match (available, pending) {
+                (Ok(available), Ok(pending)) => {
+                    res.insert(market.clone(), Balance { available, pending, currency: Currency::USD });
+                }
+                (Err(err_str), _) | (_, Err(err_str)) => log_functions::log_err(&err_str),
+            }
+        }
+        res
+    }
+}
+
+/// Reads the balance of every market
+///
+/// - The markets without a balance endpoint are left out
+/// - A market whose balance can't be read is logged and left out
+pub async fn get_all_balances() -> HashMap<Market, Balance> {
+    let mut res = HashMap::new();
+
+    for market in Market::iter() {
+        let balance = match market {
+            Market::BitSkins => BitSkinsBalance.get_balance().await,
+            Market::DMarket => DMarketBalance.get_balance().await,
+            market => UnsupportedBalance(market).get_balance().await,
+        };
+
+        match balance {
+            Ok(balance) => {
+                res.insert(market, balance);
+            }
+            Err(BalanceError::Unsupported(_)) => {}
+            Err(e) => log_functions::log_err(&format!("balances.rs | get_all_balances() | Cannot read the balance of {:?}. E: {}", market, e)),
+        }
+    }
+
+    res
+}
+
+/// Reads the balances of every market with the time they were read
+pub async fn get_multi_market_balance() -> MultiMarketBalance {
+    MultiMarketBalance {
+        balances: get_all_balances().await,
+        fetched_at_unix: Local::now().timestamp(),
+    }
+}
+
+/// Formats the capital distribution, the share of the funds every market holds
+pub fn format_capital_distribution(balances_usd: &HashMap<Market, Balance>) -> String {
+    let total: f32 = balances_usd.values().map(|balance| balance.total()).sum();
+    let mut lines = vec![format!("💵 Capital: ${:.2}", total)];
+
+    let mut markets: Vec<(&Market, &Balance)> = balances_usd.iter().collect();
+    markets.sort_by(|a, b| b.1.total().total_cmp(&a.1.total()));
+    for (market, balance) in markets {
+        let share = if total > 0.0 { balance.total() / total * 100.0 } else { 0.0 };
+        lines.push(format!(
+            "{:?}: ${:.2} available, ${:.2} pending ({:.1}%)",
+            market, balance.available, balance.pending, share
+        ));
+    }
+
+    lines.join("\n")
+}
+
+/// Reads the balances every capital_report_interval_secs until the bot stops
+///
+/// - The available funds are given to the budget manager, a buy can't reserve more than the market holds
+/// - The capital distribution is sent as a notification
+/// - A capital_report_interval_secs of 0 disables the loop
+pub async fn run_capital_report_loop(control: SharedControl) {
+    let interval_secs = config::get_bot_config().capital_report_interval_secs;
+    if interval_secs == 0 {
+        return;
+    }
+    let token = control.token();
+
+    while !control.should_stop() {
+        let balances_usd = get_multi_market_balance().await.to_usd().await;
+        if !balances_usd.is_empty() {
+            bankroll::set_balances(balances_usd.iter().map(|(market, balance)| (market.clone(), balance.available)).collect());
+            notifications::notify(format_capital_distribution(&balances_usd));
+        }
+
+        tokio::select! {
+            _ = token.cancelled() => break,
+            _ = sleep(Duration::from_secs(interval_secs)) => {}
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use crate::config::{self, BudgetConfig};
+use crate::log_functions;
+use crate::structs::Market;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// The file the committed spend is persisted to
+const BANKROLL_STATE_PATH: &str = "bankroll_state.json";
+
+// The global budget manager used by the buy flows
+static BUDGET_MANAGER: Mutex<Option<BudgetManager>> = Mutex::new(None);
+
+// The id of a reservation made before a buy
+pub type ReservationId = u64;
+
+// The enum that contains the reasons a reservation can be refused
+#[derive(Debug, Clone, PartialEq)]
+pub enum BudgetError {
+    TotalCapitalExceeded { available: f32 },
+    MarketAllocationExceeded { market: Market, available: f32 },
+    DailyCapExceeded { available: f32 },
+    MarketDailyCapExceeded { market: Market, available: f32 },
+    MarketBalanceExceeded { market: Market, available: f32 },
+    UnknownReservation(ReservationId),
+}
+
+// The struct that has a reserved amount waiting to be committed or released
+#[derive(Debug, Clone)]
+struct Reservation {
+    market: Market,
+    amount: f32,
+}
+
+// The struct that is persisted so restarting the bot doesn't reset the caps
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct BankrollState {
+    day: String,
+    spent_today: HashMap<Market, f32>,
+    deployed: HashMap<Market, f32>,
+}
+
+// The struct that caps the spend per market and per day
+#[derive(Debug)]
+pub struct BudgetManager {
+    config: BudgetConfig,
+    state: BankrollState,
+    reservations: HashMap<ReservationId, Reservation>,
+    next_id: ReservationId,
+    // The last read available balance of the markets in dollars, the markets without one are not capped by it
+    balances: HashMap<Market, f32>,
+}
+
+impl BudgetManager {
+    /// Creates the manager and loads the committed spend of the earlier runs
+    pub fn new(config: BudgetConfig) -> Self {
+        let state = std::fs::read_to_string(BANKROLL_STATE_PATH)
+            .ok()
+            .and_then(|file_str| serde_json::from_str(&file_str).ok())
+            .unwrap_or_default();
+
+        BudgetManager {
+            config,
+            state,
+            reservations: HashMap::new(),
+            next_id: 0,
+            balances: HashMap::new(),
+        }
+    }
+
+    /// Reserves the amount for a buy, has to be committed or released afterwards
+    pub fn reserve(&mut self, market: Market, amount: f32) -> Result<ReservationId, BudgetError> {
+        self.roll_day();
+
+        let reserved_total: f32 = self.reservations.values().map(|r| r.amount).sum();
+        let reserved_market: f32 = self
+            .reservations
+            .values()
+            .filter(|r| r.market == market)
+            .map(|r| r.amount)
+            .sum();
+
+        let deployed_total: f32 = self.state.deployed.values().sum();
+        let deployed_market = *self.state.deployed.get(&market).unwrap_or(&0.0);
+        let spent_total: f32 = self.state.spent_today.values().sum();
+        let spent_market = *self.state.spent_today.get(&market).unwrap_or(&0.0);
+
+        let available = self.config.total_capital - deployed_total - reserved_total;
+        if amount > available {
+            return Err(BudgetError::TotalCapitalExceeded { available });
+        }
+
+        if let Some(allocation) = self.config.market_allocations.get(&market) {
+            let available = allocation - deployed_market - reserved_market;
+            if amount > available {
+                return Err(BudgetError::MarketAllocationExceeded { market, available });
+            }
+        }
+
+        let available = self.config.daily_spend_cap - spent_total - reserved_total;
+        if amount > available {
+            return Err(BudgetError::DailyCapExceeded { available });
+        }
+
+        if let Some(cap) = self.config.market_daily_spend_caps.get(&market) {
+            let available = cap - spent_market - reserved_market;
+            if amount > available {
+                return Err(BudgetError::MarketDailyCapExceeded { market, available });
+            }
+        }
+
+        if let Some(balance) = self.balances.get(&market) {
+            let available = balance - reserved_market;
+            if amount > available {
+                return Err(BudgetError::MarketBalanceExceeded { market, available });
+            }
+        }
+
+        self.next_id += 1;
+        self.reservations.insert(self.next_id, Reservation { market, amount });
+        Ok(self.next_id)
+    }
+
+    /// Commits the reservation after a successful buy, the actual spent amount can differ from the reserved one
+    pub fn commit(&mut self, reservation: ReservationId, spent: f32) -> Result<(), BudgetError> {
+        let res = self
+            .reservations
+            .remove(&reservation)
+            .ok_or(BudgetError::UnknownReservation(reservation))?;
+
+        self.roll_day();
+        *self.state.spent_today.entry(res.market.clone()).or_insert(0.0) += spent;
+        *self.state.deployed.entry(res.market).or_insert(0.0) += spent;
+        self.save();
+        Ok(())
+    }
+
+    /// Releases the reservation after a failed buy
+    pub fn release(&mut self, reservation: ReservationId) -> Result<(), BudgetError> {
+        self.reservations
+            .remove(&reservation)
+            .map(|_| ())
+            .ok_or(BudgetError::UnknownReservation(reservation))
+    }
+
+    /// Frees the deployed capital of the market after an item bought there was sold or refunded
+    pub fn return_capital(&mut self, market: Market, amount: f32) {
+        let deployed = self.state.deployed.entry(market).or_insert(0.0);
+        *deployed = f32::max(*deployed - amount, 0.0);
+        self.save();
+    }
+
+    /// Replaces the available balances of the markets, read by the balances module
+    pub fn set_balances(&mut self, balances: HashMap<Market, f32>) {
+        self.balances = balances;
+    }
+
+    // Resets the daily spend when the day changes
+    fn roll_day(&mut self) {
+        let today = Local::now().format("%Y-%m-%d").to_string();
+        if self.state.day != today {
+            self.state.day = today;
+            self.state.spent_today.clear();
+            self.save();
+        }
+    }
+
+    // Writes the committed spend to the disk
+    fn save(&self) {
+        let res = serde_json::to_string(&self.state)
+            .map_err(|e| format!("{:?}", e))
+            .and_then(|state_str| std::fs::write(BANKROLL_STATE_PATH, state_str).map_err(|e| format!("{:?}", e)));
+
+        if let Err(e) = res {
+            log_functions::log_err(&format!("Cannot save the bankroll state. E: {:?}", e));
+        }
+    }
+}
+
+/// Reserves the amount in the global budget manager
+pub fn reserve(market: Market, amount: f32) -> Result<ReservationId, BudgetError> {
+    let mut manager = BUDGET_MANAGER.lock().unwrap();
+    manager
+        .get_or_insert_with(|| BudgetManager::new(config::get_bot_config().budget.clone()))
+        .reserve(market, amount)
+}
+
+/// Commits the reservation in the global budget manager
+pub fn commit(reservation: ReservationId, spent: f32) -> Result<(), BudgetError> {
+    let mut manager = BUDGET_MANAGER.lock().unwrap();
+    manager
+        .get_or_insert_with(|| BudgetManager::new(config::get_bot_config().budget.clone()))
+        .commit(reservation, spent)
+}
+
+/// Releases the reservation in the global budget manager
+pub fn release(reservation: ReservationId) -> Result<(), BudgetError> {
+    let mut manager = BUDGET_MANAGER.lock().unwrap();
+    manager
+        .get_or_insert_with(|| BudgetManager::new(config::get_bot_config().budget.clone()))
+        .release(reservation)
+}
+
+/// Replaces the available balances of the markets in the global budget manager
+pub fn set_balances(balances: HashMap<Market, f32>) {
+    let mut manager = BUDGET_MANAGER.lock().unwrap();
+    manager
+        .get_or_insert_with(|| BudgetManager::new(config::get_bot_config().budget.clone()))
+        .set_balances(balances)
+}
+
+/// Frees the deployed capital of the market in the global budget manager, called after a sale or a refund
+pub fn return_capital(market: Market, amount: f32) {
+    let mut manager = BUDGET_MANAGER.lock().unwrap();
+    manager
+        .get_or_insert_with(|| BudgetManager::new(config::get_bot_config().budget.clone()))
+        .return_capital(market, amount)
+}
@@ -0,0 +1,15 @@
+// bin/csbot.rs
+//
+// The csbot command line tool, the subcommands are in cli.rs
+//
+//     csbot price "AK-47 | Redline (Field-Tested)" --markets bitskins,dmarket
+//     csbot compare --inventory snapshot.json --min-profit 8 --json
+
+use clap::Parser;
+use cs_trading_bot::cli::{self, Cli};
+use std::process::ExitCode;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    cli::run(Cli::parse()).await
+}
@@ -0,0 +1,185 @@
+use crate::structs::{Market, Price};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// Maximum amount of prices that are kept in the cache
+const MAX_ENTRIES: usize = 5000;
+
+// The global price cache shared by all the market handlers
+static PRICE_CACHE: Mutex<Option<PriceCache>> = Mutex::new(None);
+
+// The struct that has a cached price, the time it expires and the tick of its last use
+#[derive(Debug, Clone)]
+struct PriceCacheEntry {
+    price: Price,
+    expires_unix: u64,
+    last_used: u64,
+}
+
+// The struct that caches the fetched prices per (market, item name)
+#[derive(Debug, Clone, Default)]
+pub struct PriceCache {
+    entries: HashMap<(Market, String), PriceCacheEntry>,
+    // The keys ordered by their last use, the first one is the least recently used
+    order: BTreeMap<u64, (Market, String)>,
+    // Increases on every get and insert
+    tick: u64,
+}
+
+impl PriceCache {
+    pub fn new() -> Self {
+        PriceCache::default()
+    }
+
+    /// Returns the cached price if it exists and is not expired yet
+    ///
+    /// - A hit moves the entry to the back of the eviction order
+    pub fn get(&mut self, market: &Market, name: &str) -> Option<&Price> {
+        let key = (market.clone(), name.to_string());
+        let expires_unix = self.entries.get(&key)?.expires_unix;
+        if expires_unix <= get_sys_time_in_secs() {
+            return None;
+        }
+
+        let tick = self.next_tick();
+        let entry = self.entries.get_mut(&key)?;
+        self.order.remove(&entry.last_used);
+        entry.last_used = tick;
+        self.order.insert(tick, key);
+        Some(&entry.price)
+    }
+
+    /// Inserts the price, when the cache is full the expired entries are removed first
+    /// and then the least recently used entry gets evicted
+    pub fn insert(&mut self, market: Market, name: String, price: Price, ttl_secs: u64) {
+        let now = get_sys_time_in_secs();
+        let key = (market, name);
+
+        if let Some(old) = self.entries.remove(&key) {
+            self.order.remove(&old.last_used);
+        }
+        if self.entries.len() >= MAX_ENTRIES {
+            self.entries.retain(|_, entry| entry.expires_unix > now);
+            let entries = &self.entries;
+            self.order.retain(|_, key| entries.contains_key(key));
+        }
+        if self.entries.len() >= MAX_ENTRIES {
+            if let Some((_, key)) = self.order.pop_first() {
+                self.entries.remove(&key);
+            }
+        }
+
+        let tick = self.next_tick();
+        self.order.insert(tick, key.clone());
+        self.entries.insert(key, PriceCacheEntry { price, expires_unix: now + ttl_secs, last_used: tick });
+    }
+
+    /// Removes the cached price of the item in the market
+    pub fn remove(&mut self, market: &Market, name: &str) {
+        if let Some(entry) = self.entries.remove(&(market.clone(), name.to_string())) {
+            self.order.remove(&entry.last_used);
+        }
+    }
+
+    /// Removes every cached price of the market
+    pub fn remove_market(&mut self, market: &Market) {
+        self.entries.retain(|(entry_market, _), _| entry_market != market);
+        self.order.retain(|_, (entry_market, _)| entry_market != market);
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+/// Returns a copy of the cached price from the global cache
+pub fn get_cached_price(market: &Market, name: &str) -> Option<Price> {
+    let mut cache = PRICE_CACHE.lock().unwrap();
+    cache.as_mut()?.get(market, name).cloned()
+}
+
+/// Stores the price in the global cache with the ttl of the market from the config
+pub fn cache_price(market: Market, name: String, price: Price) {
+    let ttl_secs = crate::config::get_bot_config().get_price_cache_ttl(&market);
+    let mut cache = PRICE_CACHE.lock().unwrap();
+    cache
+        .get_or_insert_with(PriceCache::new)
+        .insert(market, name, price, ttl_secs);
+}
+
+/// Drops the cached price of the item in the market from the global cache
+pub fn invalidate(market: &Market, name: &str) {
+    let mut cache = PRICE_CACHE.lock().unwrap();
+    if let Some(cache) = cache.as_mut() {
+        cache.remove(market, name);
+    }
+}
+
+/// Drops every cached price of the market from the global cache
+pub fn invalidate_market(market: &Market) {
+    let mut cache = PRICE_CACHE.lock().unwrap();
+    if let Some(cache) = cache.as_mut() {
+        cache.remove_market(market);
+    }
+}
+
+// Get the UNIX timestamp
+fn get_sys_time_in_secs() -> u64 {
+    match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(n) => n.as_secs(),
+        Err(_) => panic!("SystemTime before UNIX EPOCH!"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(market: Market) -> Price {
+        Price {
+            market,
+            commision: 0,
+            price_buy_trade: (1.0, 1.0, 1.0),
+            price_buy_trade_w_comm: (1.0, 1.0, 1.0),
+            price_buy: 1.0,
+            price_buy_w_comm: 1.0,
+            price_sell: 1.0,
+            price_sell_w_comm: 1.0,
+            sale_stats: None,
+            fetched_at_unix: 0,
+            volume_24h: None,
+            currency: Default::default(),
+        }
+    }
+
+    #[test]
+    fn full_cache_evicts_the_least_recently_used_entry() {
+        let mut cache = PriceCache::new();
+        for i in 0..MAX_ENTRIES {
+            cache.insert(Market::Steam, format!("item {}", i), price(Market::Steam), 3600);
+        }
+
+        // The first item is read again so the second one is now the least recently used
+        assert!(cache.get(&Market::Steam, "item 0").is_some());
+        cache.insert(Market::Steam, "new item".to_string(), price(Market::Steam), 3600);
+
+        assert_eq!(cache.entries.len(), MAX_ENTRIES);
+        assert_eq!(cache.order.len(), MAX_ENTRIES);
+        assert!(cache.get(&Market::Steam, "item 0").is_some());
+        assert!(cache.get(&Market::Steam, "item 1").is_none());
+        assert!(cache.get(&Market::Steam, "new item").is_some());
+    }
+
+    #[test]
+    fn reinserting_an_entry_does_not_leave_a_stale_order_key() {
+        let mut cache = PriceCache::new();
+        cache.insert(Market::Steam, "item".to_string(), price(Market::Steam), 3600);
+        cache.insert(Market::Steam, "item".to_string(), price(Market::Steam), 3600);
+        cache.remove_market(&Market::Steam);
+
+        assert!(cache.entries.is_empty());
+        assert!(cache.order.is_empty());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::config;
+use crate::markets::{bitskins, steam};
+use crate::price_cache;
+use crate::price_functions;
+use crate::structs::{Item, ItemSaleStats, Market, Price, PriceCompare, PriceCompareFilter, SteamId};
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::process::ExitCode;
+use strum::IntoEnumIterator;
+
+// The exit code of a failed command
+const EXIT_ERROR: u8 = 1;
+
+// The exit code of a compare that found nothing, 2 is taken by clap for the usage errors
+const EXIT_NO_OPPORTUNITIES: u8 = 3;
+
+// The command line tool for the ad-hoc price checks and the arbitrage scans
+#[derive(Debug, Parser)]
+#[command(name = "csbot", about = "Price checks and arbitrage scans on top of the market handlers")]
+pub struct Cli {
+    /// The config file, the defaults are used if it doesn't exist
+    #[arg(long, global = true, default_value = "config.json")]
+    pub config: String,
+    /// Prints json instead of a table
+    #[arg(long, global = true)]
+    pub json: bool,
+    #[command(subcommand)]
+    pub command: CliCommand,
+}
+
+// The enum which differentiates the subcommands
+#[derive(Debug, Subcommand)]
+pub enum CliCommand {
+    /// Prints the buy and sell prices of an item with and without the commision
+    Price {
+        name: String,
+        /// Comma separated markets, e.g. bitskins,dmarket, every market if empty
+        #[arg(long, value_delimiter = ',')]
+        markets: Vec<String>,
+    },
+    /// Runs price_compare_all on an inventory snapshot and prints the top opportunities
+    Compare {
+        /// The inventory json, keyed by the item name
+        #[arg(long)]
+        inventory: String,
+        /// The minimum profit percentage after the commisions
+        #[arg(long, default_value_t = 0)]
+        min_profit: i32,
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+        /// The prices of the snapshot older than this are skipped
+        #[arg(long, default_value_t = 86400)]
+        max_price_age_secs: u64,
+    },
+    /// Fetches the Steam inventory and dumps it to json
+    Inventory {
+        /// The steam64 id of the inventory, the default account of the config if not given
+        #[arg(long)]
+        steam_id: Option<u64>,
+        /// The file the json is written to, stdout if not given
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Prints the BitSkins sale stats of an item
+    Stats { name: String },
+}
+
+// The outcome of a subcommand that isn't an error
+enum CliOutcome {
+    Done,
+    NoOpportunities,
+}
+
+/// Runs the subcommand and returns the exit code
+///
+/// - 0 when the command succeeded, EXIT_NO_OPPORTUNITIES when compare found nothing
+///   and EXIT_ERROR when the command failed, the error is printed to stderr
+/// - bin/csbot.rs calls it from main with the parsed arguments
+pub async fn run(cli: Cli) -> ExitCode {
+    if std::path::Path::new(&cli.config).exists() {
+        if let Err(err_str) = config::load_bot_config(&cli.config) {
+            eprintln!("{}", err_str);
+            return ExitCode::from(EXIT_ERROR);
+        }
+    }
+
+    let res = match &cli.command {
+        CliCommand::Price { name, markets } => run_price(name, markets, cli.json).await,
+        CliCommand::Compare { inventory, min_profit, top, max_price_age_secs } => {
+            run_compare(inventory, *min_profit, *top, *max_price_age_secs, cli.json).await
+        }
+        CliCommand::Inventory { steam_id, output } => run_inventory(*steam_id, output.as_deref(), cli.json).await,
+        CliCommand::Stats { name } => run_stats(name, cli.json).await,
+    };
+
+    match res {
+        Ok(CliOutcome::Done) => ExitCode::SUCCESS,
+        Ok(CliOutcome::NoOpportunities) => ExitCode::from(EXIT_NO_OPPORTUNITIES),
+        Err(err_str) => {
+            eprintln!("{}", err_str);
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+/// Parses a market name case insensitively, "bitskins" is Market::BitSkins
+pub fn parse_market(name: &str) -> Result<Market, String> {
+    let name = name.trim().to_lowercase();
+    Market::iter()
+        .find(|market| format!("{:?}", market).to_lowercase() == name)
+        .ok_or(format!("cli.rs | parse_market(name={}) | Error occured, there is no such market.", name))
+}
+
+// Fetches the price of the item on every market, a market that fails is printed to stderr and left out
+async fn run_price(name: &str, markets: &[String], json: bool) -> Result<CliOutcome, String> {
+    let markets: Vec<Market> = if markets.is_empty() {
+        Market::iter().collect()
+    } else {
+        markets.iter().map(|market| parse_market(market)).collect::<Result<_, _>>()?
+    };
+
+    let mut prices: Vec<Price> = Vec::new();
+    for market in markets {
+        match price_cache::get_cached_price(market.clone(), name, 0).await {
+            Ok(price) => prices.push(price),
+            Err(err_str) => eprintln!("{:?}: {}", market, err_str),
+        }
+    }
+    if prices.is_empty() {
+        return Err(format!("cli.rs | run_price(name={}) | Error occured, no market returned a price.", name));
+    }
+
+    if json {
+        print_json(&prices)?;
+        return Ok(CliOutcome::Done);
+    }
+
+    println!("{}", name);
+    println!("{:<12} {:>10} {:>12} {:>10} {:>12} {:>8}", "Market", "Buy", "Buy w/comm", "Sell", "Sell w/comm", "Currency");
+    for price in prices.iter() {
+        println!(
+            "{:<12} {:>10.2} {:>12.2} {:>10.2} {:>12.2} {:>8}",
+            format!("{:?}", price.market), price.price_buy, price.price_buy_w_comm, price.price_sell, price.price_sell_w_comm, price.currency.code()
+        );
+    }
+    Ok(CliOutcome::Done)
+}
+
+// Compares the prices of the snapshot and prints the best opportunities
+async fn run_compare(inventory_path: &str, min_profit: i32, top: usize, max_price_age_secs: u64, json: bool) -> Result<CliOutcome, String> {
+    let file_str = std::fs::read_to_string(inventory_path)
+        .map_err(|e| format!("cli.rs | run_compare(inventory_path={}) | Error occured when reading the file. E: {:?}", inventory_path, e))?;
+    let map: HashMap<String, Item> = serde_json::from_str(&file_str)
+        .map_err(|e| format!("cli.rs | run_compare(inventory_path={}) | Error occured when parsing the file. E: {:?}", inventory_path, e))?;
+
+    let filter = PriceCompareFilter {
+        min_profit_perc_after_comm: min_profit,
+        ..PriceCompareFilter::default()
+    };
+    let compare_map = price_functions::price_compare_all(&map, max_price_age_secs, &filter).await;
+    let best: Vec<&PriceCompare> = compare_map.top_n(top);
+    if best.is_empty() {
+        if !json {
+            println!("No opportunities with at least {}% profit after the commisions.", min_profit);
+        } else {
+            print_json(&best)?;
+        }
+        return Ok(CliOutcome::NoOpportunities);
+    }
+
+    if json {
+        print_json(&best)?;
+        return Ok(CliOutcome::Done);
+    }
+
+    println!("{:<50} {:<12} {:<12} {:>8} {:>10}", "Item", "Buy", "Sell", "Profit", "Profit $");
+    for compare in best {
+        println!(
+            "{:<50} {:<12} {:<12} {:>7}% {:>10.2}",
+            compare.name,
+            format!("{:?}", compare.price.0.market),
+            format!("{:?}", compare.price.1.market),
+            compare.diff_perc_after_comm,
+            compare.diff_val_after_comm
+        );
+    }
+    Ok(CliOutcome::Done)
+}
+
+// Fetches the inventory and writes it as json to the output file or stdout
+// Without an output file the table is printed unless --json is given
+async fn run_inventory(steam_id: Option<u64>, output: Option<&str>, json: bool) -> Result<CliOutcome, String> {
+    let user_id = match steam_id {
+        Some(steam64) => SteamId::from_steam64(steam64).map_err(|e| format!("cli.rs | run_inventory(steam_id={}) | Error occured, invalid steam id. E: {:?}", steam64, e))?,
+        None => steam::default_account()?.steam_id,
+    };
+
+    let inv = steam::get_inventory(user_id)
+        .await
+        .map_err(|e| format!("cli.rs | run_inventory(user_id={}) | Error occured when fetching the inventory. E: {}", user_id, e))?;
+
+    if let Some(path) = output {
+        let json_str = serde_json::to_string_pretty(&inv)
+            .map_err(|e| format!("cli.rs | run_inventory(user_id={}) | Error occured when serializing the inventory. E: {:?}", user_id, e))?;
+        std::fs::write(path, json_str)
+            .map_err(|e| format!("cli.rs | run_inventory(path={}) | Error occured when writing the file. E: {:?}", path, e))?;
+        println!("Wrote {} items to {}", inv.len(), path);
+        return Ok(CliOutcome::Done);
+    }
+
+    if json {
+        print_json(&inv)?;
+        return Ok(CliOutcome::Done);
+    }
+
+    let mut items: Vec<&Item> = inv.values().collect();
+    items.sort_by(|a, b| a.name.cmp(&b.name));
+    println!("{:<60} {:>6} {:>10} {:>8}", "Item", "Total", "Available", "On hold");
+    for item in items {
+        println!("{:<60} {:>6} {:>10} {:>8}", item.name, item.count.total, item.count.available, item.count.on_hold);
+    }
+    Ok(CliOutcome::Done)
+}
+
+// Prints the sale stats of the item
+async fn run_stats(name: &str, json: bool) -> Result<CliOutcome, String> {
+    let stats: ItemSaleStats = bitskins::get_item_sale_stats(name).await?;
+
+    if json {
+        print_json(&stats)?;
+        return Ok(CliOutcome::Done);
+    }
+
+    println!("{}", name);
+    println!("VWAP ({} days): {:.2} ({:.2} after the commision)", stats.short_window_days, stats.weekly_vwap, stats.weekly_vwap_w_comm);
+    println!("Sales: {} in {} days, {} in {} days", stats.weekly_sale_count, stats.short_window_days, stats.monthly_sale_count, stats.long_window_days);
+    println!("Average ({} days): {:.2}, median: {:.2}", stats.long_window_days, stats.monthly_avg_price, stats.median_price);
+    println!("Change: {:.2}%, volatility: {:.2}", stats.weekly_price_change, stats.price_volatility_std_dev);
+    println!("Outliers removed: {}", stats.outliers_removed);
+    Ok(CliOutcome::Done)
+}
+
+fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<(), String> {
+    let json_str = serde_json::to_string_pretty(value)
+        .map_err(|e| format!("cli.rs | print_json() | Error occured when serializing the output. E: {:?}", e))?;
+    println!("{}", json_str);
+    Ok(())
+}
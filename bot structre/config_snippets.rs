@@ -0,0 +1,338 @@
+use crate::currency::Currency;
+use crate::naming;
+use crate::proxy_handler::{BasicAuth, ProxyConfig, ProxyType};
+use crate::structs::{ItemCategory, Market, SteamAccount, SteamApp, TradeHoldPremiums};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+// The global config, loaded once at startup
+static BOT_CONFIG: OnceLock<BotConfig> = OnceLock::new();
+
+// The proxies that are used when the config doesn't list any
+const DEFAULT_PROXIES: [&str; 10] = [
+    "45.86.48.213:50100",
+    "45.86.50.46:50100",
+    "45.86.49.52:50100",
+    "45.86.50.11:50100",
+    "45.86.48.9:50100",
+    "45.86.50.186:50100",
+    "45.86.48.7:50100",
+    "45.86.50.124:50100",
+    "45.86.50.128:50100",
+    "45.86.50.63:50100",
+];
+
+// The struct that has all the user configurable settings of the bot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BotConfig {
+    pub price_cache_ttl_secs: HashMap<Market, u64>,
+    pub default_price_cache_ttl_secs: u64,
+    pub sale_stats_mad_multiple: f32,
+    pub sale_stats_outlier_std_devs: f32,
+    pub sale_stats_short_window_days: u32,
+    pub sale_stats_long_window_days: u32,
+    pub dry_run: bool,
+    pub telegram_bot_token: Option<String>,
+    pub telegram_chat_id: Option<String>,
+    pub minimum_profit_margin: f32,
+    pub trade_hold_premiums: TradeHoldPremiums,
+    pub category_min_profit_margin: HashMap<ItemCategory, f32>,
+    pub category_max_count: HashMap<ItemCategory, i16>,
+    pub global_max_count_per_item: i16,
+    pub max_hold_weekly_volume_ratio: f32,
+    pub default_velocity_cap: i16,
+    pub purchase_cooldown_days: u32,
+    pub category_purchase_cooldown_days: HashMap<ItemCategory, u32>,
+    pub max_price_slippage_perc: f32,
+    pub min_exit_liquidity: Option<u32>,
+    pub shutdown_grace_secs: u64,
+    pub shutdown_task_timeout_secs: u64,
+    pub metrics_enabled: bool,
+    pub metrics_port: u16,
+    pub webhook_enabled: bool,
+    pub webhook_port: u16,
+    pub webhook_secret: Option<String>,
+    pub budget: BudgetConfig,
+    pub proxies: Vec<ProxyConfig>,
+    pub proxies_file: Option<String>,
+    // Lets the requests of a proxied market go out on a direct connection when it has no usable proxy
+    pub allow_direct_connection: bool,
+    pub market_proxy_types: HashMap<Market, ProxyType>,
+    pub dmarket_public_key: Option<String>,
+    pub dmarket_secret_key: Option<String>,
+    pub dmarket_balance_buffer_usd: f32,
+    pub bitskins_scrape_keys: Vec<String>,
+    pub steam_accounts: Vec<SteamAccount>,
+    pub steam_inventory_page_count: u32,
+    pub skinport_refresh_secs: u64,
+    pub marketcsgo_poll_secs: u64,
+    pub trade_offer_poll_timeout_secs: u64,
+    pub reprice_interval_secs: u64,
+    pub reprice_cooldown_secs: i64,
+    pub reprice_undercut_usd: f32,
+    pub reprice_min_margin: f32,
+    pub inspect_service_url: String,
+    pub inspect_min_value_usd: f32,
+    pub sticker_price_market: Market,
+    pub sticker_review_perc: f32,
+    pub sell_min_margin: f32,
+    pub sell_full_margin_days: u32,
+    pub sell_break_even_days: u32,
+    pub use_http2: HashMap<Market, bool>,
+    // The (buyer fee, seller fee, platform fee) in percent of the markets whose fees are not built into data.rs,
+    // taken from the fee pages of the accounts
+    pub market_commisions: HashMap<Market, (i32, i32, i32)>,
+    pub circuit_failure_threshold: u32,
+    pub circuit_cooldown_secs: u64,
+    pub app_configs: HashMap<u32, AppConfig>,
+    pub base_currency: Currency,
+    pub market_currencies: HashMap<Market, Currency>,
+    pub exchange_rate_ttl_hours: u64,
+    pub currency_offline: bool,
+    pub capital_report_interval_secs: u64,
+    // Glob patterns of the only items that are bought, None buys every item
+    pub buy_allowlist: Option<Vec<String>>,
+    // Glob patterns of the items that are never bought
+    pub buy_blocklist: Vec<String>,
+    // The items whose lowest prices are recorded for the spread analysis
+    pub snapshot_items: Vec<String>,
+    pub snapshot_markets: Vec<Market>,
+    pub snapshot_interval_secs: u64,
+    // The snapshots are downsampled to one per hour after the raw retention and removed after the retention
+    pub snapshot_raw_retention_days: u32,
+    pub snapshot_retention_days: u32,
+}
+
+// The struct that has the spend limits of the budget manager
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BudgetConfig {
+    pub total_capital: f32,
+    pub market_allocations: HashMap<Market, f32>,
+    pub daily_spend_cap: f32,
+    pub market_daily_spend_caps: HashMap<Market, f32>,
+}
+
+// The struct that has the game specific settings of a Steam app
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    // Glob patterns of the inventory items that are not tracked, they are never bought either
+    pub inventory_ignore: Vec<String>,
+    // The BitSkins category of the names without a marker, no category filter if it is not set
+    pub bitskins_default_category: Option<u32>,
+    // (name marker, BitSkins category), the last marker the name contains wins
+    pub bitskins_name_categories: Vec<(String, u32)>,
+}
+
+impl AppConfig {
+    // The settings of CS, the bot was written for it
+    fn cs() -> Self {
+        AppConfig {
+            inventory_ignore: ["*Loyalty Badge*", "*5 Year Veteran Coin*", "*Music Kit*", "*Graffiti | *", "*Global Offensive Badge*"]
+                .iter()
+                .map(|ignore_str| ignore_str.to_string())
+                .collect(),
+            bitskins_default_category: Some(1),
+            bitskins_name_categories: vec![("StatTrak".to_string(), 3), ("Souvenir".to_string(), 5)],
+        }
+    }
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        BudgetConfig {
+            total_capital: 1000.0,
+            market_allocations: HashMap::new(),
+            daily_spend_cap: 300.0,
+            market_daily_spend_caps: HashMap::new(),
+        }
+    }
+}
+
+impl Default for BotConfig {
+    fn default() -> Self {
+        BotConfig {
+            // BitSkins prices are stable for a couple of minutes, CSFloat moves faster
+            price_cache_ttl_secs: HashMap::from([
+                (Market::BitSkins, 120),
+                (Market::CSFloat, 30),
+            ]),
+            default_price_cache_ttl_secs: 60,
+            sale_stats_mad_multiple: 3.0,
+            // The days more than 3 standard deviations from the mean are dropped too, 0.0 turns it off
+            sale_stats_outlier_std_devs: 3.0,
+            sale_stats_short_window_days: 7,
+            sale_stats_long_window_days: 30,
+            dry_run: false,
+            telegram_bot_token: None,
+            telegram_chat_id: None,
+            minimum_profit_margin: 8.0,
+            trade_hold_premiums: TradeHoldPremiums::default(),
+            category_min_profit_margin: HashMap::new(),
+            category_max_count: HashMap::new(),
+            global_max_count_per_item: 0,
+            // Never hold more than half a week of sales
+            max_hold_weekly_volume_ratio: 0.5,
+            default_velocity_cap: 2,
+            purchase_cooldown_days: 1,
+            category_purchase_cooldown_days: HashMap::new(),
+            max_price_slippage_perc: 2.0,
+            min_exit_liquidity: None,
+            shutdown_grace_secs: 30,
+            shutdown_task_timeout_secs: 60,
+            metrics_enabled: false,
+            metrics_port: 9100,
+            webhook_enabled: false,
+            webhook_port: 9200,
+            webhook_secret: None,
+            budget: BudgetConfig::default(),
+            proxies: DEFAULT_PROXIES
+                .iter()
+                .map(|url| ProxyConfig::Http {
+                    url: url.to_string(),
+                    auth: Some(BasicAuth {
+                        username: "XXX".to_string(),
+                        password: "XXX".to_string(),
+                    }),
+                })
+                .collect(),
+            // The proxies.toml with the own credentials of every proxy, used instead of the proxies above when it is set
+            proxies_file: None,
+            allow_direct_connection: false,
+            market_proxy_types: HashMap::new(),
+            dmarket_public_key: None,
+            dmarket_secret_key: None,
+            dmarket_balance_buffer_usd: 1.0,
+            bitskins_scrape_keys: Vec::new(),
+            steam_accounts: Vec::new(),
+            // Steam accepts up to about 2000 items per inventory page, fewer pages means fewer requests
+            steam_inventory_page_count: 2000,
+            skinport_refresh_secs: 600,
+            marketcsgo_poll_secs: 60,
+            trade_offer_poll_timeout_secs: 60,
+            // Check the listings every 5 minutes but move the price of a listing at most once an hour
+            reprice_interval_secs: 300,
+            reprice_cooldown_secs: 3600,
+            reprice_undercut_usd: 0.01,
+            // The received price must be at least 3% over the cost of the item
+            reprice_min_margin: 0.03,
+            // The items worth $20 or more are inspected, stickers worth over 10% of the item are left for a manual review
+            inspect_service_url: "https://api.csfloat.com/".to_string(),
+            inspect_min_value_usd: 20.0,
+            sticker_price_market: Market::BitSkins,
+            sticker_review_perc: 10.0,
+            // A new item is listed for 8% over its cost, from the 5th day the margin goes down to break-even by the 21st day
+            sell_min_margin: 0.08,
+            sell_full_margin_days: 5,
+            sell_break_even_days: 21,
+            // CSFloat and DMarket support HTTP/2, the rest stay on HTTP/1.1 until they are checked
+            use_http2: HashMap::from([
+                (Market::CSFloat, true),
+                (Market::DMarket, true),
+            ]),
+            market_commisions: HashMap::new(),
+            // 5 failures in a row open the circuit for 5 minutes unless the market sends a Retry-After
+            circuit_failure_threshold: 5,
+            circuit_cooldown_secs: 300,
+            app_configs: HashMap::from([(SteamApp::CS.app_id, AppConfig::cs())]),
+            base_currency: Currency::USD,
+            // Buff163 quotes in yuan, the markets not listed quote in dollars
+            market_currencies: HashMap::from([(Market::Buff, Currency::CNY)]),
+            // The yuan prices of Buff163 move with the rate, so it is refetched every hour
+            exchange_rate_ttl_hours: 1,
+            currency_offline: false,
+            // The balances are read and the capital distribution is sent once a day
+            capital_report_interval_secs: 86400,
+            buy_allowlist: None,
+            buy_blocklist: Vec::new(),
+            snapshot_items: Vec::new(),
+            snapshot_markets: vec![Market::BitSkins, Market::CSFloat, Market::WaxPeer],
+            snapshot_interval_secs: 900,
+            snapshot_raw_retention_days: 7,
+            snapshot_retention_days: 180,
+        }
+    }
+}
+
+impl BotConfig {
+    /// Returns how long a fetched price of the given market can be reused
+    pub fn get_price_cache_ttl(&self, market: &Market) -> u64 {
+        *self
+            .price_cache_ttl_secs
+            .get(market)
+            .unwrap_or(&self.default_price_cache_ttl_secs)
+    }
+
+    /// Returns the minimum profit margin of the category, the global one if it is not overridden
+    pub fn get_min_profit_margin(&self, category: &ItemCategory) -> f32 {
+        *self
+            .category_min_profit_margin
+            .get(category)
+            .unwrap_or(&self.minimum_profit_margin)
+    }
+
+    /// Returns the type of proxies the requests of the market go through, HTTP if it is not set
+    pub fn get_proxy_type(&self, market: &Market) -> ProxyType {
+        self.market_proxy_types.get(market).copied().unwrap_or_default()
+    }
+
+    /// Returns if the requests of the market are sent over HTTP/2, false if it is not set
+    pub fn use_http2(&self, market: &Market) -> bool {
+        self.use_http2.get(market).copied().unwrap_or(false)
+    }
+
+    /// Returns the currency the prices of the market are quoted in, USD if it is not set
+    pub fn get_market_currency(&self, market: &Market) -> Currency {
+        self.market_currencies.get(market).copied().unwrap_or_default()
+    }
+
+    /// Returns the Steam account the single account functions use, the first one in the config
+    pub fn get_default_steam_account(&self) -> Option<&SteamAccount> {
+        self.steam_accounts.first()
+    }
+
+    /// Returns the settings of the Steam app, the empty settings if the app is not configured
+    pub fn get_app_config(&self, app: SteamApp) -> AppConfig {
+        self.app_configs.get(&app.app_id).cloned().unwrap_or_default()
+    }
+
+    /// Returns how many items of the category can be held, None means no category limit
+    pub fn get_category_max_count(&self, category: &ItemCategory) -> Option<i16> {
+        self.category_max_count.get(category).copied()
+    }
+
+    /// Checks if the item can be bought
+    ///
+    /// - With a buy_allowlist the name has to match one of its patterns
+    /// - A name that matches the buy_blocklist or the inventory_ignore of the app is never bought
+    pub fn is_buy_allowed(&self, app: SteamApp, item_name: &str) -> bool {
+        if let Some(allowlist) = &self.buy_allowlist {
+            if !naming::matches_any(allowlist, item_name) {
+                return false;
+            }
+        }
+
+        !naming::matches_any(&self.buy_blocklist, item_name) && !naming::matches_any(&self.get_app_config(app).inventory_ignore, item_name)
+    }
+}
+
+/// Loads the config from the given json file, has to be called once at startup
+pub fn load_bot_config(path: &str) -> Result<(), String> {
+    let file_str = std::fs::read_to_string(path)
+        .map_err(|e| format!("config.rs | load_bot_config(path={}) | Error occured when reading the file. E: {:?}", path, e))?;
+
+    let config: BotConfig = serde_json::from_str(&file_str)
+        .map_err(|e| format!("config.rs | load_bot_config(path={}) | Error occured when parsing the file. E: {:?}", path, e))?;
+
+    BOT_CONFIG
+        .set(config)
+        .map_err(|_| format!("config.rs | load_bot_config(path={}) | Error occured, the config is already loaded.", path))
+}
+
+/// Returns the loaded config, falls back to the defaults if nothing was loaded
+pub fn get_bot_config() -> &'static BotConfig {
+    BOT_CONFIG.get_or_init(BotConfig::default)
+}
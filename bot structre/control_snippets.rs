@@ -0,0 +1,437 @@
+use crate::config;
+use crate::log_functions;
+use crate::markets::{bitskins, csmoney, steam, waxpeer};
+use crate::notifications;
+use crate::structs::{Item, ItemStatus, ItemStatusChangeTicket, ItemStatusChanges, Market};
+use crate::ticket_bus;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::time::{sleep, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+// The file the inventory is written to on shutdown
+const INVENTORY_SNAPSHOT_PATH: &str = "inventory_snapshot.json";
+
+// The file the critical operations that were still running at the shutdown are written to
+const PENDING_OPS_PATH: &str = "pending_ops.json";
+
+// The token that is cancelled when the shutdown starts, shared by every SharedControl
+static SHUTDOWN_TOKEN: OnceLock<CancellationToken> = OnceLock::new();
+
+// The critical operations that are running right now, the shutdown waits for them
+static CRITICAL_OPS: Mutex<Option<HashMap<u64, (CriticalOp, String)>>> = Mutex::new(None);
+static NEXT_CRITICAL_ID: AtomicU64 = AtomicU64::new(0);
+
+// The enum which differentiates the operations that must not be interrupted
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CriticalOp {
+    Buy,
+    Withdraw,
+    TradeAccept,
+}
+
+// The struct that keeps a critical operation registered until it is dropped
+pub struct CriticalGuard {
+    id: u64,
+}
+
+impl Drop for CriticalGuard {
+    fn drop(&mut self) {
+        if let Some(ops) = CRITICAL_OPS.lock().unwrap().as_mut() {
+            ops.remove(&self.id);
+        }
+    }
+}
+
+// The struct that has a critical operation that didn't finish before the shutdown
+// name is the item id of a withdrawal, the trade offer id of a trade accept and the item name of a buy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingOp {
+    pub op: CriticalOp,
+    pub name: String,
+    pub interrupted_unix: i64,
+}
+
+// The struct that tells the long running tasks to stop and waits for them to finish their current operation
+// It is built on the process wide shutdown token, so the tasks it spawned and the loops that
+// wait on control::shutdown_token() stop on the same signal
+#[derive(Default)]
+pub struct ShutdownCoordinator {
+    tasks: Vec<(String, tokio::task::JoinHandle<()>)>,
+}
+
+impl ShutdownCoordinator {
+    pub fn new() -> ShutdownCoordinator {
+        ShutdownCoordinator::default()
+    }
+
+    // The token a task selects on between its operations
+    pub fn token(&self) -> CancellationToken {
+        shutdown_token()
+    }
+
+    // Spawns a task with the shutdown token and waits for it on the shutdown
+    pub fn spawn<F, Fut>(&mut self, name: &str, task: F)
+    where
+        F: FnOnce(CancellationToken) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handle = tokio::spawn(task(self.token()));
+        self.register(name, handle);
+    }
+
+    // Waits for an already spawned task on the shutdown, e.g. the loops that stop on the shutdown token
+    pub fn register(&mut self, name: &str, handle: tokio::task::JoinHandle<()>) {
+        self.tasks.push((name.to_string(), handle));
+    }
+
+    // Tells every task to stop once its current operation is done
+    pub fn shutdown(&self) {
+        shutdown_token().cancel();
+    }
+
+    // Waits for every task until the timeout, the tasks that are still running are aborted
+    // The critical operations that were still running are written to pending_ops.json
+    // Returns the names of the aborted tasks
+    pub async fn wait_for_tasks(self, timeout: Duration) -> Vec<String> {
+        let deadline = Instant::now() + timeout;
+        let mut aborted = Vec::new();
+
+        for (name, mut handle) in self.tasks {
+            match tokio::time::timeout_at(deadline, &mut handle).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => log_functions::log_err(&format!(
+                    "control.rs | wait_for_tasks() | Error occured, the task {} panicked. E: {:?}",
+                    name, e
+                )),
+                Err(_) => {
+                    handle.abort();
+                    aborted.push(name);
+                }
+            }
+        }
+
+        persist_critical_ops();
+        aborted
+    }
+}
+
+// The struct that is shared by every long running task to stop or pause them
+// Cloning it shares the same flags
+#[derive(Debug, Clone)]
+pub struct SharedControl {
+    emergency_stop: Arc<AtomicBool>,
+    pause: Arc<AtomicBool>,
+    token: CancellationToken,
+}
+
+impl Default for SharedControl {
+    fn default() -> Self {
+        SharedControl {
+            emergency_stop: Arc::new(AtomicBool::new(false)),
+            pause: Arc::new(AtomicBool::new(false)),
+            token: shutdown_token(),
+        }
+    }
+}
+
+impl SharedControl {
+    pub fn new() -> SharedControl {
+        SharedControl::default()
+    }
+
+    // Sets the emergency stop and cancels the token, every loop breaks on its next iteration
+    pub fn stop(&self) {
+        self.emergency_stop.store(true, Ordering::SeqCst);
+        self.token.cancel();
+    }
+
+    // Checked by every trading loop iteration
+    pub fn should_stop(&self) -> bool {
+        self.emergency_stop.load(Ordering::SeqCst) || self.token.is_cancelled()
+    }
+
+    // The token for the loops that wait with tokio::select!
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    // Pauses the trading loops without stopping them, the pending operations keep being checked
+    pub fn pause(&self) {
+        self.pause.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.pause.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pause.load(Ordering::SeqCst)
+    }
+}
+
+/// Returns the process wide shutdown token
+/// Used by the code paths that don't get a SharedControl, like the market handlers
+pub fn shutdown_token() -> CancellationToken {
+    SHUTDOWN_TOKEN.get_or_init(CancellationToken::new).clone()
+}
+
+/// Registers a critical operation, the shutdown waits until the returned guard is dropped
+pub fn begin_critical(op: CriticalOp, name: &str) -> CriticalGuard {
+    let id = NEXT_CRITICAL_ID.fetch_add(1, Ordering::Relaxed);
+    CRITICAL_OPS
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(id, (op, name.to_string()));
+    CriticalGuard { id }
+}
+
+/// Returns the critical operations that are running right now
+pub fn get_critical_ops() -> Vec<(CriticalOp, String)> {
+    match CRITICAL_OPS.lock().unwrap().as_ref() {
+        Some(ops) => ops.values().cloned().collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Waits until every critical operation is done, returns false if the grace period ran out first
+pub async fn wait_for_critical(grace: Duration) -> bool {
+    let deadline = Instant::now() + grace;
+    loop {
+        if get_critical_ops().is_empty() {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        sleep(Duration::from_millis(200)).await;
+    }
+}
+
+/// Spawns the task that shuts the bot down on SIGINT (Ctrl+C) or SIGTERM
+///
+/// - Sets the emergency stop and cancels the token so the loops stop starting new work
+/// - Waits for the running buys, withdrawals and trade accepts, at most shutdown_grace_secs,
+///   the ones still running are written to pending_ops.json
+/// - Removes every active sell listing so nothing gets sold while the bot is down
+/// - Writes the inventory snapshot and the tickets that could not be applied, then exits
+pub fn spawn_signal_handler(control: SharedControl, inv: Arc<tokio::sync::Mutex<HashMap<String, Item>>>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        if let Err(err_str) = wait_for_signal().await {
+            log_functions::log_err(&err_str);
+            return;
+        }
+
+        control.stop();
+        log_functions::log_structured("Warning", "Emergency stop triggered");
+
+        let grace = Duration::from_secs(config::get_bot_config().shutdown_grace_secs);
+        if !wait_for_critical(grace).await {
+            log_functions::log_structured(
+                "Error",
+                &format!("The grace period ran out, still running: {:?}", get_critical_ops()),
+            );
+            persist_critical_ops();
+        }
+
+        cancel_all_listings(&inv).await;
+
+        // Let the consumer apply the tickets that were pushed by the finished operations
+        if !ticket_bus::wait_until_drained(grace).await {
+            log_functions::log_structured("Error", "The ticket bus was not drained before the shutdown.");
+        }
+        flush_state(&inv).await;
+
+        log_functions::log_structured("Info", "Emergency stop finished, exiting.");
+        std::process::exit(0);
+    })
+}
+
+/// Waits for SIGINT or SIGTERM and shuts the bot down through the coordinator, returns when everything is written
+///
+/// - Sets the emergency stop and tells every task to finish its current operation
+/// - Waits for the tasks at most shutdown_task_timeout_secs, the critical operations still running go to pending_ops.json
+/// - Removes every active sell listing, lets the ticket bus drain and writes the inventory snapshot
+/// - Unlike spawn_signal_handler it doesn't exit the process, the main loop returns after it
+pub async fn shutdown_on_signal(control: SharedControl, coordinator: ShutdownCoordinator, inv: Arc<tokio::sync::Mutex<HashMap<String, Item>>>) {
+    if let Err(err_str) = wait_for_signal().await {
+        log_functions::log_err(&err_str);
+        return;
+    }
+
+    control.stop();
+    coordinator.shutdown();
+    log_functions::log_structured("Warning", "Shutdown started, waiting for the running operations.");
+
+    let timeout = Duration::from_secs(config::get_bot_config().shutdown_task_timeout_secs);
+    let aborted = coordinator.wait_for_tasks(timeout).await;
+    if !aborted.is_empty() {
+        log_functions::log_structured("Error", &format!("The shutdown timeout ran out, aborted the tasks: {:?}", aborted));
+    }
+
+    cancel_all_listings(&inv).await;
+
+    let grace = Duration::from_secs(config::get_bot_config().shutdown_grace_secs);
+    if !ticket_bus::wait_until_drained(grace).await {
+        log_functions::log_structured("Error", "The ticket bus was not drained before the shutdown.");
+    }
+    flush_state(&inv).await;
+
+    log_functions::log_structured("Info", "Shutdown finished.");
+}
+
+/// Retries the critical operations that were interrupted by the last shutdown, called once at startup
+///
+/// - Withdrawals are sent to BitSkins again and trade offers are accepted again
+/// - A buy can't be repeated without risking a second purchase, it is notified to be checked by hand,
+///   an item that was bought is still found in the BitSkins buy inventory by check_buy_operations
+/// - The operations that failed again are kept for the next startup
+pub async fn resume_pending_operations() -> Result<(), String> {
+    let pending = read_pending_ops()?;
+    if pending.is_empty() {
+        return Ok(());
+    }
+    log_functions::log_structured("Info", &format!("Resuming {} interrupted operations.", pending.len()));
+
+    let mut failed: Vec<PendingOp> = Vec::new();
+    for pending_op in pending {
+        match pending_op.op {
+            CriticalOp::Withdraw => {
+                if !bitskins::withdraw(&pending_op.name).await {
+                    log_functions::log_err(&format!(
+                        "control.rs | resume_pending_operations() | Error occured when withdrawing the item {} again.",
+                        pending_op.name
+                    ));
+                    failed.push(pending_op);
+                }
+            }
+            CriticalOp::TradeAccept => {
+                if let Err(err_str) = steam::accept_trade_offer(pending_op.name.clone(), &[]).await {
+                    log_functions::log_err(&format!(
+                        "control.rs | resume_pending_operations() | Error occured when accepting the trade offer {} again. E: {}",
+                        pending_op.name, err_str
+                    ));
+                    failed.push(pending_op);
+                }
+            }
+            CriticalOp::Buy => notifications::notify(format!(
+                "⚠️ A buy of {} was interrupted by the shutdown\nCheck the market if it went through",
+                pending_op.name
+            )),
+        }
+    }
+
+    write_pending_ops(&failed)
+}
+
+// Writes the critical operations that are still running so they are resumed on the next startup
+fn persist_critical_ops() {
+    let pending: Vec<PendingOp> = get_critical_ops()
+        .into_iter()
+        .map(|(op, name)| PendingOp { op, name, interrupted_unix: Local::now().timestamp() })
+        .collect();
+    if pending.is_empty() {
+        return;
+    }
+
+    log_functions::log_structured("Error", &format!("Writing the interrupted operations: {:?}", pending));
+    if let Err(err_str) = write_pending_ops(&pending) {
+        log_functions::log_err(&err_str);
+    }
+}
+
+// Reads the interrupted operations, no file means there are none
+fn read_pending_ops() -> Result<Vec<PendingOp>, String> {
+    let pending_str = match std::fs::read_to_string(PENDING_OPS_PATH) {
+        Ok(pending_str) => pending_str,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("control.rs | read_pending_ops() | Error occured when reading the file. E: {:?}", e)),
+    };
+
+    serde_json::from_str(&pending_str)
+        .map_err(|e| format!("control.rs | read_pending_ops() | Error occured when parsing the file. E: {:?}", e))
+}
+
+// Writes the interrupted operations, the file is removed when there are none
+fn write_pending_ops(pending: &[PendingOp]) -> Result<(), String> {
+    if pending.is_empty() {
+        return match std::fs::remove_file(PENDING_OPS_PATH) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("control.rs | write_pending_ops() | Error occured when removing the file. E: {:?}", e))
+            }
+            _ => Ok(()),
+        };
+    }
+
+    let pending_str = serde_json::to_string_pretty(pending)
+        .map_err(|e| format!("control.rs | write_pending_ops() | Error occured when serializing the operations. E: {:?}", e))?;
+    std::fs::write(PENDING_OPS_PATH, pending_str)
+        .map_err(|e| format!("control.rs | write_pending_ops() | Error occured when writing the file. E: {:?}", e))
+}
+
+// Waits for SIGINT or SIGTERM
+async fn wait_for_signal() -> Result<(), String> {
+    let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .map_err(|e| format!("control.rs | wait_for_signal() | Error occured when listening for SIGTERM. E: {:?}", e))?;
+
+    tokio::select! {
+        res = tokio::signal::ctrl_c() => res.map_err(|e| format!("control.rs | wait_for_signal() | Error occured when listening for SIGINT. E: {:?}", e)),
+        _ = sigterm.recv() => Ok(()),
+    }
+}
+
+// Writes the inventory snapshot and the dead letter tickets
+async fn flush_state(inv: &Arc<tokio::sync::Mutex<HashMap<String, Item>>>) {
+    let inv = inv.lock().await;
+    let res = serde_json::to_string_pretty(&*inv)
+        .map_err(|e| format!("control.rs | flush_state() | Error occured when serializing the inventory. E: {:?}", e))
+        .and_then(|inv_str| {
+            std::fs::write(INVENTORY_SNAPSHOT_PATH, inv_str)
+                .map_err(|e| format!("control.rs | flush_state() | Error occured when writing the inventory snapshot. E: {:?}", e))
+        });
+    if let Err(err_str) = res {
+        log_functions::log_err(&err_str);
+    }
+
+    if let Err(err_str) = ticket_bus::persist_dead_letters() {
+        log_functions::log_err(&err_str);
+    }
+}
+
+// Removes every listing that is still waiting for a buyer
+// Items with a CS.Money id are listed on CS.Money, the rest on WaxPeer
+async fn cancel_all_listings(inv: &Arc<tokio::sync::Mutex<HashMap<String, Item>>>) {
+    let mut inv = inv.lock().await;
+
+    for item in inv.values_mut() {
+        let listed: Vec<(String, Option<String>)> = item
+            .data
+            .iter()
+            .filter(|data| data.status == ItemStatus::OnSellOfferWaitingBuyer)
+            .map(|data| (data.asset_id.clone(), data.market_ids.get(&Market::CSMoney).map(|id| id.to_string())))
+            .collect();
+
+        for (asset_id, csmoney_item_id) in listed {
+            let res = if let Some(csmoney_item_id) = csmoney_item_id {
+                csmoney::remove_listing(&csmoney_item_id).await
+            } else {
+                waxpeer::remove_listing(&asset_id).await
+            };
+
+            match res {
+                Ok(()) => {
+                    let ticket = ItemStatusChangeTicket::new(asset_id.clone(), ItemStatusChanges::SellTradeCanceled);
+                    if let Err(err_str) = item.apply_ticket(&ticket) {
+                        log_functions::log_err(&err_str);
+                    }
+                }
+                Err(err_str) => log_functions::log_err(&format!("Cannot cancel the listing of {} ({}). E: {}", item.name, asset_id, err_str)),
+            }
+        }
+    }
+}
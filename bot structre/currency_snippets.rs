@@ -0,0 +1,274 @@
+use crate::config;
+use crate::log_functions;
+use crate::markets::api::exchange_rate_api;
+use crate::structs::{ItemSaleStats, Price};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+// The file the last fetched rates are kept in, used when the rates can't be fetched
+const EXCHANGE_RATES_PATH: &str = "exchange_rates.json";
+
+// The rates in memory, loaded from the file or fetched on the first use
+static RATES: Mutex<Option<ExchangeRates>> = Mutex::new(None);
+
+// Set while a background refresh is running, so a stale cache starts only one
+static REFRESHING: AtomicBool = AtomicBool::new(false);
+
+// The enum which differentiates the currencies the markets quote in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, EnumIter, Serialize, Deserialize)]
+pub enum Currency {
+    #[default]
+    USD,
+    EUR,
+    RUB,
+    CNY,
+}
+
+impl Currency {
+    // The ISO 4217 code of the currency
+    pub fn code(&self) -> &'static str {
+        match self {
+            Currency::USD => "USD",
+            Currency::EUR => "EUR",
+            Currency::RUB => "RUB",
+            Currency::CNY => "CNY",
+        }
+    }
+}
+
+// The struct that has the exchange rates, every rate is how much of the currency one dollar buys
+// It is the converter of the prices, the rates are keyed by the Currency enum instead of the code strings
+// so a misspelled code can't miss a rate
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExchangeRates {
+    pub rates: HashMap<Currency, f32>,
+    pub fetched_at_unix: i64,
+}
+
+impl ExchangeRates {
+    // Checks if the rates were fetched more than max_age_secs ago
+    pub fn is_stale(&self, max_age_secs: i64) -> bool {
+        Local::now().timestamp() - self.fetched_at_unix > max_age_secs
+    }
+
+    // The rate of the currency to the dollar, the dollar is always 1.0
+    fn rate(&self, currency: Currency) -> Option<f32> {
+        match currency {
+            Currency::USD => Some(1.0),
+            currency => self.rates.get(&currency).copied(),
+        }
+    }
+
+    /// Converts the amount between the currencies, None if a rate is missing
+    pub fn convert(&self, amount: f32, from: Currency, to: Currency) -> Option<f32> {
+        if from == to {
+            return Some(amount);
+        }
+        let from_rate = self.rate(from)?;
+        let to_rate = self.rate(to)?;
+        Some(amount / from_rate * to_rate)
+    }
+}
+
+/// Converts the amount between the currencies with the current exchange rates
+pub async fn convert(amount: f32, from: Currency, to: Currency) -> Result<f32, String> {
+    if from == to {
+        return Ok(amount);
+    }
+
+    let rates = get_rates().await?;
+    rates.convert(amount, from, to).ok_or(format!(
+        "currency.rs | convert(amount={}, from={:?}, to={:?}) | Error occured, there is no exchange rate for the currency.",
+        amount, from, to
+    ))
+}
+
+/// Returns how many dollars one yuan buys, the rate the Buff163 prices are converted with
+///
+/// The rate comes from the cached rates of get_rates (exchange_rate_ttl_hours, 1 hour by default),
+/// the Buff163 prices are quoted in CNY (market_currencies) and converted to the base currency
+/// with normalize_prices before they are compared
+pub async fn get_cny_usd_rate() -> Result<f32, String> {
+    convert(1.0, Currency::CNY, Currency::USD).await
+}
+
+/// Returns the exchange rates, the cached ones if they are not older than exchange_rate_ttl_hours
+///
+/// - Stale rates are returned right away and refreshed in the background (stale-while-revalidate)
+/// - Without rates in memory the last rates of the file are used, the rates are only fetched
+///   while waiting when there are none at all
+/// - In currency_offline mode nothing is fetched, the last cached rates are used with a warning
+pub async fn get_rates() -> Result<ExchangeRates, String> {
+    let bot_config = config::get_bot_config();
+
+    let cached = {
+        let mut rates = RATES.lock().unwrap();
+        if rates.is_none() {
+            *rates = read_rates_file();
+        }
+        rates.clone()
+    };
+
+    if bot_config.currency_offline {
+        let rates = cached.ok_or("currency.rs | get_rates() | Error occured, offline mode without cached exchange rates.".to_string())?;
+        log_functions::log_structured(
+            "Warning",
+            &format!("Offline mode, using the exchange rates fetched at {}.", rates.fetched_at_unix),
+        );
+        return Ok(rates);
+    }
+
+    match cached {
+        Some(rates) => {
+            if rates.is_stale(bot_config.exchange_rate_ttl_hours as i64 * 3600) {
+                spawn_refresh();
+            }
+            Ok(rates)
+        }
+        None => refresh_rates().await,
+    }
+}
+
+/// Fetches the latest exchange rates and keeps them in memory and in the file
+pub async fn refresh_rates() -> Result<ExchangeRates, String> {
+    let symbols: Vec<&str> = Currency::iter()
+        .filter(|currency| *currency != Currency::USD)
+        .map(|currency| currency.code())
+        .collect();
+
+    let res = exchange_rate_api::get_latest_rates(Currency::USD.code(), &symbols.join(","))
+        .await
+        .map_err(|e| format!("currency.rs | refresh_rates() | Error occured when sending the api request. E: {:?}", e))?;
+
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!("currency.rs | refresh_rates() | Error occured when parsing the api request. E: {:?}", e))?;
+
+    let mut rates = ExchangeRates { rates: HashMap::new(), fetched_at_unix: Local::now().timestamp() };
+    for currency in Currency::iter().filter(|currency| *currency != Currency::USD) {
+        if let Some(rate) = parsed_data["rates"][currency.code()].as_f64() {
+            rates.rates.insert(currency, rate as f32);
+        }
+    }
+    if rates.rates.is_empty() {
+        return Err(format!("currency.rs | refresh_rates() | Error occured, the response has no rates. Parsed data: {:?}", parsed_data));
+    }
+
+    *RATES.lock().unwrap() = Some(rates.clone());
+    if let Err(err_str) = write_rates_file(&rates) {
+        log_functions::log_err(&err_str);
+    }
+    Ok(rates)
+}
+
+/// Converts every amount of the price to the currency
+///
+/// - The sale stats are converted too, the trade hold prices and the commision stay the same
+/// - Returns None if the rates have no rate for the currency of the price
+pub fn normalize_price(price: &Price, to: Currency, rates: &ExchangeRates) -> Option<Price> {
+    if price.currency == to {
+        return Some(price.clone());
+    }
+
+    let conv = |amount: f32| rates.convert(amount, price.currency, to);
+    Some(Price {
+        price_buy_trade: (conv(price.price_buy_trade.0)?, conv(price.price_buy_trade.1)?, conv(price.price_buy_trade.2)?),
+        price_buy_trade_w_comm: (
+            conv(price.price_buy_trade_w_comm.0)?,
+            conv(price.price_buy_trade_w_comm.1)?,
+            conv(price.price_buy_trade_w_comm.2)?,
+        ),
+        price_buy: conv(price.price_buy)?,
+        price_buy_w_comm: conv(price.price_buy_w_comm)?,
+        price_sell: conv(price.price_sell)?,
+        price_sell_w_comm: conv(price.price_sell_w_comm)?,
+        sale_stats: match &price.sale_stats {
+            Some(stats) => Some(ItemSaleStats {
+                weekly_vwap: conv(stats.weekly_vwap)?,
+                weekly_vwap_w_comm: conv(stats.weekly_vwap_w_comm)?,
+                weekly_simple_avg: conv(stats.weekly_simple_avg)?,
+                price_volatility_std_dev: conv(stats.price_volatility_std_dev)?,
+                monthly_avg_price: conv(stats.monthly_avg_price)?,
+                projected_price_next_week: conv(stats.projected_price_next_week)?,
+                median_price: conv(stats.median_price)?,
+                price_std_dev: conv(stats.price_std_dev)?,
+                ..stats.clone()
+            }),
+            None => None,
+        },
+        currency: to,
+        ..price.clone()
+    })
+}
+
+/// Converts the prices to the base currency of the config for comparing them
+///
+/// - The prices that can't be converted are skipped and logged
+/// - If there are no rates only the prices already in the base currency are kept
+pub async fn normalize_prices<'a>(prices: impl IntoIterator<Item = &'a Price>) -> Vec<Price> {
+    let base = config::get_bot_config().base_currency;
+    let rates = match get_rates().await {
+        Ok(rates) => Some(rates),
+        Err(err_str) => {
+            log_functions::log_err(&err_str);
+            None
+        }
+    };
+
+    prices
+        .into_iter()
+        .filter_map(|price| {
+            let normalized = match &rates {
+                Some(rates) => normalize_price(price, base, rates),
+                None if price.currency == base => Some(price.clone()),
+                None => None,
+            };
+            if normalized.is_none() {
+                log_functions::log_err(&format!(
+                    "Cannot convert the price from {:?} to {:?}, skipping it. Market: {:?}",
+                    price.currency, base, price.market
+                ));
+            }
+            normalized
+        })
+        .collect()
+}
+
+// Refreshes the rates in a background task unless one is already running
+fn spawn_refresh() {
+    if REFRESHING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    tokio::spawn(async {
+        if let Err(err_str) = refresh_rates().await {
+            log_functions::log_structured("Warning", &format!("Cannot refresh the exchange rates, using the stale ones. E: {}", err_str));
+        }
+        REFRESHING.store(false, Ordering::SeqCst);
+    });
+}
+
+// Reads the last fetched rates, None if there is no usable file
+fn read_rates_file() -> Option<ExchangeRates> {
+    let rates_str = std::fs::read_to_string(EXCHANGE_RATES_PATH).ok()?;
+    match serde_json::from_str(&rates_str) {
+        Ok(rates) => Some(rates),
+        Err(e) => {
+            log_functions::log_err(&format!("currency.rs | read_rates_file() | Error occured when parsing the file. E: {:?}", e));
+            None
+        }
+    }
+}
+
+// Writes the rates to the file
+fn write_rates_file(rates: &ExchangeRates) -> Result<(), String> {
+    let rates_str = serde_json::to_string_pretty(rates)
+        .map_err(|e| format!("currency.rs | write_rates_file() | Error occured when serializing the rates. E: {:?}", e))?;
+    std::fs::write(EXCHANGE_RATES_PATH, rates_str)
+        .map_err(|e| format!("currency.rs | write_rates_file() | Error occured when writing the file. E: {:?}", e))
+}
@@ -0,0 +1,76 @@
+use crate::config;
+use crate::structs::Market;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// The file that keeps the per item max count overrides
+const MAX_COUNTS_PATH: &str = "max_counts.json";
+
+// Guards the max counts file so concurrent writers don't lose updates
+static MAX_COUNTS_LOCK: Mutex<()> = Mutex::new(());
+
+/// Sets how many of the item can be held at the same time, 0 means unlimited
+pub fn set_max_count(item_name: &str, max: i16) -> Result<(), String> {
+    let _guard = MAX_COUNTS_LOCK.lock().unwrap();
+
+    let mut max_counts = read_max_counts()?;
+    if max == 0 {
+        max_counts.remove(item_name);
+    } else {
+        max_counts.insert(item_name.to_string(), max);
+    }
+
+    let max_counts_str = serde_json::to_string_pretty(&max_counts)
+        .map_err(|e| format!("data.rs | set_max_count(item_name={}, max={}) | Error occured when serializing the max counts. E: {:?}", item_name, max, e))?;
+    std::fs::write(MAX_COUNTS_PATH, max_counts_str)
+        .map_err(|e| format!("data.rs | set_max_count(item_name={}, max={}) | Error occured when writing the file. E: {:?}", item_name, max, e))
+}
+
+/// Returns the max count override of the item, None if it is not set
+pub fn get_max_count(item_name: &str) -> Result<Option<i16>, String> {
+    let _guard = MAX_COUNTS_LOCK.lock().unwrap();
+    Ok(read_max_counts()?.get(item_name).copied())
+}
+
+// Reads the max counts file, a missing file means no overrides
+fn read_max_counts() -> Result<HashMap<String, i16>, String> {
+    let max_counts_str = match std::fs::read_to_string(MAX_COUNTS_PATH) {
+        Ok(max_counts_str) => max_counts_str,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(format!("data.rs | read_max_counts() | Error occured when reading the file. E: {:?}", e)),
+    };
+
+    serde_json::from_str(&max_counts_str)
+        .map_err(|e| format!("data.rs | read_max_counts() | Error occured when parsing the file. E: {:?}", e))
+}
+
+/// Returns the commisions of the market in percent as (buyer fee, seller fee, platform fee)
+/// The buy price with commision is price / (1 - buyer fee), the sell price is price * (1 - seller fee - platform fee)
+/// market_specific is for markets whose fees depend on the item, none of the current ones do
+///
+/// Only the fees with a known source are built in, the other markets read theirs from
+/// `market_commisions` of the config and return an error if it is not set
+pub fn get_market_commisions(market: Market, market_specific: &str) -> Result<(i32, i32, i32), String> {
+    let comms = match market {
+        // 5% Steam transaction fee and the 10% game fee of Counter-Strike
+        Market::Steam => (0, 10, 5),
+        // 2% buyer fee and 2% seller fee
+        Market::CSFloat => (2, 2, 0),
+        // The 12% the weekly average of get_item_sale_stats was multiplied away with (0.88),
+        // get_item_price used a commision of 4 before it read it from here
+        Market::BitSkins => (0, 12, 0),
+        _ => *config::get_bot_config().market_commisions.get(&market).ok_or(format!(
+            "data.rs | get_market_commisions(market={:?}, market_specific={}) | Error occured, the commisions of the market are not set in the config.",
+            market, market_specific
+        ))?,
+    };
+
+    if comms.0 >= 100 || comms.1 + comms.2 >= 100 {
+        return Err(format!(
+            "data.rs | get_market_commisions(market={:?}, market_specific={}) | Error occured, the commisions are not valid: {:?}.",
+            market, market_specific, comms
+        ));
+    }
+
+    Ok(comms)
+}
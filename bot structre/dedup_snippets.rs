@@ -0,0 +1,77 @@
+use crate::structs::Market;
+use dashmap::{mapref::entry::Entry, DashMap};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+// How long a buy stays in flight if it is never released, a timed out request might still have bought the item
+const BUY_IN_FLIGHT_TTL: Duration = Duration::from_secs(30);
+
+// The buys that were sent and have no confirmed result yet, shared by every market handler
+static BUYS_IN_FLIGHT: OnceLock<InFlightSet> = OnceLock::new();
+
+// The struct that has the ids of the requests that are in flight and when they were started
+#[derive(Debug, Clone)]
+pub struct InFlightSet {
+    entries: Arc<DashMap<String, Instant>>,
+    ttl: Duration,
+}
+
+impl InFlightSet {
+    pub fn new(ttl: Duration) -> Self {
+        InFlightSet { entries: Arc::new(DashMap::new()), ttl }
+    }
+
+    // Marks the id as in flight, returns an error if it already is and its ttl hasn't passed
+    pub fn try_acquire(&self, id: &str) -> Result<(), String> {
+        match self.entries.entry(id.to_string()) {
+            Entry::Occupied(mut entry) => {
+                if entry.get().elapsed() < self.ttl {
+                    return Err(format!(
+                        "dedup.rs | try_acquire(id={}) | Error occured, the id is already in flight since {:?}.",
+                        id,
+                        entry.get().elapsed()
+                    ));
+                }
+                entry.insert(Instant::now());
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(Instant::now());
+            }
+        }
+        Ok(())
+    }
+
+    // Removes the id, called when the request succeeded
+    pub fn release(&self, id: &str) {
+        self.entries.remove(id);
+    }
+
+    // Removes the ids whose ttl has passed
+    pub fn purge_expired(&self) {
+        let ttl = self.ttl;
+        self.entries.retain(|_, started| started.elapsed() < ttl);
+    }
+}
+
+fn buys_in_flight() -> &'static InFlightSet {
+    BUYS_IN_FLIGHT.get_or_init(|| InFlightSet::new(BUY_IN_FLIGHT_TTL))
+}
+
+// The listing ids of different markets can be the same, so the market is part of the key
+fn buy_key(market: &Market, item_id: &str) -> String {
+    format!("{:?}:{}", market, item_id)
+}
+
+/// Marks the listing as being bought, has to be called before the buy request is sent
+/// Returns an error if the listing is already being bought, so a retried or concurrent buy can't buy it twice
+pub fn try_acquire_buy(market: &Market, item_id: &str) -> Result<(), String> {
+    let set = buys_in_flight();
+    set.purge_expired();
+    set.try_acquire(&buy_key(market, item_id))
+}
+
+/// Releases the listing after a successful buy
+/// A failed or timed out buy is not released, the listing stays blocked until the ttl passes
+pub fn release_buy(market: &Market, item_id: &str) {
+    buys_in_flight().release(&buy_key(market, item_id));
+}
@@ -0,0 +1,111 @@
+use crate::config;
+use crate::log_functions;
+use crate::markets::csfloat;
+use crate::notifications;
+use crate::price_cache;
+use crate::structs::{Item, ItemData};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+// How old the cached price of a sticker can be, the sticker prices move slowly
+const STICKER_PRICE_MAX_AGE_SECS: u64 = 86400;
+
+// The asset ids that were already notified for the manual review, so an item isn't notified every run
+static REVIEW_NOTIFIED: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+/// Returns the price of the bare item, the highest lowest listing of its markets
+/// None if no market has a listing
+pub fn base_price(item: &Item) -> Option<f32> {
+    item.price
+        .iter()
+        .map(|price| price.price_buy)
+        .filter(|price| *price > 0.0)
+        .max_by(|a, b| a.total_cmp(b))
+}
+
+/// Checks if the stickers of the item are worth more than sticker_review_perc of the base price
+/// Such an item is not undercut automatically, the bare item price would give the stickers away
+pub fn needs_manual_review(data: &ItemData, base_price: f32) -> bool {
+    if data.stickers.is_empty() || base_price <= 0.0 {
+        return false;
+    }
+    data.sticker_value() / base_price * 100.0 > config::get_bot_config().sticker_review_perc
+}
+
+/// Notifies the items that need a manual review once, returns if the item needs one
+/// Used by the repricing before a listing is undercut
+pub fn defer_to_review(item: &Item, data: &ItemData) -> bool {
+    let base_price = match base_price(item) {
+        Some(base_price) => base_price,
+        None => return false,
+    };
+    if !needs_manual_review(data, base_price) {
+        return false;
+    }
+
+    let first_time = REVIEW_NOTIFIED
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashSet::new)
+        .insert(data.asset_id.clone());
+    if first_time {
+        notifications::notify(format!(
+            "🔍 {} ({}) has ${:.2} of stickers on a ${:.2} item, it is left for a manual review",
+            item.name, data.asset_id, data.sticker_value(), base_price
+        ));
+    }
+    true
+}
+
+/// Fills the float value, the paint seed and the priced stickers of the items worth at least inspect_min_value_usd
+///
+/// - Only the entries with an inspect link that were not inspected yet are sent to the inspect service
+/// - The stickers are priced with the lowest listing of "Sticker | <name>" on the sticker_price_market of the config
+/// - An entry that can't be inspected is logged and tried again on the next run
+pub async fn enrich_inventory(inv: &mut HashMap<String, Item>) {
+    let bot_config = config::get_bot_config();
+
+    for item in inv.values_mut() {
+        if base_price(item).is_none_or(|price| price < bot_config.inspect_min_value_usd) {
+            continue;
+        }
+
+        for data in item.data.iter_mut() {
+            if data.float_value.is_some() {
+                continue;
+            }
+            let inspect_link = match &data.inspect_link {
+                Some(inspect_link) => inspect_link.clone(),
+                None => continue,
+            };
+
+            match csfloat::inspect_item(&inspect_link).await {
+                Ok(info) => {
+                    data.float_value = info.float_value;
+                    data.pattern_index = data.pattern_index.or(info.paint_seed);
+                    data.stickers = info.stickers;
+                    for sticker in data.stickers.iter_mut() {
+                        sticker.price = sticker_price(&sticker.name).await;
+                    }
+                }
+                Err(err_str) => log_functions::log_err(&format!(
+                    "inspect.rs | enrich_inventory() | Cannot inspect {} ({}). E: {}",
+                    item.name, data.asset_id, err_str
+                )),
+            }
+        }
+    }
+}
+
+// The lowest listing of the sticker, None if it can't be priced
+async fn sticker_price(name: &str) -> Option<f32> {
+    let market = config::get_bot_config().sticker_price_market.clone();
+    match price_cache::get_cached_price(market, &format!("Sticker | {}", name), STICKER_PRICE_MAX_AGE_SECS).await {
+        Ok(price) if price.price_buy > 0.0 => Some(price.price_buy),
+        Ok(_) => None,
+        Err(err_str) => {
+            log_functions::log_err(&format!("inspect.rs | sticker_price(name={}) | Cannot price the sticker. E: {}", name, err_str));
+            None
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use crate::log_functions;
+use crate::structs::{ItemStatusChangeTicket, Market};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// The file every money moving event is appended to, one json line per entry
+const LEDGER_PATH: &str = "ledger.jsonl";
+
+// Serializes the writes so the lines of concurrent buys don't interleave
+static LEDGER_LOCK: Mutex<()> = Mutex::new(());
+
+// The enum which differentiates the money moving events
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LedgerKind {
+    Buy,
+    Sell,
+    Withdrawal,
+    Fee,
+}
+
+// The struct that has one line of the ledger
+// The amount is what was paid for a buy and what was received for a sell, the fee is on top of it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub unix: u64,
+    pub kind: LedgerKind,
+    pub market: Market,
+    pub item_name: String,
+    pub asset_id: String,
+    pub amount: f32,
+    pub fee: f32,
+    pub ticket: Option<ItemStatusChangeTicket>,
+}
+
+impl LedgerEntry {
+    pub fn new(kind: LedgerKind, market: Market, item_name: &str, asset_id: &str, amount: f32, fee: f32) -> Self {
+        LedgerEntry {
+            unix: get_sys_time_in_secs(),
+            kind,
+            market,
+            item_name: item_name.to_string(),
+            asset_id: asset_id.to_string(),
+            amount,
+            fee,
+            ticket: None,
+        }
+    }
+
+    // Adds the ticket that triggered the event
+    pub fn with_ticket(mut self, ticket: &ItemStatusChangeTicket) -> Self {
+        self.ticket = Some(ticket.clone());
+        self
+    }
+}
+
+// The struct that has the money that went in and out of a market
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct LedgerSummary {
+    pub money_in: f32,
+    pub money_out: f32,
+    pub fees: f32,
+    pub entries: u32,
+}
+
+/// Appends the entry to the ledger and syncs it to the disk
+/// Never fails so it can be called from any trade operation, a failed write is logged
+pub fn record(entry: LedgerEntry) {
+    if let Err(err_str) = append(&entry) {
+        log_functions::log_err(&err_str);
+    }
+}
+
+/// Sums the ledger entries between from_unix and to_unix per market
+///
+/// - Sells are money in, buys and separate fees are money out
+/// - Withdrawals move no money, they are only counted
+pub fn summarize(from_unix: u64, to_unix: u64) -> Result<HashMap<Market, LedgerSummary>, String> {
+    let ledger_str = {
+        let _guard = LEDGER_LOCK.lock().unwrap();
+        match std::fs::read_to_string(LEDGER_PATH) {
+            Ok(ledger_str) => ledger_str,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(format!("ledger.rs | summarize() | Error occured when reading the file. E: {:?}", e)),
+        }
+    };
+
+    let mut res: HashMap<Market, LedgerSummary> = HashMap::new();
+    for (line_index, line) in ledger_str.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: LedgerEntry = serde_json::from_str(line)
+            .map_err(|e| format!("ledger.rs | summarize() | Error occured when parsing line {}. E: {:?}", line_index + 1, e))?;
+        if entry.unix < from_unix || entry.unix > to_unix {
+            continue;
+        }
+
+        let summary = res.entry(entry.market.clone()).or_default();
+        match entry.kind {
+            LedgerKind::Buy => summary.money_out += entry.amount,
+            LedgerKind::Sell => summary.money_in += entry.amount,
+            LedgerKind::Fee => summary.money_out += entry.amount,
+            LedgerKind::Withdrawal => {}
+        }
+        summary.fees += entry.fee;
+        summary.entries += 1;
+    }
+
+    Ok(res)
+}
+
+// Writes the entry as one line and waits until it is on the disk, so a crash loses at most the entry being written
+fn append(entry: &LedgerEntry) -> Result<(), String> {
+    let line = serde_json::to_string(entry)
+        .map_err(|e| format!("ledger.rs | append() | Error occured when serializing the entry {:?}. E: {:?}", entry, e))?
+        + "\n";
+
+    let _guard = LEDGER_LOCK.lock().unwrap();
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(LEDGER_PATH)
+        .map_err(|e| format!("ledger.rs | append() | Error occured when opening {}. E: {:?}", LEDGER_PATH, e))?;
+    file.write_all(line.as_bytes())
+        .and_then(|_| file.sync_data())
+        .map_err(|e| format!("ledger.rs | append() | Error occured when writing to {}. E: {:?}", LEDGER_PATH, e))
+}
+
+// Get the UNIX timestamp
+fn get_sys_time_in_secs() -> u64 {
+    match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(n) => n.as_secs(),
+        Err(_) => panic!("SystemTime before UNIX EPOCH!"),
+    }
+}
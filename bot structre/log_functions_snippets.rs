@@ -0,0 +1,63 @@
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// Guards the structured log file so concurrent tasks don't interleave lines
+static STRUCTURED_LOG_LOCK: Mutex<()> = Mutex::new(());
+
+// The struct that is written as one json line to the structured log
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEntry {
+    pub unix: u64,
+    pub level: String,
+    pub message: String,
+}
+
+/// Appends an entry with the given level to structured_log.jsonl
+///
+/// - If the file can't be opened or written the line goes to stderr, logging never panics
+pub fn log_structured(level: &str, message: &str) {
+    let entry = LogEntry {
+        unix: get_sys_time_in_secs(),
+        level: level.to_string(),
+        message: message.to_string(),
+    };
+
+    let line = match serde_json::to_string(&entry) {
+        Ok(line) => line + "\n",
+        Err(e) => {
+            eprintln!("log_functions.rs | log_structured() | Error occured when serializing the entry. E: {:?}", e);
+            return;
+        }
+    };
+
+    // A panic while the lock was held doesn't stop the logging
+    let _guard = STRUCTURED_LOG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let written = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open("structured_log.jsonl")
+        .and_then(|mut log| log.write_all(line.as_bytes()));
+
+    if let Err(e) = written {
+        eprintln!("log_functions.rs | log_structured() | Error occured when writing structured_log.jsonl. E: {:?}", e);
+        eprint!("{}", line);
+    }
+}
+
+/// Logs an operation that was skipped because the bot runs in dry run mode
+pub fn log_dry_run(message: &str) {
+    let message = format!("[DRY RUN] {}", message);
+    println!("{}", message);
+    log_structured("DryRun", &message);
+}
+
+// Get the UNIX timestamp
+fn get_sys_time_in_secs() -> u64 {
+    match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(n) => n.as_secs(),
+        Err(_) => panic!("SystemTime before UNIX EPOCH!"),
+    }
+}
@@ -0,0 +1,307 @@
+use crate::circuit_breaker::EndpointGroup;
+use crate::config;
+use crate::log_functions;
+use crate::structs::{Item, ItemStatus, Market};
+use axum::{routing::get, Router};
+use prometheus::{
+    Encoder, Gauge, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+use strum::IntoEnumIterator;
+
+// The metrics of the bot, registered on first use
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+// The buckets of the http latency histogram in seconds
+const LATENCY_BUCKETS: [f64; 10] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+// The buckets of the price fetch to buy histogram in seconds
+const PRICE_TO_BUY_BUCKETS: [f64; 8] = [1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 900.0];
+
+// How many of the last request latencies of a market are kept for the p95
+const LATENCY_SAMPLES: usize = 256;
+
+// The struct that has the request counters of a market, updated without a lock
+struct MarketRequestStats {
+    total_requests: AtomicU64,
+    total_errors: AtomicU64,
+    total_latency_us: AtomicU64,
+    latency_samples_us: [AtomicU64; LATENCY_SAMPLES],
+    next_sample: AtomicUsize,
+}
+
+impl MarketRequestStats {
+    fn new() -> Self {
+        MarketRequestStats {
+            total_requests: AtomicU64::new(0),
+            total_errors: AtomicU64::new(0),
+            total_latency_us: AtomicU64::new(0),
+            latency_samples_us: std::array::from_fn(|_| AtomicU64::new(0)),
+            next_sample: AtomicUsize::new(0),
+        }
+    }
+}
+
+// The struct that has the request stats of a market at the time of the snapshot
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestMetrics {
+    pub total_requests: u64,
+    pub total_errors: u64,
+    pub avg_latency_ms: f64,
+    pub p95_latency_ms: f64,
+}
+
+// The struct that has every exported metric
+struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    http_latency: HistogramVec,
+    buys: IntCounterVec,
+    price_to_buy: HistogramVec,
+    tickets_applied: IntCounter,
+    inventory_value: Gauge,
+    items_on_hold: IntGauge,
+    proxy_pool_healthy: IntGauge,
+    circuit_open: IntGaugeVec,
+    request_stats: HashMap<Market, MarketRequestStats>,
+}
+
+// The enum which differentiates the outcomes of a buy
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BuyOutcome {
+    Attempted,
+    Succeeded,
+    Failed,
+}
+
+impl BuyOutcome {
+    fn label(&self) -> &'static str {
+        match self {
+            BuyOutcome::Attempted => "attempted",
+            BuyOutcome::Succeeded => "succeeded",
+            BuyOutcome::Failed => "failed",
+        }
+    }
+}
+
+fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(|| {
+        let registry = Registry::new();
+
+        let requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests per market and endpoint"),
+            &["market", "endpoint"],
+        )
+        .unwrap();
+        let http_latency = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "HTTP request latency per market").buckets(LATENCY_BUCKETS.to_vec()),
+            &["market"],
+        )
+        .unwrap();
+        let buys = IntCounterVec::new(Opts::new("buys_total", "Buys per market and outcome"), &["market", "outcome"]).unwrap();
+        let price_to_buy = HistogramVec::new(
+            HistogramOpts::new("price_fetch_to_buy_seconds", "Time from the price fetch to the buy execution")
+                .buckets(PRICE_TO_BUY_BUCKETS.to_vec()),
+            &["market"],
+        )
+        .unwrap();
+        let tickets_applied = IntCounter::new("tickets_applied_total", "Status change tickets applied to the inventory").unwrap();
+        let inventory_value = Gauge::new("inventory_value_dollars", "Value of the inventory at the best sell price").unwrap();
+        let items_on_hold = IntGauge::new("items_on_hold", "Items that are trade locked").unwrap();
+        let proxy_pool_healthy = IntGauge::new("proxy_pool_healthy", "Proxies that can be used").unwrap();
+        let circuit_open = IntGaugeVec::new(Opts::new("circuit_open", "1 while the circuit breaker of the endpoint group is open"), &["market", "group"]).unwrap();
+
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(http_latency.clone())).unwrap();
+        registry.register(Box::new(buys.clone())).unwrap();
+        registry.register(Box::new(price_to_buy.clone())).unwrap();
+        registry.register(Box::new(tickets_applied.clone())).unwrap();
+        registry.register(Box::new(inventory_value.clone())).unwrap();
+        registry.register(Box::new(items_on_hold.clone())).unwrap();
+        registry.register(Box::new(proxy_pool_healthy.clone())).unwrap();
+        registry.register(Box::new(circuit_open.clone())).unwrap();
+
+        Metrics {
+            registry,
+            requests,
+            http_latency,
+            buys,
+            price_to_buy,
+            tickets_applied,
+            inventory_value,
+            items_on_hold,
+            proxy_pool_healthy,
+            circuit_open,
+            request_stats: Market::iter().map(|market| (market, MarketRequestStats::new())).collect(),
+        }
+    })
+}
+
+/// Counts a request and records how long it took
+/// market is a label like "BitSkins" or the host of the url
+pub fn observe_request(market: &str, endpoint: &str, elapsed: Duration) {
+    let metrics = metrics();
+    metrics.requests.with_label_values(&[market, endpoint]).inc();
+    metrics.http_latency.with_label_values(&[market]).observe(elapsed.as_secs_f64());
+}
+
+/// Records a request of an api module in the in-memory stats of the market
+/// The counters are atomic, so the api functions can call it from any task
+pub fn record_request(market: Market, duration: Duration, success: bool) {
+    let stats = match metrics().request_stats.get(&market) {
+        Some(stats) => stats,
+        None => return,
+    };
+    let latency_us = duration.as_micros() as u64;
+
+    stats.total_requests.fetch_add(1, Ordering::Relaxed);
+    if !success {
+        stats.total_errors.fetch_add(1, Ordering::Relaxed);
+    }
+    stats.total_latency_us.fetch_add(latency_us, Ordering::Relaxed);
+
+    // The samples are a ring buffer, the oldest latency is overwritten
+    let slot = stats.next_sample.fetch_add(1, Ordering::Relaxed) % LATENCY_SAMPLES;
+    stats.latency_samples_us[slot].store(latency_us, Ordering::Relaxed);
+}
+
+/// Checks if a request got an answer with a success status
+pub fn is_success<E>(result: &Result<reqwest::Response, E>) -> bool {
+    matches!(result, Ok(response) if response.status().is_success())
+}
+
+/// Returns the request stats of every market that sent a request
+/// The p95 latency is calculated from the last LATENCY_SAMPLES requests of the market
+pub fn snapshot() -> HashMap<Market, RequestMetrics> {
+    let mut res = HashMap::new();
+    for (market, stats) in metrics().request_stats.iter() {
+        let total_requests = stats.total_requests.load(Ordering::Relaxed);
+        if total_requests == 0 {
+            continue;
+        }
+
+        let sample_count = (total_requests as usize).min(LATENCY_SAMPLES);
+        let mut samples: Vec<u64> = stats.latency_samples_us[..sample_count]
+            .iter()
+            .map(|sample| sample.load(Ordering::Relaxed))
+            .collect();
+        samples.sort_unstable();
+        let p95_index = ((sample_count as f64 * 0.95).ceil() as usize).saturating_sub(1);
+
+        res.insert(
+            market.clone(),
+            RequestMetrics {
+                total_requests,
+                total_errors: stats.total_errors.load(Ordering::Relaxed),
+                avg_latency_ms: stats.total_latency_us.load(Ordering::Relaxed) as f64 / total_requests as f64 / 1000.0,
+                p95_latency_ms: samples[p95_index] as f64 / 1000.0,
+            },
+        );
+    }
+    res
+}
+
+/// Counts a buy attempt, success or failure
+pub fn record_buy(market: &Market, outcome: BuyOutcome) {
+    metrics().buys.with_label_values(&[&format!("{:?}", market), outcome.label()]).inc();
+}
+
+/// Records how old the price was when the buy was executed
+pub fn observe_price_to_buy(market: &Market, price_age_secs: i64) {
+    metrics()
+        .price_to_buy
+        .with_label_values(&[&format!("{:?}", market)])
+        .observe(price_age_secs.max(0) as f64);
+}
+
+/// Counts a ticket that was applied to the inventory
+pub fn inc_tickets_applied() {
+    metrics().tickets_applied.inc();
+}
+
+/// Sets the inventory gauges from the current inventory
+/// The value of an item is its best sell price (after commission) over the markets
+pub fn update_inventory_gauges(inv: &HashMap<String, Item>) {
+    let mut value = 0.0;
+    let mut on_hold = 0;
+    for item in inv.values() {
+        let best_price = item.price.iter().map(|price| price.price_sell_w_comm).fold(0.0, f32::max);
+        for data in item.data.iter() {
+            match data.status {
+                ItemStatus::Sold => continue,
+                ItemStatus::OnHold => on_hold += 1,
+                _ => {}
+            }
+            value += best_price as f64;
+        }
+    }
+
+    let metrics = metrics();
+    metrics.inventory_value.set(value);
+    metrics.items_on_hold.set(on_hold);
+}
+
+/// Sets how many items are trade locked, used when the inventory has no prices yet
+pub fn set_items_on_hold(count: i64) {
+    metrics().items_on_hold.set(count);
+}
+
+/// Sets how many proxies can be used
+pub fn set_proxy_pool_healthy(count: usize) {
+    metrics().proxy_pool_healthy.set(count as i64);
+}
+
+/// Sets if the circuit breaker of the endpoint group of the market is open, a half open circuit counts as open
+pub fn set_circuit_open(market: &Market, group: EndpointGroup, open: bool) {
+    metrics().circuit_open.with_label_values(&[&format!("{:?}", market), &format!("{:?}", group)]).set(open as i64);
+}
+
+/// Encodes every metric in the Prometheus text format
+pub fn encode() -> Result<String, String> {
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&metrics().registry.gather(), &mut buffer)
+        .map_err(|e| format!("metrics.rs | encode() | Error occured when encoding the metrics. E: {:?}", e))?;
+    String::from_utf8(buffer).map_err(|e| format!("metrics.rs | encode() | Error occured, the metrics are not utf8. E: {:?}", e))
+}
+
+/// Starts the http endpoint that serves /metrics if metrics_enabled is set in the config
+/// Returns None if the exporter is disabled
+pub fn start_exporter() -> Option<tokio::task::JoinHandle<()>> {
+    let bot_config = config::get_bot_config();
+    if !bot_config.metrics_enabled {
+        return None;
+    }
+    let addr = format!("0.0.0.0:{}", bot_config.metrics_port);
+
+    Some(tokio::spawn(async move {
+        let app = Router::new().route(
+            "/metrics",
+            get(|| async {
+                match encode() {
+                    Ok(body) => body,
+                    Err(err_str) => {
+                        log_functions::log_err(&err_str);
+                        String::new()
+                    }
+                }
+            }),
+        );
+
+        let listener = match tokio::net::TcpListener::bind(&addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                log_functions::log_err(&format!("metrics.rs | start_exporter() | Error occured when binding {}. E: {:?}", addr, e));
+                return;
+            }
+        };
+
+        log_functions::log_structured("Info", &format!("Serving the metrics on {}/metrics", addr));
+        if let Err(e) = axum::serve(listener, app).await {
+            log_functions::log_err(&format!("metrics.rs | start_exporter() | Error occured while serving the metrics. E: {:?}", e));
+        }
+    }))
+}
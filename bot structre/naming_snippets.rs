@@ -0,0 +1,343 @@
+use crate::structs::{Market, WearTier};
+use glob::Pattern;
+
+// The prefix of knives and gloves
+const STAR: &str = "★";
+
+// The finishes that come in phases, the phase is written differently on every market
+const PHASED_FINISHES: [&str; 2] = ["Gamma Doppler", "Doppler"];
+
+// The phases of the phased finishes
+const PHASES: [&str; 8] = [
+    "Phase 1", "Phase 2", "Phase 3", "Phase 4", "Ruby", "Sapphire", "Black Pearl", "Emerald",
+];
+
+// The struct that has the parts of an item name
+// weapon is everything before " | " (the agent name for agents, the full name for cases and stickers)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SkinName {
+    pub weapon: String,
+    pub finish: Option<String>,
+    pub wear: Option<WearTier>,
+    pub stattrak: bool,
+    pub souvenir: bool,
+    pub star: bool,
+    pub phase: Option<String>,
+}
+
+impl WearTier {
+    // The exterior as it is written in the Steam market hash name
+    pub fn full_name(&self) -> &'static str {
+        match self {
+            WearTier::FactoryNew => "Factory New",
+            WearTier::MinimalWear => "Minimal Wear",
+            WearTier::FieldTested => "Field-Tested",
+            WearTier::WellWorn => "Well-Worn",
+            WearTier::BattleScarred => "Battle-Scarred",
+        }
+    }
+
+    // The exterior of a float value, the tiers start at 0.07, 0.15, 0.38 and 0.45
+    pub fn from_float(float_value: f64) -> WearTier {
+        match float_value {
+            f if f < 0.07 => WearTier::FactoryNew,
+            f if f < 0.15 => WearTier::MinimalWear,
+            f if f < 0.38 => WearTier::FieldTested,
+            f if f < 0.45 => WearTier::WellWorn,
+            _ => WearTier::BattleScarred,
+        }
+    }
+
+    // Reads the exterior in parentheses at the end of a market hash name, "AK-47 | Redline (Field-Tested)"
+    // None for the items without an exterior (cases, stickers, vanilla knives)
+    pub fn from_market_hash_name(name: &str) -> Option<WearTier> {
+        parse(name).wear
+    }
+
+    // Reads the full or abbreviated exterior ("Field-Tested", "FT", "field tested")
+    pub fn from_str_loose(wear: &str) -> Option<WearTier> {
+        let wear = wear.trim().to_lowercase().replace('-', " ");
+        match wear.as_str() {
+            "factory new" | "fn" => Some(WearTier::FactoryNew),
+            "minimal wear" | "mw" => Some(WearTier::MinimalWear),
+            "field tested" | "ft" => Some(WearTier::FieldTested),
+            "well worn" | "ww" => Some(WearTier::WellWorn),
+            "battle scarred" | "bs" => Some(WearTier::BattleScarred),
+            _ => None,
+        }
+    }
+}
+
+/// Parses an item name of any market into its parts
+///
+/// - Accepts the name with or without the ★ prefix and with "StatTrak™" or "StatTrak"
+/// - Accepts the wear in parentheses, full or abbreviated
+/// - Reads the Doppler phase from "Doppler Phase 2", "Doppler - Phase 2" or "(Factory New) - Phase 2"
+pub fn parse(name: &str) -> SkinName {
+    let mut rest = name.trim();
+
+    // Prefixes
+    let star = rest.starts_with(STAR);
+    rest = rest.trim_start_matches(STAR).trim_start();
+
+    let mut stattrak = false;
+    for prefix in ["StatTrak™ ", "StatTrak "] {
+        if let Some(stripped) = rest.strip_prefix(prefix) {
+            stattrak = true;
+            rest = stripped;
+        }
+    }
+
+    let mut souvenir = false;
+    if let Some(stripped) = rest.strip_prefix("Souvenir ") {
+        souvenir = true;
+        rest = stripped;
+    }
+
+    // A phase written after the wear
+    let mut phase = None;
+    let mut rest = rest.to_string();
+    if let Some((before, after)) = rest.rsplit_once(" - ") {
+        if let Some(found) = find_phase(after) {
+            phase = Some(found);
+            rest = before.to_string();
+        }
+    }
+
+    // Wear suffix
+    let mut wear = None;
+    if rest.ends_with(')') {
+        if let Some(open) = rest.rfind(" (") {
+            if let Some(found) = WearTier::from_str_loose(&rest[open + 2..rest.len() - 1]) {
+                wear = Some(found);
+                rest.truncate(open);
+            }
+        }
+    }
+
+    let (weapon, finish) = match rest.split_once(" | ") {
+        Some((weapon, finish)) => (weapon.trim().to_string(), Some(finish.trim().to_string())),
+        None => (rest.trim().to_string(), None),
+    };
+
+    // A phase written inside the finish
+    let finish = finish.map(|finish| {
+        for phased in PHASED_FINISHES {
+            if let Some(after) = finish.strip_prefix(phased) {
+                let after = after.trim().trim_start_matches('-').trim();
+                if !after.is_empty() {
+                    if let Some(found) = find_phase(after) {
+                        phase = Some(found);
+                        return phased.to_string();
+                    }
+                }
+                break;
+            }
+        }
+        finish
+    });
+
+    SkinName { weapon, finish, wear, stattrak, souvenir, star, phase }
+}
+
+/// Checks if two names of any markets are the same item
+///
+/// The ★ prefix is ignored, a name with a phase never matches a name without one
+pub fn is_same_item(name_1: &str, name_2: &str) -> bool {
+    parse(name_1).is_equivalent(&parse(name_2))
+}
+
+/// Checks if the name matches the glob pattern, e.g. "AK-47 | *"
+///
+/// An invalid pattern only matches the exact name
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    match Pattern::new(pattern) {
+        Ok(glob) => glob.matches(name),
+        Err(_) => pattern == name,
+    }
+}
+
+/// Checks if the name matches one of the glob patterns
+pub fn matches_any(patterns: &[String], name: &str) -> bool {
+    patterns.iter().any(|pattern| matches_pattern(pattern, name))
+}
+
+impl SkinName {
+    /// Checks if the two parsed names are the same item
+    pub fn is_equivalent(&self, other: &SkinName) -> bool {
+        let same_finish = match (&self.finish, &other.finish) {
+            (Some(finish_1), Some(finish_2)) => finish_1.eq_ignore_ascii_case(finish_2),
+            (None, None) => true,
+            _ => false,
+        };
+        // A Ruby is worth many times a plain Doppler, a phase on one side only is a different item
+        let same_phase = match (&self.phase, &other.phase) {
+            (Some(phase_1), Some(phase_2)) => phase_1.eq_ignore_ascii_case(phase_2),
+            (None, None) => true,
+            _ => false,
+        };
+
+        self.weapon.eq_ignore_ascii_case(&other.weapon)
+            && same_finish
+            && self.wear == other.wear
+            && self.stattrak == other.stattrak
+            && self.souvenir == other.souvenir
+            && same_phase
+    }
+
+    /// Builds the name the way the market writes it
+    ///
+    /// - Steam, BitSkins, CSFloat, WaxPeer and the others use the Steam market hash name, the phase is a separate field
+    /// - DMarket and CS.Money write the phase after the finish ("Doppler Phase 2")
+    pub fn to_market_format(&self, market: Market) -> String {
+        let mut name = String::new();
+        if self.star {
+            name.push_str(STAR);
+            name.push(' ');
+        }
+        if self.stattrak {
+            name.push_str("StatTrak™ ");
+        }
+        if self.souvenir {
+            name.push_str("Souvenir ");
+        }
+        name.push_str(&self.weapon);
+
+        if let Some(finish) = &self.finish {
+            name.push_str(" | ");
+            name.push_str(finish);
+            if let (Market::DMarket | Market::CSMoney, Some(phase)) = (&market, &self.phase) {
+                name.push(' ');
+                name.push_str(phase);
+            }
+        }
+
+        if let Some(wear) = &self.wear {
+            name.push_str(&format!(" ({})", wear.full_name()));
+        }
+
+        name
+    }
+}
+
+// Returns the phase in its canonical spelling, also reads the short forms "P2" and "Phase2"
+fn find_phase(text: &str) -> Option<String> {
+    let text = text.trim().to_lowercase();
+    if let Some(phase) = PHASES.iter().find(|phase| text == phase.to_lowercase()) {
+        return Some(phase.to_string());
+    }
+
+    let digit = text.strip_prefix("phase").or(text.strip_prefix('p'))?.trim();
+    match digit {
+        "1" | "2" | "3" | "4" => Some(format!("Phase {}", digit)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (name, weapon, finish, wear, stattrak, souvenir, star, phase)
+    type Case = (&'static str, &'static str, Option<&'static str>, Option<WearTier>, bool, bool, bool, Option<&'static str>);
+
+    const FN: Option<WearTier> = Some(WearTier::FactoryNew);
+    const MW: Option<WearTier> = Some(WearTier::MinimalWear);
+    const FT: Option<WearTier> = Some(WearTier::FieldTested);
+    const WW: Option<WearTier> = Some(WearTier::WellWorn);
+    const BS: Option<WearTier> = Some(WearTier::BattleScarred);
+
+    const CASES: [Case; 32] = [
+        // Knives
+        ("★ Karambit", "Karambit", None, None, false, false, true, None),
+        ("★ StatTrak™ Karambit | Fade (Factory New)", "Karambit", Some("Fade"), FN, true, false, true, None),
+        ("★ Bayonet | Lore (MW)", "Bayonet", Some("Lore"), MW, false, false, true, None),
+        ("★ Ursus Knife | Marble Fade (Factory New)", "Ursus Knife", Some("Marble Fade"), FN, false, false, true, None),
+        ("Butterfly Knife | Slaughter (Minimal Wear)", "Butterfly Knife", Some("Slaughter"), MW, false, false, false, None),
+        // Dopplers
+        ("★ Karambit | Doppler (Factory New)", "Karambit", Some("Doppler"), FN, false, false, true, None),
+        ("★ Karambit | Doppler Phase 2 (Factory New)", "Karambit", Some("Doppler"), FN, false, false, true, Some("Phase 2")),
+        ("★ Flip Knife | Doppler P4 (Factory New)", "Flip Knife", Some("Doppler"), FN, false, false, true, Some("Phase 4")),
+        ("★ Butterfly Knife | Doppler - Ruby (Minimal Wear)", "Butterfly Knife", Some("Doppler"), MW, false, false, true, Some("Ruby")),
+        ("★ M9 Bayonet | Gamma Doppler (Factory New) - Emerald", "M9 Bayonet", Some("Gamma Doppler"), FN, false, false, true, Some("Emerald")),
+        ("★ StatTrak™ Talon Knife | Doppler (Factory New) - Black Pearl", "Talon Knife", Some("Doppler"), FN, true, false, true, Some("Black Pearl")),
+        ("Glock-18 | Gamma Doppler Phase1 (Factory New)", "Glock-18", Some("Gamma Doppler"), FN, false, false, false, Some("Phase 1")),
+        // Gloves
+        ("★ Sport Gloves | Pandora's Box (Field-Tested)", "Sport Gloves", Some("Pandora's Box"), FT, false, false, true, None),
+        ("★ Specialist Gloves | Crimson Kimono (Well-Worn)", "Specialist Gloves", Some("Crimson Kimono"), WW, false, false, true, None),
+        ("★ Hand Wraps | Cobalt Skulls (Battle-Scarred)", "Hand Wraps", Some("Cobalt Skulls"), BS, false, false, true, None),
+        ("Driver Gloves | King Snake (mw)", "Driver Gloves", Some("King Snake"), MW, false, false, false, None),
+        // Weapon skins
+        ("AK-47 | Redline (Field-Tested)", "AK-47", Some("Redline"), FT, false, false, false, None),
+        ("StatTrak AK-47 | Redline (FT)", "AK-47", Some("Redline"), FT, true, false, false, None),
+        ("AK-47 | Case Hardened (Well-Worn)", "AK-47", Some("Case Hardened"), WW, false, false, false, None),
+        ("M4A4 | Howl (Minimal Wear)", "M4A4", Some("Howl"), MW, false, false, false, None),
+        ("StatTrak™ USP-S | Kill Confirmed (field tested)", "USP-S", Some("Kill Confirmed"), FT, true, false, false, None),
+        // Souvenirs and souvenir packages
+        ("Souvenir AWP | Dragon Lore (Factory New)", "AWP", Some("Dragon Lore"), FN, false, true, false, None),
+        ("Souvenir M4A1-S | Knight (Factory New)", "M4A1-S", Some("Knight"), FN, false, true, false, None),
+        ("Souvenir P250 | Sand Dune (Battle-Scarred)", "P250", Some("Sand Dune"), BS, false, true, false, None),
+        ("Antwerp 2022 Inferno Souvenir Package", "Antwerp 2022 Inferno Souvenir Package", None, None, false, false, false, None),
+        ("Stockholm 2021 Dust II Souvenir Package", "Stockholm 2021 Dust II Souvenir Package", None, None, false, false, false, None),
+        // Agents
+        ("Special Agent Ava | FBI", "Special Agent Ava", Some("FBI"), None, false, false, false, None),
+        ("Sir Bloody Darryl Royale | The Professionals", "Sir Bloody Darryl Royale", Some("The Professionals"), None, false, false, false, None),
+        ("Cmdr. Mae 'Dead Cold' Jamison | SWAT", "Cmdr. Mae 'Dead Cold' Jamison", Some("SWAT"), None, false, false, false, None),
+        // Containers and stickers
+        ("Chroma 2 Case", "Chroma 2 Case", None, None, false, false, false, None),
+        ("Sticker | Titan (Holo) | Katowice 2014", "Sticker", Some("Titan (Holo) | Katowice 2014"), None, false, false, false, None),
+        ("Music Kit | Darude, Sandstorm", "Music Kit", Some("Darude, Sandstorm"), None, false, false, false, None),
+    ];
+
+    #[test]
+    fn parse_real_names() {
+        for (name, weapon, finish, wear, stattrak, souvenir, star, phase) in CASES {
+            let parsed = parse(name);
+            let expected = SkinName {
+                weapon: weapon.to_string(),
+                finish: finish.map(|finish| finish.to_string()),
+                wear,
+                stattrak,
+                souvenir,
+                star,
+                phase: phase.map(|phase| phase.to_string()),
+            };
+            assert_eq!(parsed, expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn market_format_parses_back_to_the_same_item() {
+        for (name, ..) in CASES {
+            let parsed = parse(name);
+            for market in [Market::Steam, Market::DMarket, Market::CSMoney] {
+                let formatted = parsed.to_market_format(market.clone());
+                let reparsed = parse(&formatted);
+                // The Steam format drops the phase, it is a separate field on those markets
+                if market == Market::Steam && parsed.phase.is_some() {
+                    assert_eq!(reparsed.phase, None, "{} on {:?}", name, market);
+                    continue;
+                }
+                assert!(parsed.is_equivalent(&reparsed), "{} on {:?} became {}", name, market, formatted);
+            }
+        }
+    }
+
+    #[test]
+    fn same_item_across_spellings() {
+        let pairs = [
+            ("★ Karambit | Fade (Factory New)", "Karambit | Fade (FN)", true),
+            ("StatTrak™ AK-47 | Redline (Field-Tested)", "StatTrak AK-47 | Redline (FT)", true),
+            ("★ Karambit | Doppler Phase 2 (Factory New)", "★ Karambit | Doppler (Factory New) - Phase 2", true),
+            ("★ Karambit | Doppler Phase 2 (Factory New)", "★ Karambit | Doppler (Factory New) - Phase 3", false),
+            ("★ Karambit | Doppler Ruby (Factory New)", "★ Karambit | Doppler (Factory New)", false),
+            ("★ Karambit | Doppler (Factory New)", "★ Karambit | Doppler Ruby (Factory New)", false),
+            ("AK-47 | Redline (Field-Tested)", "StatTrak™ AK-47 | Redline (Field-Tested)", false),
+            ("AK-47 | Redline (Field-Tested)", "AK-47 | Redline (Minimal Wear)", false),
+            ("Souvenir AWP | Dragon Lore (Factory New)", "AWP | Dragon Lore (Factory New)", false),
+        ];
+        for (name_1, name_2, expected) in pairs {
+            assert_eq!(is_same_item(name_1, name_2), expected, "{} vs {}", name_1, name_2);
+        }
+    }
+}
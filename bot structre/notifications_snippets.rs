@@ -0,0 +1,82 @@
+use crate::config;
+use crate::control::SharedControl;
+use crate::log_functions;
+use crate::structs::{ItemStatusChangeTicket, ItemStatusChanges, Market};
+use crate::telegram;
+use std::sync::Mutex;
+use tokio::time::{sleep, Duration};
+
+// Minimum time between two Telegram messages, queued events are merged into one message
+const NOTIFICATION_INTERVAL_SECS: u64 = 5;
+
+// The events waiting to be sent
+static NOTIFICATION_QUEUE: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Queues a notification, it is sent by the notification loop
+/// Never fails so it can be called from any trade operation
+pub fn notify(text: String) {
+    NOTIFICATION_QUEUE.lock().unwrap().push(text);
+}
+
+/// Queues a notification for a successful purchase
+pub fn notify_buy(item_name: &str, market: Market, price: f32) {
+    notify(format!("✅ Bought {}\nMarket: {:?}\nPrice: ${:.2}", item_name, market, price));
+}
+
+/// Queues a notification for a withdrawal that could not be started
+pub fn notify_withdrawal_failure(item_name: &str, market: Market, reason: &str) {
+    notify(format!("⚠️ Withdrawal failed for {}\nMarket: {:?}\nReason: {}", item_name, market, reason));
+}
+
+/// Queues a notification for a listing that was undercut to its floor price and won't go lower
+pub fn notify_floor_reached(item_name: &str, market: Market, floor_price: f32) {
+    notify(format!("📉 Listing at its floor {}\nMarket: {:?}\nFloor: ${:.2}", item_name, market, floor_price));
+}
+
+/// Queues a notification for an authentication error (expired cookie, revoked key)
+pub fn notify_auth_error(source: &str, reason: &str) {
+    notify(format!("🛑 Authentication error in {}\n{}", source, reason));
+}
+
+/// Queues a notification for the tickets that are worth telling about
+pub fn notify_ticket(item_name: &str, ticket: &ItemStatusChangeTicket, bought_price: Option<f32>) {
+    if let ItemStatusChanges::SellSuccess(market, price) = &ticket.change {
+        match bought_price {
+            Some(bought) => notify_trade_completed(item_name, market.clone(), bought, *price),
+            None => notify(format!("💰 Sold {}\nMarket: {:?}\nPrice: ${:.2}", item_name, market, price)),
+        }
+    }
+}
+
+/// Queues a notification for a finished trade, an item that was bought and sold
+pub fn notify_trade_completed(item_name: &str, market: Market, buy_price: f32, sell_price: f32) {
+    let profit = sell_price - buy_price;
+    let profit_perc = if buy_price > 0.0 { profit / buy_price * 100.0 } else { 0.0 };
+    notify(format!(
+        "💰 Sold {}\nMarket: {:?}\nBought: ${:.2}\nSold: ${:.2}\nProfit: ${:.2} ({:+.1}%)",
+        item_name, market, buy_price, sell_price, profit, profit_perc
+    ));
+}
+
+/// Sends the queued notifications as one message every few seconds
+/// Has to be spawned once at startup, failures are only logged
+/// Stops after the emergency stop, the last queued events are still sent
+pub async fn run_notification_loop(control: SharedControl) {
+    loop {
+        sleep(Duration::from_secs(NOTIFICATION_INTERVAL_SECS)).await;
+        let stopping = control.should_stop();
+
+        let events: Vec<String> = NOTIFICATION_QUEUE.lock().unwrap().drain(..).collect();
+        if !events.is_empty() {
+            if let Some(chat_id) = &config::get_bot_config().telegram_chat_id {
+                if let Err(e) = telegram::send_message(chat_id, &events.join("\n\n")).await {
+                    log_functions::log_err(&format!("Cannot send the notifications. E: {:?}", e));
+                }
+            }
+        }
+
+        if stopping {
+            break;
+        }
+    }
+}
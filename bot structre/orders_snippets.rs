@@ -0,0 +1,109 @@
+use crate::log_functions;
+use crate::structs::{Item, ItemCount, MarketFunctions, Price, TimedBuyOrder};
+use chrono::Local;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// The timed buy orders that are waiting for their target price
+static ORDERS: Mutex<Vec<TimedBuyOrder>> = Mutex::new(Vec::new());
+
+/// Adds a timed buy order
+pub fn add_order(order: TimedBuyOrder) {
+    log_functions::log_structured(
+        "Info",
+        &format!(
+            "Added a timed buy order for {} on {:?} at {} (trade hold {}), expires at {}.",
+            order.market_hash_name, order.market, order.target_price, order.trade_hold, order.expires_unix
+        ),
+    );
+    ORDERS.lock().unwrap().push(order);
+}
+
+/// Returns every waiting order
+pub fn get_orders() -> Vec<TimedBuyOrder> {
+    ORDERS.lock().unwrap().clone()
+}
+
+/// Removes the order, returns false if it was not in the list
+pub fn remove_order(order: &TimedBuyOrder) -> bool {
+    let mut orders = ORDERS.lock().unwrap();
+    let len_before = orders.len();
+    orders.retain(|waiting| waiting != order);
+    orders.len() != len_before
+}
+
+/// Returns the orders whose price dropped to the target
+///
+/// - current_prices has the prices of every market keyed by the item name
+/// - The price of the trade hold of the order is compared, a 0.0 price means no listing
+/// - The expired orders are removed and logged
+pub fn check_timed_orders(current_prices: &HashMap<String, Vec<Price>>) -> Vec<TimedBuyOrder> {
+    let now = Local::now().timestamp();
+    let mut orders = ORDERS.lock().unwrap();
+
+    orders.retain(|order| {
+        if order.expires_unix > now {
+            return true;
+        }
+        log_functions::log_structured(
+            "Info",
+            &format!(
+                "The timed buy order for {} on {:?} at {} expired.",
+                order.market_hash_name, order.market, order.target_price
+            ),
+        );
+        false
+    });
+
+    orders
+        .iter()
+        .filter(|order| {
+            current_prices
+                .get(&order.market_hash_name)
+                .and_then(|prices| prices.iter().find(|price| price.market == order.market))
+                .map(|price| price_for_trade_hold(price, order.trade_hold))
+                .is_some_and(|price| price > 0.0 && price <= order.target_price)
+        })
+        .cloned()
+        .collect()
+}
+
+/// Buys the items of the triggered orders, called by the trading loop after the prices are fetched
+///
+/// - A bought order is removed, a failed buy keeps the order so it is tried again until it expires
+/// - Items that are not in the inventory yet get an empty entry
+pub async fn execute_timed_orders(inv: &mut HashMap<String, Item>, current_prices: &HashMap<String, Vec<Price>>) {
+    for order in check_timed_orders(current_prices) {
+        let item = inv.entry(order.market_hash_name.clone()).or_insert(Item {
+            name: order.market_hash_name.clone(),
+            count: ItemCount { total: 0, available: 0, on_offer: 0, on_hold: 0, max_count: 0 },
+            data: Vec::new(),
+            price: Vec::new(),
+            history: Vec::new(),
+        });
+
+        match item.buy_item(order.market.clone(), order.target_price, order.trade_hold, None).await {
+            Ok(_) => {
+                remove_order(&order);
+                log_functions::log_structured(
+                    "Info",
+                    &format!("Executed the timed buy order for {} on {:?} at {}.", order.market_hash_name, order.market, order.target_price),
+                );
+            }
+            Err(err_str) => log_functions::log_err(&format!(
+                "orders.rs | execute_timed_orders(market_hash_name={}) | Error occured when buying the item. E: {}",
+                order.market_hash_name, err_str
+            )),
+        }
+    }
+}
+
+/// Returns the buy price of the trade hold bucket, the buckets are (7 days, 4 days, 2 days)
+pub fn price_for_trade_hold(price: &Price, trade_hold: i32) -> f32 {
+    match trade_hold {
+        i32::MIN..=0 => price.price_buy,
+        1..=2 => price.price_buy_trade.2,
+        3..=4 => price.price_buy_trade.1,
+        _ => price.price_buy_trade.0,
+    }
+}
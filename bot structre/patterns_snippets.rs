@@ -0,0 +1,55 @@
+use crate::naming;
+use crate::structs::{PatternRarity, PatternValue};
+
+// The struct that has one row of the pattern table
+// finish is matched with the finish of the item, weapon with the weapon ("" matches every weapon)
+struct PatternEntry {
+    weapon: &'static str,
+    finish: &'static str,
+    pattern_indexes: &'static [u32],
+    rarity: PatternRarity,
+    value_multiplier: f32,
+}
+
+// The high value patterns, everything else is valued at the base price
+const PATTERN_TABLE: [PatternEntry; 9] = [
+    // Case Hardened blue gems
+    PatternEntry { weapon: "AK-47", finish: "Case Hardened", pattern_indexes: &[661], rarity: PatternRarity::Legendary, value_multiplier: 100.0 },
+    PatternEntry { weapon: "AK-47", finish: "Case Hardened", pattern_indexes: &[670, 321, 955, 179, 151], rarity: PatternRarity::VeryRare, value_multiplier: 15.0 },
+    PatternEntry { weapon: "AK-47", finish: "Case Hardened", pattern_indexes: &[387, 168, 809, 92, 828], rarity: PatternRarity::Rare, value_multiplier: 3.0 },
+    PatternEntry { weapon: "Karambit", finish: "Case Hardened", pattern_indexes: &[387, 269, 463, 442], rarity: PatternRarity::Legendary, value_multiplier: 20.0 },
+    PatternEntry { weapon: "Five-SeveN", finish: "Case Hardened", pattern_indexes: &[278, 690, 868, 363], rarity: PatternRarity::VeryRare, value_multiplier: 8.0 },
+    PatternEntry { weapon: "", finish: "Case Hardened", pattern_indexes: &[555, 760, 905, 184], rarity: PatternRarity::Rare, value_multiplier: 2.0 },
+    // Full fades of the Fade knives (the highest fade percentages)
+    PatternEntry { weapon: "Karambit", finish: "Fade", pattern_indexes: &[412, 16, 146, 241, 359, 393, 541, 602, 649, 688, 701], rarity: PatternRarity::Rare, value_multiplier: 1.3 },
+    PatternEntry { weapon: "", finish: "Fade", pattern_indexes: &[763, 406, 941, 688, 356, 301], rarity: PatternRarity::Common, value_multiplier: 1.1 },
+    // Marble Fade fire and ice
+    PatternEntry { weapon: "", finish: "Marble Fade", pattern_indexes: &[412, 16, 146, 241, 359, 393, 541, 602, 649, 688, 701], rarity: PatternRarity::VeryRare, value_multiplier: 2.5 },
+];
+
+/// Returns the value of the item's pattern if it is in the table
+/// The most specific row (with the weapon) wins over the rows for every weapon
+pub fn get_pattern_value(item_name: &str, pattern_index: u32) -> Option<PatternValue> {
+    let name = naming::parse(item_name);
+    let finish = name.finish.as_deref()?;
+
+    PATTERN_TABLE
+        .iter()
+        .filter(|entry| entry.finish == finish && entry.pattern_indexes.contains(&pattern_index))
+        .filter(|entry| entry.weapon.is_empty() || name.weapon.ends_with(entry.weapon))
+        .max_by_key(|entry| !entry.weapon.is_empty())
+        .map(|entry| PatternValue {
+            item_name: item_name.to_string(),
+            pattern_index,
+            rarity: entry.rarity,
+            value_multiplier: entry.value_multiplier,
+        })
+}
+
+/// Returns how many times the base price the pattern is worth, 1.0 for unknown or missing patterns
+pub fn value_multiplier(item_name: &str, pattern_index: Option<u32>) -> f32 {
+    pattern_index
+        .and_then(|pattern_index| get_pattern_value(item_name, pattern_index))
+        .map(|pattern_value| pattern_value.value_multiplier)
+        .unwrap_or(1.0)
+}
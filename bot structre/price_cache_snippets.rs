@@ -0,0 +1,130 @@
+use crate::cache;
+use crate::log_functions;
+use crate::markets::{bitskins, csfloat, lisskins, skinport, waxpeer};
+use crate::structs::{Market, Price};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+// Counters of the cache lookups, used to tune the ttls
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+static COALESCED: AtomicU64 = AtomicU64::new(0);
+
+// One lock per (market, item name) that is being fetched right now, so concurrent callers wait for a single request
+static IN_FLIGHT: Mutex<Option<HashMap<(Market, String), Arc<tokio::sync::Mutex<()>>>>> = Mutex::new(None);
+
+// The struct that has the counters of the price cache
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PriceCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub coalesced: u64,
+}
+
+/// Returns the price of the item in the market, fetched at most max_age_secs ago
+///
+/// - Returns the cached price if it is fresh enough
+/// - Otherwise fetches it with the market handler and caches it
+/// - Concurrent callers for the same item wait for the one running request instead of sending their own
+pub async fn get_cached_price(market: Market, name: &str, max_age_secs: u64) -> Result<Price, String> {
+    if let Some(price) = get_fresh(&market, name, max_age_secs) {
+        HITS.fetch_add(1, Ordering::Relaxed);
+        return Ok(price);
+    }
+
+    let key = (market.clone(), name.to_string());
+    let flight = {
+        let mut in_flight = IN_FLIGHT.lock().unwrap();
+        in_flight
+            .get_or_insert_with(HashMap::new)
+            .entry(key.clone())
+            .or_insert_with(|| Arc::new(tokio::sync::Mutex::new(())))
+            .clone()
+    };
+
+    let res = {
+        let _guard = flight.lock().await;
+
+        // Another caller might have fetched it while this one was waiting
+        if let Some(price) = get_fresh(&market, name, max_age_secs) {
+            COALESCED.fetch_add(1, Ordering::Relaxed);
+            Ok(price)
+        } else {
+            MISSES.fetch_add(1, Ordering::Relaxed);
+
+            // Drop the older copy so the handler doesn't answer from its own cache
+            cache::invalidate(&market, name);
+            fetch_price(&market, name).await
+        }
+    };
+
+    // Remove the lock if no other caller is holding it anymore
+    let mut in_flight = IN_FLIGHT.lock().unwrap();
+    if let Some(in_flight) = in_flight.as_mut() {
+        if Arc::strong_count(&flight) <= 2 {
+            in_flight.remove(&key);
+        }
+    }
+
+    res
+}
+
+/// Drops the cached price of the item, called after a successful buy since the lowest listing is gone
+pub fn invalidate_after_buy(market: &Market, name: &str) {
+    cache::invalidate(market, name);
+}
+
+/// Returns the current hit/miss counters
+pub fn get_stats() -> PriceCacheStats {
+    PriceCacheStats {
+        hits: HITS.load(Ordering::Relaxed),
+        misses: MISSES.load(Ordering::Relaxed),
+        coalesced: COALESCED.load(Ordering::Relaxed),
+    }
+}
+
+/// Writes the hit/miss counters to the structured log
+pub fn dump_stats() {
+    let stats = get_stats();
+    let lookups = stats.hits + stats.misses + stats.coalesced;
+    let hit_rate = if lookups > 0 {
+        (stats.hits + stats.coalesced) as f32 / lookups as f32 * 100.0
+    } else {
+        0.0
+    };
+
+    log_functions::log_structured(
+        "Info",
+        &format!(
+            "Price cache stats: hits={}, misses={}, coalesced={}, hit rate={:.1}%",
+            stats.hits, stats.misses, stats.coalesced, hit_rate
+        ),
+    );
+}
+
+// Returns the cached price if it is not older than max_age_secs
+fn get_fresh(market: &Market, name: &str, max_age_secs: u64) -> Option<Price> {
+    cache::get_cached_price(market, name).filter(|price| !price.is_stale(max_age_secs))
+}
+
+// Fetches the price with the handler of the market and stores it in the cache
+async fn fetch_price(market: &Market, name: &str) -> Result<Price, String> {
+    let price = match market {
+        Market::BitSkins => bitskins::get_item_price(name.to_string(), None).await?,
+        Market::CSFloat => csfloat::get_item_price(name.to_string()).await?,
+        Market::LisSkins => lisskins::get_item_price(name.to_string()).await?,
+        Market::Skinport => skinport::get_item_price(name.to_string()).await?,
+        Market::WaxPeer => waxpeer::get_item_price(name.to_string()).await?,
+        _ => {
+            return Err(format!(
+                "price_cache.rs | fetch_price(market={:?}, name={}) | Error occured, the market has no price handler.",
+                market, name
+            ))
+        }
+    };
+
+    cache::cache_price(market.clone(), name.to_string(), price.clone());
+
+    Ok(price)
+}
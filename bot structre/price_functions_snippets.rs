@@ -1,105 +1,243 @@
+use crate::circuit_breaker::{self, EndpointGroup};
+use crate::config;
+use crate::currency;
 use crate::data;
 use crate::log_functions;
-use crate::structs::{Item, Market, Price, PriceCompare};
+use crate::naming;
+use crate::reporting;
+use crate::stats;
+use crate::structs::{ArbitrageChain, BuySignal, HistoryEvent, Item, ItemCategory, ItemData, ItemHistory, ItemSaleStats, ItemStatus, Listing, Market, OrderBookDepth, PnLReport, Price, PriceCompare, PriceCompareFilter, PriceCompareMap, SteamApp, TradeHoldPremiums, WearTier};
+use chrono::{DateTime, NaiveDate};
+use std::collections::VecDeque;
 use std::collections::HashMap;
+use strum::IntoEnumIterator;
+
+// The most optimal_sell_price goes under the lowest listing, when no sale is expected in the target days
+const OPTIMAL_PRICE_MAX_UNDERCUT: f32 = 0.05;
+
+// The expected sales in the target days at which optimal_sell_price lists at the weekly VWAP
+const OPTIMAL_PRICE_FULL_DEMAND: f32 = 5.0;
+
+// The weight of the latest weekly return in the moving average of price_momentum
+const MOMENTUM_EMA_ALPHA: f32 = 0.5;
+
+// The average weekly return that gives a momentum of about 0.76 (tanh(1.0))
+const MOMENTUM_RETURN_SCALE: f32 = 0.1;
 
 /// Compares prices across all markets to identify arbitrage opportunities
-/// Returns a hashmap with market pairs as keys and profitable items as values
+/// Every ordered (buy market, sell market) pair is compared once and only the entries passing the filter are kept
+/// Prices older than max_price_age_secs are skipped and logged
+/// The markets whose circuit breaker is open are skipped
+/// Prices are converted to the base currency of the config before they are compared
 pub async fn price_compare_all(
     map: &HashMap<String, Item>,
-) -> HashMap<(Market, Market), Vec<PriceCompare>> {
-    let mut res: HashMap<(Market, Market), Vec<PriceCompare>> = HashMap::new();
+    max_price_age_secs: u64,
+    filter: &PriceCompareFilter,
+) -> PriceCompareMap {
+    let mut res = PriceCompareMap::default();
     
     // Go through all the items in the Inventory hashmap
     for (_key, value) in map {
-        let mut start_i = 0;
-        // Loop through all the price entry combinations
-        while start_i < value.price.len() {
-            for i in (start_i + 1)..value.price.len() {
-                let mut price_1 = value.price[start_i].clone();
-                let mut price_2 = value.price[i].clone();
-                let mut reversed = 0;
-
-                while reversed < 2 {
-                    // First take the price_1 as the buy market and the price_2 as the sell
-                    let diff_perc_before_comm: i32 =
-                        ((price_2.price_sell - price_1.price_buy) / price_1.price_buy * 100.0)
-                            as i32;
-
-                    let diff_perc_after_comm: i32 = ((price_2.price_sell
-                        - (price_2.price_sell * price_2.commision as f32 / 100.0)
-                        - price_1.price_buy)
-                        / price_1.price_buy
-                        * 100.0) as i32;
-
-                    let diff_val_before_comm: f32 = price_2.price_sell - price_1.price_buy;
-
-                    let diff_val_after_comm: f32 = (price_2.price_sell
-                        - (price_2.price_sell * price_2.commision as f32 / 100.0))
-                        - price_1.price_buy;
-
-                    // Enter the value to the hashmap
-                    let entry = res.get_mut(&(price_1.market.clone(), price_2.market.clone()));
-                    if let Some(val) = entry {
-                        val.push(PriceCompare {
-                            name: value.name.clone(),
-                            diff_perc_before_comm,
-                            diff_perc_after_comm,
-                            diff_val_before_comm,
-                            diff_val_after_comm,
-                            price: (price_1.clone(), price_2.clone()),
-                        });
-                    } else {
-                        res.entry((price_1.market.clone(), price_2.market.clone()))
-                            .or_insert(
-                                [PriceCompare {
-                                    name: value.name.clone(),
-                                    diff_perc_before_comm,
-                                    diff_perc_after_comm,
-                                    diff_val_before_comm,
-                                    diff_val_after_comm,
-                                    price: (price_1.clone(), price_2.clone()),
-                                }]
-                                .to_vec(),
-                            );
-                    }
+        // Only compare the prices that are still fresh, of the markets that can be traded on right now
+        let fresh_prices: Vec<&Price> = value
+            .price
+            .iter()
+            .filter(|price| circuit_breaker::is_available(&price.market, EndpointGroup::Trading))
+            .filter(|price| {
+                let stale = price.is_stale(max_price_age_secs);
+                if stale {
+                    log_functions::log_err(&format!("Skipping stale price. Item: {:?}, Market: {:?}, Fetched at: {}", value.name, price.market, price.fetched_at_unix));
+                }
+                !stale
+            })
+            .collect();
+
+        // Compare every price in the base currency
+        let prices = currency::normalize_prices(fresh_prices).await;
+
+        compare_prices(&value.name, &prices, filter, &mut res);
+    }
+
+    res
+}
+
+// Compares every ordered (buy, sell) pair of the prices of an item and adds the ones passing the filter to res
+fn compare_prices(name: &str, prices: &[Price], filter: &PriceCompareFilter, res: &mut PriceCompareMap) {
+    // Loop through all the ordered (buy, sell) price entry combinations
+    for buy_price in prices.iter() {
+        if !filter.buy_markets.is_empty() && !filter.buy_markets.contains(&buy_price.market) {
+            continue;
+        }
+
+        for sell_price in prices.iter() {
+            if buy_price.market == sell_price.market {
+                continue;
+            }
+            if !filter.sell_markets.is_empty() && !filter.sell_markets.contains(&sell_price.market) {
+                continue;
+            }
+
+            let sell_w_comm: f32 = sell_price.price_sell - (sell_price.price_sell * sell_price.commision as f32 / 100.0);
+            let diff_val_after_comm: f32 = sell_w_comm - buy_price.price_buy;
+            let diff_perc_after_comm: i32 = (diff_val_after_comm / buy_price.price_buy * 100.0) as i32;
 
-                    // Swap the markets and calculate again
-                    let price_temp = price_1.clone();
-                    price_1 = price_2.clone();
-                    price_2 = price_temp.clone();
-                    reversed += 1;
+            // Skip the losing directions before anything gets cloned
+            if diff_perc_after_comm < filter.min_profit_perc_after_comm
+                || diff_val_after_comm < filter.min_profit_val_after_comm
+            {
+                continue;
+            }
+
+            let diff_val_before_comm: f32 = sell_price.price_sell - buy_price.price_buy;
+            let diff_perc_before_comm: i32 = (diff_val_before_comm / buy_price.price_buy * 100.0) as i32;
+
+            // Enter the value to the hashmap
+            res.pairs
+                .entry((buy_price.market.clone(), sell_price.market.clone()))
+                .or_default()
+                .push(PriceCompare {
+                    name: name.to_string(),
+                    diff_perc_before_comm,
+                    diff_perc_after_comm,
+                    diff_val_before_comm,
+                    diff_val_after_comm,
+                    price: (buy_price.clone(), sell_price.clone()),
+                });
+        }
+    }
+}
+
+/// Finds the chains of trades where the money of a sale is spent on the market it was sold on
+/// e.g. buy an item on DMarket and sell it on BitSkins, then buy another item on BitSkins and sell it on MarketCSGO
+///
+/// - Only the chains of 2 to max_hops steps are returned, the single trades are the ones of price_compare_all
+/// - Every (buy market, sell market) step uses the item with the highest profit after the commision,
+///   a step is used once per chain and doesn't trade the item of the step before it
+/// - The steps that don't make a profit are pruned, so every intermediate step is positive
+/// - Only the prices of the markets whose circuit breaker is closed are used
+/// - Prices are converted to the base currency of the config before they are compared
+/// - Sorted by the total profit, highest first
+pub async fn find_multi_hop_opportunities(items: &HashMap<String, Item>, max_hops: u8) -> Vec<ArbitrageChain> {
+    if max_hops < 2 {
+        return Vec::new();
+    }
+
+    // The best step of every (buy market, sell market) pair
+    let mut best_steps: HashMap<(Market, Market), (String, f32)> = HashMap::new();
+    for item in items.values() {
+        let available_prices = item
+            .price
+            .iter()
+            .filter(|price| circuit_breaker::is_available(&price.market, EndpointGroup::Trading));
+
+        // Compare every price in the base currency
+        let prices = currency::normalize_prices(available_prices).await;
+
+        for buy_price in prices.iter().filter(|price| price.price_buy > 0.0) {
+            for sell_price in prices.iter() {
+                if buy_price.market == sell_price.market {
+                    continue;
+                }
+
+                let sell_w_comm = sell_price.price_sell - (sell_price.price_sell * sell_price.commision as f32 / 100.0);
+                let profit_perc = (sell_w_comm - buy_price.price_buy) / buy_price.price_buy * 100.0;
+
+                let entry = best_steps
+                    .entry((buy_price.market.clone(), sell_price.market.clone()))
+                    .or_insert((item.name.clone(), profit_perc));
+                if profit_perc > entry.1 {
+                    *entry = (item.name.clone(), profit_perc);
                 }
             }
-            start_i += 1;
         }
     }
 
+    chains_from_steps(&best_steps, max_hops as usize)
+}
+
+// Builds every chain of 2 to max_hops steps out of the best steps, the steps without a profit are pruned
+fn chains_from_steps(best_steps: &HashMap<(Market, Market), (String, f32)>, max_hops: usize) -> Vec<ArbitrageChain> {
+    let mut res = Vec::new();
+    let mut steps: Vec<(String, Market, Market, f32)> = Vec::new();
+    for ((buy_market, sell_market), (name, profit_perc)) in best_steps.iter() {
+        if *profit_perc <= 0.0 {
+            continue;
+        }
+
+        steps.push((name.clone(), buy_market.clone(), sell_market.clone(), *profit_perc));
+        extend_chain(best_steps, &mut steps, max_hops, &mut res);
+        steps.pop();
+    }
+
+    res.sort_by(|a: &ArbitrageChain, b: &ArbitrageChain| b.total_profit_perc.total_cmp(&a.total_profit_perc));
     res
 }
 
+// Adds every step that continues from the market the last step sold on, records the chains of 2 or more steps
+// A step that is already in the chain is skipped, its listing can't be bought twice
+fn extend_chain(best_steps: &HashMap<(Market, Market), (String, f32)>, steps: &mut Vec<(String, Market, Market, f32)>, max_hops: usize, res: &mut Vec<ArbitrageChain>) {
+    if steps.len() >= 2 {
+        let total = steps.iter().fold(1.0, |acc, step| acc * (1.0 + step.3 / 100.0));
+        res.push(ArbitrageChain {
+            steps: steps.clone(),
+            total_profit_perc: (total - 1.0) * 100.0,
+        });
+    }
+    if steps.len() >= max_hops {
+        return;
+    }
+
+    let (last_name, _, last_sell_market, _) = steps.last().cloned().unwrap();
+    for ((buy_market, sell_market), (name, profit_perc)) in best_steps.iter() {
+        if *buy_market != last_sell_market || *name == last_name || *profit_perc <= 0.0 {
+            continue;
+        }
+        if steps.iter().any(|step| step.1 == *buy_market && step.2 == *sell_market) {
+            continue;
+        }
+
+        steps.push((name.clone(), buy_market.clone(), sell_market.clone(), *profit_perc));
+        extend_chain(best_steps, steps, max_hops, res);
+        steps.pop();
+    }
+}
+
 /// Finds the most profitable trade between markets for a given item
 /// Returns (buy market, sell market, profit percentage, trade hold days)
-pub async fn most_profitable(prices: Vec<Price>, item_hash_name: String) -> (Market, Market, f32, i32) {
+/// Prices older than max_price_age_secs are never used, so a buy can't be based on them
+/// Prices are converted to the base currency of the config before they are compared
+pub async fn most_profitable(prices: Vec<Price>, item_hash_name: String, max_price_age_secs: u64) -> (Market, Market, f32, i32) {
+    let prices = currency::normalize_prices(&prices).await;
+    let premiums = config::get_bot_config().trade_hold_premiums;
+    most_profitable_with(&prices, &item_hash_name, max_price_age_secs, &premiums)
+}
+
+/// The comparison of `most_profitable` with the given trade hold premiums, the prices have to be in the same currency
+/// Used by the backtest so it runs the same decision with the premiums of the strategy
+pub fn most_profitable_with(prices: &[Price], item_hash_name: &str, max_price_age_secs: u64, premiums: &TradeHoldPremiums) -> (Market, Market, f32, i32) {
     let buy_markets = vec![Market::DMarket, Market::BitSkins, Market::CSFloat, Market::LisSkins, Market::CSMoney];
     let sell_markets = vec![Market::MarketCSGO];
     let mut res = (Market::DMarket, Market::MarketCSGO, 0.0, 0);
-    
-    // Trade hold premium multipliers
-    let trade_hold_2_extra = 1.02;
-    let trade_hold_4_extra = 1.04;
-    let trade_hold_7_extra = 1.07;
 
     // Search for the buy_markets
     for buy_market in &buy_markets {
         // Get the price of the buy_market
-        for buy_price in &prices {
+        for buy_price in prices {
             if buy_price.market == *buy_market {
+                if buy_price.is_stale(max_price_age_secs) {
+                    log_functions::log_err(&format!("Stale buy price, not using it. Item: {:?}, Buy Price: {:?}", item_hash_name, buy_price));
+                    continue;
+                }
                 // Search for the sell_markets
                 for sell_market in &sell_markets {
                     // Get the price of the sell_market
-                    for sell_price in &prices {
+                    for sell_price in prices {
                         if sell_price.market == *sell_market {
+                            if sell_price.is_stale(max_price_age_secs) {
+                                log_functions::log_err(&format!("Stale sell price, not using it. Item: {:?}, Sell Price: {:?}", item_hash_name, sell_price));
+                                continue;
+                            }
+
                             // Check if sales data exists
                             let sales_data = sell_price.sale_stats.clone();
 
@@ -108,9 +246,9 @@ pub async fn most_profitable(prices: Vec<Price>, item_hash_name: String) -> (Mar
                             } else {
                                 // Calculate prices accounting for trade hold periods
                                 let current_buy = buy_price.price_buy_w_comm;
-                                let trade_hold_2_price = buy_price.price_buy_trade_w_comm.2 * trade_hold_2_extra;
-                                let trade_hold_4_price = buy_price.price_buy_trade_w_comm.1 * trade_hold_4_extra;
-                                let trade_hold_7_price = buy_price.price_buy_trade_w_comm.0 * trade_hold_7_extra;
+                                let trade_hold_2_price = buy_price.price_buy_trade_w_comm.2 * premiums.two_days;
+                                let trade_hold_4_price = buy_price.price_buy_trade_w_comm.1 * premiums.four_days;
+                                let trade_hold_7_price = buy_price.price_buy_trade_w_comm.0 * premiums.seven_days;
 
                                 // Find best price considering all trade hold periods
                                 let buy_price_best = f32::min(
@@ -131,7 +269,7 @@ pub async fn most_profitable(prices: Vec<Price>, item_hash_name: String) -> (Mar
                                 };
 
                                 // Calculate profit percentage
-                                let profit_perc = ((sales_data.unwrap().weekly_avg_price_w_comm / buy_price_best) - 1.0) * 100.0; 
+                                let profit_perc = ((sales_data.unwrap().weekly_vwap_w_comm / buy_price_best) - 1.0) * 100.0; 
 
                                 // Update if better than current best
                                 if profit_perc > res.2 {
@@ -148,6 +286,90 @@ pub async fn most_profitable(prices: Vec<Price>, item_hash_name: String) -> (Mar
     res
 }
 
+/// Calculates the price momentum of an item from its daily prices, between -1.0 (falling) and 1.0 (rising)
+///
+/// - The days are grouped into weeks from the first day and every week is averaged
+/// - The returns between the weeks are averaged exponentially (MOMENTUM_EMA_ALPHA), so the recent weeks count more
+/// - Returns 0.0 if there are less than 2 weeks of prices
+pub fn price_momentum(history: &[(NaiveDate, f32)]) -> f32 {
+    let first_date = match history.iter().map(|(date, _)| *date).min() {
+        Some(first_date) => first_date,
+        None => return 0.0,
+    };
+
+    // (sum, count) of the prices of every week
+    let mut weeks: Vec<(f32, u32)> = Vec::new();
+    for (date, price) in history.iter().filter(|(_, price)| *price > 0.0) {
+        let week = ((*date - first_date).num_days() / 7) as usize;
+        if weeks.len() <= week {
+            weeks.resize(week + 1, (0.0, 0));
+        }
+        weeks[week].0 += price;
+        weeks[week].1 += 1;
+    }
+    // A week without prices is skipped, the return is taken to the next week that has them
+    let weekly_avgs: Vec<f32> = weeks.iter().filter(|(_, count)| *count > 0).map(|(sum, count)| sum / *count as f32).collect();
+    if weekly_avgs.len() < 2 {
+        return 0.0;
+    }
+
+    let ema = weekly_avgs
+        .windows(2)
+        .map(|pair| pair[1] / pair[0] - 1.0)
+        .fold(None, |ema: Option<f32>, weekly_return| match ema {
+            Some(ema) => Some(MOMENTUM_EMA_ALPHA * weekly_return + (1.0 - MOMENTUM_EMA_ALPHA) * ema),
+            None => Some(weekly_return),
+        })
+        .unwrap_or(0.0);
+
+    (ema / MOMENTUM_RETURN_SCALE).tanh()
+}
+
+/// Checks if every buy signal fires, no signals always fire
+/// Used by check_buy_conditions_and_buy with the profit of most_profitable and the daily prices of the item
+pub fn buy_signals_fire(signals: &[BuySignal], profit_perc: f32, profit_margin: f32, history: &[(NaiveDate, f32)]) -> bool {
+    signals.iter().all(|signal| match signal {
+        BuySignal::Arbitrage => profit_perc >= profit_margin,
+        BuySignal::Momentum { threshold } => price_momentum(history) >= *threshold,
+    })
+}
+
+/// Returns how much a skin of the wear tier is worth compared to the Factory New one
+/// The multipliers are averages of the market prices of the tiers, a single skin can differ
+pub fn wear_discount_factor(tier: WearTier) -> f32 {
+    match tier {
+        WearTier::FactoryNew => 1.0,
+        WearTier::MinimalWear => 0.85,
+        WearTier::FieldTested => 0.65,
+        WearTier::WellWorn => 0.5,
+        WearTier::BattleScarred => 0.4,
+    }
+}
+
+/// Normalizes a price to the Factory New value, `effective_value = price / wear_discount_factor(tier)`
+/// Prices without a known tier are returned as they are
+pub fn wear_normalized_value(price: f32, tier: Option<WearTier>) -> f32 {
+    match tier {
+        Some(tier) => price / wear_discount_factor(tier),
+        None => price,
+    }
+}
+
+/// Returns the highest price of a listing whose float puts it in another tier than the item name
+///
+/// - The max price is for the tier of the name, the listing has to be as cheap after normalizing both
+/// - A listing of a worse tier than the name (a mislabeled or mixed search result) gets a lower max price,
+///   a better tier never raises it
+/// - Without a float or a tier in the name the max price is returned as it is
+pub fn wear_adjusted_max_price(max_price: f32, market_hash_name: &str, float_value: Option<f64>) -> f32 {
+    match (naming::parse(market_hash_name).wear, float_value.map(WearTier::from_float)) {
+        (Some(name_tier), Some(listing_tier)) => {
+            (wear_normalized_value(max_price, Some(name_tier)) * wear_discount_factor(listing_tier)).min(max_price)
+        }
+        _ => max_price,
+    }
+}
+
 /// Calculates the maximum price to pay when buying an item to ensure target profit margin
 pub fn max_buy_price(avg_sell_price_w_comm: f32, buy_market: Market, minimum_profit_margin: f32) -> f32 {
     let commisions_ = data::get_market_commisions(buy_market.clone(), "");
@@ -168,3 +390,830 @@ pub fn max_buy_price(avg_sell_price_w_comm: f32, buy_market: Market, minimum_pro
     // Adjust for buying commission and round to appropriate decimal precision
     ((max_buy_price - (max_buy_price * (commisions.0 as f32 / 100.0))) * decimal).ceil() / decimal 
 }
+
+/// Checks if the CS item is on the buy allowlist and not on the buy blocklist of the config
+/// Used by check_buy_conditions_and_buy before evaluating the profitability
+pub fn is_buy_allowed(market_hash_name: &str) -> bool {
+    config::get_bot_config().is_buy_allowed(SteamApp::CS, market_hash_name)
+}
+
+/// Returns the minimum profit margin that applies to the item based on its category
+/// Used by check_buy_conditions_and_buy before evaluating the profitability
+pub fn category_profit_margin(market_hash_name: &str) -> f32 {
+    let category = ItemCategory::classify(market_hash_name);
+    config::get_bot_config().get_min_profit_margin(&category)
+}
+
+/// Checks if another item can be bought without going over the category hold limit
+pub fn category_has_capacity(map: &HashMap<String, Item>, market_hash_name: &str) -> bool {
+    let category = ItemCategory::classify(market_hash_name);
+    let max_count = match config::get_bot_config().get_category_max_count(&category) {
+        Some(max_count) => max_count,
+        None => return true,
+    };
+
+    // Count every held item of the same category
+    let held: i16 = map
+        .values()
+        .filter(|item| ItemCategory::classify(&item.name) == category)
+        .map(|item| item.count.total)
+        .sum();
+
+    held < max_count
+}
+
+/// Returns how many of the item can be held, 0 means unlimited
+/// The item's own max_count wins, then the data override, then the global default of the config
+pub fn effective_max_count(item: &Item) -> i16 {
+    if item.count.max_count > 0 {
+        return item.count.max_count;
+    }
+
+    match data::get_max_count(&item.name) {
+        Ok(Some(max_count)) if max_count > 0 => max_count,
+        Ok(_) => config::get_bot_config().global_max_count_per_item,
+        Err(e) => {
+            log_functions::log_err(&format!("Cannot read the max count of {}. E: {:?}", item.name, e));
+            config::get_bot_config().global_max_count_per_item
+        }
+    }
+}
+
+/// Returns how many of the item can be held based on how fast it sells
+///
+/// - The weekly sale count times max_hold_weekly_volume_ratio, at least 1
+/// - default_velocity_cap of the config when there are no sale stats
+pub fn velocity_max_count(sale_stats: Option<&ItemSaleStats>) -> i16 {
+    let bot_config = config::get_bot_config();
+    velocity_max_count_with(sale_stats, bot_config.max_hold_weekly_volume_ratio, bot_config.default_velocity_cap)
+}
+
+/// The rule of `velocity_max_count` with the given ratio and default cap instead of the ones of the config
+pub fn velocity_max_count_with(sale_stats: Option<&ItemSaleStats>, weekly_volume_ratio: f32, default_cap: i16) -> i16 {
+    match sale_stats {
+        Some(sale_stats) if sale_stats.weekly_sale_count > 0 => {
+            let max_count = (sale_stats.weekly_sale_count as f32 * weekly_volume_ratio).floor() as i16;
+            max_count.max(1)
+        }
+        _ => default_cap,
+    }
+}
+
+/// Sets the max count of the item from the sale stats of its prices
+/// A max count set in the data file is kept, it is a manual override
+pub fn update_velocity_max_count(item: &mut Item) {
+    if let Ok(Some(max_count)) = data::get_max_count(&item.name) {
+        if max_count > 0 {
+            item.count.max_count = max_count;
+            return;
+        }
+    }
+
+    let sale_stats = item.price.iter().find_map(|price| price.sale_stats.as_ref());
+    item.count.max_count = velocity_max_count(sale_stats);
+}
+
+/// Checks if another one of the item can be bought without going over its max count
+/// Used by check_buy_conditions_and_buy before buying
+pub fn item_has_capacity(item: &Item) -> bool {
+    let max_count = effective_max_count(item);
+    max_count <= 0 || item.count.total < max_count
+}
+
+/// Checks if there are enough listings near the floor price to exit the position quickly
+/// Always true if min_exit_liquidity is not set in the config
+/// Used by check_buy_conditions_and_buy before buying
+pub fn has_exit_liquidity(depth: &OrderBookDepth) -> bool {
+    match config::get_bot_config().min_exit_liquidity {
+        Some(min_exit_liquidity) => depth.listings_within_depth >= min_exit_liquidity,
+        None => true,
+    }
+}
+
+/// Calculates the price an item is received for (after the commision) when it is listed to sell in target_days_to_sell
+///
+/// - The expected sales in the target days come from the daily sale rate of the short window (weekly_sale_count / 7.0 for a week)
+/// - Less than one expected sale has to get in front of the existing listings,
+///   the listing goes under the floor_price (the lowest listing) by up to OPTIMAL_PRICE_MAX_UNDERCUT
+/// - One expected sale lists at the floor, more move the price towards the weekly VWAP,
+///   OPTIMAL_PRICE_FULL_DEMAND expected sales list at the VWAP
+/// - The commision of the market is taken from the ratio of the VWAP with and without it
+pub fn optimal_sell_price(stats: &ItemSaleStats, target_days_to_sell: u32, floor_price: f32) -> f32 {
+    let window_days = stats.short_window_days.max(1) as f32;
+    let daily_sale_rate = stats.weekly_sale_count.max(0) as f32 / window_days;
+    let expected_sales = daily_sale_rate * target_days_to_sell.max(1) as f32;
+
+    let list_price = if expected_sales < 1.0 {
+        floor_price * (1.0 - (1.0 - expected_sales) * OPTIMAL_PRICE_MAX_UNDERCUT)
+    } else {
+        let premium = f32::max(stats.weekly_vwap - floor_price, 0.0);
+        let demand = ((expected_sales - 1.0) / (OPTIMAL_PRICE_FULL_DEMAND - 1.0)).min(1.0);
+        floor_price + premium * demand
+    };
+    let list_price = (list_price * 100.0).floor() / 100.0;
+
+    let comm_ratio = if stats.weekly_vwap > 0.0 { stats.weekly_vwap_w_comm / stats.weekly_vwap } else { 1.0 };
+    ((list_price * comm_ratio) * 100.0).floor() / 100.0
+}
+
+/// Checks if a sell listing has been waiting for a buyer longer than relist_after_days
+/// The age is counted from the last price change, or from the listing time if the price never changed
+/// Used by ItemDataFunctions::relist_if_stale to decide if the item should be relisted
+pub fn is_listing_stale(item: &ItemData, relist_after_days: u32, now_unix: i64) -> bool {
+    if item.status != ItemStatus::OnSellOfferWaitingBuyer {
+        return false;
+    }
+
+    match item.list_price_updated.or(item.timestamp_unix) {
+        Some(listed_unix) => now_unix - listed_unix >= relist_after_days as i64 * 86400,
+        None => false,
+    }
+}
+
+/// Calculates the new price of a relisted item
+/// Lowers the current price by decay_rate (0.03 = 3%) but never below the min_sale_price
+pub fn decayed_sell_price(current_price: f32, decay_rate: f32, min_sale_price: f32) -> f32 {
+    let decayed = ((current_price * (1.0 - decay_rate)) * 100.0).floor() / 100.0;
+    f32::max(decayed, min_sale_price)
+}
+
+/// Returns the margin a held item is still sold for, it goes down the longer the item is held
+///
+/// - The full min_margin (0.08 = 8%) for the first full_margin_days
+/// - Then linearly down to 0.0 (break-even after the fees) at break_even_days
+pub fn held_sell_margin(min_margin: f32, held_days: f32, full_margin_days: u32, break_even_days: u32) -> f32 {
+    if held_days <= full_margin_days as f32 {
+        return min_margin;
+    }
+    if break_even_days <= full_margin_days || held_days >= break_even_days as f32 {
+        return 0.0;
+    }
+
+    let decay = (held_days - full_margin_days as f32) / (break_even_days - full_margin_days) as f32;
+    min_margin * (1.0 - decay)
+}
+
+/// Calculates the listing price of a held item on the market, the margin decays with the time it is held
+///
+/// - cost is the buy price with the buy fees, the received price after the sell commision has to cover it
+///   plus the held_sell_margin of the sell_min_margin, sell_full_margin_days and sell_break_even_days of the config
+/// - The ask starts at the weekly VWAP of the sale stats, or under the lowest listing (current_price)
+///   by reprice_undercut_usd when there are no sale stats
+/// - An ask under the margin floor is raised to the floor, the listing waits for the margin to decay
+/// - None if even break-even is above the ask, or there is no price to start from, the item should be held
+pub fn time_decayed_sell_price(cost: f32, market: Market, current_price: f32, sales_data: Option<&ItemSaleStats>, bought_time_unix: i64, now_unix: i64) -> Option<f32> {
+    let bot_config = config::get_bot_config();
+
+    let ask = match sales_data {
+        Some(stats) if stats.weekly_vwap > 0.0 => stats.weekly_vwap,
+        _ if current_price > 0.0 => current_price - bot_config.reprice_undercut_usd,
+        _ => return None,
+    };
+    let ask = (ask * 100.0).floor() / 100.0;
+
+    let held_days = (now_unix - bought_time_unix).max(0) as f32 / 86400.0;
+    let margin = held_sell_margin(bot_config.sell_min_margin, held_days, bot_config.sell_full_margin_days, bot_config.sell_break_even_days);
+
+    let sell_comm_perc = data::get_market_commisions(market.clone(), "").map(|comms| comms.1 + comms.2).unwrap_or(0);
+    sell_price_with_margin(cost, ask, sell_comm_perc, margin)
+}
+
+// The ask raised to the price whose received amount covers the cost plus the margin
+// None if the received amount of the ask doesn't even cover the cost
+fn sell_price_with_margin(cost: f32, ask: f32, sell_comm_perc: i32, margin: f32) -> Option<f32> {
+    let floor_for = |margin: f32| ((cost * (1.0 + margin) / (1.0 - sell_comm_perc as f32 / 100.0)) * 100.0).ceil() / 100.0;
+
+    if floor_for(0.0) > ask {
+        return None;
+    }
+    Some(f32::max(ask, floor_for(margin)))
+}
+
+/// Picks the market a held item is sold on and its listing price
+///
+/// - The cost and the buy time are of the oldest purchase that isn't sold yet (FIFO)
+/// - Every market with a price is priced with `time_decayed_sell_price`, the highest received price after the commision wins
+/// - The current_market wins a tie, so a listing isn't moved for nothing
+/// - None if the item has no unsold purchase or no market reaches break-even
+pub fn min_sell_price_auto(item: &Item, current_market: Option<Market>, now_unix: i64) -> Option<(f32, Market)> {
+    let entry = item
+        .history
+        .iter()
+        .filter(|entry| entry.event == HistoryEvent::Buy && entry.sold_unix.is_none())
+        .min_by_key(|entry| entry.unix)?;
+    let cost = entry.price + entry.fees;
+
+    let mut best: Option<(f32, f32, Market)> = None;
+    for price in item.price.iter() {
+        let sell_price = match time_decayed_sell_price(cost, price.market.clone(), price.price_buy, price.sale_stats.as_ref(), entry.unix, now_unix) {
+            Some(sell_price) => sell_price,
+            None => continue,
+        };
+        let sell_comm_perc = data::get_market_commisions(price.market.clone(), "").map(|comms| comms.1 + comms.2).unwrap_or(0);
+        let received = sell_price * (1.0 - sell_comm_perc as f32 / 100.0);
+
+        let is_better = match &best {
+            None => true,
+            Some((best_received, _, best_market)) => {
+                received > *best_received || (received == *best_received && current_market.as_ref() == Some(&price.market) && *best_market != price.market)
+            }
+        };
+        if is_better {
+            best = Some((received, sell_price, price.market.clone()));
+        }
+    }
+
+    best.map(|(_, sell_price, market)| (sell_price, market))
+}
+
+/// Calculates the realized profit of an item's history
+/// Every sale is matched with the oldest unmatched purchase (FIFO cost basis), the cost of a purchase includes its fees
+pub fn realized_pnl(history: &[ItemHistory]) -> PnLReport {
+    let (report, _wins) = fifo_match(history);
+    report
+}
+
+/// Calculates the realized profit of the whole inventory
+pub fn realized_pnl_portfolio(map: &HashMap<String, Item>) -> PnLReport {
+    let mut res = PnLReport::default();
+    let mut wins = 0;
+
+    for item in map.values() {
+        let (report, item_wins) = fifo_match(&item.history);
+        res.total_cost_basis += report.total_cost_basis;
+        res.total_proceeds += report.total_proceeds;
+        res.realized_gain += report.realized_gain;
+        res.num_trades += report.num_trades;
+        wins += item_wins;
+    }
+
+    res.win_rate = if res.num_trades > 0 { wins as f32 / res.num_trades as f32 } else { 0.0 };
+    res
+}
+
+/// Writes the realized profit of the inventory to a json report file for the given day
+pub fn write_pnl_report(map: &HashMap<String, Item>, path: &str) -> Result<(), String> {
+    let report = realized_pnl_portfolio(map);
+    let report_str = serde_json::to_string_pretty(&report)
+        .map_err(|e| format!("price_functions.rs | write_pnl_report(path={}) | Error occured when serializing the report. E: {:?}", path, e))?;
+
+    std::fs::write(path, report_str)
+        .map_err(|e| format!("price_functions.rs | write_pnl_report(path={}) | Error occured when writing the report. E: {:?}", path, e))
+}
+
+/// Writes every history entry of the inventory to a csv file, oldest first
+///
+/// - Columns: item_name, unix_timestamp, date_iso, price, bought_market, min_sale_price, event
+/// - The event is written as json so `import_history_csv` can restore it
+/// - Item names with commas or quotes are quoted
+pub fn export_history_csv(items: &HashMap<String, Item>, output_path: &str) -> Result<(), std::io::Error> {
+    let mut rows: Vec<(&str, &ItemHistory)> = items
+        .values()
+        .flat_map(|item| item.history.iter().map(move |entry| (item.name.as_str(), entry)))
+        .collect();
+    rows.sort_by_key(|(_, entry)| entry.unix);
+
+    let mut csv = String::from("item_name,unix_timestamp,date_iso,price,bought_market,min_sale_price,event\n");
+    for (item_name, entry) in rows {
+        let date_iso = DateTime::from_timestamp(entry.unix, 0)
+            .map(|date| date.to_rfc3339())
+            .unwrap_or_default();
+        let event = serde_json::to_string(&entry.event).map_err(std::io::Error::other)?;
+
+        csv += &format!(
+            "{},{},{},{},{:?},{},{}\n",
+            reporting::csv_field(item_name),
+            entry.unix,
+            date_iso,
+            entry.price,
+            entry.bought_market,
+            entry.min_sale_price,
+            reporting::csv_field(&event)
+        );
+    }
+
+    std::fs::write(output_path, csv)
+}
+
+/// Reads a csv file written by `export_history_csv` back into the history entries keyed by the item name
+///
+/// - A file without the event column is read as purchases
+/// - The fields that are not in the csv (fees, sale data) get their defaults
+pub fn import_history_csv(path: &str) -> Result<HashMap<String, Vec<ItemHistory>>, String> {
+    let file_str = std::fs::read_to_string(path)
+        .map_err(|e| format!("price_functions.rs | import_history_csv(path={}) | Error occured when reading the file. E: {:?}", path, e))?;
+
+    let mut res: HashMap<String, Vec<ItemHistory>> = HashMap::new();
+    for (line_index, line) in file_str.lines().enumerate().skip(1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let fields = parse_csv_line(line);
+        if fields.len() < 6 {
+            return Err(format!(
+                "price_functions.rs | import_history_csv(path={}) | Error occured, line {} has {} fields instead of 6.",
+                path, line_index + 1, fields.len()
+            ));
+        }
+        let parse_err = |field: &str| format!(
+            "price_functions.rs | import_history_csv(path={}) | Error occured when parsing the {} on line {}.",
+            path, field, line_index + 1
+        );
+
+        let bought_market = Market::iter()
+            .find(|market| format!("{:?}", market) == fields[4])
+            .ok_or(parse_err("bought_market"))?;
+        let event = match fields.get(6) {
+            Some(event) => serde_json::from_str(event).map_err(|_| parse_err("event"))?,
+            None => HistoryEvent::Buy,
+        };
+
+        res.entry(fields[0].clone()).or_default().push(ItemHistory {
+            unix: fields[1].parse().map_err(|_| parse_err("unix_timestamp"))?,
+            price: fields[3].parse().map_err(|_| parse_err("price"))?,
+            bought_market,
+            min_sale_price: fields[5].parse().map_err(|_| parse_err("min_sale_price"))?,
+            event,
+            fees: 0.0,
+            sold_price: None,
+            sold_market: None,
+            sell_fees: 0.0,
+            sold_unix: None,
+        });
+    }
+
+    Ok(res)
+}
+
+// Splits a csv line into its fields, a quoted field can have commas and doubled quotes
+fn parse_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', _) => in_quotes = !in_quotes,
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+// Matches the sales with the purchases in FIFO order, returns the report and the amount of profitable trades
+// A sale is the sold_* fields record_sale writes on a Buy entry, the cost basis of a purchase is its price and fees
+fn fifo_match(history: &[ItemHistory]) -> (PnLReport, u32) {
+    let mut res = PnLReport::default();
+    let mut wins = 0;
+
+    let mut buys: Vec<&ItemHistory> = history.iter().filter(|entry| entry.event == HistoryEvent::Buy).collect();
+    buys.sort_by_key(|entry| entry.unix);
+    let mut open_buys: VecDeque<f32> = buys.iter().map(|entry| entry.price + entry.fees).collect();
+
+    // The received price of every sale, oldest sale first
+    let mut sales: Vec<(i64, f32)> = buys
+        .iter()
+        .filter_map(|entry| Some((entry.sold_unix.unwrap_or(entry.unix), entry.sold_price?)))
+        .collect();
+    sales.sort_by_key(|(sold_unix, _)| *sold_unix);
+
+    for (_, proceeds) in sales {
+        // Every sale is written on a purchase, so there is always one to match
+        let Some(cost) = open_buys.pop_front() else { break };
+        res.total_cost_basis += cost;
+        res.total_proceeds += proceeds;
+        res.num_trades += 1;
+        if proceeds > cost {
+            wins += 1;
+        }
+    }
+
+    res.realized_gain = res.total_proceeds - res.total_cost_basis;
+    res.win_rate = if res.num_trades > 0 { wins as f32 / res.num_trades as f32 } else { 0.0 };
+    (res, wins)
+}
+
+/// Calculates Pearson's correlation coefficient of two price series of the same item on two markets
+/// Series of different length are aligned on their latest points
+/// Returns 0.0 if there are less than 2 points or one of the series never changes
+pub fn market_correlation(history_a: &[f32], history_b: &[f32]) -> f32 {
+    let len = history_a.len().min(history_b.len());
+    if len < 2 {
+        return 0.0;
+    }
+    let a = &history_a[history_a.len() - len..];
+    let b = &history_b[history_b.len() - len..];
+
+    let mean_a = a.iter().sum::<f32>() / len as f32;
+    let mean_b = b.iter().sum::<f32>() / len as f32;
+
+    let mut covariance = 0.0;
+    let mut variance_a = 0.0;
+    let mut variance_b = 0.0;
+    for (price_a, price_b) in a.iter().zip(b.iter()) {
+        covariance += (price_a - mean_a) * (price_b - mean_b);
+        variance_a += (price_a - mean_a).powi(2);
+        variance_b += (price_b - mean_b).powi(2);
+    }
+
+    if variance_a == 0.0 || variance_b == 0.0 {
+        return 0.0;
+    }
+    covariance / (variance_a.sqrt() * variance_b.sqrt())
+}
+
+/// Returns the names of the items whose latest spread between the cheapest and the most expensive market
+/// is more than threshold percent, used as a divergence signal
+/// Markets without a listing (price 0) are skipped
+pub fn find_diverging_items(items: &HashMap<String, Item>, threshold: f32) -> Vec<String> {
+    let mut diverging: Vec<String> = items
+        .values()
+        .filter_map(|item| {
+            let prices: Vec<f32> = item.price.iter().map(|price| price.price_buy).filter(|price| *price > 0.0).collect();
+            if prices.len() < 2 {
+                return None;
+            }
+
+            let min = prices.iter().cloned().fold(f32::MAX, f32::min);
+            let max = prices.iter().cloned().fold(f32::MIN, f32::max);
+            let spread_perc = (max - min) / min * 100.0;
+
+            (spread_perc > threshold).then(|| item.name.clone())
+        })
+        .collect();
+
+    diverging.sort();
+    diverging
+}
+
+/// Estimates what buying quantity units of an item costs
+/// Every listing is one unit, the cheapest ones under max_price are filled first
+/// Returns (average price of the filled units, units that can be filled), the average is 0.0 if none can
+pub fn estimate_fill_price(listings: &[Listing], quantity: u32, max_price: f32) -> (f32, u32) {
+    let mut prices: Vec<f32> = listings.iter().map(|listing| listing.price).filter(|price| *price <= max_price).collect();
+    prices.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    prices.truncate(quantity as usize);
+
+    if prices.is_empty() {
+        return (0.0, 0);
+    }
+    (prices.iter().sum::<f32>() / prices.len() as f32, prices.len() as u32)
+}
+
+/// Removes the prices that are more than std_dev_threshold standard deviations from the mean
+/// A single sale at 10x the market price would skew the averages, less than 3 prices are kept as they are
+pub fn remove_outliers(prices: &[f32], std_dev_threshold: f32) -> Vec<f32> {
+    stats::filter_outliers_z(prices.to_vec(), |price| *price, std_dev_threshold)
+}
+
+/// Converts a price in thousandths of a dollar (BitSkins, WaxPeer) to dollars
+pub fn from_thousandths(price: i64) -> f32 {
+    price as f32 / 1000.0
+}
+
+/// Converts a price in dollars to thousandths of a dollar (BitSkins, WaxPeer)
+pub fn to_thousandths(price: f32) -> i64 {
+    (price * 1000.0).round() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::ItemCount;
+
+    fn price(market: Market, price_buy: f32, price_sell: f32, commision: i32) -> Price {
+        Price {
+            market,
+            commision,
+            price_buy_trade: (price_buy, price_buy, price_buy),
+            price_buy_trade_w_comm: (price_buy, price_buy, price_buy),
+            price_buy,
+            price_buy_w_comm: price_buy,
+            price_sell,
+            price_sell_w_comm: price_sell * (1.0 - commision as f32 / 100.0),
+            sale_stats: None,
+            fetched_at_unix: 0,
+            volume_24h: None,
+            currency: Default::default(),
+        }
+    }
+
+    fn keep_everything() -> PriceCompareFilter {
+        PriceCompareFilter { min_profit_perc_after_comm: i32::MIN, min_profit_val_after_comm: f32::MIN, ..Default::default() }
+    }
+
+    // DMarket and BitSkins trade both ways, BitSkins sells on to CSFloat, CSFloat back to DMarket loses money
+    fn best_steps() -> HashMap<(Market, Market), (String, f32)> {
+        HashMap::from([
+            ((Market::DMarket, Market::BitSkins), ("x".to_string(), 10.0)),
+            ((Market::BitSkins, Market::DMarket), ("y".to_string(), 5.0)),
+            ((Market::BitSkins, Market::CSFloat), ("z".to_string(), 20.0)),
+            ((Market::CSFloat, Market::DMarket), ("w".to_string(), -3.0)),
+        ])
+    }
+
+    fn chain_names(chain: &ArbitrageChain) -> Vec<&str> {
+        chain.steps.iter().map(|step| step.0.as_str()).collect()
+    }
+
+    #[test]
+    fn chains_have_two_to_max_hops_steps_sorted_by_profit() {
+        let chains = chains_from_steps(&best_steps(), 3);
+
+        let names: Vec<Vec<&str>> = chains.iter().map(chain_names).collect();
+        assert_eq!(names.len(), 4);
+        assert_eq!(names[0], vec!["y", "x", "z"]);
+        assert_eq!(names[1], vec!["x", "z"]);
+        assert!(names[2..].contains(&vec!["x", "y"]) && names[2..].contains(&vec!["y", "x"]));
+
+        // 5%, 10% and 20% compound to 38.6%
+        assert!((chains[0].total_profit_perc - 38.6).abs() < 1e-3);
+        assert!(chains.windows(2).all(|pair| pair[0].total_profit_perc >= pair[1].total_profit_perc));
+        assert!(chains.iter().all(|chain| (2..=3).contains(&chain.steps.len())));
+
+        // With 2 hops only the chains of 2 steps are left
+        assert!(chains_from_steps(&best_steps(), 2).iter().all(|chain| chain.steps.len() == 2));
+        assert_eq!(chains_from_steps(&best_steps(), 2).len(), 3);
+    }
+
+    #[test]
+    fn chains_skip_losing_steps_and_never_reuse_a_step() {
+        let chains = chains_from_steps(&best_steps(), 5);
+
+        // CSFloat to DMarket loses money, so nothing continues after z
+        assert!(chains.iter().all(|chain| !chain_names(chain).contains(&"w")));
+        for chain in chains.iter() {
+            for (i, step) in chain.steps.iter().enumerate() {
+                assert!(
+                    !chain.steps[i + 1..].iter().any(|other| other.1 == step.1 && other.2 == step.2),
+                    "{:?}",
+                    chain_names(chain)
+                );
+            }
+        }
+        // x, y, x would buy the x listing twice
+        assert!(!chains.iter().any(|chain| chain_names(chain).starts_with(&["x", "y", "x"])));
+    }
+
+    #[test]
+    fn every_ordered_pair_is_compared_once() {
+        let prices = vec![price(Market::Steam, 10.0, 10.0, 15), price(Market::DMarket, 5.0, 8.0, 0), price(Market::CSFloat, 9.0, 9.5, 2)];
+        let mut res = PriceCompareMap::default();
+        compare_prices("item", &prices, &keep_everything(), &mut res);
+
+        assert_eq!(res.pairs.len(), 6);
+        assert!(res.pairs.values().all(|entries| entries.len() == 1));
+        assert!(res.pairs.keys().all(|(buy, sell)| buy != sell));
+    }
+
+    #[test]
+    fn losing_directions_are_filtered_out() {
+        let prices = vec![price(Market::Steam, 10.0, 10.0, 15), price(Market::DMarket, 5.0, 8.0, 0)];
+        let mut res = PriceCompareMap::default();
+        compare_prices("item", &prices, &PriceCompareFilter::default(), &mut res);
+
+        // Buying on Steam for 10 and selling on DMarket for 8 loses money
+        assert!(!res.pairs.contains_key(&(Market::Steam, Market::DMarket)));
+        let entry = &res.pairs[&(Market::DMarket, Market::Steam)][0];
+        assert_eq!(entry.diff_perc_before_comm, 100);
+        assert_eq!(entry.diff_perc_after_comm, 70);
+        assert!((entry.diff_val_after_comm - 3.5).abs() < 1e-4);
+        assert_eq!(entry.price.0.market, Market::DMarket);
+        assert_eq!(entry.price.1.market, Market::Steam);
+    }
+
+    #[test]
+    fn market_filters_limit_the_buy_and_sell_sides() {
+        let prices = vec![price(Market::Steam, 10.0, 10.0, 0), price(Market::DMarket, 5.0, 8.0, 0), price(Market::CSFloat, 9.0, 9.5, 0)];
+        let filter = PriceCompareFilter { buy_markets: vec![Market::DMarket], sell_markets: vec![Market::CSFloat], ..keep_everything() };
+        let mut res = PriceCompareMap::default();
+        compare_prices("item", &prices, &filter, &mut res);
+
+        assert_eq!(res.pairs.keys().collect::<Vec<_>>(), vec![&(Market::DMarket, Market::CSFloat)]);
+    }
+
+    #[test]
+    fn top_n_sorts_across_the_pairs() {
+        let mut res = PriceCompareMap::default();
+        compare_prices("a", &[price(Market::Steam, 10.0, 10.0, 0), price(Market::DMarket, 5.0, 8.0, 0)], &PriceCompareFilter::default(), &mut res);
+        compare_prices("b", &[price(Market::CSFloat, 4.0, 5.0, 0), price(Market::BitSkins, 2.0, 4.0, 0)], &PriceCompareFilter::default(), &mut res);
+
+        let top: Vec<(&str, i32)> = res.top_n(2).iter().map(|entry| (entry.name.as_str(), entry.diff_perc_after_comm)).collect();
+        assert_eq!(top, vec![("b", 150), ("a", 100)]);
+    }
+
+    fn sale_stats(weekly_sale_count: i32) -> ItemSaleStats {
+        serde_json::from_value(serde_json::json!({
+            "name": "item",
+            "weekly_vwap": 10.0,
+            "weekly_vwap_w_comm": 9.5,
+            "weekly_sale_count": weekly_sale_count,
+            "monthly_avg_price": 10.0,
+            "monthly_sale_count": weekly_sale_count * 4,
+            "weekly_price_change": 0.0,
+            "projected_price_next_week": 10.0,
+        }))
+        .unwrap()
+    }
+
+    fn held_item(total: i16, max_count: i16) -> Item {
+        Item {
+            name: "AK-47 | Redline (Field-Tested)".to_string(),
+            count: ItemCount { total, available: total, on_offer: 0, on_hold: 0, max_count },
+            data: Vec::new(),
+            price: Vec::new(),
+            history: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn velocity_cap_is_a_share_of_the_weekly_sales() {
+        assert_eq!(velocity_max_count_with(Some(&sale_stats(7)), 0.5, 2), 3);
+        assert_eq!(velocity_max_count_with(Some(&sale_stats(40)), 0.25, 2), 10);
+        // A slow item can still be held once
+        assert_eq!(velocity_max_count_with(Some(&sale_stats(1)), 0.5, 2), 1);
+    }
+
+    #[test]
+    fn velocity_cap_falls_back_to_the_default_without_sales() {
+        assert_eq!(velocity_max_count_with(Some(&sale_stats(0)), 0.5, 2), 2);
+        assert_eq!(velocity_max_count_with(None, 0.5, 2), 2);
+    }
+
+    #[test]
+    fn capacity_ends_at_the_max_count_and_a_sale_frees_it() {
+        let mut item = held_item(2, 3);
+        assert!(item_has_capacity(&item));
+
+        item.count.total += 1;
+        assert!(!item_has_capacity(&item));
+        item.count.total += 1;
+        assert!(!item_has_capacity(&item));
+
+        // A sold item is not counted anymore
+        item.count.total -= 2;
+        assert!(item_has_capacity(&item));
+    }
+
+    #[test]
+    fn held_margin_decays_linearly_to_break_even() {
+        let margin = |held_days: f32| held_sell_margin(0.1, held_days, 5, 21);
+
+        assert_eq!(margin(0.0), 0.1);
+        assert_eq!(margin(5.0), 0.1);
+        assert!((margin(13.0) - 0.05).abs() < 1e-6);
+        assert!((margin(17.0) - 0.025).abs() < 1e-6);
+        assert_eq!(margin(21.0), 0.0);
+        assert_eq!(margin(60.0), 0.0);
+    }
+
+    #[test]
+    fn held_margin_without_a_decay_window_drops_to_break_even() {
+        // break_even_days at or before full_margin_days has no window to decay in
+        assert_eq!(held_sell_margin(0.1, 4.0, 5, 5), 0.1);
+        assert_eq!(held_sell_margin(0.1, 6.0, 5, 5), 0.0);
+        assert_eq!(held_sell_margin(0.1, 6.0, 5, 3), 0.0);
+    }
+
+    #[test]
+    fn sell_price_is_raised_to_the_margin_floor() {
+        // 10 / 0.85 = 11.77 covers the cost after a 15% commision, 10% more is 12.95
+        assert_eq!(sell_price_with_margin(10.0, 12.0, 15, 0.1), Some(12.95));
+        // An ask above the floor is kept
+        assert_eq!(sell_price_with_margin(10.0, 14.0, 15, 0.1), Some(14.0));
+        // The floor is lower once the margin decayed
+        assert_eq!(sell_price_with_margin(10.0, 12.0, 15, 0.0), Some(12.0));
+    }
+
+    #[test]
+    fn sell_price_under_break_even_holds_the_item() {
+        assert_eq!(sell_price_with_margin(10.0, 11.5, 15, 0.1), None);
+        assert_eq!(sell_price_with_margin(10.0, 11.5, 15, 0.0), None);
+        assert_eq!(sell_price_with_margin(10.0, 11.78, 15, 0.0), Some(11.78));
+    }
+
+    // Stands in for a benchmark: with 10 markets an item has 90 ordered pairs,
+    // the filter keeps only the profitable ones so the rest is never cloned
+    #[test]
+    fn filter_keeps_only_the_profitable_pairs_of_a_large_inventory() {
+        let markets: Vec<Market> = Market::iter().collect();
+        let items: Vec<Vec<Price>> = (0..500)
+            .map(|i| {
+                markets
+                    .iter()
+                    .enumerate()
+                    .map(|(m, market)| {
+                        let base = 10.0 + (i % 7) as f32 + m as f32 * 0.5;
+                        price(market.clone(), base, base * 1.02, 5)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut all = PriceCompareMap::default();
+        let mut profitable = PriceCompareMap::default();
+        let filter = PriceCompareFilter { min_profit_perc_after_comm: 5, ..Default::default() };
+        for (i, prices) in items.iter().enumerate() {
+            compare_prices(&i.to_string(), prices, &keep_everything(), &mut all);
+            compare_prices(&i.to_string(), prices, &filter, &mut profitable);
+        }
+
+        let pairs = markets.len() * (markets.len() - 1);
+        assert_eq!(all.pairs.values().map(|entries| entries.len()).sum::<usize>(), 500 * pairs);
+        let kept = profitable.pairs.values().flatten().count();
+        assert!(kept > 0 && kept < 500 * pairs / 2);
+        assert!(profitable.pairs.values().flatten().all(|entry| entry.diff_perc_after_comm >= 5));
+    }
+
+    // A purchase of the history, sold is (received price, sold unix)
+    fn bought(unix: i64, price: f32, fees: f32, sold: Option<(f32, i64)>) -> ItemHistory {
+        ItemHistory {
+            unix,
+            price,
+            bought_market: Market::DMarket,
+            min_sale_price: 0.0,
+            event: HistoryEvent::Buy,
+            fees,
+            sold_price: sold.map(|sold| sold.0),
+            sold_market: sold.map(|_| Market::Steam),
+            sell_fees: 0.0,
+            sold_unix: sold.map(|sold| sold.1),
+        }
+    }
+
+    #[test]
+    fn sales_are_matched_with_the_oldest_purchase() {
+        // The history is not in time order, the first purchase of 10 + 0.5 fees is the one that was sold
+        let history = vec![bought(2000, 20.0, 0.0, None), bought(1000, 10.0, 0.5, Some((15.0, 2500)))];
+        let report = realized_pnl(&history);
+
+        assert_eq!(report.num_trades, 1);
+        assert!((report.total_cost_basis - 10.5).abs() < 1e-4);
+        assert!((report.total_proceeds - 15.0).abs() < 1e-4);
+        assert!((report.realized_gain - 4.5).abs() < 1e-4);
+
+        // A sale written on the newer purchase still costs the oldest one
+        let history = vec![bought(1000, 10.0, 0.5, None), bought(2000, 20.0, 0.0, Some((15.0, 2500)))];
+        assert!((realized_pnl(&history).total_cost_basis - 10.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn partial_sell_through_only_counts_the_sold_purchases() {
+        let history = vec![
+            bought(1000, 10.0, 0.0, Some((11.0, 1500))),
+            bought(1100, 12.0, 0.0, Some((9.0, 1600))),
+            bought(1200, 14.0, 0.0, None),
+        ];
+        let report = realized_pnl(&history);
+
+        assert_eq!(report.num_trades, 2);
+        assert!((report.total_cost_basis - 22.0).abs() < 1e-4);
+        assert!((report.total_proceeds - 20.0).abs() < 1e-4);
+        assert!((report.realized_gain + 2.0).abs() < 1e-4);
+        assert!((report.win_rate - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn only_the_sold_purchases_are_trades() {
+        let mut sell = bought(1500, 15.0, 0.0, None);
+        sell.event = HistoryEvent::Sell;
+        let mut reassigned = bought(1600, 0.0, 0.0, None);
+        reassigned.event = HistoryEvent::AssetIdReassigned { old_asset_id: "1".to_string(), new_asset_id: "2".to_string() };
+
+        let report = realized_pnl(&[bought(1000, 10.0, 0.0, None), sell, reassigned]);
+        assert_eq!(report, PnLReport::default());
+    }
+
+    #[test]
+    fn portfolio_sums_the_items_and_their_win_rate() {
+        let item = |name: &str, history: Vec<ItemHistory>| {
+            let item = Item {
+                name: name.to_string(),
+                count: ItemCount { total: 0, available: 0, on_offer: 0, on_hold: 0, max_count: 0 },
+                data: Vec::new(),
+                price: Vec::new(),
+                history,
+            };
+            (name.to_string(), item)
+        };
+        let map = HashMap::from([
+            item("a", vec![bought(1000, 10.0, 0.5, Some((15.0, 1500))), bought(1100, 20.0, 0.0, Some((18.0, 1600)))]),
+            item("b", vec![bought(1000, 5.0, 0.0, Some((7.0, 1500))), bought(1100, 5.0, 0.0, None)]),
+        ]);
+        let report = realized_pnl_portfolio(&map);
+
+        assert_eq!(report.num_trades, 3);
+        assert!((report.total_cost_basis - 35.5).abs() < 1e-4);
+        assert!((report.total_proceeds - 40.0).abs() < 1e-4);
+        assert!((report.realized_gain - 4.5).abs() < 1e-4);
+        assert!((report.win_rate - 2.0 / 3.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,118 @@
+use crate::config;
+use crate::log_functions;
+use crate::structs::ItemCategory;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+// The file the last purchase times are persisted to
+const PURCHASE_COOLDOWN_PATH: &str = "purchase_cooldowns.json";
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+// The global cooldowns used by the buy flows
+static PURCHASE_COOLDOWN: Mutex<Option<PurchaseCooldown>> = Mutex::new(None);
+
+// The struct that has when every item was last bought
+// min_days is the default cooldown, the config can override it per item category
+#[derive(Debug)]
+pub struct PurchaseCooldown {
+    pub cooldowns: HashMap<String, Instant>,
+    pub min_days: u32,
+}
+
+impl PurchaseCooldown {
+    /// Creates the cooldowns and loads the purchase times of the earlier runs
+    /// The times are stored as unix seconds since an Instant can't outlive the process
+    pub fn new(min_days: u32) -> Self {
+        let stored: HashMap<String, u64> = std::fs::read_to_string(PURCHASE_COOLDOWN_PATH)
+            .ok()
+            .and_then(|file_str| serde_json::from_str(&file_str).ok())
+            .unwrap_or_default();
+
+        let now_unix = get_sys_time_in_secs();
+        let now = Instant::now();
+        let cooldowns = stored
+            .into_iter()
+            .filter_map(|(item_name, bought_unix)| {
+                now.checked_sub(Duration::from_secs(now_unix.saturating_sub(bought_unix)))
+                    .map(|bought_at| (item_name, bought_at))
+            })
+            .collect();
+
+        PurchaseCooldown { cooldowns, min_days }
+    }
+
+    /// Checks if the item was bought less than its cooldown ago
+    pub fn is_on_cooldown(&self, item_name: &str) -> bool {
+        let min_days = self.min_days_for(item_name);
+        match self.cooldowns.get(item_name) {
+            Some(bought_at) => bought_at.elapsed() < Duration::from_secs(min_days as u64 * SECS_PER_DAY),
+            None => false,
+        }
+    }
+
+    /// Starts the cooldown of the item and writes it to the disk
+    pub fn record_purchase(&mut self, item_name: &str) {
+        self.cooldowns.insert(item_name.to_string(), Instant::now());
+        self.save();
+    }
+
+    // The cooldown of the items category, the default if the category isn't overridden
+    fn min_days_for(&self, item_name: &str) -> u32 {
+        config::get_bot_config()
+            .category_purchase_cooldown_days
+            .get(&ItemCategory::classify(item_name))
+            .copied()
+            .unwrap_or(self.min_days)
+    }
+
+    // Writes the purchase times to the disk, the ones whose cooldown can't matter anymore are left out
+    fn save(&self) {
+        let now_unix = get_sys_time_in_secs();
+        let max_days = config::get_bot_config()
+            .category_purchase_cooldown_days
+            .values()
+            .copied()
+            .fold(self.min_days, u32::max);
+
+        let stored: HashMap<&String, u64> = self
+            .cooldowns
+            .iter()
+            .filter(|(_, bought_at)| bought_at.elapsed() < Duration::from_secs(max_days as u64 * SECS_PER_DAY))
+            .map(|(item_name, bought_at)| (item_name, now_unix - bought_at.elapsed().as_secs()))
+            .collect();
+
+        let res = serde_json::to_string(&stored)
+            .map_err(|e| format!("{:?}", e))
+            .and_then(|stored_str| std::fs::write(PURCHASE_COOLDOWN_PATH, stored_str).map_err(|e| format!("{:?}", e)));
+
+        if let Err(e) = res {
+            log_functions::log_err(&format!("Cannot save the purchase cooldowns. E: {:?}", e));
+        }
+    }
+}
+
+/// Checks the item in the global cooldowns
+pub fn is_on_cooldown(item_name: &str) -> bool {
+    let mut cooldown = PURCHASE_COOLDOWN.lock().unwrap();
+    cooldown
+        .get_or_insert_with(|| PurchaseCooldown::new(config::get_bot_config().purchase_cooldown_days))
+        .is_on_cooldown(item_name)
+}
+
+/// Records a purchase in the global cooldowns
+pub fn record_purchase(item_name: &str) {
+    let mut cooldown = PURCHASE_COOLDOWN.lock().unwrap();
+    cooldown
+        .get_or_insert_with(|| PurchaseCooldown::new(config::get_bot_config().purchase_cooldown_days))
+        .record_purchase(item_name)
+}
+
+// Get the UNIX timestamp
+fn get_sys_time_in_secs() -> u64 {
+    match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(n) => n.as_secs(),
+        Err(_) => panic!("SystemTime before UNIX EPOCH!"),
+    }
+}
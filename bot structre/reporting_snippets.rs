@@ -0,0 +1,252 @@
+use crate::structs::{HistoryEvent, Item, Market};
+use std::collections::HashMap;
+
+// How many of the best and the worst trades the report keeps
+const TOP_TRADES: usize = 10;
+
+// The struct that has a bought and sold item from the history
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradeRecord {
+    pub name: String,
+    pub bought_market: Market,
+    pub sold_market: Market,
+    pub buy_price: f32,
+    pub sold_price: f32,
+    pub fees: f32,
+    pub profit: f32,
+    pub bought_unix: i64,
+    pub sold_unix: i64,
+}
+
+// The struct that has the realized profit and loss of the trades sold in a time range
+// The profit of a trade is the received price minus the buy price and the buy fees
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PnlReport {
+    pub from_unix: i64,
+    pub to_unix: i64,
+    pub realized_profit: f32,
+    pub profit_per_market_pair: HashMap<(Market, Market), f32>,
+    pub total_fees: f32,
+    pub avg_hold_secs: i64,
+    pub num_trades: u32,
+    pub win_rate: f32,
+    pub top_trades: Vec<TradeRecord>,
+    pub bottom_trades: Vec<TradeRecord>,
+}
+
+/// Builds the profit and loss report of the trades sold between from_unix and to_unix
+///
+/// - Only the purchases with a recorded sale are counted, the unsold items are not valued
+/// - The fees are the buy fees plus the sell fees
+pub fn generate_pnl(inventory: &HashMap<String, Item>, from_unix: i64, to_unix: i64) -> PnlReport {
+    let mut trades: Vec<TradeRecord> = Vec::new();
+    for item in inventory.values() {
+        for entry in item.history.iter() {
+            if entry.event != HistoryEvent::Buy {
+                continue;
+            }
+            let (sold_price, sold_market, sold_unix) = match (entry.sold_price, &entry.sold_market, entry.sold_unix) {
+                (Some(sold_price), Some(sold_market), Some(sold_unix)) => (sold_price, sold_market.clone(), sold_unix),
+                _ => continue,
+            };
+            if sold_unix < from_unix || sold_unix > to_unix {
+                continue;
+            }
+
+            trades.push(TradeRecord {
+                name: item.name.clone(),
+                bought_market: entry.bought_market.clone(),
+                sold_market,
+                buy_price: entry.price,
+                sold_price,
+                fees: entry.fees + entry.sell_fees,
+                profit: sold_price - entry.price - entry.fees,
+                bought_unix: entry.unix,
+                sold_unix,
+            });
+        }
+    }
+
+    let mut res = PnlReport { from_unix, to_unix, ..Default::default() };
+    if trades.is_empty() {
+        return res;
+    }
+
+    let mut wins = 0;
+    let mut total_hold_secs = 0;
+    for trade in trades.iter() {
+        res.realized_profit += trade.profit;
+        res.total_fees += trade.fees;
+        *res
+            .profit_per_market_pair
+            .entry((trade.bought_market.clone(), trade.sold_market.clone()))
+            .or_insert(0.0) += trade.profit;
+        total_hold_secs += trade.sold_unix - trade.bought_unix;
+        if trade.profit > 0.0 {
+            wins += 1;
+        }
+    }
+    res.num_trades = trades.len() as u32;
+    res.win_rate = wins as f32 / trades.len() as f32;
+    res.avg_hold_secs = total_hold_secs / trades.len() as i64;
+
+    // Best trades first
+    trades.sort_by(|a, b| b.profit.total_cmp(&a.profit));
+    res.top_trades = trades.iter().take(TOP_TRADES).cloned().collect();
+    res.bottom_trades = trades.iter().rev().take(TOP_TRADES).cloned().collect();
+
+    res
+}
+
+impl PnlReport {
+    /// Writes the report as csv, the summary rows first and the top and bottom trades after them
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("section,key,value\n");
+        csv += &format!("summary,from_unix,{}\n", self.from_unix);
+        csv += &format!("summary,to_unix,{}\n", self.to_unix);
+        csv += &format!("summary,realized_profit,{:.2}\n", self.realized_profit);
+        csv += &format!("summary,total_fees,{:.2}\n", self.total_fees);
+        csv += &format!("summary,num_trades,{}\n", self.num_trades);
+        csv += &format!("summary,win_rate,{:.4}\n", self.win_rate);
+        csv += &format!("summary,avg_hold_secs,{}\n", self.avg_hold_secs);
+
+        let mut pairs: Vec<(&(Market, Market), &f32)> = self.profit_per_market_pair.iter().collect();
+        pairs.sort_by(|a, b| b.1.total_cmp(a.1));
+        for ((bought_market, sold_market), profit) in pairs {
+            csv += &format!("market_pair,{:?} -> {:?},{:.2}\n", bought_market, sold_market, profit);
+        }
+
+        csv += "\nrank,name,bought_market,sold_market,buy_price,sold_price,fees,profit,bought_unix,sold_unix\n";
+        for (rank, trades) in [("top", &self.top_trades), ("bottom", &self.bottom_trades)] {
+            for trade in trades.iter() {
+                csv += &format!(
+                    "{},{},{:?},{:?},{:.2},{:.2},{:.2},{:.2},{},{}\n",
+                    rank,
+                    csv_field(&trade.name),
+                    trade.bought_market,
+                    trade.sold_market,
+                    trade.buy_price,
+                    trade.sold_price,
+                    trade.fees,
+                    trade.profit,
+                    trade.bought_unix,
+                    trade.sold_unix
+                );
+            }
+        }
+
+        csv
+    }
+}
+
+/// Quotes a csv field, item names can have commas ("Sticker | Team, Holo")
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::{ItemCount, ItemHistory};
+
+    const DAY: i64 = 86400;
+
+    // A purchase, sold is (sold price, sold market, sold unix, sell fees)
+    fn buy(unix: i64, price: f32, fees: f32, bought_market: Market, sold: Option<(f32, Market, i64, f32)>) -> ItemHistory {
+        ItemHistory {
+            unix,
+            price,
+            bought_market,
+            min_sale_price: 0.0,
+            event: HistoryEvent::Buy,
+            fees,
+            sold_price: sold.as_ref().map(|sold| sold.0),
+            sold_market: sold.as_ref().map(|sold| sold.1.clone()),
+            sell_fees: sold.as_ref().map_or(0.0, |sold| sold.3),
+            sold_unix: sold.as_ref().map(|sold| sold.2),
+        }
+    }
+
+    fn item(name: &str, history: Vec<ItemHistory>) -> (String, Item) {
+        let item = Item {
+            name: name.to_string(),
+            count: ItemCount { total: 0, available: 0, on_offer: 0, on_hold: 0, max_count: 0 },
+            data: Vec::new(),
+            price: Vec::new(),
+            history,
+        };
+        (name.to_string(), item)
+    }
+
+    fn inventory() -> HashMap<String, Item> {
+        let mut sale = buy(4000, 5.0, 0.0, Market::DMarket, Some((7.0, Market::Steam, 4000 + DAY, 0.7)));
+        sale.event = HistoryEvent::Sell;
+
+        HashMap::from([
+            item("AK-47 | Redline (Field-Tested)", vec![
+                buy(1000, 10.0, 0.5, Market::DMarket, Some((15.0, Market::Steam, 1000 + DAY, 1.5))),
+                buy(2000, 20.0, 0.0, Market::CSFloat, Some((18.0, Market::Steam, 2000 + 3 * DAY, 0.9))),
+            ]),
+            item("AWP | Asiimov (Field-Tested)", vec![
+                buy(3000, 5.0, 0.25, Market::DMarket, Some((7.0, Market::Steam, 3000 + 2 * DAY, 0.7))),
+                // Not sold yet
+                buy(3500, 5.0, 0.25, Market::DMarket, None),
+                // Sold after the range
+                buy(3600, 5.0, 0.25, Market::DMarket, Some((9.0, Market::Steam, 10 * DAY, 0.9))),
+                // Only the purchases are counted
+                sale,
+            ]),
+        ])
+    }
+
+    #[test]
+    fn aggregates_the_trades_sold_in_the_range() {
+        let report = generate_pnl(&inventory(), 0, 5 * DAY);
+
+        assert_eq!(report.num_trades, 3);
+        // 15 - 10 - 0.5, 18 - 20, 7 - 5 - 0.25
+        assert!((report.realized_profit - 4.25).abs() < 1e-4);
+        assert!((report.total_fees - 3.85).abs() < 1e-4);
+        assert!((report.win_rate - 2.0 / 3.0).abs() < 1e-4);
+        assert_eq!(report.avg_hold_secs, 2 * DAY);
+        assert_eq!(report.profit_per_market_pair.len(), 2);
+        assert!((report.profit_per_market_pair[&(Market::DMarket, Market::Steam)] - 6.25).abs() < 1e-4);
+        assert!((report.profit_per_market_pair[&(Market::CSFloat, Market::Steam)] + 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn top_and_bottom_trades_are_ordered_and_capped() {
+        let history: Vec<ItemHistory> = (0..25)
+            .map(|i| buy(i, 10.0, 0.0, Market::DMarket, Some((i as f32, Market::Steam, 100 + i, 0.0))))
+            .collect();
+        let report = generate_pnl(&HashMap::from([item("Sticker | Team, Holo", history)]), 0, 1000);
+
+        let top: Vec<f32> = report.top_trades.iter().map(|trade| trade.profit).collect();
+        let bottom: Vec<f32> = report.bottom_trades.iter().map(|trade| trade.profit).collect();
+        assert_eq!(top, (15..25).rev().map(|i| i as f32 - 10.0).collect::<Vec<f32>>());
+        assert_eq!(bottom, (0..10).map(|i| i as f32 - 10.0).collect::<Vec<f32>>());
+        assert!((report.win_rate - 14.0 / 25.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn empty_range_gives_an_empty_report() {
+        let report = generate_pnl(&inventory(), 20 * DAY, 30 * DAY);
+
+        assert_eq!(report, PnlReport { from_unix: 20 * DAY, to_unix: 30 * DAY, ..Default::default() });
+    }
+
+    #[test]
+    fn csv_has_the_summary_the_pairs_and_the_quoted_names() {
+        let csv = generate_pnl(&inventory(), 0, 5 * DAY).to_csv();
+
+        assert!(csv.contains("summary,realized_profit,4.25\n"));
+        assert!(csv.contains("summary,num_trades,3\n"));
+        assert!(csv.find("market_pair,DMarket -> Steam,6.25").unwrap() < csv.find("market_pair,CSFloat -> Steam,-2.00").unwrap());
+        assert!(csv.contains("top,AK-47 | Redline (Field-Tested),DMarket,Steam,10.00,15.00,2.00,4.50,1000,87400\n"));
+        assert_eq!(csv_field("Sticker | Team, Holo"), "\"Sticker | Team, Holo\"");
+    }
+}
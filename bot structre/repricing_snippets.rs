@@ -0,0 +1,259 @@
+use crate::config;
+use crate::control;
+use crate::data;
+use crate::inspect;
+use crate::log_functions;
+use crate::markets::waxpeer;
+use crate::notifications;
+use crate::structs::{HistoryEvent, Item, ItemData, ItemStatus, Market};
+use chrono::Local;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::time::{sleep, Duration};
+
+// The asset ids that were already notified for sitting at their floor, so a held listing isn't notified every run
+static AT_FLOOR: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+// The struct that has a listing that can be repriced
+#[derive(Debug, Clone)]
+struct RepriceCandidate {
+    item_name: String,
+    asset_id: String,
+    market: Market,
+    floor_price: f32,
+}
+
+// The enum which differentiates what is done with the price of a listing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RepriceDecision {
+    // The listing is the lowest or there is nothing to undercut
+    Keep,
+    // The listing is lowered to undercut the lowest listing
+    Lower(f32),
+    // The undercut would go under the floor, the listing is lowered to the floor if it isn't there yet
+    Floor(Option<f32>),
+}
+
+/// Decides the new price of a listing from the lowest listing of the others
+///
+/// - A listing that is already the lowest keeps its price
+/// - Otherwise it is lowered to lowest - undercut, but never below the floor, at the floor it holds
+pub fn reprice_decision(my_price: f32, lowest_other: Option<f32>, undercut: f32, floor_price: f32) -> RepriceDecision {
+    let lowest_other = match lowest_other {
+        Some(lowest_other) => lowest_other,
+        None => return RepriceDecision::Keep,
+    };
+    if my_price < lowest_other {
+        return RepriceDecision::Keep;
+    }
+
+    let target = (((lowest_other - undercut) * 100.0).floor() / 100.0).max(0.0);
+    if target >= floor_price {
+        return RepriceDecision::Lower(target);
+    }
+
+    if my_price > floor_price {
+        RepriceDecision::Floor(Some(floor_price))
+    } else {
+        RepriceDecision::Floor(None)
+    }
+}
+
+/// Calculates the lowest listing price of an item on the market
+///
+/// - The cost is the buy price and the buy fees of the oldest purchase that isn't sold yet (FIFO, like the sales are recorded)
+/// - The received price after the sell commisions has to cover the cost plus min_margin (0.03 = 3%)
+/// - Never lower than the min_sale_price of the purchase
+/// - None if the item has no unsold purchase, its listings are not repriced without a known cost
+pub fn reprice_floor(item: &Item, market: &Market, min_margin: f32) -> Option<f32> {
+    let entry = item
+        .history
+        .iter()
+        .filter(|entry| entry.event == HistoryEvent::Buy && entry.sold_unix.is_none())
+        .min_by_key(|entry| entry.unix)?;
+
+    let sell_comm_perc = data::get_market_commisions(market.clone(), "").map(|comms| comms.1 + comms.2).unwrap_or(0);
+    let cost = entry.price + entry.fees;
+    let floor_price = ((cost * (1.0 + min_margin) / (1.0 - sell_comm_perc as f32 / 100.0)) * 100.0).ceil() / 100.0;
+    Some(f32::max(floor_price, entry.min_sale_price))
+}
+
+/// Returns the market the listing of the item is on
+/// Items with a CS.Money id are listed on CS.Money, the rest on WaxPeer
+fn listing_market(data: &ItemData) -> Market {
+    if data.market_ids.get(&Market::CSMoney).is_some() {
+        Market::CSMoney
+    } else {
+        Market::WaxPeer
+    }
+}
+
+/// Reprices the listings right away and then every `reprice_interval_secs` until the shutdown
+pub fn spawn_repricing_loop(inv: Arc<tokio::sync::Mutex<HashMap<String, Item>>>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let token = control::shutdown_token();
+        loop {
+            reprice_listings(&inv).await;
+
+            let interval = Duration::from_secs(config::get_bot_config().reprice_interval_secs);
+            tokio::select! {
+                _ = token.cancelled() => break,
+                _ = sleep(interval) => {}
+            }
+        }
+    })
+}
+
+/// Undercuts the lowest listing of every item that is waiting for a buyer
+///
+/// - Only the WaxPeer listings are repriced, CS.Money has no price update
+/// - A listing whose price changed in the last `reprice_cooldown_secs` is skipped
+/// - The new prices are sent in one request per market and the time of the change is kept in list_price_updated
+/// - A listing that reaches its floor is notified once and held there
+/// - A listing whose stickers are worth more than sticker_review_perc of the item is left for a manual review
+pub async fn reprice_listings(inv: &Arc<tokio::sync::Mutex<HashMap<String, Item>>>) {
+    let bot_config = config::get_bot_config();
+    let now = Local::now().timestamp();
+
+    // Collect the listings without holding the inventory during the requests
+    let candidates: Vec<RepriceCandidate> = {
+        let inv = inv.lock().await;
+        let mut candidates = Vec::new();
+        for item in inv.values() {
+            for data in item.data.iter() {
+                if data.status != ItemStatus::OnSellOfferWaitingBuyer {
+                    continue;
+                }
+                let market = listing_market(data);
+                if market != Market::WaxPeer {
+                    continue;
+                }
+                if data.list_price_updated.is_some_and(|updated| now - updated < bot_config.reprice_cooldown_secs) {
+                    continue;
+                }
+                if inspect::defer_to_review(item, data) {
+                    continue;
+                }
+                let floor_price = match reprice_floor(item, &market, bot_config.reprice_min_margin) {
+                    Some(floor_price) => floor_price,
+                    None => continue,
+                };
+                candidates.push(RepriceCandidate {
+                    item_name: item.name.clone(),
+                    asset_id: data.asset_id.clone(),
+                    market,
+                    floor_price,
+                });
+            }
+        }
+        candidates
+    };
+
+    {
+        let mut at_floor = AT_FLOOR.lock().unwrap();
+        at_floor
+            .get_or_insert_with(HashSet::new)
+            .retain(|asset_id| candidates.iter().any(|candidate| candidate.asset_id == *asset_id));
+    }
+
+    // The listings of every item are searched once, the accounts own listings are in the results
+    let mut listings: HashMap<String, Vec<(String, f32)>> = HashMap::new();
+    let mut batches: HashMap<Market, Vec<(String, f32)>> = HashMap::new();
+    let mut names: HashMap<String, String> = HashMap::new();
+    for candidate in candidates.iter() {
+        if !listings.contains_key(&candidate.item_name) {
+            match waxpeer::get_listing_prices(&candidate.item_name).await {
+                Ok(prices) => {
+                    listings.insert(candidate.item_name.clone(), prices);
+                }
+                Err(err_str) => {
+                    log_functions::log_err(&format!(
+                        "repricing.rs | reprice_listings() | Error occured when getting the listings of {}. E: {}",
+                        candidate.item_name, err_str
+                    ));
+                    continue;
+                }
+            }
+        }
+        let prices = &listings[&candidate.item_name];
+
+        // The listing is not in the search results, its price is unknown
+        let my_price = match prices.iter().find(|(asset_id, _)| *asset_id == candidate.asset_id) {
+            Some((_, my_price)) => *my_price,
+            None => continue,
+        };
+        let lowest_other = prices
+            .iter()
+            .filter(|(asset_id, _)| *asset_id != candidate.asset_id)
+            .map(|(_, price)| *price)
+            .min_by(|a, b| a.total_cmp(b));
+
+        let new_price = match reprice_decision(my_price, lowest_other, bot_config.reprice_undercut_usd, candidate.floor_price) {
+            RepriceDecision::Keep => continue,
+            RepriceDecision::Lower(new_price) => new_price,
+            RepriceDecision::Floor(new_price) => {
+                notify_at_floor(candidate);
+                match new_price {
+                    Some(new_price) => new_price,
+                    None => continue,
+                }
+            }
+        };
+
+        batches
+            .entry(candidate.market.clone())
+            .or_default()
+            .push((candidate.asset_id.clone(), new_price));
+        names.insert(candidate.asset_id.clone(), candidate.item_name.clone());
+    }
+
+    for (market, prices) in batches {
+        if bot_config.dry_run {
+            for (asset_id, price) in prices.iter() {
+                log_functions::log_dry_run(&format!("Would reprice {} ({}) to {} on {:?}", names[asset_id], asset_id, price, market));
+            }
+            continue;
+        }
+
+        let updated = match market {
+            Market::WaxPeer => waxpeer::update_prices(&prices).await,
+            _ => continue,
+        };
+        let updated = match updated {
+            Ok(updated) => updated,
+            Err(err_str) => {
+                log_functions::log_err(&err_str);
+                continue;
+            }
+        };
+
+        let mut inv = inv.lock().await;
+        for (asset_id, price) in prices.iter().filter(|(asset_id, _)| updated.contains(asset_id)) {
+            let item_name = &names[asset_id];
+            if let Some(data) = inv
+                .get_mut(item_name)
+                .and_then(|item| item.data.iter_mut().find(|data| data.asset_id == *asset_id))
+            {
+                data.list_price_updated = Some(now);
+            }
+            log_functions::log_structured("Info", &format!("Repriced {} ({}) to {} on {:?}.", item_name, asset_id, price, market));
+        }
+    }
+}
+
+// Logs and notifies that the listing reached its floor, once per listing
+fn notify_at_floor(candidate: &RepriceCandidate) {
+    let mut at_floor = AT_FLOOR.lock().unwrap();
+    if !at_floor.get_or_insert_with(HashSet::new).insert(candidate.asset_id.clone()) {
+        return;
+    }
+
+    log_functions::log_structured(
+        "Warning",
+        &format!(
+            "The listing of {} ({}) on {:?} is undercut below its floor of {}, holding the price.",
+            candidate.item_name, candidate.asset_id, candidate.market, candidate.floor_price
+        ),
+    );
+    notifications::notify_floor_reached(&candidate.item_name, candidate.market.clone(), candidate.floor_price);
+}
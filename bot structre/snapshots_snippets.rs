@@ -0,0 +1,319 @@
+use crate::config;
+use crate::control;
+use crate::currency;
+use crate::log_functions;
+use crate::markets::bitskins;
+use crate::price_cache;
+use crate::stats;
+use crate::structs::{Listing, Market, Price};
+use chrono::Local;
+use rusqlite::{params, Connection};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tokio::time::{sleep, Duration};
+
+// The database the snapshots are appended to
+const SNAPSHOTS_DB_PATH: &str = "snapshots.db";
+
+// The resolution of the rows that were compacted to one row per hour
+const HOURLY_RESOLUTION_SECS: i64 = 3600;
+
+// The connection to the database, opened on the first use
+static DB: Mutex<Option<Connection>> = Mutex::new(None);
+
+// The struct that has one row of the snapshots
+// The lowest prices are in the base currency, 0.0 means there was no listing with that trade hold
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub unix: i64,
+    pub item_name: String,
+    pub market: Market,
+    pub lowest_now: f32,
+    pub lowest_2_days: f32,
+    pub lowest_4_days: f32,
+    pub lowest_7_days: f32,
+    // Only the markets whose handler returns the listings have it
+    pub listing_count: Option<u32>,
+}
+
+// The struct that has the spread between two markets at a point in time
+// spread_perc is what selling on market b earns over buying on market a, before the commisions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpreadPoint {
+    pub unix: i64,
+    pub price_a: f32,
+    pub price_b: f32,
+    pub spread_perc: f32,
+}
+
+// The struct that summarizes a spread time series
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SpreadSummary {
+    pub points: usize,
+    pub mean_spread_perc: f32,
+    pub spread_volatility: f32,
+    // The fraction (0.0 - 1.0) of the points whose spread was above the threshold
+    pub fraction_above: f32,
+}
+
+/// Takes a snapshot of the configured items right away and then every snapshot_interval_secs until the shutdown
+///
+/// - The rows are compacted after every snapshot so the database doesn't grow unbounded
+/// - Nothing is done if there are no snapshot_items in the config
+pub fn spawn_snapshot_loop() -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let token = control::shutdown_token();
+        loop {
+            let bot_config = config::get_bot_config();
+            if bot_config.snapshot_items.is_empty() {
+                break;
+            }
+
+            take_snapshots(&bot_config.snapshot_items, &bot_config.snapshot_markets).await;
+            if let Err(err_str) = compact(Local::now().timestamp()) {
+                log_functions::log_err(&err_str);
+            }
+
+            tokio::select! {
+                _ = token.cancelled() => break,
+                _ = sleep(Duration::from_secs(bot_config.snapshot_interval_secs)) => {}
+            }
+        }
+    })
+}
+
+/// Takes a snapshot of every item on every market and appends the rows
+///
+/// - BitSkins rows are built from its listings, so they have the listing count
+/// - The other markets use the price of their handler
+/// - A market whose price can't be read is logged and skipped
+pub async fn take_snapshots(item_names: &[String], markets: &[Market]) {
+    let unix = Local::now().timestamp();
+    let mut rows = Vec::new();
+
+    for item_name in item_names {
+        for market in markets {
+            match snapshot_item(unix, item_name, market).await {
+                Ok(row) => rows.push(row),
+                Err(err_str) => log_functions::log_err(&format!(
+                    "snapshots.rs | take_snapshots() | Cannot take the snapshot of {} on {:?}. E: {}",
+                    item_name, market, err_str
+                )),
+            }
+        }
+    }
+
+    if let Err(err_str) = insert_snapshots(&rows) {
+        log_functions::log_err(&err_str);
+    }
+}
+
+/// Appends the rows to the database
+pub fn insert_snapshots(rows: &[Snapshot]) -> Result<(), String> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    with_db(|conn| {
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO snapshots (unix, item_name, market, lowest_now, lowest_2_days, lowest_4_days, lowest_7_days, listing_count, resolution)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, 0)",
+            )?;
+            for row in rows {
+                stmt.execute(params![
+                    row.unix,
+                    row.item_name,
+                    format!("{:?}", row.market),
+                    row.lowest_now,
+                    row.lowest_2_days,
+                    row.lowest_4_days,
+                    row.lowest_7_days,
+                    row.listing_count,
+                ])?;
+            }
+        }
+        tx.commit()
+    })
+    .map_err(|e| format!("snapshots.rs | insert_snapshots() | Error occured when writing the rows. E: {:?}", e))
+}
+
+/// Returns the spread of the item between buying on market_a and selling on market_b over the last days
+///
+/// - The rows of both markets are matched by the hour, the lowest price without a trade hold of the hour is used
+/// - The hours where one of the markets had no listing are skipped
+pub fn spread_history(item_name: &str, market_a: Market, market_b: Market, days: u32) -> Result<Vec<SpreadPoint>, String> {
+    let since = Local::now().timestamp() - days as i64 * 86400;
+
+    let rows = with_db(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT (unix / ?1) * ?1 AS hour, market, MIN(lowest_now)
+             FROM snapshots
+             WHERE item_name = ?2 AND unix >= ?3 AND lowest_now > 0 AND market IN (?4, ?5)
+             GROUP BY hour, market",
+        )?;
+        let rows = stmt
+            .query_map(
+                params![HOURLY_RESOLUTION_SECS, item_name, since, format!("{:?}", market_a), format!("{:?}", market_b)],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, f32>(2)?)),
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    })
+    .map_err(|e| format!(
+        "snapshots.rs | spread_history(item_name={}, market_a={:?}, market_b={:?}, days={}) | Error occured when reading the rows. E: {:?}",
+        item_name, market_a, market_b, days, e
+    ))?;
+
+    // (price a, price b) of every hour
+    let market_a = format!("{:?}", market_a);
+    let mut hours: BTreeMap<i64, (Option<f32>, Option<f32>)> = BTreeMap::new();
+    for (hour, market, price) in rows {
+        let entry = hours.entry(hour).or_default();
+        if market == market_a {
+            entry.0 = Some(price);
+        } else {
+            entry.1 = Some(price);
+        }
+    }
+
+    Ok(hours
+        .into_iter()
+        .filter_map(|(unix, prices)| match prices {
+            (Some(price_a), Some(price_b)) => Some(SpreadPoint {
+                unix,
+                price_a,
+                price_b,
+                spread_perc: (price_b - price_a) / price_a * 100.0,
+            }),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Summarizes the spread time series, threshold_perc is the spread the fraction_above is counted for
+pub fn summarize_spread(points: &[SpreadPoint], threshold_perc: f32) -> SpreadSummary {
+    if points.is_empty() {
+        return SpreadSummary::default();
+    }
+
+    let spreads: Vec<f32> = points.iter().map(|point| point.spread_perc).collect();
+    let above = spreads.iter().filter(|spread| **spread > threshold_perc).count();
+
+    SpreadSummary {
+        points: spreads.len(),
+        mean_spread_perc: spreads.iter().sum::<f32>() / spreads.len() as f32,
+        spread_volatility: stats::std_dev(&spreads),
+        fraction_above: above as f32 / spreads.len() as f32,
+    }
+}
+
+/// Downsamples and drops the old rows
+///
+/// - The rows older than snapshot_raw_retention_days are replaced by one row per item, market and hour
+///   with the lowest prices and the highest listing count of the hour
+/// - The rows older than snapshot_retention_days are removed
+pub fn compact(now_unix: i64) -> Result<(), String> {
+    let bot_config = config::get_bot_config();
+    let raw_before = now_unix - bot_config.snapshot_raw_retention_days as i64 * 86400;
+    let drop_before = now_unix - bot_config.snapshot_retention_days as i64 * 86400;
+
+    with_db(|conn| {
+        let tx = conn.transaction()?;
+        // The 0.0 of a missing listing would always be the MIN, so it is turned into NULL first
+        tx.execute(
+            "INSERT INTO snapshots (unix, item_name, market, lowest_now, lowest_2_days, lowest_4_days, lowest_7_days, listing_count, resolution)
+             SELECT (unix / ?1) * ?1 AS hour, item_name, market,
+                    IFNULL(MIN(NULLIF(lowest_now, 0)), 0), IFNULL(MIN(NULLIF(lowest_2_days, 0)), 0),
+                    IFNULL(MIN(NULLIF(lowest_4_days, 0)), 0), IFNULL(MIN(NULLIF(lowest_7_days, 0)), 0),
+                    MAX(listing_count), ?1
+             FROM snapshots
+             WHERE resolution = 0 AND unix < ?2
+             GROUP BY hour, item_name, market",
+            params![HOURLY_RESOLUTION_SECS, raw_before],
+        )?;
+        tx.execute("DELETE FROM snapshots WHERE resolution = 0 AND unix < ?1", params![raw_before])?;
+        tx.execute("DELETE FROM snapshots WHERE unix < ?1", params![drop_before])?;
+        tx.commit()
+    })
+    .map_err(|e| format!("snapshots.rs | compact(now_unix={}) | Error occured when compacting the rows. E: {:?}", now_unix, e))
+}
+
+// Takes the snapshot of the item on the market
+async fn snapshot_item(unix: i64, item_name: &str, market: &Market) -> Result<Snapshot, String> {
+    if *market == Market::BitSkins {
+        let listings = bitskins::get_item_listings(item_name, 7).await?;
+        return Ok(snapshot_from_listings(unix, item_name, &listings));
+    }
+
+    let price = price_cache::get_cached_price(market.clone(), item_name, config::get_bot_config().snapshot_interval_secs).await?;
+    let price = currency::normalize_prices([&price])
+        .await
+        .pop()
+        .ok_or("Error occured, the price can't be converted to the base currency.".to_string())?;
+
+    Ok(snapshot_from_price(unix, item_name, &price))
+}
+
+// Builds the row from the listings, every listing counts for the buckets of its trade hold and the longer ones
+fn snapshot_from_listings(unix: i64, item_name: &str, listings: &[Listing]) -> Snapshot {
+    let lowest = |max_hold_days: i64| {
+        listings
+            .iter()
+            .filter(|listing| listing.trade_hold_days <= max_hold_days)
+            .map(|listing| listing.price)
+            .min_by(|a, b| a.total_cmp(b))
+            .unwrap_or(0.0)
+    };
+
+    Snapshot {
+        unix,
+        item_name: item_name.to_string(),
+        market: Market::BitSkins,
+        lowest_now: lowest(0),
+        lowest_2_days: lowest(2),
+        lowest_4_days: lowest(4),
+        lowest_7_days: lowest(7),
+        listing_count: Some(listings.len() as u32),
+    }
+}
+
+// Builds the row from the price of a market handler
+fn snapshot_from_price(unix: i64, item_name: &str, price: &Price) -> Snapshot {
+    Snapshot {
+        unix,
+        item_name: item_name.to_string(),
+        market: price.market.clone(),
+        lowest_now: price.price_buy,
+        lowest_2_days: price.price_buy_trade.0,
+        lowest_4_days: price.price_buy_trade.1,
+        lowest_7_days: price.price_buy_trade.2,
+        listing_count: None,
+    }
+}
+
+// Runs the function with the connection, the database and the table are created on the first use
+fn with_db<T>(f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>) -> rusqlite::Result<T> {
+    let mut db = DB.lock().unwrap();
+    if db.is_none() {
+        let conn = Connection::open(SNAPSHOTS_DB_PATH)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS snapshots (
+                unix INTEGER NOT NULL,
+                item_name TEXT NOT NULL,
+                market TEXT NOT NULL,
+                lowest_now REAL NOT NULL,
+                lowest_2_days REAL NOT NULL,
+                lowest_4_days REAL NOT NULL,
+                lowest_7_days REAL NOT NULL,
+                listing_count INTEGER,
+                resolution INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS snapshots_item_market_unix ON snapshots (item_name, market, unix);",
+        )?;
+        *db = Some(conn);
+    }
+
+    f(db.as_mut().unwrap())
+}
@@ -0,0 +1,70 @@
+use crate::log_functions;
+use std::sync::{OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+// The environment variable the BitSkins api key is loaded from at startup
+const BITSKINS_API_KEY_ENV: &str = "BITSKINS_API_KEY";
+
+// The current BitSkins api key, replaced by refresh_bitskins_key when it expires or is rotated
+static BITSKINS_AUTH: OnceLock<BitSkinsAuth> = OnceLock::new();
+
+// Called when a BitSkins request answers 401, it should get a new key and pass it to refresh_bitskins_key
+static BITSKINS_REFRESH_CALLBACK: OnceLock<Box<dyn Fn() + Send + Sync>> = OnceLock::new();
+
+// The struct that has the BitSkins api key and when it was set
+pub struct BitSkinsAuth {
+    pub api_key: RwLock<String>,
+    pub last_refreshed: RwLock<Instant>,
+}
+
+fn bitskins_auth() -> &'static BitSkinsAuth {
+    BITSKINS_AUTH.get_or_init(|| BitSkinsAuth {
+        api_key: RwLock::new(std::env::var(BITSKINS_API_KEY_ENV).unwrap_or_default()),
+        last_refreshed: RwLock::new(Instant::now()),
+    })
+}
+
+/// Loads the BitSkins api key from the BITSKINS_API_KEY environment variable, has to be called at startup
+pub fn load_bitskins_key_from_env() -> Result<(), String> {
+    let api_key = std::env::var(BITSKINS_API_KEY_ENV)
+        .map_err(|e| format!("statics.rs | load_bitskins_key_from_env() | Error occured when reading {}. E: {:?}", BITSKINS_API_KEY_ENV, e))?;
+    refresh_bitskins_key(&api_key);
+    Ok(())
+}
+
+/// Returns the current BitSkins api key
+pub fn get_bitskins_api_key() -> String {
+    bitskins_auth().api_key.read().unwrap().clone()
+}
+
+/// Replaces the BitSkins api key, every following request uses the new key
+pub fn refresh_bitskins_key(new_key: &str) {
+    let auth = bitskins_auth();
+    *auth.api_key.write().unwrap() = new_key.to_string();
+    *auth.last_refreshed.write().unwrap() = Instant::now();
+}
+
+/// Returns how long ago the BitSkins api key was set
+pub fn bitskins_key_age() -> Duration {
+    bitskins_auth().last_refreshed.read().unwrap().elapsed()
+}
+
+/// Sets the callback that is called when the BitSkins api key expired, can only be set once
+pub fn set_bitskins_refresh_callback(callback: Box<dyn Fn() + Send + Sync>) -> Result<(), String> {
+    BITSKINS_REFRESH_CALLBACK
+        .set(callback)
+        .map_err(|_| "statics.rs | set_bitskins_refresh_callback() | Error occured, the callback is already set.".to_string())
+}
+
+/// Called by the BitSkins handler when a request answered 401
+pub fn on_bitskins_auth_expired() {
+    log_functions::log_structured(
+        "Error",
+        &format!("The BitSkins api key expired, it was set {:?} ago.", bitskins_key_age()),
+    );
+
+    match BITSKINS_REFRESH_CALLBACK.get() {
+        Some(callback) => callback(),
+        None => log_functions::log_structured("Error", "No BitSkins refresh callback is set, the key has to be refreshed by hand."),
+    }
+}
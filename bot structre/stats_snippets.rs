@@ -0,0 +1,159 @@
+use chrono::{NaiveDate, ParseError, TimeDelta};
+
+// Scale factor that makes the median absolute deviation comparable to a standard deviation
+const MAD_SCALE: f32 = 1.4826;
+
+/// Returns the median of the values, 0.0 for an empty slice
+pub fn median(values: &[f32]) -> f32 {
+    if values.is_empty() {
+        return 0.0;
+    }
+
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 0 {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+/// Returns the sample standard deviation of the values, 0.0 if there are less than 2 values
+pub fn std_dev(values: &[f32]) -> f32 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+
+    let mean = values.iter().sum::<f32>() / values.len() as f32;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / (values.len() - 1) as f32;
+    variance.sqrt()
+}
+
+/// Drops the entries whose value deviates from the median more than mad_multiple
+/// times the (scaled) median absolute deviation
+/// Shared by the sale stats of every market so the outlier rule is the same everywhere
+pub fn filter_outliers<T>(entries: Vec<T>, value: impl Fn(&T) -> f32, mad_multiple: f32) -> Vec<T> {
+    // Not enough data to tell what an outlier is
+    if entries.len() < 3 {
+        return entries;
+    }
+
+    let values: Vec<f32> = entries.iter().map(&value).collect();
+    let med = median(&values);
+    let deviations: Vec<f32> = values.iter().map(|v| (v - med).abs()).collect();
+    let mad = median(&deviations) * MAD_SCALE;
+
+    // Every day has the same price, nothing to filter
+    if mad == 0.0 {
+        return entries;
+    }
+
+    entries
+        .into_iter()
+        .filter(|entry| (value(entry) - med).abs() <= mad_multiple * mad)
+        .collect()
+}
+
+/// Drops the entries whose value is more than std_dev_threshold standard deviations from the mean (Z-score)
+///
+/// - A threshold of 0.0 or less keeps every entry
+/// - With n entries no z-score can be above (n - 1) / sqrt(n), a few days can't drop a spike at 3.0
+pub fn filter_outliers_z<T>(entries: Vec<T>, value: impl Fn(&T) -> f32, std_dev_threshold: f32) -> Vec<T> {
+    if entries.len() < 3 || std_dev_threshold <= 0.0 {
+        return entries;
+    }
+
+    let values: Vec<f32> = entries.iter().map(&value).collect();
+    let mean = values.iter().sum::<f32>() / values.len() as f32;
+    let sd = std_dev(&values);
+
+    // Every value is the same, nothing to filter
+    if sd == 0.0 {
+        return entries;
+    }
+
+    entries
+        .into_iter()
+        .filter(|entry| ((value(entry) - mean) / sd).abs() <= std_dev_threshold)
+        .collect()
+}
+
+/// Checks if a "%Y-%m-%d" date is within the last `days` days before today
+///
+/// - today is a parameter so the window doesn't depend on when the function runs
+/// - Returns the parse error of a malformed date, the caller decides what to do with it
+pub fn within_days(date_str: &str, days: u32, today: NaiveDate) -> Result<bool, ParseError> {
+    let date = NaiveDate::parse_from_str(date_str, "%Y-%m-%d")?;
+    Ok(date > today - TimeDelta::days(days as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 30 days of (date, price) around 10.2 with a mispriced low day and a mispriced high day
+    fn thirty_day_series(today: NaiveDate) -> Vec<(String, f32)> {
+        (0..30)
+            .map(|i| {
+                let date = (today - TimeDelta::days(i)).format("%Y-%m-%d").to_string();
+                let price = match i {
+                    10 => 0.5,
+                    20 => 100.0,
+                    _ => 10.0 + (i % 5) as f32 * 0.1,
+                };
+                (date, price)
+            })
+            .collect()
+    }
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, 31).unwrap()
+    }
+
+    #[test]
+    fn mad_filter_drops_both_spikes_of_the_series() {
+        let kept = filter_outliers(thirty_day_series(today()), |(_, price)| *price, 3.0);
+
+        assert_eq!(kept.len(), 28);
+        assert!(kept.iter().all(|(_, price)| (10.0..=10.4).contains(price)));
+    }
+
+    #[test]
+    fn z_filter_only_drops_the_high_spike_of_the_series() {
+        // The high spike inflates the standard deviation so the low day stays within 3
+        let kept = filter_outliers_z(thirty_day_series(today()), |(_, price)| *price, 3.0);
+
+        assert_eq!(kept.len(), 29);
+        assert!(kept.iter().any(|(_, price)| *price == 0.5));
+        assert!(!kept.iter().any(|(_, price)| *price == 100.0));
+    }
+
+    #[test]
+    fn z_filter_threshold_of_zero_keeps_the_series() {
+        assert_eq!(filter_outliers_z(thirty_day_series(today()), |(_, price)| *price, 0.0).len(), 30);
+    }
+
+    #[test]
+    fn median_and_std_dev_of_the_series_without_spikes() {
+        let prices: Vec<f32> = filter_outliers(thirty_day_series(today()), |(_, price)| *price, 3.0)
+            .into_iter()
+            .map(|(_, price)| price)
+            .collect();
+
+        assert!((median(&prices) - 10.2).abs() < 1e-4);
+        assert!(std_dev(&prices) < 0.2);
+    }
+
+    #[test]
+    fn within_days_splits_the_series_into_weeks() {
+        let series = thirty_day_series(today());
+        let weekly = series.iter().filter(|(date, _)| within_days(date, 7, today()).unwrap()).count();
+        let monthly = series.iter().filter(|(date, _)| within_days(date, 30, today()).unwrap()).count();
+
+        assert_eq!(weekly, 7);
+        assert_eq!(monthly, 30);
+        assert!(within_days("31-03-2024", 7, today()).is_err());
+    }
+}
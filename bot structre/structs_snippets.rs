@@ -1,5 +1,10 @@
+use crate::bankroll;
+use crate::currency::Currency;
+use crate::data;
+use crate::ledger::{self, LedgerEntry, LedgerKind};
+use std::collections::HashMap;
 use std::time::SystemTime;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use strum_macros::EnumIter;
 
 // The enum which differentiates the markets
@@ -14,6 +19,99 @@ pub enum Market {
     BitSkins,
     LisSkins,
     WaxPeer,
+    Skinport,
+}
+
+// The enum which differentiates the wear tiers (exteriors) of skins
+#[derive(Debug, Clone, Copy, PartialEq, Hash, Eq, Serialize, Deserialize)]
+pub enum WearTier {
+    FactoryNew,
+    MinimalWear,
+    FieldTested,
+    WellWorn,
+    BattleScarred,
+}
+
+// The enum which differentiates the item categories
+#[derive(Debug, Clone, PartialEq, Hash, Eq, Serialize, Deserialize)]
+pub enum ItemCategory {
+    Knife,
+    Glove,
+    Rifle,
+    Pistol,
+    SMG,
+    SniperRifle,
+    Shotgun,
+    MachineGun,
+    Case,
+    Key,
+    Capsule,
+    Pin,
+    Graffiti,
+    MusicKit,
+    Other,
+}
+
+// The weapon names of every gun category
+const RIFLES: [&str; 7] = ["AK-47", "M4A4", "M4A1-S", "FAMAS", "Galil AR", "AUG", "SG 553"];
+const SNIPER_RIFLES: [&str; 4] = ["AWP", "SSG 08", "SCAR-20", "G3SG1"];
+const PISTOLS: [&str; 10] = ["Glock-18", "USP-S", "P2000", "P250", "Five-SeveN", "Tec-9", "CZ75-Auto", "Desert Eagle", "Dual Berettas", "R8 Revolver"];
+const SMGS: [&str; 7] = ["MAC-10", "MP9", "MP7", "MP5-SD", "UMP-45", "P90", "PP-Bizon"];
+const SHOTGUNS: [&str; 4] = ["Nova", "XM1014", "MAG-7", "Sawed-Off"];
+const MACHINE_GUNS: [&str; 2] = ["M249", "Negev"];
+
+impl ItemCategory {
+    // Finds the category of an item from its market hash name
+    // The weapon skins are matched before the containers, "AK-47 | Case Hardened" is a rifle and not a case
+    pub fn classify(market_hash_name: &str) -> ItemCategory {
+        let name = market_hash_name;
+        let weapon = name.split(" | ").next().unwrap_or(name);
+        let is_skin = name.contains(" | ");
+        let weapon_is = |list: &[&str]| is_skin && list.iter().any(|w| weapon.ends_with(w));
+
+        if name.contains("Gloves") || name.contains("Hand Wraps") {
+            ItemCategory::Glove
+        } else if name.starts_with("★") {
+            ItemCategory::Knife
+        } else if name.starts_with("Sealed Graffiti") || name.starts_with("Graffiti |") {
+            ItemCategory::Graffiti
+        } else if name.starts_with("Music Kit") || name.contains("Music Kit |") {
+            ItemCategory::MusicKit
+        } else if weapon_is(&SNIPER_RIFLES) {
+            ItemCategory::SniperRifle
+        } else if weapon_is(&RIFLES) {
+            ItemCategory::Rifle
+        } else if weapon_is(&PISTOLS) {
+            ItemCategory::Pistol
+        } else if weapon_is(&SMGS) {
+            ItemCategory::SMG
+        } else if weapon_is(&SHOTGUNS) {
+            ItemCategory::Shotgun
+        } else if weapon_is(&MACHINE_GUNS) {
+            ItemCategory::MachineGun
+        } else if name.ends_with(" Pin") {
+            ItemCategory::Pin
+        } else if name.ends_with(" Key") {
+            ItemCategory::Key
+        } else if name.contains("Capsule") {
+            ItemCategory::Capsule
+        } else if is_container_name(name) {
+            ItemCategory::Case
+        } else {
+            ItemCategory::Other
+        }
+    }
+}
+
+// Checks if the name is of a weapon case, "Chroma 2 Case" or the numbered "CS:GO Weapon Case 2"
+fn is_container_name(name: &str) -> bool {
+    if name.contains(" | ") {
+        return false;
+    }
+    name.ends_with(" Case")
+        || name
+            .rsplit_once(" Case ")
+            .is_some_and(|(_, number)| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
 }
 
 // The struct for every item type
@@ -26,6 +124,216 @@ pub struct Item {
     pub history: Vec<ItemHistory>,
 }
 
+impl Item {
+    // Applies the status change of the ticket to the ItemData with the tickets asset id
+    // A rejected ticket leaves the item as it was
+    pub fn apply_ticket(&mut self, ticket: &ItemStatusChangeTicket) -> Result<(), String> {
+        // The asset id changes are checked before the status changes, so a failing rebind can't leave a changed status behind
+        match &ticket.change {
+            ItemStatusChanges::BuySuccessLisSkins(new_asset_id) => self.check_asset_id_update(&ticket.asset_id, new_asset_id)?,
+            ItemStatusChanges::AssetIdRebound { old, new } => self.check_asset_id_update(old, new)?,
+            _ => {}
+        }
+
+        let data = self
+            .data
+            .iter_mut()
+            .find(|data| data.asset_id == ticket.asset_id)
+            .ok_or(format!("structs.rs | apply_ticket(asset_id={}) | Error occured, unknown asset id for {}.", ticket.asset_id, self.name))?;
+
+        // Reject the changes the state machine doesn't allow, e.g. a Sold item becoming Available
+        if !data.status.can_apply(&ticket.change) {
+            return Err(format!(
+                "structs.rs | apply_ticket(asset_id={}) | Error occured, {:?} can't be applied to an item in {:?} ({}).",
+                ticket.asset_id, ticket.change, data.status, self.name
+            ));
+        }
+
+        // The purchase was paid when the buy started, a failure before the seller sent the item is refunded
+        let refunded_market = match (&ticket.change, &data.status) {
+            (ItemStatusChanges::BuyFailure, ItemStatus::OnBuyOfferWaitingSeller) => Some(data.market.clone()),
+            _ => None,
+        };
+
+        data.status = match &ticket.change {
+            ItemStatusChanges::Withdrawal => ItemStatus::OnHold,
+            ItemStatusChanges::WithdrawFailed => ItemStatus::PendingWithdrawal,
+            ItemStatusChanges::TradeLockDone => ItemStatus::Available,
+            ItemStatusChanges::BuyStartCSMoney
+            | ItemStatusChanges::BuyStartCSFloat
+            | ItemStatusChanges::BuyStartLisSkins => ItemStatus::OnBuyOfferWaitingSeller,
+            ItemStatusChanges::BuySuccessDmarket
+            | ItemStatusChanges::BuySuccessCSMoney
+            | ItemStatusChanges::BuySuccessCSFloat
+            | ItemStatusChanges::BuySuccessBitSkins => ItemStatus::Bought,
+            // The item was tracked with the purchase id until the trade offer gave its asset id
+            ItemStatusChanges::BuySuccessLisSkins(_) => ItemStatus::BoughtLisSkins,
+            ItemStatusChanges::BuyFailure => ItemStatus::Error,
+            ItemStatusChanges::SellOfferCreated(_) => ItemStatus::OnSellOfferWaitingBuyer,
+            ItemStatusChanges::SellOfferBought(_) => ItemStatus::OnSellOfferWaitingTradeOffer,
+            ItemStatusChanges::SellTradeCanceled => ItemStatus::Available,
+            ItemStatusChanges::SellTradeSent(_, trade_offer_id) => {
+                data.trade_offer_id = trade_offer_id.to_string();
+                ItemStatus::OnSellOfferWaitingTrade
+            }
+            ItemStatusChanges::SellSuccess(_, _) => ItemStatus::Sold,
+            ItemStatusChanges::SellError(_) => ItemStatus::Error,
+            ItemStatusChanges::AssetIdRebound { .. } => data.status.clone(),
+        };
+
+        // Keep the market ids the ticket brings
+        data.market_ids.merge(&ticket.market_ids);
+
+        // The item was tracked with the purchase id until the trade offer gave its asset id
+        if let ItemStatusChanges::BuySuccessLisSkins(new_asset_id) = &ticket.change {
+            self.update_asset_id(&ticket.asset_id, new_asset_id.clone())?;
+        }
+        if let ItemStatusChanges::AssetIdRebound { old, new } = &ticket.change {
+            self.update_asset_id(old, new.clone())?;
+        }
+
+        if let ItemStatusChanges::SellSuccess(market, received_price) = &ticket.change {
+            let sell_fees = self.record_sale(market, *received_price);
+            ledger::record(
+                LedgerEntry::new(LedgerKind::Sell, market.clone(), &self.name, &ticket.asset_id, *received_price, sell_fees).with_ticket(ticket),
+            );
+        }
+        if let Some(market) = refunded_market {
+            self.record_refund(market);
+        }
+
+        self.recount();
+        Ok(())
+    }
+
+    // Rebinds the ItemData with the old asset id to the new one, Steam gives the item a new asset id after every trade
+    // The reassignment is written to the history so the old id can still be traced
+    pub fn update_asset_id(&mut self, old_asset_id: &str, new_asset_id: String) -> Result<(), String> {
+        if old_asset_id == new_asset_id {
+            return Ok(());
+        }
+        self.check_asset_id_update(old_asset_id, &new_asset_id)?;
+
+        let data = self
+            .data
+            .iter_mut()
+            .find(|data| data.asset_id == old_asset_id)
+            .ok_or(format!(
+                "structs.rs | update_asset_id(old_asset_id={}, new_asset_id={}) | Error occured, unknown asset id for {}.",
+                old_asset_id, new_asset_id, self.name
+            ))?;
+        data.asset_id = new_asset_id.clone();
+        let market = data.market.clone();
+
+        self.history.push(ItemHistory {
+            unix: get_sys_time_in_secs() as i64,
+            price: 0.0,
+            bought_market: market,
+            min_sale_price: 0.0,
+            event: HistoryEvent::AssetIdReassigned { old_asset_id: old_asset_id.to_string(), new_asset_id },
+            fees: 0.0,
+            sold_price: None,
+            sold_market: None,
+            sell_fees: 0.0,
+            sold_unix: None,
+        });
+        Ok(())
+    }
+
+    // Checks that the ItemData with the old asset id can be rebound to the new one
+    fn check_asset_id_update(&self, old_asset_id: &str, new_asset_id: &str) -> Result<(), String> {
+        if old_asset_id == new_asset_id {
+            return Ok(());
+        }
+        if self.data.iter().any(|data| data.asset_id == new_asset_id) {
+            return Err(format!(
+                "structs.rs | update_asset_id(old_asset_id={}, new_asset_id={}) | Error occured, the new asset id is already tracked for {}.",
+                old_asset_id, new_asset_id, self.name
+            ));
+        }
+        if !self.data.iter().any(|data| data.asset_id == old_asset_id) {
+            return Err(format!(
+                "structs.rs | update_asset_id(old_asset_id={}, new_asset_id={}) | Error occured, unknown asset id for {}.",
+                old_asset_id, new_asset_id, self.name
+            ));
+        }
+        Ok(())
+    }
+
+    // Writes a purchase to the history, the fees are what the market charged on top of the listed price
+    // charged_price is the second price the buy_item functions return
+    pub fn record_buy(&mut self, market: Market, listed_price: f32, charged_price: f32, min_sale_price: f32) {
+        self.history.push(ItemHistory {
+            unix: get_sys_time_in_secs() as i64,
+            price: listed_price,
+            bought_market: market,
+            min_sale_price,
+            event: HistoryEvent::Buy,
+            fees: (((charged_price - listed_price) * 100.0).round() / 100.0).max(0.0),
+            sold_price: None,
+            sold_market: None,
+            sell_fees: 0.0,
+            sold_unix: None,
+        });
+    }
+
+    // Writes the sale to the oldest purchase in the history that isn't sold yet, returns the sell fees
+    // received_price is after the fees, the fees are calculated back from the commisions of the market
+    fn record_sale(&mut self, market: &Market, received_price: f32) -> f32 {
+        let sell_comm_perc = data::get_market_commisions(market.clone(), "").map(|comms| comms.1 + comms.2).unwrap_or(0);
+        let gross_price = received_price / (1.0 - sell_comm_perc as f32 / 100.0);
+        let sell_fees = ((gross_price - received_price) * 100.0).round() / 100.0;
+
+        if let Some(entry) = self
+            .history
+            .iter_mut()
+            .filter(|entry| entry.event == HistoryEvent::Buy && entry.sold_unix.is_none())
+            .min_by_key(|entry| entry.unix)
+        {
+            entry.sold_price = Some(received_price);
+            entry.sold_market = Some(market.clone());
+            entry.sell_fees = sell_fees;
+            entry.sold_unix = Some(get_sys_time_in_secs() as i64);
+            // The capital of the purchase can be spent again
+            bankroll::return_capital(entry.bought_market.clone(), entry.price + entry.fees);
+        }
+
+        sell_fees
+    }
+
+    // Removes the newest unsold purchase on the market after the market refunded it and frees its capital
+    // The purchase never completed, so it is not left in the history as an open buy
+    fn record_refund(&mut self, market: Market) {
+        let index = self
+            .history
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.event == HistoryEvent::Buy && entry.sold_unix.is_none() && entry.bought_market == market)
+            .max_by_key(|(_, entry)| entry.unix)
+            .map(|(index, _)| index);
+        if let Some(index) = index {
+            let entry = self.history.remove(index);
+            bankroll::return_capital(market, entry.price + entry.fees);
+        }
+    }
+
+    // Recalculates the counts from the statuses of the ItemData entries
+    fn recount(&mut self) {
+        let mut count = ItemCount { total: 0, available: 0, on_offer: 0, on_hold: 0, max_count: self.count.max_count };
+        for data in self.data.iter() {
+            match data.status {
+                ItemStatus::Sold => continue,
+                ItemStatus::Available => count.available += 1,
+                ItemStatus::OnHold => count.on_hold += 1,
+                ItemStatus::Untradable => {}
+                _ => count.on_offer += 1,
+            }
+            count.total += 1;
+        }
+        self.count = count;
+    }
+}
+
 // The struct that exists in every Item, tracks inventory counts
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ItemCount {
@@ -36,8 +344,46 @@ pub struct ItemCount {
     pub max_count: i16,
 }
 
+// The struct that has the listing and offer ids an item has on the markets, a market without an id has no entry
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MarketIds(HashMap<Market, String>);
+
+impl MarketIds {
+    // Returns the id of the item on the market
+    pub fn get(&self, market: &Market) -> Option<&str> {
+        self.0.get(market).map(|id| id.as_str())
+    }
+
+    // Sets the id of the item on the market, an empty or "0" id removes it
+    pub fn set(&mut self, market: Market, id: impl Into<String>) {
+        let id = id.into();
+        if id.is_empty() || id == "0" {
+            self.0.remove(&market);
+        } else {
+            self.0.insert(market, id);
+        }
+    }
+
+    pub fn with(mut self, market: Market, id: impl Into<String>) -> Self {
+        self.set(market, id);
+        self
+    }
+
+    // Copies every id of the other map, the ids of the other map win
+    pub fn merge(&mut self, other: &MarketIds) {
+        for (market, id) in other.0.iter() {
+            self.0.insert(market.clone(), id.clone());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
 // The struct that has all the ids about that instance of the item
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct ItemData {
     pub trade_offer_id: String,
     pub asset_id: String,
@@ -45,11 +391,239 @@ pub struct ItemData {
     pub class_id: String,
     pub market: Market,
     pub status: ItemStatus,
-    pub marketcsgo_item_id: String,
-    pub dmarket_item_id: String,
-    pub csmoney_item_id: String,
-    pub csfloat_offer_id: String,
+    pub market_ids: MarketIds,
     pub timestamp_unix: Option<i64>,
+    pub list_price_updated: Option<i64>,
+    // The paint seed of the skin
+    pub pattern_index: Option<u32>,
+    // The exterior of the market hash name
+    pub exterior: Option<WearTier>,
+    pub float_value: Option<f32>,
+    pub stickers: Vec<StickerInfo>,
+    // The inspect link of the Steam description, with the owner and the asset id filled in
+    pub inspect_link: Option<String>,
+}
+
+impl ItemData {
+    pub fn builder() -> ItemDataBuilder {
+        ItemDataBuilder::default()
+    }
+
+    // The summed value of the stickers whose price is known
+    pub fn sticker_value(&self) -> f32 {
+        self.stickers.iter().filter_map(|sticker| sticker.price).sum()
+    }
+}
+
+// The struct that has a sticker applied to a skin
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StickerInfo {
+    pub name: String,
+    pub slot: u8,
+    // How much of the sticker is scraped off, 0.0 is untouched
+    pub wear: Option<f32>,
+    // The lowest listing of the sticker, None if it couldn't be priced
+    pub price: Option<f32>,
+}
+
+// The struct that builds an ItemData, the fields that aren't set keep their empty defaults
+#[derive(Debug, Clone)]
+pub struct ItemDataBuilder {
+    data: ItemData,
+}
+
+impl Default for ItemDataBuilder {
+    fn default() -> Self {
+        ItemDataBuilder {
+            data: ItemData {
+                trade_offer_id: "0".to_string(),
+                asset_id: "".to_string(),
+                instance_id: "".to_string(),
+                class_id: "".to_string(),
+                market: Market::Steam,
+                status: ItemStatus::Available,
+                market_ids: MarketIds::default(),
+                timestamp_unix: None,
+                list_price_updated: None,
+                pattern_index: None,
+                exterior: None,
+                float_value: None,
+                stickers: Vec::new(),
+                inspect_link: None,
+            },
+        }
+    }
+}
+
+impl ItemDataBuilder {
+    pub fn asset_id(mut self, asset_id: impl Into<String>) -> Self {
+        self.data.asset_id = asset_id.into();
+        self
+    }
+
+    pub fn trade_offer_id(mut self, trade_offer_id: impl Into<String>) -> Self {
+        self.data.trade_offer_id = trade_offer_id.into();
+        self
+    }
+
+    pub fn instance_id(mut self, instance_id: impl Into<String>) -> Self {
+        self.data.instance_id = instance_id.into();
+        self
+    }
+
+    pub fn class_id(mut self, class_id: impl Into<String>) -> Self {
+        self.data.class_id = class_id.into();
+        self
+    }
+
+    pub fn market(mut self, market: Market) -> Self {
+        self.data.market = market;
+        self
+    }
+
+    pub fn status(mut self, status: ItemStatus) -> Self {
+        self.data.status = status;
+        self
+    }
+
+    pub fn market_id(mut self, market: Market, id: impl Into<String>) -> Self {
+        self.data.market_ids.set(market, id);
+        self
+    }
+
+    pub fn timestamp_unix(mut self, timestamp_unix: Option<i64>) -> Self {
+        self.data.timestamp_unix = timestamp_unix;
+        self
+    }
+
+    pub fn pattern_index(mut self, pattern_index: Option<u32>) -> Self {
+        self.data.pattern_index = pattern_index;
+        self
+    }
+
+    pub fn exterior(mut self, exterior: Option<WearTier>) -> Self {
+        self.data.exterior = exterior;
+        self
+    }
+
+    pub fn float_value(mut self, float_value: Option<f32>) -> Self {
+        self.data.float_value = float_value;
+        self
+    }
+
+    pub fn stickers(mut self, stickers: Vec<StickerInfo>) -> Self {
+        self.data.stickers = stickers;
+        self
+    }
+
+    pub fn inspect_link(mut self, inspect_link: Option<String>) -> Self {
+        self.data.inspect_link = inspect_link;
+        self
+    }
+
+    pub fn build(self) -> ItemData {
+        self.data
+    }
+}
+
+// The struct an ItemData is read into, the snapshots written before market_ids have the flat id fields
+#[derive(Deserialize)]
+struct ItemDataSnapshot {
+    trade_offer_id: String,
+    asset_id: String,
+    instance_id: String,
+    class_id: String,
+    market: Market,
+    status: ItemStatus,
+    #[serde(default)]
+    market_ids: MarketIds,
+    #[serde(default)]
+    marketcsgo_item_id: Option<String>,
+    #[serde(default)]
+    dmarket_item_id: Option<String>,
+    #[serde(default)]
+    csmoney_item_id: Option<String>,
+    #[serde(default)]
+    csfloat_offer_id: Option<String>,
+    timestamp_unix: Option<i64>,
+    #[serde(default)]
+    list_price_updated: Option<i64>,
+    #[serde(default)]
+    pattern_index: Option<u32>,
+    #[serde(default)]
+    exterior: Option<WearTier>,
+    #[serde(default)]
+    float_value: Option<f32>,
+    #[serde(default)]
+    stickers: Vec<StickerInfo>,
+    #[serde(default)]
+    inspect_link: Option<String>,
+}
+
+impl<'de> Deserialize<'de> for ItemData {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let snapshot = ItemDataSnapshot::deserialize(deserializer)?;
+
+        // Move the flat ids of the old snapshots into the map
+        let mut market_ids = snapshot.market_ids;
+        for (market, id) in [
+            (Market::MarketCSGO, snapshot.marketcsgo_item_id),
+            (Market::DMarket, snapshot.dmarket_item_id),
+            (Market::CSMoney, snapshot.csmoney_item_id),
+            (Market::CSFloat, snapshot.csfloat_offer_id),
+        ] {
+            if let Some(id) = id {
+                if market_ids.get(&market).is_none() {
+                    market_ids.set(market, id);
+                }
+            }
+        }
+
+        Ok(ItemData {
+            trade_offer_id: snapshot.trade_offer_id,
+            asset_id: snapshot.asset_id,
+            instance_id: snapshot.instance_id,
+            class_id: snapshot.class_id,
+            market: snapshot.market,
+            status: snapshot.status,
+            market_ids,
+            timestamp_unix: snapshot.timestamp_unix,
+            list_price_updated: snapshot.list_price_updated,
+            pattern_index: snapshot.pattern_index,
+            exterior: snapshot.exterior,
+            float_value: snapshot.float_value,
+            stickers: snapshot.stickers,
+            inspect_link: snapshot.inspect_link,
+        })
+    }
+}
+
+// The enum which differentiates how rare a pattern (paint seed) of a skin is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PatternRarity {
+    Common,
+    Rare,
+    VeryRare,
+    Legendary,
+}
+
+// The struct that has the value of a special pattern of a skin
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PatternValue {
+    pub item_name: String,
+    pub pattern_index: u32,
+    pub rarity: PatternRarity,
+    pub value_multiplier: f32,
+}
+
+// The struct that has the realized profit and loss of the sold items
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PnLReport {
+    pub total_cost_basis: f32,
+    pub total_proceeds: f32,
+    pub realized_gain: f32,
+    pub num_trades: u32,
+    pub win_rate: f32,
 }
 
 // The struct that has all the item operation history
@@ -59,6 +633,28 @@ pub struct ItemHistory {
     pub price: f32,
     pub bought_market: Market,
     pub min_sale_price: f32,
+    #[serde(default)]
+    pub event: HistoryEvent,
+    #[serde(default)]
+    pub fees: f32,
+    #[serde(default)]
+    pub sold_price: Option<f32>,
+    #[serde(default)]
+    pub sold_market: Option<Market>,
+    #[serde(default)]
+    pub sell_fees: f32,
+    #[serde(default)]
+    pub sold_unix: Option<i64>,
+}
+
+// The enum that differentiates the operations in the item history
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum HistoryEvent {
+    #[default]
+    Buy,
+    Sell,
+    // Steam gave the item a new asset id after a trade, no money moved
+    AssetIdReassigned { old_asset_id: String, new_asset_id: String },
 }
 
 // The enum that contains all the possible states of an item
@@ -76,12 +672,70 @@ pub enum ItemStatus {
     BoughtLisSkins,
     Error,
     OnHold,
+    PendingWithdrawal,
+    Untradable,
+}
+
+impl ItemStatus {
+    // Checks if the change is a valid transition from this status
+    pub fn can_apply(&self, change: &ItemStatusChanges) -> bool {
+        match change {
+            ItemStatusChanges::Withdrawal => {
+                matches!(self, ItemStatus::Bought | ItemStatus::PendingWithdrawal | ItemStatus::OnHold)
+            }
+            ItemStatusChanges::WithdrawFailed => {
+                matches!(self, ItemStatus::Bought | ItemStatus::PendingWithdrawal | ItemStatus::OnHold)
+            }
+            ItemStatusChanges::TradeLockDone => {
+                matches!(self, ItemStatus::OnHold | ItemStatus::Bought | ItemStatus::BoughtLisSkins)
+            }
+            ItemStatusChanges::BuyStartCSMoney
+            | ItemStatusChanges::BuyStartCSFloat
+            | ItemStatusChanges::BuyStartLisSkins => {
+                matches!(self, ItemStatus::Available | ItemStatus::OnBuyOfferWaitingSeller)
+            }
+            // The BitSkins items are tracked as OnHold from the buy since they are withdrawn right away
+            ItemStatusChanges::BuySuccessDmarket
+            | ItemStatusChanges::BuySuccessCSMoney
+            | ItemStatusChanges::BuySuccessCSFloat
+            | ItemStatusChanges::BuySuccessBitSkins => matches!(
+                self,
+                ItemStatus::OnBuyOfferWaitingSeller
+                    | ItemStatus::OnBuyOfferWaitingTradeOffer
+                    | ItemStatus::OnBuyOfferWaitingTrade
+                    | ItemStatus::OnHold
+                    | ItemStatus::Bought
+            ),
+            ItemStatusChanges::BuySuccessLisSkins(_) | ItemStatusChanges::BuyFailure => matches!(
+                self,
+                ItemStatus::OnBuyOfferWaitingSeller | ItemStatus::OnBuyOfferWaitingTradeOffer | ItemStatus::OnBuyOfferWaitingTrade
+            ),
+            ItemStatusChanges::SellOfferCreated(_) => {
+                matches!(self, ItemStatus::Available | ItemStatus::OnSellOfferWaitingBuyer)
+            }
+            ItemStatusChanges::SellOfferBought(_) => {
+                matches!(self, ItemStatus::OnSellOfferWaitingBuyer | ItemStatus::OnSellOfferWaitingTradeOffer)
+            }
+            ItemStatusChanges::SellTradeSent(_, _) => {
+                matches!(self, ItemStatus::OnSellOfferWaitingBuyer | ItemStatus::OnSellOfferWaitingTradeOffer)
+            }
+            ItemStatusChanges::SellTradeCanceled
+            | ItemStatusChanges::SellSuccess(_, _)
+            | ItemStatusChanges::SellError(_) => matches!(
+                self,
+                ItemStatus::OnSellOfferWaitingBuyer | ItemStatus::OnSellOfferWaitingTradeOffer | ItemStatus::OnSellOfferWaitingTrade
+            ),
+            // Only the asset id changes, the status is kept
+            ItemStatusChanges::AssetIdRebound { .. } => !matches!(self, ItemStatus::Sold),
+        }
+    }
 }
 
 // The enum that contains all the possible state changes
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum ItemStatusChanges {
     Withdrawal,
+    WithdrawFailed,
     TradeLockDone,
     BuySuccessDmarket,
     BuyStartCSMoney,
@@ -98,19 +752,31 @@ pub enum ItemStatusChanges {
     SellTradeSent(Market, i64),
     SellSuccess(Market, f32),
     SellError(i64),
+    // Steam gave the received item a new asset id when the trade was accepted, the ticket has the old one
+    AssetIdRebound { old: String, new: String },
 }
 
 // The struct that contains the data about the items status change
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ItemStatusChangeTicket {
-    pub dmarket_item_id: String,
-    pub csmoney_item_id: String,
-    pub marketcsgo_item_id: String,
-    pub csfloat_offer_id: String,
+    #[serde(default)]
+    pub market_ids: MarketIds,
     pub asset_id: String,
     pub change: ItemStatusChanges,
 }
 
+impl ItemStatusChangeTicket {
+    pub fn new(asset_id: impl Into<String>, change: ItemStatusChanges) -> Self {
+        ItemStatusChangeTicket { market_ids: MarketIds::default(), asset_id: asset_id.into(), change }
+    }
+
+    // Adds the id the market gave the item, e.g. the listing id of a sell offer
+    pub fn with_market_id(mut self, market: Market, id: impl Into<String>) -> Self {
+        self.market_ids.set(market, id);
+        self
+    }
+}
+
 // The struct that has all the price data of an Item
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Price {
@@ -123,6 +789,51 @@ pub struct Price {
     pub price_sell: f32,
     pub price_sell_w_comm: f32,
     pub sale_stats: Option<ItemSaleStats>,
+    #[serde(default)]
+    pub fetched_at_unix: i64,
+    // The amount of sales in the last 24 hours, only the markets that report it have it
+    #[serde(default)]
+    pub volume_24h: Option<u32>,
+    // The currency of the amounts, the prices saved before it are in dollars
+    #[serde(default)]
+    pub currency: Currency,
+}
+
+impl Price {
+    // Checks if the price was fetched more than max_age_secs ago
+    pub fn is_stale(&self, max_age_secs: u64) -> bool {
+        (get_sys_time_in_secs() as i64 - self.fetched_at_unix) > max_age_secs as i64
+    }
+}
+
+// The struct that has a buy order which is executed once the price drops to the target before it expires
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimedBuyOrder {
+    pub market_hash_name: String,
+    pub target_price: f32,
+    pub trade_hold: i32,
+    pub market: Market,
+    pub expires_unix: i64,
+}
+
+// The struct that has an item which is watched on the given markets until the wanted quantity is bought
+// Without auto_buy a qualifying listing only sends a notification
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchlistEntry {
+    pub market_hash_name: String,
+    pub markets: Vec<Market>,
+    pub max_price: f32,
+    pub max_trade_hold: i32,
+    pub quantity: u32,
+    pub auto_buy: bool,
+    #[serde(default = "default_watch_interval_secs")]
+    pub poll_interval_secs: u64,
+    #[serde(default)]
+    pub paused: bool,
+}
+
+fn default_watch_interval_secs() -> u64 {
+    60
 }
 
 // The struct that has the data of an items price in two different markets
@@ -136,17 +847,303 @@ pub struct PriceCompare {
     pub price: (Price, Price),
 }
 
+// The struct that has a chain of trades where every step buys on the market the previous step sold on
+// A step is (item name, buy market, sell market, profit percentage after the commision)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArbitrageChain {
+    pub steps: Vec<(String, Market, Market, f32)>,
+    // The compounded profit of the steps, 10% and 10% is 21%
+    pub total_profit_perc: f32,
+}
+
+// The struct that decides which price comparisons are kept by price_compare_all
+// Empty market lists mean every market is allowed
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PriceCompareFilter {
+    pub min_profit_perc_after_comm: i32,
+    pub min_profit_val_after_comm: f32,
+    pub buy_markets: Vec<Market>,
+    pub sell_markets: Vec<Market>,
+}
+
+// The struct that has the price comparisons grouped by (buy market, sell market)
+#[derive(Debug, Clone, Default)]
+pub struct PriceCompareMap {
+    pub pairs: HashMap<(Market, Market), Vec<PriceCompare>>,
+}
+
+impl PriceCompareMap {
+    // Returns the n best opportunities across all market pairs, highest profit after commission first
+    pub fn top_n(&self, n: usize) -> Vec<&PriceCompare> {
+        let mut all: Vec<&PriceCompare> = self.pairs.values().flatten().collect();
+        all.sort_by(|a, b| {
+            b.diff_perc_after_comm
+                .cmp(&a.diff_perc_after_comm)
+                .then(b.diff_val_after_comm.total_cmp(&a.diff_val_after_comm))
+        });
+        all.truncate(n);
+        all
+    }
+}
+
+// The struct that limits the buys to listings with a float in the range (inclusive)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FloatFilter {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl FloatFilter {
+    // Checks if the float value of a listing is in the range
+    pub fn contains(&self, float_value: f64) -> bool {
+        float_value >= self.min && float_value <= self.max
+    }
+}
+
+// The enum which differentiates the signals a buy decision needs, every given signal has to fire
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BuySignal {
+    // The best market pair is over the profit margin
+    Arbitrage,
+    // The price momentum of the item is at least the threshold (-1.0 to 1.0)
+    Momentum { threshold: f32 },
+}
+
+// The offset between a 32-bit Steam account id and a 64-bit SteamID of an individual account
+const STEAM64_BASE: u64 = 76561197960265728;
+
+// The struct that has the Steam app id of a game and the inventory context id of its items
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SteamApp {
+    pub app_id: u32,
+    pub context_id: u32,
+}
+
+impl SteamApp {
+    pub const CS: SteamApp = SteamApp { app_id: 730, context_id: 2 };
+    pub const DOTA2: SteamApp = SteamApp { app_id: 570, context_id: 2 };
+    pub const TF2: SteamApp = SteamApp { app_id: 440, context_id: 2 };
+    pub const RUST: SteamApp = SteamApp { app_id: 252490, context_id: 2 };
+}
+
+impl Default for SteamApp {
+    fn default() -> Self {
+        SteamApp::CS
+    }
+}
+
+// The struct that has a Steam account id, stored as the 64-bit SteamID
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SteamId(u64);
+
+// The enum that contains the reasons a SteamID can't be created
+#[derive(Debug, Clone, PartialEq)]
+pub enum SteamIdError {
+    NotNumeric(String),
+    OutOfRange(u64),
+    InvalidUrl(String),
+    MissingParam(&'static str),
+    InvalidToken(String),
+}
+
+impl std::fmt::Display for SteamIdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SteamIdError::NotNumeric(input) => write!(f, "The SteamID is not numeric: {:?}", input),
+            SteamIdError::OutOfRange(id) => write!(f, "The SteamID is not an individual account id: {}", id),
+            SteamIdError::InvalidUrl(url) => write!(f, "The trade url can't be parsed: {:?}", url),
+            SteamIdError::MissingParam(param) => write!(f, "The trade url has no {} parameter", param),
+            SteamIdError::InvalidToken(token) => write!(f, "The trade token is not 8 alphanumeric characters: {:?}", token),
+        }
+    }
+}
+
+impl SteamId {
+    pub fn from_account_id(account_id: u32) -> Self {
+        SteamId(STEAM64_BASE + account_id as u64)
+    }
+
+    // Fails if the id is not in the range of individual accounts
+    pub fn from_steam64(steam64: u64) -> Result<Self, SteamIdError> {
+        if steam64 < STEAM64_BASE || steam64 - STEAM64_BASE > u32::MAX as u64 {
+            return Err(SteamIdError::OutOfRange(steam64));
+        }
+        Ok(SteamId(steam64))
+    }
+
+    // Accepts both the account id and the 64-bit SteamID as text
+    pub fn parse(input: &str) -> Result<Self, SteamIdError> {
+        let id = input
+            .trim()
+            .parse::<u64>()
+            .map_err(|_| SteamIdError::NotNumeric(input.to_string()))?;
+
+        if id <= u32::MAX as u64 {
+            Ok(SteamId::from_account_id(id as u32))
+        } else {
+            SteamId::from_steam64(id)
+        }
+    }
+
+    // Reads the partner and token query params of a trade url, returns (SteamID, token)
+    // Only http and https urls of steamcommunity.com/tradeoffer/new are accepted, the params can be in any order
+    pub fn from_trade_url(trade_url: &str) -> Result<(Self, String), SteamIdError> {
+        let url = reqwest::Url::parse(trade_url.trim()).map_err(|_| SteamIdError::InvalidUrl(trade_url.to_string()))?;
+        if !matches!(url.scheme(), "http" | "https")
+            || url.host_str() != Some("steamcommunity.com")
+            || !url.path().starts_with("/tradeoffer/new")
+        {
+            return Err(SteamIdError::InvalidUrl(trade_url.to_string()));
+        }
+
+        let mut partner = None;
+        let mut token = None;
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "partner" => partner = Some(value.to_string()),
+                "token" => token = Some(value.to_string()),
+                _ => {}
+            }
+        }
+
+        let partner = partner.ok_or(SteamIdError::MissingParam("partner"))?;
+        let token = token.ok_or(SteamIdError::MissingParam("token"))?;
+        let account_id = partner
+            .parse::<u32>()
+            .map_err(|_| SteamIdError::NotNumeric(partner.clone()))?;
+        if token.len() != 8 || !token.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(SteamIdError::InvalidToken(token));
+        }
+
+        Ok((SteamId::from_account_id(account_id), token))
+    }
+
+    pub fn account_id(&self) -> u32 {
+        (self.0 - STEAM64_BASE) as u32
+    }
+
+    pub fn steam64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl std::fmt::Display for SteamId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// The struct that has everything needed to act as a Steam account
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SteamAccount {
+    pub steam_id: SteamId,
+    pub cookie: String,
+    pub session_id: String,
+    pub webapi_token: String,
+    pub trade_token: String,
+}
+
+// The struct that has a single listing of an item on a market
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Listing {
+    pub id: String,
+    pub asset_id: String,
+    pub price: f32,
+    pub trade_hold_days: i64,
+}
+
+// The enum which differentiates the states of a sell listing
+// at_price of Sold is the amount received after the fees
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ListingState {
+    Active,
+    Sold { at_price: f32 },
+    Canceled,
+}
+
+// The struct that has the state of a sell listing on MarketCSGO
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListingStatus {
+    pub asset_id: String,
+    pub marketcsgo_item_id: String,
+    pub status: ListingState,
+}
+
+// The struct that has the outcome of a check of the pending buy operations
+// The failures have the item id or the trade offer id and the error
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BuyOperationsReport {
+    pub withdrawn: Vec<String>,
+    pub accepted_offers: Vec<String>,
+    pub failures: Vec<(String, String)>,
+}
+
+// The struct that has the amount of listings near the lowest price of an item
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OrderBookDepth {
+    pub listings_at_floor: u32,
+    pub listings_within_depth: u32,
+    pub price_floor: f32,
+    pub price_at_depth: f32,
+}
+
 // The struct that contains all the sale stats of an item
+// The weekly fields are for the short window and the monthly fields for the long window,
+// the lengths of the windows are in short_window_days and long_window_days
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ItemSaleStats {
     pub name: String,
-    pub weekly_avg_price: f32,
-    pub weekly_avg_price_w_comm: f32,
+    #[serde(default = "default_short_window_days")]
+    pub short_window_days: u32,
+    #[serde(default = "default_long_window_days")]
+    pub long_window_days: u32,
+    #[serde(alias = "weekly_avg_price")]
+    pub weekly_vwap: f32,
+    #[serde(alias = "weekly_avg_price_w_comm")]
+    pub weekly_vwap_w_comm: f32,
+    #[serde(default)]
+    pub weekly_simple_avg: f32,
+    #[serde(default)]
+    pub price_volatility_std_dev: f32,
     pub weekly_sale_count: i32,
     pub monthly_avg_price: f32,
     pub monthly_sale_count: i32,
     pub weekly_price_change: f32,
     pub projected_price_next_week: f32,
+    #[serde(default)]
+    pub median_price: f32,
+    #[serde(default)]
+    pub price_std_dev: f32,
+    // The days dropped as outliers before the averages were calculated
+    #[serde(default)]
+    pub outliers_removed: u32,
+}
+
+// The struct that has the multipliers a trade held listing's price is compared with,
+// a held item ties up the capital so it has to be this much cheaper to be picked
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TradeHoldPremiums {
+    pub two_days: f32,
+    pub four_days: f32,
+    pub seven_days: f32,
+}
+
+impl Default for TradeHoldPremiums {
+    fn default() -> Self {
+        TradeHoldPremiums {
+            two_days: 1.02,
+            four_days: 1.04,
+            seven_days: 1.07,
+        }
+    }
+}
+
+fn default_short_window_days() -> u32 {
+    7
+}
+
+fn default_long_window_days() -> u32 {
+    30
 }
 
 // Declare the type structure of all the market functions
@@ -159,8 +1156,8 @@ pub trait MarketFunctions {
     fn get_min_sell_price_auto(&self, profit_margin: f32, current_market: Option<Market>) -> (f32, Market);
     fn get_sell_market(&self, item: ItemData) -> (Option<Market>, f32, f32);
     fn get_sell_market_other(&self, item: ItemData, main_market: Market, main_sell_price: f32) -> Vec<(Option<Market>, f32, f32)>;
-    async fn buy_item(&mut self, market: Market, price: f32, trade_hold: i32) -> Result<ItemStatusChangeTicket, String>;
-    async fn check_buy_conditions_and_buy(&mut self, profit_margin: f32, iteration: i32) -> Result<ItemStatusChangeTicket, String>;
+    async fn buy_item(&mut self, market: Market, price: f32, trade_hold: i32, float_filter: Option<FloatFilter>) -> Result<ItemStatusChangeTicket, String>;
+    async fn check_buy_conditions_and_buy(&mut self, profit_margin: f32, iteration: i32, float_filter: Option<FloatFilter>, signals: &[BuySignal]) -> Result<ItemStatusChangeTicket, String>;
 }
 
 // Declare the type structure of ItemData functions
@@ -172,6 +1169,8 @@ pub trait ItemDataFunctions {
     async fn remove_sell(&self) -> Result<ItemStatusChangeTicket, String>;
     async fn remove_sell_no_error(&self, ignored_market: Market);
     fn get_unix(&mut self, item_name: String) -> Option<i64>;
+    fn update_asset_id(&mut self, old_asset_id: &str, new_asset_id: String) -> Result<(), String>;
+    async fn relist_if_stale(&mut self, item_name: &str, relist_after_days: u32, decay_rate: f32) -> Result<Option<ItemStatusChangeTicket>, String>;
 }
 
 // Get the UNIX timestamp
@@ -181,3 +1180,209 @@ fn get_sys_time_in_secs() -> u64 {
         Err(_) => panic!("SystemTime before UNIX EPOCH!"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATUSES: [ItemStatus; 14] = [
+        ItemStatus::Available,
+        ItemStatus::OnSellOfferWaitingBuyer,
+        ItemStatus::OnSellOfferWaitingTradeOffer,
+        ItemStatus::OnSellOfferWaitingTrade,
+        ItemStatus::Sold,
+        ItemStatus::OnBuyOfferWaitingSeller,
+        ItemStatus::OnBuyOfferWaitingTradeOffer,
+        ItemStatus::OnBuyOfferWaitingTrade,
+        ItemStatus::Bought,
+        ItemStatus::BoughtLisSkins,
+        ItemStatus::Error,
+        ItemStatus::OnHold,
+        ItemStatus::PendingWithdrawal,
+        ItemStatus::Untradable,
+    ];
+
+    // Every change with the statuses it can be applied to and the status it leads to, None keeps the status
+    fn transitions() -> Vec<(ItemStatusChanges, Vec<ItemStatus>, Option<ItemStatus>)> {
+        use ItemStatus::*;
+        let waiting_buy = vec![OnBuyOfferWaitingSeller, OnBuyOfferWaitingTradeOffer, OnBuyOfferWaitingTrade];
+        let buy_success = vec![OnBuyOfferWaitingSeller, OnBuyOfferWaitingTradeOffer, OnBuyOfferWaitingTrade, OnHold, Bought];
+        let on_sell = vec![OnSellOfferWaitingBuyer, OnSellOfferWaitingTradeOffer, OnSellOfferWaitingTrade];
+        let not_sold: Vec<ItemStatus> = STATUSES.iter().filter(|status| **status != Sold).cloned().collect();
+
+        vec![
+            (ItemStatusChanges::Withdrawal, vec![Bought, PendingWithdrawal, OnHold], Some(OnHold)),
+            (ItemStatusChanges::WithdrawFailed, vec![Bought, PendingWithdrawal, OnHold], Some(PendingWithdrawal)),
+            (ItemStatusChanges::TradeLockDone, vec![OnHold, Bought, BoughtLisSkins], Some(Available)),
+            (ItemStatusChanges::BuySuccessDmarket, buy_success.clone(), Some(Bought)),
+            (ItemStatusChanges::BuyStartCSMoney, vec![Available, OnBuyOfferWaitingSeller], Some(OnBuyOfferWaitingSeller)),
+            (ItemStatusChanges::BuyStartCSFloat, vec![Available, OnBuyOfferWaitingSeller], Some(OnBuyOfferWaitingSeller)),
+            (ItemStatusChanges::BuyStartLisSkins, vec![Available, OnBuyOfferWaitingSeller], Some(OnBuyOfferWaitingSeller)),
+            (ItemStatusChanges::BuySuccessCSMoney, buy_success.clone(), Some(Bought)),
+            (ItemStatusChanges::BuySuccessCSFloat, buy_success.clone(), Some(Bought)),
+            (ItemStatusChanges::BuySuccessBitSkins, buy_success, Some(Bought)),
+            (ItemStatusChanges::BuySuccessLisSkins("2".to_string()), waiting_buy.clone(), Some(BoughtLisSkins)),
+            (ItemStatusChanges::BuyFailure, waiting_buy, Some(Error)),
+            (ItemStatusChanges::SellOfferCreated(Market::WaxPeer), vec![Available, OnSellOfferWaitingBuyer], Some(OnSellOfferWaitingBuyer)),
+            (
+                ItemStatusChanges::SellOfferBought(Market::WaxPeer),
+                vec![OnSellOfferWaitingBuyer, OnSellOfferWaitingTradeOffer],
+                Some(OnSellOfferWaitingTradeOffer),
+            ),
+            (
+                ItemStatusChanges::SellTradeSent(Market::WaxPeer, 7),
+                vec![OnSellOfferWaitingBuyer, OnSellOfferWaitingTradeOffer],
+                Some(OnSellOfferWaitingTrade),
+            ),
+            (ItemStatusChanges::SellTradeCanceled, on_sell.clone(), Some(Available)),
+            (ItemStatusChanges::SellSuccess(Market::WaxPeer, 10.0), on_sell.clone(), Some(Sold)),
+            (ItemStatusChanges::SellError(1), on_sell, Some(Error)),
+            (ItemStatusChanges::AssetIdRebound { old: "1".to_string(), new: "2".to_string() }, not_sold, None),
+        ]
+    }
+
+    fn item_with(statuses: &[(&str, ItemStatus)]) -> Item {
+        Item {
+            name: "AK-47 | Redline (Field-Tested)".to_string(),
+            count: ItemCount { total: 0, available: 0, on_offer: 0, on_hold: 0, max_count: 0 },
+            data: statuses
+                .iter()
+                .map(|(asset_id, status)| ItemData::builder().asset_id(*asset_id).market(Market::CSFloat).status(status.clone()).build())
+                .collect(),
+            price: Vec::new(),
+            history: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn the_table_covers_every_change() {
+        // No wildcard, a new change doesn't compile here until it is added to the table
+        let index = |change: &ItemStatusChanges| match change {
+            ItemStatusChanges::Withdrawal => 0,
+            ItemStatusChanges::WithdrawFailed => 1,
+            ItemStatusChanges::TradeLockDone => 2,
+            ItemStatusChanges::BuySuccessDmarket => 3,
+            ItemStatusChanges::BuyStartCSMoney => 4,
+            ItemStatusChanges::BuyStartCSFloat => 5,
+            ItemStatusChanges::BuyStartLisSkins => 6,
+            ItemStatusChanges::BuySuccessCSMoney => 7,
+            ItemStatusChanges::BuySuccessCSFloat => 8,
+            ItemStatusChanges::BuySuccessBitSkins => 9,
+            ItemStatusChanges::BuySuccessLisSkins(_) => 10,
+            ItemStatusChanges::BuyFailure => 11,
+            ItemStatusChanges::SellOfferCreated(_) => 12,
+            ItemStatusChanges::SellOfferBought(_) => 13,
+            ItemStatusChanges::SellTradeCanceled => 14,
+            ItemStatusChanges::SellTradeSent(_, _) => 15,
+            ItemStatusChanges::SellSuccess(_, _) => 16,
+            ItemStatusChanges::SellError(_) => 17,
+            ItemStatusChanges::AssetIdRebound { .. } => 18,
+        };
+
+        let mut seen: Vec<usize> = transitions().iter().map(|(change, _, _)| index(change)).collect();
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen, (0..19).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn apply_ticket_follows_the_transition_table() {
+        for (change, allowed, to) in transitions() {
+            for status in STATUSES.iter() {
+                let mut item = item_with(&[("1", status.clone())]);
+                let before = item.data.clone();
+                let res = item.apply_ticket(&ItemStatusChangeTicket::new("1", change.clone()));
+
+                if !allowed.contains(status) {
+                    assert!(res.is_err(), "{:?} was applied to {:?}", change, status);
+                    assert_eq!(item.data, before, "the rejected {:?} changed an item in {:?}", change, status);
+                    assert!(item.history.is_empty());
+                    continue;
+                }
+
+                assert!(res.is_ok(), "{:?} was rejected in {:?}: {:?}", change, status, res);
+                assert_eq!(item.data.len(), 1);
+                assert_eq!(item.data[0].status, to.clone().unwrap_or(status.clone()), "{:?} from {:?}", change, status);
+                match &change {
+                    ItemStatusChanges::BuySuccessLisSkins(new) | ItemStatusChanges::AssetIdRebound { new, .. } => {
+                        assert_eq!(&item.data[0].asset_id, new)
+                    }
+                    _ => assert_eq!(item.data[0].asset_id, "1"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_rejected_rebind_leaves_the_item_unchanged() {
+        let tickets = [
+            // The new asset id is already tracked
+            ItemStatusChangeTicket::new("1", ItemStatusChanges::BuySuccessLisSkins("2".to_string())),
+            ItemStatusChangeTicket::new("1", ItemStatusChanges::AssetIdRebound { old: "1".to_string(), new: "2".to_string() }),
+            // The old asset id is not tracked
+            ItemStatusChangeTicket::new("1", ItemStatusChanges::AssetIdRebound { old: "9".to_string(), new: "3".to_string() }),
+        ];
+
+        for ticket in tickets.iter() {
+            let mut item = item_with(&[("1", ItemStatus::OnBuyOfferWaitingSeller), ("2", ItemStatus::Available)]);
+            let before = item.data.clone();
+
+            assert!(item.apply_ticket(ticket).is_err(), "{:?} was applied", ticket.change);
+            assert_eq!(item.data, before, "{:?} changed the item", ticket.change);
+            assert!(item.history.is_empty());
+        }
+    }
+
+    #[test]
+    fn steam_id_round_trips_between_its_forms() {
+        for account_id in [0, 1, 123456789, u32::MAX] {
+            let id = SteamId::from_account_id(account_id);
+            assert_eq!(id.account_id(), account_id);
+            assert_eq!(SteamId::from_steam64(id.steam64()), Ok(id));
+            assert_eq!(SteamId::parse(&id.to_string()), Ok(id));
+            assert_eq!(SteamId::parse(&account_id.to_string()), Ok(id));
+
+            let json = serde_json::to_string(&id).unwrap();
+            assert_eq!(serde_json::from_str::<SteamId>(&json).unwrap(), id);
+        }
+
+        assert_eq!(SteamId::parse(" 76561198084389645 ").unwrap().account_id(), 124123917);
+    }
+
+    #[test]
+    fn steam_id_rejects_the_ids_of_other_account_types() {
+        assert_eq!(SteamId::from_steam64(STEAM64_BASE - 1), Err(SteamIdError::OutOfRange(STEAM64_BASE - 1)));
+        let past_last = STEAM64_BASE + u32::MAX as u64 + 1;
+        assert_eq!(SteamId::from_steam64(past_last), Err(SteamIdError::OutOfRange(past_last)));
+        assert_eq!(SteamId::parse("7656119x"), Err(SteamIdError::NotNumeric("7656119x".to_string())));
+        assert_eq!(SteamId::parse("-1"), Err(SteamIdError::NotNumeric("-1".to_string())));
+    }
+
+    #[test]
+    fn steam_id_round_trips_through_a_trade_url() {
+        let id = SteamId::from_account_id(124123917);
+        let trade_url = format!("https://steamcommunity.com/tradeoffer/new/?partner={}&token=AbC123xY", id.account_id());
+        assert_eq!(SteamId::from_trade_url(&trade_url), Ok((id, "AbC123xY".to_string())));
+
+        // The params can be in any order
+        let reordered = "https://steamcommunity.com/tradeoffer/new/?token=AbC123xY&partner=124123917";
+        assert_eq!(SteamId::from_trade_url(reordered), Ok((id, "AbC123xY".to_string())));
+    }
+
+    #[test]
+    fn steam_id_rejects_malformed_trade_urls() {
+        let cases = [
+            ("https://steamcommunity.com.evil.io/tradeoffer/new/?partner=1&token=AbC123xY", SteamIdError::InvalidUrl("https://steamcommunity.com.evil.io/tradeoffer/new/?partner=1&token=AbC123xY".to_string())),
+            ("ftp://steamcommunity.com/tradeoffer/new/?partner=1&token=AbC123xY", SteamIdError::InvalidUrl("ftp://steamcommunity.com/tradeoffer/new/?partner=1&token=AbC123xY".to_string())),
+            ("https://steamcommunity.com/tradeoffer/new/?token=AbC123xY", SteamIdError::MissingParam("partner")),
+            ("https://steamcommunity.com/tradeoffer/new/?partner=1", SteamIdError::MissingParam("token")),
+            ("https://steamcommunity.com/tradeoffer/new/?partner=abc&token=AbC123xY", SteamIdError::NotNumeric("abc".to_string())),
+            ("https://steamcommunity.com/tradeoffer/new/?partner=1&token=AbC1", SteamIdError::InvalidToken("AbC1".to_string())),
+            ("https://steamcommunity.com/tradeoffer/new/?partner=1&token=AbC1%3C%3E", SteamIdError::InvalidToken("AbC1<>".to_string())),
+        ];
+
+        for (trade_url, err) in cases {
+            assert_eq!(SteamId::from_trade_url(trade_url), Err(err), "{}", trade_url);
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use crate::balances;
+use crate::config;
+use crate::control::SharedControl;
+use crate::key_pool;
+use crate::log_functions;
+use crate::metrics;
+use crate::structs::{Item, ItemStatus};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+
+// How long a getUpdates request waits for a new message before it returns empty
+const POLL_TIMEOUT_SECS: u64 = 30;
+
+// How long to wait before polling again after a failed getUpdates request
+const POLL_RETRY_SECS: u64 = 10;
+
+// The answer of the getUpdates endpoint
+#[derive(Debug, Deserialize)]
+struct UpdatesResponse {
+    ok: bool,
+    #[serde(default)]
+    result: Vec<Update>,
+    description: Option<String>,
+}
+
+// The struct that has one update of the bot, only the messages are handled
+#[derive(Debug, Deserialize)]
+pub struct Update {
+    pub update_id: i64,
+    pub message: Option<Message>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Message {
+    pub chat: Chat,
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Chat {
+    pub id: i64,
+}
+
+// The enum which differentiates the commands the bot answers to
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Status,
+    Balance,
+    Inventory,
+    Stop,
+    Unknown(String),
+}
+
+impl Command {
+    // Parses the first word of the message text, /status@my_bot is the same as /status
+    // Returns None for the messages that aren't commands
+    pub fn parse(text: &str) -> Option<Command> {
+        let word = text.split_whitespace().next()?.strip_prefix('/')?;
+        let command = word.split('@').next().unwrap_or(word).to_lowercase();
+
+        Some(match command.as_str() {
+            "status" => Command::Status,
+            "balance" => Command::Balance,
+            "inventory" => Command::Inventory,
+            "stop" => Command::Stop,
+            _ => Command::Unknown(command),
+        })
+    }
+}
+
+/// Sends a message through the Telegram bot api
+pub async fn send_message(chat_id: &str, text: &str) -> Result<(), String> {
+    let url = api_url("sendMessage").map_err(|e| format!("telegram.rs | send_message() | {}", e))?;
+    let client = reqwest::Client::new();
+    let res = client
+        .post(url)
+        .timeout(std::time::Duration::from_secs(15))
+        .form(&[("chat_id", chat_id), ("text", text)])
+        .send()
+        .await
+        .map_err(|e| format!("telegram.rs | send_message(chat_id={}) | Error occured when sending the api request. E: {:?}", chat_id, e))?;
+
+    if !res.status().is_success() {
+        return Err(format!("telegram.rs | send_message(chat_id={}) | Telegram returned an error status: {}", chat_id, res.status()));
+    }
+
+    Ok(())
+}
+
+/// Long polls the updates that came after the offset
+/// The request waits up to POLL_TIMEOUT_SECS for a message, an empty list means none came
+pub async fn get_updates(offset: i64) -> Result<Vec<Update>, String> {
+    let url = api_url("getUpdates").map_err(|e| format!("telegram.rs | get_updates() | {}", e))?;
+    let client = reqwest::Client::new();
+    let res = client
+        .get(url)
+        .timeout(std::time::Duration::from_secs(POLL_TIMEOUT_SECS + 10))
+        .query(&[("offset", offset.to_string()), ("timeout", POLL_TIMEOUT_SECS.to_string())])
+        .send()
+        .await
+        .map_err(|e| format!("telegram.rs | get_updates(offset={}) | Error occured when sending the api request. E: {:?}", offset, e))?;
+
+    let body: UpdatesResponse = res
+        .json()
+        .await
+        .map_err(|e| format!("telegram.rs | get_updates(offset={}) | Error occured when parsing the response. E: {:?}", offset, e))?;
+
+    if !body.ok {
+        return Err(format!(
+            "telegram.rs | get_updates(offset={}) | Telegram returned an error: {}",
+            offset,
+            body.description.unwrap_or_default()
+        ));
+    }
+
+    Ok(body.result)
+}
+
+/// Answers the commands sent to the bot until the bot stops
+///
+/// - Only the messages of the telegram_chat_id of the config are answered, the rest are ignored
+/// - /status sends the request stats of every market, /balance the market balances,
+///   /inventory the item counts by status and /stop triggers the emergency stop
+/// - Has to be spawned once at startup, failures are only logged and the polling goes on
+pub async fn run_command_loop(control: SharedControl, inv: Arc<tokio::sync::Mutex<HashMap<String, Item>>>) {
+    let bot_config = config::get_bot_config();
+    let chat_id = match (&bot_config.telegram_bot_token, &bot_config.telegram_chat_id) {
+        (Some(_), Some(chat_id)) => chat_id.clone(),
+        _ => return,
+    };
+    let token = control.token();
+    let mut offset = 0;
+
+    while !control.should_stop() {
+        let updates = tokio::select! {
+            _ = token.cancelled() => break,
+            updates = get_updates(offset) => updates,
+        };
+
+        let updates = match updates {
+            Ok(updates) => updates,
+            Err(err_str) => {
+                log_functions::log_err(&err_str);
+                sleep(Duration::from_secs(POLL_RETRY_SECS)).await;
+                continue;
+            }
+        };
+
+        for update in updates {
+            // Confirms the update, getUpdates won't return it again
+            offset = offset.max(update.update_id + 1);
+
+            let Some(message) = update.message else { continue };
+            if message.chat.id.to_string() != chat_id {
+                log_functions::log_structured("Warning", &format!("Ignored a Telegram message from the chat {}.", message.chat.id));
+                continue;
+            }
+            let Some(command) = message.text.as_deref().and_then(Command::parse) else { continue };
+
+            let reply = handle_command(&command, &control, &inv).await;
+            if let Err(err_str) = send_message(&chat_id, &reply).await {
+                log_functions::log_err(&err_str);
+            }
+        }
+    }
+}
+
+// Runs the command and returns the reply
+async fn handle_command(command: &Command, control: &SharedControl, inv: &Arc<tokio::sync::Mutex<HashMap<String, Item>>>) -> String {
+    match command {
+        Command::Status => format_status(control),
+        Command::Balance => format_balance().await,
+        Command::Inventory => format_inventory(&*inv.lock().await),
+        Command::Stop => {
+            control.stop();
+            log_functions::log_structured("Warning", "Emergency stop triggered from Telegram");
+            "🛑 Emergency stop triggered".to_string()
+        }
+        Command::Unknown(command) => format!("Unknown command /{}\nCommands: /status /balance /inventory /stop", command),
+    }
+}
+
+// The request stats of every market that sent a request
+fn format_status(control: &SharedControl) -> String {
+    let state = if control.should_stop() {
+        "stopping"
+    } else if control.is_paused() {
+        "paused"
+    } else {
+        "running"
+    };
+    let mut lines = vec![format!("📊 Bot is {}", state)];
+
+    let mut stats: Vec<_> = metrics::snapshot().into_iter().collect();
+    stats.sort_by_key(|(market, _)| format!("{:?}", market));
+    for (market, stats) in stats {
+        lines.push(format!(
+            "{:?}: {} requests, {} errors, avg {:.0}ms, p95 {:.0}ms",
+            market, stats.total_requests, stats.total_errors, stats.avg_latency_ms, stats.p95_latency_ms
+        ));
+    }
+    if lines.len() == 1 {
+        lines.push("No requests sent yet".to_string());
+    }
+
+    for key in key_pool::bitskins_scrape_key_status() {
+        if key.quarantined {
+            lines.push(format!("🔑 BitSkins scrape key ...{} is quarantined", key.key_suffix));
+        } else if let Some(secs) = key.rate_limited_for_secs {
+            lines.push(format!("🔑 BitSkins scrape key ...{} is rate limited for {}s", key.key_suffix, secs));
+        }
+    }
+
+    lines.join("\n")
+}
+
+// The balance of every market that could be read and their share of the capital
+async fn format_balance() -> String {
+    let balances_usd = balances::get_multi_market_balance().await.to_usd().await;
+    balances::format_capital_distribution(&balances_usd)
+}
+
+// The number of items of every status
+fn format_inventory(inv: &HashMap<String, Item>) -> String {
+    let mut by_status: BTreeMap<String, usize> = BTreeMap::new();
+    for data in inv.values().flat_map(|item| item.data.iter()) {
+        *by_status.entry(format!("{:?}", data.status)).or_default() += 1;
+    }
+    let total: usize = by_status.values().sum();
+    let available = by_status.get(&format!("{:?}", ItemStatus::Available)).copied().unwrap_or(0);
+
+    let mut lines = vec![format!("🎒 {} items ({} available) of {} names", total, available, inv.len())];
+    for (status, count) in by_status {
+        lines.push(format!("{}: {}", status, count));
+    }
+
+    lines.join("\n")
+}
+
+// The url of a bot api method, needs the telegram_bot_token of the config
+fn api_url(method: &str) -> Result<String, String> {
+    let token = config::get_bot_config()
+        .telegram_bot_token
+        .clone()
+        .ok_or("Error occured, no telegram bot token in the config.".to_string())?;
+
+    Ok(format!("https://api.telegram.org/bot{}/{}", token, method))
+}
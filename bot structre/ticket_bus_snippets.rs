@@ -0,0 +1,172 @@
+use crate::log_functions;
+use crate::metrics;
+use crate::structs::{Item, ItemStatusChangeTicket};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+// The file every applied ticket is appended to
+const TICKET_LOG_PATH: &str = "tickets.jsonl";
+
+// The file the dead letter tickets are written to on shutdown
+const DEAD_LETTERS_PATH: &str = "dead_letters.json";
+
+// The sender side of the global bus, set when the consumer is started
+static TICKET_BUS: OnceLock<UnboundedSender<ItemStatusChangeTicket>> = OnceLock::new();
+
+// The tickets that could not be applied, kept for inspection
+static DEAD_LETTERS: Mutex<Vec<DeadLetter>> = Mutex::new(Vec::new());
+
+// The tickets that were pushed but not handled by the consumer yet
+static PENDING: AtomicU64 = AtomicU64::new(0);
+
+// The struct that has a ticket that could not be applied and the reason
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetter {
+    pub unix: u64,
+    pub ticket: ItemStatusChangeTicket,
+    pub reason: String,
+}
+
+/// Starts the consumer task that applies the pushed tickets to the inventory
+///
+/// - Every ticket is applied to the item that has its asset id, persisted and logged
+/// - Tickets that can't be applied go to the dead letter list
+pub fn start(inv: Arc<tokio::sync::Mutex<HashMap<String, Item>>>) -> Result<tokio::task::JoinHandle<()>, String> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    TICKET_BUS
+        .set(sender)
+        .map_err(|_| "ticket_bus.rs | start() | Error occured, the ticket bus is already started.".to_string())?;
+
+    Ok(tokio::spawn(run_consumer(receiver, inv)))
+}
+
+/// Pushes a ticket to the bus, goes to the dead letter list if the bus is not running
+pub fn push(ticket: ItemStatusChangeTicket) {
+    PENDING.fetch_add(1, Ordering::SeqCst);
+    let sent = match TICKET_BUS.get() {
+        Some(sender) => sender.send(ticket).map_err(|e| (e.0, "The ticket bus consumer has stopped.")),
+        None => Err((ticket, "The ticket bus is not started.")),
+    };
+
+    if let Err((ticket, reason)) = sent {
+        PENDING.fetch_sub(1, Ordering::SeqCst);
+        dead_letter(ticket, reason.to_string());
+    }
+}
+
+/// Returns a sender of the bus for the tasks that push tickets through their own channel handle
+/// None if the bus is not started
+pub fn sender() -> Option<UnboundedSender<ItemStatusChangeTicket>> {
+    TICKET_BUS.get().cloned()
+}
+
+/// Appends a ticket to the ticket log without applying it
+/// Used when the ticket can't go through the bus, e.g. a buy that finished during the shutdown
+pub fn record(ticket: &ItemStatusChangeTicket) {
+    persist(ticket);
+}
+
+/// Waits until the consumer handled every pushed ticket, returns false if the timeout ran out first
+pub async fn wait_until_drained(timeout: tokio::time::Duration) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+    while PENDING.load(Ordering::SeqCst) > 0 {
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+    true
+}
+
+/// Writes the tickets that could not be applied to a file so they can be pushed again after a restart
+pub fn persist_dead_letters() -> Result<(), String> {
+    let dead_letters = get_dead_letters();
+    if dead_letters.is_empty() {
+        return Ok(());
+    }
+
+    let dead_letters_str = serde_json::to_string_pretty(&dead_letters)
+        .map_err(|e| format!("ticket_bus.rs | persist_dead_letters() | Error occured when serializing the dead letters. E: {:?}", e))?;
+    std::fs::write(DEAD_LETTERS_PATH, dead_letters_str)
+        .map_err(|e| format!("ticket_bus.rs | persist_dead_letters() | Error occured when writing the file. E: {:?}", e))
+}
+
+/// Returns a copy of the tickets that could not be applied
+pub fn get_dead_letters() -> Vec<DeadLetter> {
+    DEAD_LETTERS.lock().unwrap().clone()
+}
+
+/// Removes and returns the tickets that could not be applied, e.g. to push them again
+pub fn take_dead_letters() -> Vec<DeadLetter> {
+    std::mem::take(&mut *DEAD_LETTERS.lock().unwrap())
+}
+
+// Applies the tickets until every sender is dropped
+async fn run_consumer(mut receiver: UnboundedReceiver<ItemStatusChangeTicket>, inv: Arc<tokio::sync::Mutex<HashMap<String, Item>>>) {
+    while let Some(ticket) = receiver.recv().await {
+        let res = {
+            let mut inv = inv.lock().await;
+            match inv.values_mut().find(|item| item.data.iter().any(|data| data.asset_id == ticket.asset_id)) {
+                Some(item) => item.apply_ticket(&ticket).map(|_| item.name.clone()),
+                None => Err(format!("ticket_bus.rs | run_consumer() | Error occured, unknown asset id {}.", ticket.asset_id)),
+            }
+        };
+
+        match res {
+            Ok(item_name) => {
+                persist(&ticket);
+                metrics::inc_tickets_applied();
+                log_functions::log_structured(
+                    "Info",
+                    &format!("Applied ticket {:?} to {} (asset id {}).", ticket.change, item_name, ticket.asset_id),
+                );
+            }
+            Err(err_str) => dead_letter(ticket, err_str),
+        }
+        PENDING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Appends the applied ticket to the ticket log
+fn persist(ticket: &ItemStatusChangeTicket) {
+    let line = match serde_json::to_string(ticket) {
+        Ok(line) => line + "\n",
+        Err(e) => {
+            log_functions::log_err(&format!("Cannot serialize the ticket {:?}. E: {:?}", ticket, e));
+            return;
+        }
+    };
+
+    let res = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(TICKET_LOG_PATH)
+        .and_then(|mut file| file.write_all(line.as_bytes()));
+    if let Err(e) = res {
+        log_functions::log_err(&format!("Cannot write the ticket to {}. E: {:?}", TICKET_LOG_PATH, e));
+    }
+}
+
+// Stores the ticket in the dead letter list
+fn dead_letter(ticket: ItemStatusChangeTicket, reason: String) {
+    log_functions::log_structured("Error", &format!("Dead letter ticket {:?}. Reason: {}", ticket, reason));
+    DEAD_LETTERS.lock().unwrap().push(DeadLetter {
+        unix: get_sys_time_in_secs(),
+        ticket,
+        reason,
+    });
+}
+
+// Get the UNIX timestamp
+fn get_sys_time_in_secs() -> u64 {
+    match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(n) => n.as_secs(),
+        Err(_) => panic!("SystemTime before UNIX EPOCH!"),
+    }
+}
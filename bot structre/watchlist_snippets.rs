@@ -0,0 +1,238 @@
+use crate::control;
+use crate::log_functions;
+use crate::notifications;
+use crate::orders::price_for_trade_hold;
+use crate::price_cache;
+use crate::structs::{Item, ItemCount, Market, MarketFunctions, WatchlistEntry};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::time::{sleep, Duration};
+
+// How often the loop checks which entries are due, every entry has its own poll interval
+const WATCHLIST_TICK_SECS: u64 = 5;
+
+// The watched items keyed by the item name
+static WATCHLIST: Mutex<Option<HashMap<String, WatchedItem>>> = Mutex::new(None);
+
+// The struct that has a watchlist entry and when it was last polled
+#[derive(Debug, Clone)]
+struct WatchedItem {
+    entry: WatchlistEntry,
+    last_polled_unix: i64,
+    // The price of the last notification, so the same listing isn't notified every poll
+    last_alert_price: Option<f32>,
+}
+
+// The layout of the watchlist file, TOML needs a table at the top so the entries are under a key
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct WatchlistFile {
+    entries: Vec<WatchlistEntry>,
+}
+
+/// Loads the watchlist entries from a .toml or a .json file and adds them to the watchlist
+///
+/// - The file has an `entries` list, an entry with the name of a watched item replaces it
+/// - Returns how many entries were loaded
+pub fn load_watchlist(path: &str) -> Result<usize, String> {
+    let file_str = std::fs::read_to_string(path)
+        .map_err(|e| format!("watchlist.rs | load_watchlist(path={}) | Error occured when reading the file. E: {:?}", path, e))?;
+
+    let file: WatchlistFile = if path.ends_with(".toml") {
+        toml::from_str(&file_str)
+            .map_err(|e| format!("watchlist.rs | load_watchlist(path={}) | Error occured when parsing the toml file. E: {:?}", path, e))?
+    } else {
+        serde_json::from_str(&file_str)
+            .map_err(|e| format!("watchlist.rs | load_watchlist(path={}) | Error occured when parsing the json file. E: {:?}", path, e))?
+    };
+
+    let count = file.entries.len();
+    for entry in file.entries {
+        add_entry(entry);
+    }
+    Ok(count)
+}
+
+/// Adds an entry to the watchlist, replaces the entry of the same item
+pub fn add_entry(entry: WatchlistEntry) {
+    log_functions::log_structured(
+        "Info",
+        &format!(
+            "Watching {} on {:?} at {} (trade hold {}), quantity {}, auto buy {}.",
+            entry.market_hash_name, entry.markets, entry.max_price, entry.max_trade_hold, entry.quantity, entry.auto_buy
+        ),
+    );
+
+    let mut watchlist = WATCHLIST.lock().unwrap();
+    watchlist.get_or_insert_with(HashMap::new).insert(
+        entry.market_hash_name.clone(),
+        WatchedItem { entry, last_polled_unix: 0, last_alert_price: None },
+    );
+}
+
+/// Removes the entry of the item, returns false if it was not watched
+pub fn remove_entry(market_hash_name: &str) -> bool {
+    let mut watchlist = WATCHLIST.lock().unwrap();
+    watchlist.get_or_insert_with(HashMap::new).remove(market_hash_name).is_some()
+}
+
+/// Pauses or resumes the entry of the item, returns false if it was not watched
+pub fn set_paused(market_hash_name: &str, paused: bool) -> bool {
+    let mut watchlist = WATCHLIST.lock().unwrap();
+    match watchlist.get_or_insert_with(HashMap::new).get_mut(market_hash_name) {
+        Some(watched) => {
+            watched.entry.paused = paused;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Returns every watched entry
+pub fn get_entries() -> Vec<WatchlistEntry> {
+    let mut watchlist = WATCHLIST.lock().unwrap();
+    watchlist
+        .get_or_insert_with(HashMap::new)
+        .values()
+        .map(|watched| watched.entry.clone())
+        .collect()
+}
+
+/// Checks the due entries right away and then every WATCHLIST_TICK_SECS until the shutdown
+///
+/// - The auto buys are done on the shared inventory so the bought items are tracked like any other buy
+pub fn spawn_watchlist_loop(inv: Arc<tokio::sync::Mutex<HashMap<String, Item>>>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let token = control::shutdown_token();
+        loop {
+            check_watchlist(&inv).await;
+
+            tokio::select! {
+                _ = token.cancelled() => break,
+                _ = sleep(Duration::from_secs(WATCHLIST_TICK_SECS)) => {}
+            }
+        }
+    })
+}
+
+/// Polls the entries whose interval has passed
+///
+/// - The cheapest market whose price for the max trade hold is at most the max price is used
+/// - With auto_buy the item is bought and the quantity is decremented, at zero the entry is retired
+/// - Without auto_buy a notification is sent, again only if the price changes
+pub async fn check_watchlist(inv: &Arc<tokio::sync::Mutex<HashMap<String, Item>>>) {
+    let now = Local::now().timestamp();
+    let due: Vec<WatchlistEntry> = {
+        let mut watchlist = WATCHLIST.lock().unwrap();
+        watchlist
+            .get_or_insert_with(HashMap::new)
+            .values_mut()
+            .filter(|watched| !watched.entry.paused && now - watched.last_polled_unix >= watched.entry.poll_interval_secs as i64)
+            .map(|watched| {
+                watched.last_polled_unix = now;
+                watched.entry.clone()
+            })
+            .collect()
+    };
+
+    for entry in due {
+        let (market, price) = match find_qualifying_price(&entry).await {
+            Some(found) => found,
+            None => continue,
+        };
+
+        if entry.auto_buy {
+            buy_entry(inv, &entry, market, price).await;
+        } else {
+            alert_entry(&entry, market, price);
+        }
+    }
+}
+
+// Returns the cheapest market of the entry whose price is at most the max price
+async fn find_qualifying_price(entry: &WatchlistEntry) -> Option<(Market, f32)> {
+    let mut best: Option<(Market, f32)> = None;
+    for market in entry.markets.iter() {
+        let price = match price_cache::get_cached_price(market.clone(), &entry.market_hash_name, entry.poll_interval_secs).await {
+            Ok(price) => price_for_trade_hold(&price, entry.max_trade_hold),
+            Err(err_str) => {
+                log_functions::log_err(&format!(
+                    "watchlist.rs | find_qualifying_price(market_hash_name={}, market={:?}) | Error occured when getting the price. E: {}",
+                    entry.market_hash_name, market, err_str
+                ));
+                continue;
+            }
+        };
+
+        // A 0.0 price means there is no listing for the trade hold
+        if price <= 0.0 || price > entry.max_price {
+            continue;
+        }
+        if best.as_ref().map_or(true, |(_, best_price)| price < *best_price) {
+            best = Some((market.clone(), price));
+        }
+    }
+    best
+}
+
+// Buys the entry on the market and decrements its quantity
+async fn buy_entry(inv: &Arc<tokio::sync::Mutex<HashMap<String, Item>>>, entry: &WatchlistEntry, market: Market, price: f32) {
+    let res = {
+        let mut inv = inv.lock().await;
+        let item = inv.entry(entry.market_hash_name.clone()).or_insert(Item {
+            name: entry.market_hash_name.clone(),
+            count: ItemCount { total: 0, available: 0, on_offer: 0, on_hold: 0, max_count: 0 },
+            data: Vec::new(),
+            price: Vec::new(),
+            history: Vec::new(),
+        });
+        item.buy_item(market.clone(), price, entry.max_trade_hold, None).await
+    };
+
+    if let Err(err_str) = res {
+        log_functions::log_err(&format!(
+            "watchlist.rs | buy_entry(market_hash_name={}, market={:?}, price={}) | Error occured when buying the item. E: {}",
+            entry.market_hash_name, market, price, err_str
+        ));
+        return;
+    }
+
+    let mut watchlist = WATCHLIST.lock().unwrap();
+    let watchlist = watchlist.get_or_insert_with(HashMap::new);
+    let remaining = match watchlist.get_mut(&entry.market_hash_name) {
+        Some(watched) => {
+            watched.entry.quantity = watched.entry.quantity.saturating_sub(1);
+            watched.entry.quantity
+        }
+        None => return,
+    };
+
+    log_functions::log_structured(
+        "Info",
+        &format!("Watchlist bought {} on {:?} at {}, {} left to buy.", entry.market_hash_name, market, price, remaining),
+    );
+
+    if remaining == 0 {
+        watchlist.remove(&entry.market_hash_name);
+        log_functions::log_structured("Info", &format!("Retired the watchlist entry of {}.", entry.market_hash_name));
+    }
+}
+
+// Sends a notification of the qualifying price unless it was already sent
+fn alert_entry(entry: &WatchlistEntry, market: Market, price: f32) {
+    let mut watchlist = WATCHLIST.lock().unwrap();
+    let watched = match watchlist.get_or_insert_with(HashMap::new).get_mut(&entry.market_hash_name) {
+        Some(watched) => watched,
+        None => return,
+    };
+    if watched.last_alert_price == Some(price) {
+        return;
+    }
+    watched.last_alert_price = Some(price);
+
+    notifications::notify(format!(
+        "👀 Watchlist: {}\nMarket: {:?}\nPrice: ${:.2} (max ${:.2})",
+        entry.market_hash_name, market, price, entry.max_price
+    ));
+}
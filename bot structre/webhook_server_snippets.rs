@@ -0,0 +1,146 @@
+use crate::config;
+use crate::log_functions;
+use crate::markets::csfloat;
+use crate::structs::{ItemStatusChangeTicket, Market};
+use axum::body::Bytes;
+use axum::http::{HeaderMap, StatusCode};
+use axum::{routing::post, Router};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use tokio::sync::mpsc::UnboundedSender;
+
+// The header the markets send the shared secret in
+const SECRET_HEADER: &str = "X-Webhook-Secret";
+
+// The handlers of the markets that send webhooks, the markets not registered get no route
+static HANDLERS: Mutex<Option<HashMap<Market, WebhookHandler>>> = Mutex::new(None);
+
+// Parses the payload of a market and does what it needs, returns the tickets of the status changes
+pub type WebhookHandler = fn(Value) -> Pin<Box<dyn Future<Output = Result<Vec<ItemStatusChangeTicket>, String>> + Send>>;
+
+/// Registers the handler of the market, replaces the registered one
+/// Has to be called before `start`, the routes are built when the server starts
+pub fn register_handler(market: Market, handler: WebhookHandler) {
+    HANDLERS
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(market, handler);
+}
+
+/// Starts the server that receives the webhooks if webhook_enabled is set in the config
+///
+/// - Every registered market gets a POST route at /webhook/<market>, CSFloat is registered by default
+/// - A request without the webhook_secret of the config in the SECRET_HEADER is rejected
+/// - The payload is handled in its own task so the market gets its answer right away,
+///   the tickets are sent through the sender to the trading loop
+/// - Returns None if the server is disabled or there is no secret to check the requests with
+pub fn start(sender: UnboundedSender<ItemStatusChangeTicket>) -> Option<tokio::task::JoinHandle<()>> {
+    let bot_config = config::get_bot_config();
+    if !bot_config.webhook_enabled {
+        return None;
+    }
+    let secret = match bot_config.webhook_secret.clone() {
+        Some(secret) if !secret.is_empty() => secret,
+        _ => {
+            log_functions::log_err("webhook_server.rs | start() | Error occured, webhook_enabled is set without a webhook_secret.");
+            return None;
+        }
+    };
+    let addr = format!("0.0.0.0:{}", bot_config.webhook_port);
+
+    let handlers = {
+        let mut handlers = HANDLERS.lock().unwrap();
+        let handlers = handlers.get_or_insert_with(HashMap::new);
+        handlers.entry(Market::CSFloat).or_insert(csfloat_handler);
+        handlers.clone()
+    };
+
+    Some(tokio::spawn(async move {
+        let mut app = Router::new();
+        for (market, handler) in handlers {
+            let path = format!("/webhook/{}", format!("{:?}", market).to_lowercase());
+            let secret = secret.clone();
+            let sender = sender.clone();
+            app = app.route(
+                &path,
+                post(move |headers: HeaderMap, body: Bytes| {
+                    let status = receive(market.clone(), handler, &secret, &sender, headers, body);
+                    async move { status }
+                }),
+            );
+        }
+
+        let listener = match tokio::net::TcpListener::bind(&addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                log_functions::log_err(&format!("webhook_server.rs | start() | Error occured when binding {}. E: {:?}", addr, e));
+                return;
+            }
+        };
+
+        log_functions::log_structured("Info", &format!("Receiving the webhooks on {}/webhook", addr));
+        if let Err(e) = axum::serve(listener, app).await {
+            log_functions::log_err(&format!("webhook_server.rs | start() | Error occured while serving the webhooks. E: {:?}", e));
+        }
+    }))
+}
+
+// The registered handler of CSFloat
+fn csfloat_handler(payload: Value) -> Pin<Box<dyn Future<Output = Result<Vec<ItemStatusChangeTicket>, String>> + Send>> {
+    Box::pin(csfloat::handle_webhook(payload))
+}
+
+// Checks the secret and the payload of a request and starts handling it
+fn receive(
+    market: Market,
+    handler: WebhookHandler,
+    secret: &str,
+    sender: &UnboundedSender<ItemStatusChangeTicket>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    let sent_secret = headers.get(SECRET_HEADER).and_then(|value| value.to_str().ok()).unwrap_or_default();
+    if !secrets_match(sent_secret, secret) {
+        log_functions::log_structured("Warning", &format!("webhook_server.rs | receive(market={:?}) | Rejected a webhook with a wrong secret.", market));
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    let payload: Value = match serde_json::from_slice(&body) {
+        Ok(payload) => payload,
+        Err(e) => {
+            log_functions::log_err(&format!("webhook_server.rs | receive(market={:?}) | Error occured when parsing the payload. E: {:?}", market, e));
+            return StatusCode::BAD_REQUEST;
+        }
+    };
+
+    let sender = sender.clone();
+    tokio::spawn(async move {
+        match handler(payload).await {
+            Ok(tickets) => {
+                for ticket in tickets {
+                    if let Err(e) = sender.send(ticket) {
+                        log_functions::log_err(&format!(
+                            "webhook_server.rs | receive(market={:?}) | Error occured, the trading loop stopped receiving. Ticket: {:?}",
+                            market, e.0
+                        ));
+                    }
+                }
+            }
+            Err(err_str) => log_functions::log_err(&err_str),
+        }
+    });
+
+    StatusCode::OK
+}
+
+// Compares the secrets without stopping at the first different byte, so the time doesn't tell how much matched
+fn secrets_match(sent: &str, expected: &str) -> bool {
+    if sent.len() != expected.len() {
+        return false;
+    }
+    sent.bytes().zip(expected.bytes()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
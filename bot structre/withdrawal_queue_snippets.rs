@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// The file that keeps the withdrawals waiting for a retry
+const WITHDRAWAL_QUEUE_PATH: &str = "withdrawal_queue.json";
+
+// The first retry waits this long, every failure doubles it
+const BACKOFF_BASE_SECS: u64 = 60;
+
+// The longest wait between two retries
+const BACKOFF_CAP_SECS: u64 = 6 * 60 * 60;
+
+// After this many failed retries the withdrawal is marked as failed and is no longer retried
+pub const MAX_WITHDRAW_ATTEMPTS: u32 = 8;
+
+// Guards the queue file so concurrent writers don't lose updates
+static WITHDRAWAL_QUEUE_LOCK: Mutex<()> = Mutex::new(());
+
+// The struct that has a bought item whose withdrawal failed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingWithdrawal {
+    pub item_id: String,
+    pub asset_id: String,
+    pub market_hash_name: String,
+    pub attempts: u32,
+    pub next_attempt_unix: u64,
+    // Set after MAX_WITHDRAW_ATTEMPTS failed retries, the item stays in the file until it is requeued or removed by hand
+    #[serde(default)]
+    pub failed: bool,
+}
+
+/// Adds a failed withdrawal to the queue, an item id that is already queued is not added again
+pub fn enqueue(item_id: &str, asset_id: &str, market_hash_name: &str) -> Result<(), String> {
+    let _guard = WITHDRAWAL_QUEUE_LOCK.lock().unwrap();
+
+    let mut queue = read_queue()?;
+    if queue.iter().any(|pending| pending.item_id == item_id) {
+        return Ok(());
+    }
+
+    queue.push(PendingWithdrawal {
+        item_id: item_id.to_string(),
+        asset_id: asset_id.to_string(),
+        market_hash_name: market_hash_name.to_string(),
+        attempts: 0,
+        next_attempt_unix: get_sys_time_in_secs() + BACKOFF_BASE_SECS,
+        failed: false,
+    });
+    write_queue(&queue)
+}
+
+/// Returns every queued withdrawal
+pub fn get_all() -> Result<Vec<PendingWithdrawal>, String> {
+    let _guard = WITHDRAWAL_QUEUE_LOCK.lock().unwrap();
+    read_queue()
+}
+
+/// Returns the queued withdrawals whose next retry time has come, the failed ones are never due
+pub fn get_due() -> Result<Vec<PendingWithdrawal>, String> {
+    let now = get_sys_time_in_secs();
+    Ok(get_all()?.into_iter().filter(|pending| !pending.failed && pending.next_attempt_unix <= now).collect())
+}
+
+/// Returns the withdrawals that reached MAX_WITHDRAW_ATTEMPTS and are no longer retried
+pub fn get_failed() -> Result<Vec<PendingWithdrawal>, String> {
+    Ok(get_all()?.into_iter().filter(|pending| pending.failed).collect())
+}
+
+/// Returns the queued item ids, a failed withdrawal is still queued so it isn't started again
+pub fn queued_item_ids() -> Result<Vec<String>, String> {
+    Ok(get_all()?.into_iter().map(|pending| pending.item_id).collect())
+}
+
+/// Records a failed retry and doubles the wait until the next one
+/// Returns the number of failed retries of the item, at MAX_WITHDRAW_ATTEMPTS the item is marked as failed
+pub fn record_failure(item_id: &str) -> Result<u32, String> {
+    let _guard = WITHDRAWAL_QUEUE_LOCK.lock().unwrap();
+
+    let mut queue = read_queue()?;
+    let pending = queue
+        .iter_mut()
+        .find(|pending| pending.item_id == item_id)
+        .ok_or(format!("withdrawal_queue.rs | record_failure(item_id={}) | Error occured, the item is not queued.", item_id))?;
+
+    let attempts = apply_failure(pending, get_sys_time_in_secs());
+
+    write_queue(&queue)?;
+    Ok(attempts)
+}
+
+/// Takes a failed withdrawal back into the retries, with its attempts reset
+pub fn requeue(item_id: &str) -> Result<(), String> {
+    let _guard = WITHDRAWAL_QUEUE_LOCK.lock().unwrap();
+
+    let mut queue = read_queue()?;
+    let pending = queue
+        .iter_mut()
+        .find(|pending| pending.item_id == item_id)
+        .ok_or(format!("withdrawal_queue.rs | requeue(item_id={}) | Error occured, the item is not queued.", item_id))?;
+
+    pending.attempts = 0;
+    pending.failed = false;
+    pending.next_attempt_unix = get_sys_time_in_secs();
+
+    write_queue(&queue)
+}
+
+// Counts the failed retry and sets the next retry time, or marks the item as failed at MAX_WITHDRAW_ATTEMPTS
+fn apply_failure(pending: &mut PendingWithdrawal, now: u64) -> u32 {
+    pending.attempts += 1;
+    if pending.attempts >= MAX_WITHDRAW_ATTEMPTS {
+        pending.failed = true;
+    }
+    let backoff = BACKOFF_BASE_SECS.saturating_mul(1 << pending.attempts.min(16)).min(BACKOFF_CAP_SECS);
+    pending.next_attempt_unix = now + backoff;
+    pending.attempts
+}
+
+/// Removes the item from the queue after a successful withdrawal
+pub fn remove(item_id: &str) -> Result<(), String> {
+    let _guard = WITHDRAWAL_QUEUE_LOCK.lock().unwrap();
+
+    let mut queue = read_queue()?;
+    queue.retain(|pending| pending.item_id != item_id);
+    write_queue(&queue)
+}
+
+// Reads the queue file, a missing file means an empty queue
+fn read_queue() -> Result<Vec<PendingWithdrawal>, String> {
+    let queue_str = match std::fs::read_to_string(WITHDRAWAL_QUEUE_PATH) {
+        Ok(queue_str) => queue_str,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("withdrawal_queue.rs | read_queue() | Error occured when reading the file. E: {:?}", e)),
+    };
+
+    serde_json::from_str(&queue_str)
+        .map_err(|e| format!("withdrawal_queue.rs | read_queue() | Error occured when parsing the file. E: {:?}", e))
+}
+
+// Writes the queue file
+fn write_queue(queue: &[PendingWithdrawal]) -> Result<(), String> {
+    let queue_str = serde_json::to_string_pretty(queue)
+        .map_err(|e| format!("withdrawal_queue.rs | write_queue() | Error occured when serializing the queue. E: {:?}", e))?;
+    std::fs::write(WITHDRAWAL_QUEUE_PATH, queue_str)
+        .map_err(|e| format!("withdrawal_queue.rs | write_queue() | Error occured when writing the file. E: {:?}", e))
+}
+
+// Get the UNIX timestamp
+fn get_sys_time_in_secs() -> u64 {
+    match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(n) => n.as_secs(),
+        Err(_) => panic!("SystemTime before UNIX EPOCH!"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending() -> PendingWithdrawal {
+        PendingWithdrawal {
+            item_id: "1".to_string(),
+            asset_id: "101".to_string(),
+            market_hash_name: "AK-47 | Redline (Field-Tested)".to_string(),
+            attempts: 0,
+            next_attempt_unix: 0,
+            failed: false,
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let mut pending = pending();
+
+        assert_eq!(apply_failure(&mut pending, 0), 1);
+        assert_eq!(pending.next_attempt_unix, 2 * BACKOFF_BASE_SECS);
+        apply_failure(&mut pending, 0);
+        assert_eq!(pending.next_attempt_unix, 4 * BACKOFF_BASE_SECS);
+
+        pending.attempts = 10;
+        apply_failure(&mut pending, 0);
+        assert_eq!(pending.next_attempt_unix, BACKOFF_CAP_SECS);
+    }
+
+    #[test]
+    fn withdrawal_fails_at_the_max_attempts() {
+        let mut pending = pending();
+
+        for _ in 1..MAX_WITHDRAW_ATTEMPTS {
+            apply_failure(&mut pending, 0);
+            assert!(!pending.failed);
+        }
+        assert_eq!(apply_failure(&mut pending, 0), MAX_WITHDRAW_ATTEMPTS);
+        assert!(pending.failed);
+    }
+
+    #[test]
+    fn queue_file_without_the_failed_field_still_parses() {
+        let queue: Vec<PendingWithdrawal> = serde_json::from_str(
+            r#"[{"item_id": "1", "asset_id": "101", "market_hash_name": "x", "attempts": 8, "next_attempt_unix": 0}]"#,
+        )
+        .unwrap();
+
+        assert!(!queue[0].failed);
+    }
+}
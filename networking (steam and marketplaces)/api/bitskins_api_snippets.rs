@@ -5,29 +5,81 @@
 // It demonstrates advanced marketplace integration with proxy rotation, retry logic, and
 // thorough error handling for reliable trading operations.
 
-use crate::{data, log_functions::log_write, structs::Market};
-use async_std::task::sleep;
-use chrono::{Duration, Local};
-use rand::Rng;
-use reqwest::{
-    header::{self, HeaderMap},
-    Client, ClientBuilder, Proxy,
+use crate::{
+    circuit_breaker::{self, EndpointGroup},
+    config,
+    key_pool,
+    log_functions::log_write,
+    metrics,
+    proxy_handler::{self, get_client_with_http2, retry, NoProxyAvailable, ProxyConfig, RetryPolicy, READ_RETRY_POLICY, WRITE_RETRY_POLICY},
+    statics,
+    structs::{Market, SteamApp},
 };
-use std::time::SystemTime;
-
-static P_KEY: &str = "XXX";
-static SCRAPE_KEYS: [&str; 4] = [
-    "XXX",
-    "XXX",
-    "XXX",
-    "XXX",
-];
-
-/// Rotates between multiple API keys to avoid rate limiting
-fn get_scrape_key() -> String {
-    let mut rng = rand::thread_rng();
-    let random_number = rng.gen_range(0..=3);
-    SCRAPE_KEYS[random_number].to_string()
+use chrono::{Duration, Local};
+use reqwest::{header::{self, HeaderMap}, StatusCode};
+use std::time::{Instant, SystemTime};
+
+/// The errors of the requests made with the account api key
+#[derive(Debug)]
+pub enum BitSkinsApiError {
+    /// BitSkins answered 401, the api key expired or was rotated
+    AuthExpired,
+    /// Every scrape key of the config is quarantined or rate limited, no request was sent
+    NoScrapeKey,
+    /// BitSkins has no usable proxy, no request was sent
+    NoProxy(NoProxyAvailable),
+    /// The api key can't be sent as a header, no request was sent
+    InvalidApiKey(header::InvalidHeaderValue),
+    Request(reqwest::Error),
+}
+
+impl From<reqwest::Error> for BitSkinsApiError {
+    fn from(err: reqwest::Error) -> Self {
+        BitSkinsApiError::Request(err)
+    }
+}
+
+impl From<NoProxyAvailable> for BitSkinsApiError {
+    fn from(err: NoProxyAvailable) -> Self {
+        BitSkinsApiError::NoProxy(err)
+    }
+}
+
+impl From<header::InvalidHeaderValue> for BitSkinsApiError {
+    fn from(err: header::InvalidHeaderValue) -> Self {
+        BitSkinsApiError::InvalidApiKey(err)
+    }
+}
+
+impl std::fmt::Display for BitSkinsApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BitSkinsApiError::AuthExpired => write!(f, "The BitSkins api key expired."),
+            BitSkinsApiError::NoScrapeKey => write!(f, "No BitSkins scrape key is available."),
+            BitSkinsApiError::NoProxy(err) => write!(f, "{}", err),
+            BitSkinsApiError::InvalidApiKey(err) => write!(f, "The BitSkins api key is not a valid header value. E: {:?}", err),
+            BitSkinsApiError::Request(err) => write!(f, "{:?}", err),
+        }
+    }
+}
+
+/// Turns a 401 answer into `BitSkinsApiError::AuthExpired`
+fn check_auth(body: Result<reqwest::Response, reqwest::Error>) -> Result<reqwest::Response, BitSkinsApiError> {
+    let res = body?;
+    if res.status() == StatusCode::UNAUTHORIZED {
+        return Err(BitSkinsApiError::AuthExpired);
+    }
+    Ok(res)
+}
+
+/// Builds the x-apikey header of the account api key, the key is refreshed at runtime so it is checked every time
+fn api_key_header() -> Result<header::HeaderValue, BitSkinsApiError> {
+    Ok(header::HeaderValue::from_str(&statics::get_bitskins_api_key())?)
+}
+
+/// Checks if the BitSkins requests should be sent over HTTP/2
+fn use_http2() -> bool {
+    config::get_bot_config().use_http2(&Market::BitSkins)
 }
 
 /// Advanced request handler with proxy support, timeout, and automatic retries
@@ -35,45 +87,30 @@ fn get_scrape_key() -> String {
 /// This function demonstrates techniques for building reliable marketplace integration:
 /// - Proxy rotation to avoid IP-based rate limiting
 /// - Timeout handling to prevent hung connections
-/// - Automatic retry logic for transient failures
+/// - Retries the failures the policy allows with jittered exponential backoff
 async fn send_request_with_proxy_and_timeout_and_retry(
     url: &str,
-    proxy_url: &str,
+    proxy: Option<&ProxyConfig>,
     headers: HeaderMap,
     body: String,
-    username: &str,
-    password: &str,
     timeout_secs: u64,
-    max_retries: usize,
+    policy: &RetryPolicy,
 ) -> Result<reqwest::Response, reqwest::Error> {
-    let proxy = Proxy::all(proxy_url)
-        .unwrap()
-        .basic_auth(username, password);
-    let client = Client::builder()
-        .proxy(proxy)
-        .timeout(std::time::Duration::from_secs(timeout_secs))
-        .build()?;
-
-    let mut attempts = 0;
-
-    loop {
-        attempts += 1;
-        match client
+    let client = get_client_with_http2(proxy, timeout_secs, use_http2())?;
+
+    let start = Instant::now();
+    let res = retry(&format!("bitskins_api | send_request(url: {})", url), policy, || {
+        client
             .post(url)
             .headers(headers.clone())
             .body(body.clone())
             .send()
-            .await
-        {
-            Ok(response) => {
-                return Ok(response);
-            }
-            Err(_) if attempts <= max_retries => {
-                sleep(std::time::Duration::from_secs(1)).await; // Wait before retry
-            }
-            Err(e) => return Err(e),
-        }
-    }
+    })
+    .await;
+    proxy_handler::record_result(proxy, &res);
+
+    metrics::observe_request("BitSkins", url.trim_start_matches("https://api.bitskins.com"), start.elapsed());
+    res
 }
 
 /// Searches for a specific CS item on BitSkins marketplace
@@ -85,24 +122,41 @@ async fn send_request_with_proxy_and_timeout_and_retry(
 pub async fn get_item_price(
     market_hash_name: String,
     max_trade_hold: i32,
-) -> Result<reqwest::Response, reqwest::Error> {
+) -> Result<reqwest::Response, BitSkinsApiError> {
+    get_item_price_for_app(SteamApp::CS, market_hash_name, max_trade_hold).await
+}
+
+/// Searches for an item of the given Steam app on BitSkins marketplace
+///
+/// - The category filter comes from the app config, apps without categories are not filtered
+/// - Fails with `NoProxy` instead of sending the request from our own address
+pub async fn get_item_price_for_app(
+    app: SteamApp,
+    market_hash_name: String,
+    max_trade_hold: i32,
+) -> Result<reqwest::Response, BitSkinsApiError> {
     // Start the timer for performance logging
     let start = SystemTime::now();
 
-    // Determine correct category ID based on CS:GO item naming conventions
-    let mut category = "1";
-    if market_hash_name.contains("StatTrak") {
-        category = "3";
-    }
-    if market_hash_name.contains("Souvenir") {
-        category = "5";
-    }
+    // Determine the category ID from the naming conventions of the app (StatTrak™, Souvenir for CS)
+    let app_config = config::get_bot_config().get_app_config(app);
+    let category = app_config
+        .bitskins_name_categories
+        .iter()
+        .rev()
+        .find(|(marker, _)| market_hash_name.contains(marker.as_str()))
+        .map(|(_, category)| *category)
+        .or(app_config.bitskins_default_category);
+    let category_filter = match category {
+        Some(category) => format!(r#","category_id":[{}]"#, category),
+        None => "".to_string(),
+    };
 
     // Build search query with appropriate filters
-    let url = "https://api.bitskins.com/market/search/730";
+    let url = format!("https://api.bitskins.com/market/search/{}", app.app_id);
     let json_str = format!(
-        r#"{{"order":[{{"field":"price","order":"ASC"}}],"offset":0,"limit":30,"where":{{"skin_name":"{}","tradehold_to":{},"price_from":10,"price_to":25000000,"category_id":[{}]}}}}"#,
-        market_hash_name, max_trade_hold, category
+        r#"{{"order":[{{"field":"price","order":"ASC"}}],"offset":0,"limit":30,"where":{{"skin_name":"{}","tradehold_to":{},"price_from":10,"price_to":25000000{}}}}}"#,
+        market_hash_name, max_trade_hold, category_filter
     );
 
     // Set up request headers
@@ -113,16 +167,14 @@ pub async fn get_item_price(
     );
 
     // Send request through proxy system to avoid rate limiting
-    let proxy_data = data::get_proxy(Market::BitSkins);
+    let proxy = proxy_handler::get_proxy(Market::BitSkins)?;
     let body = send_request_with_proxy_and_timeout_and_retry(
-        url,
-        &proxy_data.0,
+        &url,
+        proxy.as_ref(),
         header.clone(),
         json_str.clone(),
-        &proxy_data.1,
-        &proxy_data.2,
         15,
-        0,
+        &RetryPolicy { max_retries: 0, ..READ_RETRY_POLICY },
     )
     .await;
 
@@ -134,15 +186,44 @@ pub async fn get_item_price(
         market_hash_name, passed
     );
     log_write(&log_txt);
-    body
+    metrics::record_request(Market::BitSkins, passed, metrics::is_success(&body));
+    circuit_breaker::record(Market::BitSkins, EndpointGroup::Prices, &body);
+    Ok(body?)
+}
+
+/// Finds the BitSkins skin id of a CS item, the id the sale stats are queried with
+///
+/// - Searches the listings of the item and reads the skin_id of the first one,
+///   an item without a listing on BitSkins has no id to find
+pub async fn search_skin_id(market_hash_name: &str) -> Result<i64, String> {
+    let res = get_item_price(market_hash_name.to_string(), 7)
+        .await
+        .map_err(|e| format!(
+            "bitskins_api | search_skin_id(market_hash_name: {}) | Error occured when sending the api request. E: {:?}",
+            market_hash_name, e
+        ))?;
+
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!(
+            "bitskins_api | search_skin_id(market_hash_name: {}) | Error occured when parsing the api request. E: {:?}",
+            market_hash_name, e
+        ))?;
+
+    parsed_data["list"][0]["skin_id"]
+        .as_i64()
+        .ok_or(format!(
+            "bitskins_api | search_skin_id(market_hash_name: {}) | Error occured, the first result has no skin_id. Parsed data: {:?}",
+            market_hash_name, parsed_data
+        ))
 }
 
 /// Retrieves 30-day price history for a specific CS item
 /// 
 /// - Fetches historical data for trend analysis
 /// - Uses proper date formatting for API compatibility
-/// - Implements key rotation for higher throughput
-pub async fn get_sale_stats(skin_id: String) -> Result<reqwest::Response, reqwest::Error> {
+/// - Sends the request with the least loaded healthy scrape key of the key pool
+pub async fn get_sale_stats(skin_id: String) -> Result<reqwest::Response, BitSkinsApiError> {
     let start = SystemTime::now();
 
     // Calculate 30-day date range for historical data
@@ -158,8 +239,11 @@ pub async fn get_sale_stats(skin_id: String) -> Result<reqwest::Response, reqwes
         skin_id, formatted_date_ago, formatted_date_now
     );
 
-    // Set up headers with API key rotation
-    let auth_token = get_scrape_key();
+    // Take the proxy first so a missing proxy doesn't use up a request of the scrape key
+    let proxy = proxy_handler::get_proxy(Market::BitSkins)?;
+
+    // Set up headers with the scrape key of the pool
+    let auth_token = key_pool::acquire_bitskins_scrape_key().ok_or(BitSkinsApiError::NoScrapeKey)?;
     let mut header = reqwest::header::HeaderMap::new();
     header.insert(
         header::CONTENT_TYPE,
@@ -167,20 +251,17 @@ pub async fn get_sale_stats(skin_id: String) -> Result<reqwest::Response, reqwes
     );
     header.insert(
         "x-apikey",
-        header::HeaderValue::from_str(&auth_token).unwrap(),
+        header::HeaderValue::from_str(&auth_token)?,
     );
 
     // Send request via proxy with retry capability
-    let proxy_data = data::get_bitskins_proxy();
     let body = send_request_with_proxy_and_timeout_and_retry(
         url,
-        &proxy_data.0,
+        proxy.as_ref(),
         header.clone(),
         json_str.clone(),
-        &proxy_data.1,
-        &proxy_data.2,
         10,
-        2,
+        &READ_RETRY_POLICY,
     )
     .await;
 
@@ -192,7 +273,10 @@ pub async fn get_sale_stats(skin_id: String) -> Result<reqwest::Response, reqwes
         skin_id, passed
     );
     log_write(&log_txt);
-    body
+    metrics::record_request(Market::BitSkins, passed, metrics::is_success(&body));
+    circuit_breaker::record(Market::BitSkins, EndpointGroup::Prices, &body);
+    key_pool::record_bitskins_scrape_key(&auth_token, &body);
+    Ok(body?)
 }
 
 /// Purchases a CS item from BitSkins marketplace
@@ -200,14 +284,19 @@ pub async fn get_sale_stats(skin_id: String) -> Result<reqwest::Response, reqwes
 /// - Properly formatted purchase request
 /// - Maximum price specification to prevent price manipulation
 /// - Direct API key authentication for secure transactions
-pub async fn buy_item(item_id: String, price: i64) -> Result<reqwest::Response, reqwest::Error> {
+pub async fn buy_item(item_id: String, price: i64) -> Result<reqwest::Response, BitSkinsApiError> {
+    buy_item_for_app(SteamApp::CS, item_id, price).await
+}
+
+/// Purchases an item of the given Steam app from BitSkins marketplace
+pub async fn buy_item_for_app(app: SteamApp, item_id: String, price: i64) -> Result<reqwest::Response, BitSkinsApiError> {
     let start = SystemTime::now();
 
     // Build purchase request payload
     let url = "https://api.bitskins.com/market/buy/many";
     let json_str = format!(
-        r#"{{"app_id":730,"items":[{{"id":"{}","max_price":{}}}]}}"#,
-        item_id, price
+        r#"{{"app_id":{},"items":[{{"id":"{}","max_price":{}}}]}}"#,
+        app.app_id, item_id, price
     );
 
     // Set up headers with API key for authenticated transaction
@@ -216,17 +305,19 @@ pub async fn buy_item(item_id: String, price: i64) -> Result<reqwest::Response,
         header::CONTENT_TYPE,
         header::HeaderValue::from_str("application/json").unwrap(),
     );
-    header.insert("x-apikey", header::HeaderValue::from_str(P_KEY).unwrap());
+    header.insert("x-apikey", api_key_header()?);
 
     // Send purchase request
-    let client = ClientBuilder::new().build()?;
-    let body = client
-        .post(url)
-        .timeout(std::time::Duration::from_secs(30))
-        .headers(header)
-        .body(json_str)
-        .send()
-        .await;
+    let client = get_client_with_http2(None, 0, use_http2())?;
+    let body = retry("bitskins_api | buy_item()", &WRITE_RETRY_POLICY, || {
+        client
+            .post(url)
+            .timeout(std::time::Duration::from_secs(30))
+            .headers(header.clone())
+            .body(json_str.clone())
+            .send()
+    })
+    .await;
 
     // Log transaction attempt
     let after = SystemTime::now();
@@ -236,7 +327,58 @@ pub async fn buy_item(item_id: String, price: i64) -> Result<reqwest::Response,
         item_id, price, passed
     );
     log_write(&log_txt);
-    body
+    metrics::record_request(Market::BitSkins, passed, metrics::is_success(&body));
+    circuit_breaker::record(Market::BitSkins, EndpointGroup::Trading, &body);
+    check_auth(body)
+}
+
+/// Purchases several CS items from BitSkins marketplace in one request
+/// 
+/// - Sends up to 10 (item_id, max_price) pairs to the buy/many endpoint
+/// - Every item keeps its own maximum price
+/// - The response has one result entry per item
+pub async fn buy_items_batch(items: &[(String, i64)]) -> Result<reqwest::Response, BitSkinsApiError> {
+    let start = SystemTime::now();
+
+    // Build purchase request payload
+    let url = "https://api.bitskins.com/market/buy/many";
+    let items_str: Vec<String> = items
+        .iter()
+        .map(|(item_id, price)| format!(r#"{{"id":"{}","max_price":{}}}"#, item_id, price))
+        .collect();
+    let json_str = format!(r#"{{"app_id":730,"items":[{}]}}"#, items_str.join(","));
+
+    // Set up headers with API key for authenticated transaction
+    let mut header = reqwest::header::HeaderMap::new();
+    header.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_str("application/json").unwrap(),
+    );
+    header.insert("x-apikey", api_key_header()?);
+
+    // Send purchase request
+    let client = get_client_with_http2(None, 0, use_http2())?;
+    let body = retry("bitskins_api | buy_items_batch()", &WRITE_RETRY_POLICY, || {
+        client
+            .post(url)
+            .timeout(std::time::Duration::from_secs(30))
+            .headers(header.clone())
+            .body(json_str.clone())
+            .send()
+    })
+    .await;
+
+    // Log transaction attempt
+    let after = SystemTime::now();
+    let passed = after.duration_since(start).unwrap();
+    let log_txt = format!(
+        "bitskins_api | buy_items_batch(items={:?}) | The HTTP request took {:?}.\n",
+        items, passed
+    );
+    log_write(&log_txt);
+    metrics::record_request(Market::BitSkins, passed, metrics::is_success(&body));
+    circuit_breaker::record(Market::BitSkins, EndpointGroup::Trading, &body);
+    check_auth(body)
 }
 
 /// Withdraws a purchased item to Steam inventory
@@ -244,12 +386,17 @@ pub async fn buy_item(item_id: String, price: i64) -> Result<reqwest::Response,
 /// - Initiates withdrawal process to player inventory
 /// - Maintains proper API authorization
 /// - Enables cross-marketplace arbitrage completion
-pub async fn withdraw_item(item_id: String) -> Result<reqwest::Response, reqwest::Error> {
+pub async fn withdraw_item(item_id: String) -> Result<reqwest::Response, BitSkinsApiError> {
+    withdraw_item_for_app(SteamApp::CS, item_id).await
+}
+
+/// Withdraws a purchased item of the given Steam app to Steam inventory
+pub async fn withdraw_item_for_app(app: SteamApp, item_id: String) -> Result<reqwest::Response, BitSkinsApiError> {
     let start = SystemTime::now();
 
     // Build withdrawal request
     let url = "https://api.bitskins.com/market/withdraw/many";
-    let json_str = format!(r#"{{"items":[{{"app_id":730,"id":"{}"}}]}}"#, item_id);
+    let json_str = format!(r#"{{"items":[{{"app_id":{},"id":"{}"}}]}}"#, app.app_id, item_id);
 
     // Set up authenticated headers
     let mut header = reqwest::header::HeaderMap::new();
@@ -257,17 +404,19 @@ pub async fn withdraw_item(item_id: String) -> Result<reqwest::Response, reqwest
         header::CONTENT_TYPE,
         header::HeaderValue::from_str("application/json").unwrap(),
     );
-    header.insert("x-apikey", header::HeaderValue::from_str(P_KEY).unwrap());
+    header.insert("x-apikey", api_key_header()?);
 
     // Send withdrawal request
-    let client = ClientBuilder::new().build()?;
-    let body = client
-        .post(url)
-        .timeout(std::time::Duration::from_secs(30))
-        .headers(header)
-        .body(json_str)
-        .send()
-        .await;
+    let client = get_client_with_http2(None, 0, use_http2())?;
+    let body = retry("bitskins_api | withdraw_item()", &WRITE_RETRY_POLICY, || {
+        client
+            .post(url)
+            .timeout(std::time::Duration::from_secs(30))
+            .headers(header.clone())
+            .body(json_str.clone())
+            .send()
+    })
+    .await;
 
     // Log withdrawal attempt
     let after = SystemTime::now();
@@ -277,7 +426,9 @@ pub async fn withdraw_item(item_id: String) -> Result<reqwest::Response, reqwest
         item_id, passed
     );
     log_write(&log_txt);
-    body
+    metrics::record_request(Market::BitSkins, passed, metrics::is_success(&body));
+    circuit_breaker::record(Market::BitSkins, EndpointGroup::Trading, &body);
+    check_auth(body)
 }
 
 /// Retrieves currently owned items on BitSkins
@@ -285,11 +436,16 @@ pub async fn withdraw_item(item_id: String) -> Result<reqwest::Response, reqwest
 /// - Filters by trade hold status for arbitrage planning
 /// - Properly handles authentication for protected inventory access
 /// - Supports complete item lifecycle management
-pub async fn get_buy_inventory() -> Result<reqwest::Response, reqwest::Error> {
+pub async fn get_buy_inventory() -> Result<reqwest::Response, BitSkinsApiError> {
+    get_buy_inventory_for_app(SteamApp::CS).await
+}
+
+/// Retrieves the currently owned items of the given Steam app on BitSkins
+pub async fn get_buy_inventory_for_app(app: SteamApp) -> Result<reqwest::Response, BitSkinsApiError> {
     let start = SystemTime::now();
 
     // Build inventory query with trade hold filter
-    let url = "https://api.bitskins.com/market/search/mine/730";
+    let url = format!("https://api.bitskins.com/market/search/mine/{}", app.app_id);
     let json_str = r#"{"offset":0,"where":{"tradehold_to":0},"where_mine":{"status":[4,0]},"limit":100,"order":[{"field":"bumped_at","order":"DESC"}]}"#.to_string();
 
     // Set up authenticated headers
@@ -298,17 +454,19 @@ pub async fn get_buy_inventory() -> Result<reqwest::Response, reqwest::Error> {
         header::CONTENT_TYPE,
         header::HeaderValue::from_str("application/json").unwrap(),
     );
-    header.insert("x-apikey", header::HeaderValue::from_str(P_KEY).unwrap());
+    header.insert("x-apikey", api_key_header()?);
 
     // Send inventory request
-    let client = ClientBuilder::new().build()?;
-    let body = client
-        .post(url)
-        .timeout(std::time::Duration::from_secs(30))
-        .headers(header)
-        .body(json_str)
-        .send()
-        .await;
+    let client = get_client_with_http2(None, 0, use_http2())?;
+    let body = retry("bitskins_api | get_buy_inventory()", &READ_RETRY_POLICY, || {
+        client
+            .post(&url)
+            .timeout(std::time::Duration::from_secs(30))
+            .headers(header.clone())
+            .body(json_str.clone())
+            .send()
+    })
+    .await;
 
     // Log request
     let after = SystemTime::now();
@@ -318,7 +476,9 @@ pub async fn get_buy_inventory() -> Result<reqwest::Response, reqwest::Error> {
         passed
     );
     log_write(&log_txt);
-    body
+    metrics::record_request(Market::BitSkins, passed, metrics::is_success(&body));
+    circuit_breaker::record(Market::BitSkins, EndpointGroup::Account, &body);
+    check_auth(body)
 }
 
 /// Monitors active Steam trade offers for BitSkins withdrawals
@@ -326,7 +486,7 @@ pub async fn get_buy_inventory() -> Result<reqwest::Response, reqwest::Error> {
 /// - Monitors active offers to track withdrawal status
 /// - Ensures trades are completing successfully
 /// - Provides data for automated trade management
-pub async fn get_active_trades() -> Result<reqwest::Response, reqwest::Error> {
+pub async fn get_active_trades() -> Result<reqwest::Response, BitSkinsApiError> {
     let start = SystemTime::now();
 
     // Build trade status query
@@ -339,17 +499,19 @@ pub async fn get_active_trades() -> Result<reqwest::Response, reqwest::Error> {
         header::CONTENT_TYPE,
         header::HeaderValue::from_str("application/json").unwrap(),
     );
-    header.insert("x-apikey", header::HeaderValue::from_str(P_KEY).unwrap());
+    header.insert("x-apikey", api_key_header()?);
 
     // Send trade status request
-    let client = ClientBuilder::new().build()?;
-    let body = client
-        .post(url)
-        .timeout(std::time::Duration::from_secs(30))
-        .headers(header)
-        .body(json_str)
-        .send()
-        .await;
+    let client = get_client_with_http2(None, 0, use_http2())?;
+    let body = retry("bitskins_api | get_active_trades()", &READ_RETRY_POLICY, || {
+        client
+            .post(url)
+            .timeout(std::time::Duration::from_secs(30))
+            .headers(header.clone())
+            .body(json_str.clone())
+            .send()
+    })
+    .await;
 
     // Log request
     let after = SystemTime::now();
@@ -359,5 +521,50 @@ pub async fn get_active_trades() -> Result<reqwest::Response, reqwest::Error> {
         passed
     );
     log_write(&log_txt);
-    body
+    metrics::record_request(Market::BitSkins, passed, metrics::is_success(&body));
+    circuit_breaker::record(Market::BitSkins, EndpointGroup::Trading, &body);
+    check_auth(body)
+}
+
+/// Retrieves the account balance
+/// 
+/// - The balance is in thousandths of a dollar
+/// - Used to find the real amount a purchase cost when the buy response doesn't say it
+pub async fn get_balance() -> Result<reqwest::Response, BitSkinsApiError> {
+    let start = SystemTime::now();
+
+    let url = "https://api.bitskins.com/account/profile/balance";
+    let json_str = "{}".to_string();
+
+    // Set up authenticated headers
+    let mut header = reqwest::header::HeaderMap::new();
+    header.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_str("application/json").unwrap(),
+    );
+    header.insert("x-apikey", api_key_header()?);
+
+    // Send balance request
+    let client = get_client_with_http2(None, 0, use_http2())?;
+    let body = retry("bitskins_api | get_balance()", &READ_RETRY_POLICY, || {
+        client
+            .post(url)
+            .timeout(std::time::Duration::from_secs(30))
+            .headers(header.clone())
+            .body(json_str.clone())
+            .send()
+    })
+    .await;
+
+    // Log request
+    let after = SystemTime::now();
+    let passed = after.duration_since(start).unwrap();
+    let log_txt = format!(
+        "bitskins_api | get_balance() | The HTTP request took {:?}.\n",
+        passed
+    );
+    log_write(&log_txt);
+    metrics::record_request(Market::BitSkins, passed, metrics::is_success(&body));
+    circuit_breaker::record(Market::BitSkins, EndpointGroup::Account, &body);
+    check_auth(body)
 }
@@ -0,0 +1,137 @@
+// csfloat_api.rs
+//
+// This module provides a client implementation for CSFloat's marketplace API,
+// handling the listing search and buying listings. Prices are sent and received in cents.
+
+use crate::{
+    circuit_breaker::{self, EndpointGroup},
+    config,
+    log_functions::log_write,
+    metrics,
+    proxy_handler::{self, get_client_with_http2, retry, READ_RETRY_POLICY, WRITE_RETRY_POLICY},
+    structs::Market,
+};
+use reqwest::header::{self, HeaderMap};
+use std::time::SystemTime;
+
+static API_KEY: &str = "XXX";
+
+/// Checks if the CSFloat requests should be sent over HTTP/2
+fn use_http2() -> bool {
+    config::get_bot_config().use_http2(&Market::CSFloat)
+}
+
+/// Searches the buy now listings of a CS item by name, cheapest first
+///
+/// - Goes through the proxy rotation since it is used while scanning prices, it is not sent without a proxy
+/// - Every listing has the float value and the paint seed (pattern index) of the item
+pub async fn get_listings(market_hash_name: &str) -> Result<reqwest::Response, String> {
+    let start = SystemTime::now();
+
+    let proxy = proxy_handler::get_proxy(Market::CSFloat)
+        .map_err(|e| format!("csfloat_api | get_listings(market_hash_name: {}) | {}", market_hash_name, e))?;
+    let client = get_client_with_http2(proxy.as_ref(), 15, use_http2())
+        .map_err(|e| format!("csfloat_api | get_listings(market_hash_name: {}) | Error occured when building the client. E: {:?}", market_hash_name, e))?;
+    let body = retry("csfloat_api | get_listings()", &READ_RETRY_POLICY, || {
+        client
+            .get("https://csfloat.com/api/v1/listings")
+            .headers(get_headers())
+            .query(&[
+                ("market_hash_name", market_hash_name),
+                ("sort_by", "lowest_price"),
+                ("type", "buy_now"),
+                ("limit", "50"),
+            ])
+            .send()
+    })
+    .await;
+    proxy_handler::record_result(proxy.as_ref(), &body);
+
+    log_request(&format!("get_listings(market_hash_name: {})", market_hash_name), EndpointGroup::Prices, start, &body);
+    body.map_err(|e| format!("{:?}", e))
+}
+
+/// Buys a listing, the seller has to send the item with a Steam trade afterwards
+///
+/// - `total_price` is in cents and has to match the listing price
+pub async fn buy_listing(contract_id: &str, total_price: i64) -> Result<reqwest::Response, reqwest::Error> {
+    let start = SystemTime::now();
+
+    let json_str = format!(r#"{{"total_price":{},"contract_ids":["{}"]}}"#, total_price, contract_id);
+
+    let client = get_client_with_http2(None, 30, use_http2())?;
+    let body = retry("csfloat_api | buy_listing()", &WRITE_RETRY_POLICY, || {
+        client
+            .post("https://csfloat.com/api/v1/listings/buy")
+            .headers(get_headers())
+            .body(json_str.clone())
+            .send()
+    })
+    .await;
+
+    log_request(&format!("buy_listing(contract_id: {}, total_price: {})", contract_id, total_price), EndpointGroup::Trading, start, &body);
+    body
+}
+
+/// Accepts an offer, CSFloat asks the seller to send the Steam trade offer afterwards
+pub async fn accept_offer(offer_id: &str) -> Result<reqwest::Response, reqwest::Error> {
+    let start = SystemTime::now();
+
+    let url = format!("https://csfloat.com/api/v1/offers/{}/accept", offer_id);
+    let client = get_client_with_http2(None, 30, use_http2())?;
+    let body = retry("csfloat_api | accept_offer()", &WRITE_RETRY_POLICY, || {
+        client
+            .post(&url)
+            .headers(get_headers())
+            .body("{}")
+            .send()
+    })
+    .await;
+
+    log_request(&format!("accept_offer(offer_id: {})", offer_id), EndpointGroup::Trading, start, &body);
+    body
+}
+
+/// Inspects an item by its inspect link, the answer has the float value, the paint seed and the stickers
+///
+/// - Sent to the inspect_service_url of the config, CSFloat's inspect api or a local service with the same api
+/// - Not recorded in the CSFloat circuit breaker, the inspect service is not the market
+pub async fn get_inspect_info(inspect_link: &str) -> Result<reqwest::Response, reqwest::Error> {
+    let start = SystemTime::now();
+
+    let url = config::get_bot_config().inspect_service_url.clone();
+    let client = get_client_with_http2(None, 30, false)?;
+    let body = retry("csfloat_api | get_inspect_info()", &READ_RETRY_POLICY, || {
+        client
+            .get(&url)
+            .query(&[("url", inspect_link)])
+            .send()
+    })
+    .await;
+
+    let passed = SystemTime::now().duration_since(start).unwrap();
+    log_write(&format!("csfloat_api | get_inspect_info(inspect_link: {}) | The HTTP request took {:?}.\n", inspect_link, passed));
+    body
+}
+
+/// Builds the authenticated json headers
+fn get_headers() -> HeaderMap {
+    let mut header = HeaderMap::new();
+    header.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_str("application/json").unwrap(),
+    );
+    header.insert(
+        header::AUTHORIZATION,
+        header::HeaderValue::from_str(API_KEY).unwrap(),
+    );
+    header
+}
+
+/// Logs how long the request took and records it in the request metrics and the circuit breaker
+fn log_request<E>(call: &str, group: EndpointGroup, start: SystemTime, body: &Result<reqwest::Response, E>) {
+    let passed = SystemTime::now().duration_since(start).unwrap();
+    log_write(&format!("csfloat_api | {} | The HTTP request took {:?}.\n", call, passed));
+    metrics::record_request(Market::CSFloat, passed, metrics::is_success(body));
+    circuit_breaker::record(Market::CSFloat, group, body);
+}
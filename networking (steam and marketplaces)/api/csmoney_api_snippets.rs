@@ -0,0 +1,78 @@
+// csmoney_api.rs
+//
+// This module provides a client implementation for CS.Money's sell side API,
+// handling the creation and removal of sell listings. Requests are authenticated
+// with the session cookie of the account.
+
+use crate::{circuit_breaker::{self, EndpointGroup}, log_functions::log_write, metrics, proxy_handler::{self, get_client}, structs::Market};
+use reqwest::header::{self, HeaderMap};
+use std::time::SystemTime;
+
+static SESSION_COOKIE: &str = "XXX";
+
+/// Creates a sell listing of an item in the Steam inventory
+///
+/// - CS.Money needs the asset, class and instance ids to find the item
+/// - Price is in dollars
+pub async fn list_item(asset_id: &str, instance_id: &str, class_id: &str, price: f32) -> Result<reqwest::Response, String> {
+    let start = SystemTime::now();
+
+    let url = "https://cs.money/2.0/market/sell-orders";
+    let json_str = format!(
+        r#"{{"items":[{{"assetId":"{}","classId":"{}","instanceId":"{}","price":{:.2}}}]}}"#,
+        asset_id, class_id, instance_id, price
+    );
+
+    let body = send_post(url, json_str).await;
+
+    log_request(&format!("list_item(asset_id: {}, price: {})", asset_id, price), EndpointGroup::Trading, start, &body);
+    body
+}
+
+/// Removes a sell listing by its CS.Money id
+pub async fn remove_listing(csmoney_item_id: &str) -> Result<reqwest::Response, String> {
+    let start = SystemTime::now();
+
+    let url = "https://cs.money/2.0/market/sell-orders/remove";
+    let json_str = format!(r#"{{"ids":["{}"]}}"#, csmoney_item_id);
+
+    let body = send_post(url, json_str).await;
+
+    log_request(&format!("remove_listing(csmoney_item_id: {})", csmoney_item_id), EndpointGroup::Trading, start, &body);
+    body
+}
+
+/// Sends an authenticated json POST request through the next CS.Money proxy
+/// The request is not sent if CS.Money has no usable proxy
+async fn send_post(url: &str, json_str: String) -> Result<reqwest::Response, String> {
+    let mut header = HeaderMap::new();
+    header.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_str("application/json").unwrap(),
+    );
+    header.insert(
+        header::COOKIE,
+        header::HeaderValue::from_str(SESSION_COOKIE).unwrap(),
+    );
+
+    let proxy = proxy_handler::get_proxy(Market::CSMoney)
+        .map_err(|e| format!("csmoney_api | send_post(url: {}) | {}", url, e))?;
+    let client = get_client(proxy.as_ref(), 30)
+        .map_err(|e| format!("csmoney_api | send_post(url: {}) | Error occured when building the client. E: {:?}", url, e))?;
+    let res = client
+        .post(url)
+        .headers(header)
+        .body(json_str)
+        .send()
+        .await;
+    proxy_handler::record_result(proxy.as_ref(), &res);
+    res.map_err(|e| format!("{:?}", e))
+}
+
+/// Logs how long the request took and records it in the request metrics and the circuit breaker
+fn log_request<E>(call: &str, group: EndpointGroup, start: SystemTime, body: &Result<reqwest::Response, E>) {
+    let passed = SystemTime::now().duration_since(start).unwrap();
+    log_write(&format!("csmoney_api | {} | The HTTP request took {:?}.\n", call, passed));
+    metrics::record_request(Market::CSMoney, passed, metrics::is_success(body));
+    circuit_breaker::record(Market::CSMoney, group, body);
+}
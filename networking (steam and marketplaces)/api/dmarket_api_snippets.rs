@@ -0,0 +1,209 @@
+// dmarket_api.rs
+//
+// This module provides a client implementation for DMarket's marketplace API.
+// Every request is signed: the request string (method + path + query + body + timestamp)
+// is signed with the Ed25519 secret key and sent in the X-Sign-Date and X-Request-Sign headers.
+// The keys are read from the bot config so they never end up in the source.
+
+use crate::{circuit_breaker::{self, EndpointGroup}, config, log_functions::log_write, metrics, proxy_handler::{self, get_client_with_http2}, structs::Market};
+use ed25519_dalek::{Signer, SigningKey};
+use reqwest::{header::{self, HeaderMap}, Method, Url};
+use std::time::SystemTime;
+
+const API_URL: &str = "https://api.dmarket.com";
+
+// The game id of CS on DMarket
+const GAME_ID: &str = "a8db";
+
+/// Computes the signature headers of a DMarket request
+///
+/// - The string to sign is METHOD + path + ("?" + query) + body + timestamp in seconds, the body is sent as is
+/// - The secret key of the DMarket account is 64 bytes, the Ed25519 seed followed by the public key, only the seed signs
+/// - Returns (X-Sign-Date value, X-Request-Sign value)
+pub fn sign_request(
+    secret_key: &[u8],
+    method: &str,
+    path: &str,
+    query: &str,
+    body: &str,
+    timestamp_ms: u64,
+) -> Result<(String, String), String> {
+    let seed: [u8; 32] = secret_key
+        .get(..32)
+        .and_then(|seed| seed.try_into().ok())
+        .ok_or(format!(
+            "dmarket_api.rs | sign_request(path={}) | Error occured, the secret key has {} bytes instead of at least 32.",
+            path, secret_key.len()
+        ))?;
+    let date_header = (timestamp_ms / 1000).to_string();
+
+    let mut to_sign = method.to_uppercase();
+    to_sign.push_str(path);
+    if !query.is_empty() {
+        to_sign.push('?');
+        to_sign.push_str(query);
+    }
+    to_sign.push_str(body);
+    to_sign.push_str(&date_header);
+
+    let signature = SigningKey::from_bytes(&seed).sign(to_sign.as_bytes());
+    Ok((date_header, format!("dmar ed25519 {}", hex::encode(signature.to_bytes()))))
+}
+
+/// Searches the DMarket listings of a CS item by name, cheapest first
+///
+/// - Goes through the proxy rotation since it is used while scanning prices
+pub async fn get_item_price(market_hash_name: &str) -> Result<reqwest::Response, String> {
+    let start = SystemTime::now();
+
+    let query = [
+        ("gameId", GAME_ID),
+        ("title", market_hash_name),
+        ("currency", "USD"),
+        ("orderBy", "price"),
+        ("orderDir", "asc"),
+        ("limit", "30"),
+    ];
+
+    let body = send_signed(Method::GET, "/exchange/v1/market/items", &query, "", 15).await;
+
+    log_request(&format!("get_item_price(market_hash_name: {})", market_hash_name), EndpointGroup::Prices, start, &body);
+    body
+}
+
+/// Retrieves the balance of the account
+///
+/// - The amounts are returned as strings of cents, `usd` is what can be spent
+pub async fn get_balance() -> Result<reqwest::Response, String> {
+    let start = SystemTime::now();
+
+    let body = send_signed(Method::GET, "/account/v1/balance", &[], "", 15).await;
+
+    log_request("get_balance()", EndpointGroup::Account, start, &body);
+    body
+}
+
+/// Builds, signs and sends a DMarket request through the next DMarket proxy
+async fn send_signed(
+    method: Method,
+    path: &str,
+    query: &[(&str, &str)],
+    body: &str,
+    timeout_secs: u64,
+) -> Result<reqwest::Response, String> {
+    let bot_config = config::get_bot_config();
+    let (public_key, secret_key) = match (&bot_config.dmarket_public_key, &bot_config.dmarket_secret_key) {
+        (Some(public_key), Some(secret_key)) => (public_key, secret_key),
+        _ => return Err(format!("dmarket_api.rs | send_signed(path={}) | Error occured, the DMarket keys are not set in the config.", path)),
+    };
+    let secret_key = hex::decode(secret_key)
+        .map_err(|e| format!("dmarket_api.rs | send_signed(path={}) | Error occured when decoding the secret key. E: {:?}", path, e))?;
+
+    let url = Url::parse_with_params(&format!("{}{}", API_URL, path), query)
+        .map_err(|e| format!("dmarket_api.rs | send_signed(path={}) | Error occured when building the url. E: {:?}", path, e))?;
+
+    // The signature has to be over the exact encoded query that is sent
+    let timestamp_ms = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|n| n.as_millis() as u64)
+        .unwrap_or(0);
+    let (date_header, signature_header) = sign_request(
+        &secret_key,
+        method.as_str(),
+        path,
+        url.query().unwrap_or(""),
+        body,
+        timestamp_ms,
+    )?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, header::HeaderValue::from_static("application/json"));
+    headers.insert("X-Api-Key", header::HeaderValue::from_str(public_key).map_err(|e| e.to_string())?);
+    headers.insert("X-Sign-Date", header::HeaderValue::from_str(&date_header).map_err(|e| e.to_string())?);
+    headers.insert("X-Request-Sign", header::HeaderValue::from_str(&signature_header).map_err(|e| e.to_string())?);
+
+    let proxy = proxy_handler::get_proxy(Market::DMarket)
+        .map_err(|e| format!("dmarket_api.rs | send_signed(path={}) | {}", path, e))?;
+    let client = get_client_with_http2(proxy.as_ref(), timeout_secs, config::get_bot_config().use_http2(&Market::DMarket))
+        .map_err(|e| format!("dmarket_api.rs | send_signed(path={}) | Error occured when building the client. E: {:?}", path, e))?;
+
+    let res = client
+        .request(method, url)
+        .headers(headers)
+        .body(body.to_string())
+        .send()
+        .await;
+    proxy_handler::record_result(proxy.as_ref(), &res);
+
+    res.map_err(|e| format!("dmarket_api.rs | send_signed(path={}) | Error occured when sending the request. E: {:?}", path, e))
+}
+
+/// Logs how long the request took and records it in the request metrics and the circuit breaker
+fn log_request<E>(call: &str, group: EndpointGroup, start: SystemTime, body: &Result<reqwest::Response, E>) {
+    let passed = SystemTime::now().duration_since(start).unwrap();
+    log_write(&format!("dmarket_api | {} | The HTTP request took {:?}.\n", call, passed));
+    metrics::record_request(Market::DMarket, passed, metrics::is_success(body));
+    circuit_breaker::record(Market::DMarket, group, body);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The Ed25519 key of the first test vector of RFC 8032, in the DMarket format (seed followed by the public key)
+    const SECRET_KEY: &str = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60\
+                              d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+
+    // 2020-11-17T13:33:14Z
+    const TIMESTAMP_MS: u64 = 1_605_619_994_000;
+
+    fn secret_key() -> Vec<u8> {
+        hex::decode(SECRET_KEY).unwrap()
+    }
+
+    #[test]
+    fn signs_the_rfc_8032_vector() {
+        // The signature of the empty message of RFC 8032, checks that the seed half of the key is the one signing
+        let seed: [u8; 32] = secret_key()[..32].try_into().unwrap();
+        let signature = SigningKey::from_bytes(&seed).sign(b"");
+        assert_eq!(
+            hex::encode(signature.to_bytes()),
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b"
+        );
+    }
+
+    #[test]
+    fn signs_a_request_without_query_and_body() {
+        let (date, signature) = sign_request(&secret_key(), "GET", "/account/v1/balance", "", "", TIMESTAMP_MS).unwrap();
+        assert_eq!(date, "1605619994");
+        assert_eq!(
+            signature,
+            "dmar ed25519 ac29a8068d11ef757d6e591dd618086ca7d8daf2717057775dd4cb7b092a4c8ed7be899246e7cf5eaa1cc57dbf126554edd65c5abc9a814d89acd410c6b5440e"
+        );
+    }
+
+    #[test]
+    fn signs_the_encoded_query() {
+        let query = "gameId=a8db&title=AK-47+%7C+Redline+%28Field-Tested%29&currency=USD";
+        let (_, signature) = sign_request(&secret_key(), "get", "/exchange/v1/market/items", query, "", TIMESTAMP_MS).unwrap();
+        assert_eq!(
+            signature,
+            "dmar ed25519 08257bf8d96d05a94501d4a9f0e7f24a1ee18452505f9f748cc094f4de33eb2c081c1240d7aa0f36c8ca9a4877691a7eaa65bc4d111fc1cb93057051882b0107"
+        );
+    }
+
+    #[test]
+    fn signs_the_body() {
+        let body = r#"{"offers":[{"offerId":"abc","price":{"amount":"1250","currency":"USD"}}]}"#;
+        let (_, signature) = sign_request(&secret_key(), "POST", "/exchange/v1/offers-buy", "", body, TIMESTAMP_MS).unwrap();
+        assert_eq!(
+            signature,
+            "dmar ed25519 29451c1a027754e375895c884864d49b50615c5739a87319a705bc377c805615d54ba2a9afa1b8b002b3c4de23560211366f170e32ebbd1146999331c858770a"
+        );
+    }
+
+    #[test]
+    fn rejects_a_short_secret_key() {
+        assert!(sign_request(&[0u8; 16], "GET", "/account/v1/balance", "", "", TIMESTAMP_MS).is_err());
+    }
+}
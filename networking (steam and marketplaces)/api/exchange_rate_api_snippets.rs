@@ -0,0 +1,34 @@
+// exchange_rate_api.rs
+//
+// This module provides a client implementation for exchangerate.host's public API,
+// handling the queries of the latest exchange rates that are used to normalize
+// the prices of the markets that don't quote in dollars.
+
+use crate::{
+    log_functions::log_write,
+    proxy_handler::{get_client, retry, READ_RETRY_POLICY},
+};
+use std::time::SystemTime;
+
+/// Retrieves the latest exchange rates of the base currency
+///
+/// - `symbols` are the ISO codes of the wanted currencies, comma separated
+/// - Every rate is the amount of the currency one unit of the base currency buys
+pub async fn get_latest_rates(base: &str, symbols: &str) -> Result<reqwest::Response, reqwest::Error> {
+    let start = SystemTime::now();
+
+    let url = "https://api.exchangerate.host/latest";
+
+    let client = get_client(None, 30)?;
+    let body = retry("exchange_rate_api | get_latest_rates()", &READ_RETRY_POLICY, || {
+        client
+            .get(url)
+            .query(&[("base", base), ("symbols", symbols)])
+            .send()
+    })
+    .await;
+
+    let passed = SystemTime::now().duration_since(start).unwrap();
+    log_write(&format!("exchange_rate_api | get_latest_rates(base: {}) | The HTTP request took {:?}.\n", base, passed));
+    body
+}
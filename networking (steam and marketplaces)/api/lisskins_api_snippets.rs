@@ -0,0 +1,113 @@
+// lisskins_api.rs
+//
+// This module provides a client implementation for LisSkins' marketplace API,
+// handling item searching, purchase creation and purchase status checks.
+// LisSkins doesn't rate limit by ip, so the requests go out without a proxy.
+
+use crate::{
+    circuit_breaker::{self, EndpointGroup},
+    log_functions::log_write,
+    metrics,
+    proxy_handler::{get_client, retry, READ_RETRY_POLICY, WRITE_RETRY_POLICY},
+    structs::Market,
+};
+use reqwest::header::{self, HeaderMap};
+use std::time::SystemTime;
+
+static API_KEY: &str = "XXX";
+
+// The trade url parts of the account the bought items are sent to
+static TRADE_PARTNER: &str = "XXX";
+static TRADE_TOKEN: &str = "XXX";
+
+/// Searches the LisSkins listings of a CS item by name
+///
+/// - Listings are sorted by price, cheapest first
+/// - Each listing has its unlock date if it is still trade locked
+pub async fn search_items(market_hash_name: &str) -> Result<reqwest::Response, reqwest::Error> {
+    let start = SystemTime::now();
+
+    let client = get_client(None, 0)?;
+    let body = retry("lisskins_api | search_items()", &READ_RETRY_POLICY, || {
+        client
+            .get("https://api.lis-skins.com/v1/market/search")
+            .timeout(std::time::Duration::from_secs(15))
+            .headers(get_headers())
+            .query(&[("game", "csgo"), ("names[]", market_hash_name), ("sort_by", "lowest_price")])
+            .send()
+    })
+    .await;
+
+    log_request(&format!("search_items(market_hash_name: {})", market_hash_name), EndpointGroup::Prices, start, &body);
+    body
+}
+
+/// Creates a purchase of a listing, LisSkins sends the item with a Steam trade offer afterwards
+///
+/// - `max_price` protects from buying if the listing price changed, in dollars
+/// - `custom_id` is our own id of the purchase, used to poll its status
+pub async fn create_purchase(item_id: i64, max_price: f32, custom_id: &str) -> Result<reqwest::Response, reqwest::Error> {
+    let start = SystemTime::now();
+
+    let json_str = format!(
+        r#"{{"ids":[{}],"partner":"{}","token":"{}","max_price":{:.2},"custom_id":"{}"}}"#,
+        item_id, TRADE_PARTNER, TRADE_TOKEN, max_price, custom_id
+    );
+
+    let client = get_client(None, 0)?;
+    let body = retry("lisskins_api | create_purchase()", &WRITE_RETRY_POLICY, || {
+        client
+            .post("https://api.lis-skins.com/v1/market/buy")
+            .timeout(std::time::Duration::from_secs(30))
+            .headers(get_headers())
+            .body(json_str.clone())
+            .send()
+    })
+    .await;
+
+    log_request(&format!("create_purchase(item_id: {}, max_price: {}, custom_id: {})", item_id, max_price, custom_id), EndpointGroup::Trading, start, &body);
+    body
+}
+
+/// Retrieves the status and the Steam trade offer id of the purchases by their custom ids
+pub async fn get_purchase_status(custom_ids: &[String]) -> Result<reqwest::Response, reqwest::Error> {
+    let start = SystemTime::now();
+
+    let query: Vec<(&str, &str)> = custom_ids.iter().map(|id| ("custom_ids[]", id.as_str())).collect();
+
+    let client = get_client(None, 0)?;
+    let body = retry("lisskins_api | get_purchase_status()", &READ_RETRY_POLICY, || {
+        client
+            .get("https://api.lis-skins.com/v1/market/info")
+            .timeout(std::time::Duration::from_secs(15))
+            .headers(get_headers())
+            .query(&query)
+            .send()
+    })
+    .await;
+
+    log_request(&format!("get_purchase_status(custom_ids: {:?})", custom_ids), EndpointGroup::Trading, start, &body);
+    body
+}
+
+/// Builds the authenticated json headers
+fn get_headers() -> HeaderMap {
+    let mut header = HeaderMap::new();
+    header.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_str("application/json").unwrap(),
+    );
+    header.insert(
+        header::AUTHORIZATION,
+        header::HeaderValue::from_str(&format!("Bearer {}", API_KEY)).unwrap(),
+    );
+    header
+}
+
+/// Logs how long the request took and records it in the request metrics and the circuit breaker
+fn log_request<E>(call: &str, group: EndpointGroup, start: SystemTime, body: &Result<reqwest::Response, E>) {
+    let passed = SystemTime::now().duration_since(start).unwrap();
+    log_write(&format!("lisskins_api | {} | The HTTP request took {:?}.\n", call, passed));
+    metrics::record_request(Market::LisSkins, passed, metrics::is_success(body));
+    circuit_breaker::record(Market::LisSkins, group, body);
+}
@@ -0,0 +1,46 @@
+// marketcsgo_api.rs
+//
+// This module provides a client implementation for MarketCSGO's (market.csgo.com) v2 API,
+// handling the queries of the sell listings of the account. Requests are authenticated
+// with the api key as a query parameter.
+
+use crate::{
+    circuit_breaker::{self, EndpointGroup},
+    log_functions::log_write,
+    metrics,
+    proxy_handler::{get_client, retry, READ_RETRY_POLICY},
+    structs::Market,
+};
+use std::time::SystemTime;
+
+static API_KEY: &str = "XXX";
+
+/// Retrieves the items the account has on MarketCSGO
+///
+/// - Has the listings that are still for sale and the sold ones that wait for the trade
+/// - Prices are in the currency of the account, the bot uses a USD account
+pub async fn get_items() -> Result<reqwest::Response, reqwest::Error> {
+    let start = SystemTime::now();
+
+    let url = "https://market.csgo.com/api/v2/items";
+
+    let client = get_client(None, 30)?;
+    let body = retry("marketcsgo_api | get_items()", &READ_RETRY_POLICY, || {
+        client
+            .get(url)
+            .query(&[("key", API_KEY)])
+            .send()
+    })
+    .await;
+
+    log_request("get_items()", EndpointGroup::Prices, start, &body);
+    body
+}
+
+/// Logs how long the request took and records it in the request metrics and the circuit breaker
+fn log_request<E>(call: &str, group: EndpointGroup, start: SystemTime, body: &Result<reqwest::Response, E>) {
+    let passed = SystemTime::now().duration_since(start).unwrap();
+    log_write(&format!("marketcsgo_api | {} | The HTTP request took {:?}.\n", call, passed));
+    metrics::record_request(Market::MarketCSGO, passed, metrics::is_success(body));
+    circuit_breaker::record(Market::MarketCSGO, group, body);
+}
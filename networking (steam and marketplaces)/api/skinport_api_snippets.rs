@@ -0,0 +1,47 @@
+// skinport_api.rs
+//
+// This module provides a client for Skinport's public API.
+// Skinport has no bot api for buying, so it is only used as a price signal
+// for the arbitrage calculations.
+
+use crate::{circuit_breaker::{self, EndpointGroup}, log_functions::log_write, metrics, proxy_handler::get_client, structs::Market};
+use reqwest::header::{self, HeaderMap};
+use std::time::SystemTime;
+
+/// Fetches the Skinport prices of every CS item
+///
+/// - Uses the bulk items endpoint, the response contains every item so it is large
+/// - Requests a brotli encoded response as required by Skinport
+pub async fn get_all_prices() -> Result<reqwest::Response, reqwest::Error> {
+    let start = SystemTime::now();
+
+    let url = "https://api.skinport.com/v1/items?app_id=730&currency=USD";
+
+    // Set up request headers
+    let mut header = HeaderMap::new();
+    header.insert(
+        header::ACCEPT_ENCODING,
+        header::HeaderValue::from_str("br").unwrap(),
+    );
+
+    // Send the request
+    let client = get_client(None, 0)?;
+    let body = client
+        .get(url)
+        .timeout(std::time::Duration::from_secs(60))
+        .headers(header)
+        .send()
+        .await;
+
+    // Log performance data
+    let after = SystemTime::now();
+    let passed = after.duration_since(start).unwrap();
+    let log_txt = format!(
+        "skinport_api | get_all_prices() | The HTTP request took {:?}.\n",
+        passed
+    );
+    log_write(&log_txt);
+    metrics::record_request(Market::Skinport, passed, metrics::is_success(&body));
+    circuit_breaker::record(Market::Skinport, EndpointGroup::Prices, &body);
+    body
+}
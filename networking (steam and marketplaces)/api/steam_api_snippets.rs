@@ -5,16 +5,18 @@
 // It demonstrates advanced HTTP client implementation with proper error handling,
 // authentication management, and response validation.
 
-use crate::statics::{
-    self, get_marketcsgo_access_token, get_steam_cookie, get_steam_session_id, get_steam_web_api,
-};
+use crate::circuit_breaker::{self, EndpointGroup};
+use crate::config;
+use crate::metrics;
+use crate::proxy_handler::{self, get_client, retry, READ_RETRY_POLICY, WRITE_RETRY_POLICY};
+use crate::structs::{Market, SteamAccount, SteamApp, SteamId};
 use async_std::{fs::OpenOptions, io::WriteExt};
 use reqwest::{
     self,
-    header::{HeaderMap, CONTENT_TYPE, COOKIE, REFERER},
+    header::{HeaderMap, HeaderValue, CONTENT_TYPE, COOKIE, REFERER},
 };
 use serde::{Deserialize, Serialize};
-use std::{i128, time::SystemTime};
+use std::time::{Instant, SystemTime};
 
 /// Data structure for creating trade offers
 #[derive(Debug, Serialize, Deserialize)]
@@ -37,34 +39,199 @@ struct TradeOfferAcceptData {
     captcha: String,
 }
 
+/// Data structure for canceling trade offers
+#[derive(Debug, Serialize, Deserialize)]
+struct TradeOfferCancelData {
+    sessionid: String,
+}
+
 /// Retrieves detailed information about a specific trade offer
 /// 
-/// This function demonstrates API key authentication and proper
+/// This function demonstrates access token authentication and proper
 /// request parameter handling with Steam's API
-pub async fn get_trade_offer(tradeofferid: String) -> Result<reqwest::Response, reqwest::Error> {
+pub async fn get_trade_offer(account: &SteamAccount, tradeofferid: String) -> Result<reqwest::Response, reqwest::Error> {
     let url = "https://api.steampowered.com/IEconService/GetTradeOffer/v1/";
 
-    let access_token = get_marketcsgo_access_token().unwrap_or("0".to_string());
-    let web_api = get_steam_web_api().unwrap_or("0".to_string());
-
-    let client = reqwest::Client::new();
-    client
-        .get(url)
-        .timeout(std::time::Duration::from_secs(30))
-        .query(&[
-            ("key", &web_api),
-            ("access_token", &access_token),
-            ("tradeofferid", &tradeofferid),
-        ])
-        .send()
-        .await
+    let start = Instant::now();
+    let client = get_client(None, 0)?;
+    let body = retry("steam_api | get_trade_offer()", &READ_RETRY_POLICY, || {
+        client
+            .get(url)
+            .timeout(std::time::Duration::from_secs(30))
+            .query(&[
+                ("access_token", &account.webapi_token),
+                ("tradeofferid", &tradeofferid),
+            ])
+            .send()
+    })
+    .await;
+
+    record_request(EndpointGroup::Trading, start, &body);
+    body
+}
+
+/// Retrieves the status of an accepted trade
+///
+/// - The trade id is given by the accepted trade offer, it is not the trade offer id
+/// - The received assets have the asset id they got in our inventory
+pub async fn get_trade_status(account: &SteamAccount, trade_id: &str) -> Result<reqwest::Response, reqwest::Error> {
+    let url = "https://api.steampowered.com/IEconService/GetTradeStatus/v1/";
+
+    let start = Instant::now();
+    let client = get_client(None, 0)?;
+    let body = retry("steam_api | get_trade_status()", &READ_RETRY_POLICY, || {
+        client
+            .get(url)
+            .timeout(std::time::Duration::from_secs(30))
+            .query(&[
+                ("access_token", account.webapi_token.as_str()),
+                ("tradeid", trade_id),
+                ("get_descriptions", "0"),
+            ])
+            .send()
+    })
+    .await;
+
+    record_request(EndpointGroup::Trading, start, &body);
+    body
+}
+
+/// Retrieves the most recent finished trades of the account
+///
+/// - The failed trades are left out, their assets never left the inventory
+/// - The given and received assets have their asset id before and after the trade
+pub async fn get_trade_history(account: &SteamAccount, max_trades: u32) -> Result<reqwest::Response, reqwest::Error> {
+    let url = "https://api.steampowered.com/IEconService/GetTradeHistory/v1/";
+    let max_trades = max_trades.to_string();
+
+    let start = Instant::now();
+    let client = get_client(None, 0)?;
+    let body = retry("steam_api | get_trade_history()", &READ_RETRY_POLICY, || {
+        client
+            .get(url)
+            .timeout(std::time::Duration::from_secs(30))
+            .query(&[
+                ("access_token", account.webapi_token.as_str()),
+                ("max_trades", max_trades.as_str()),
+                ("include_failed", "0"),
+                ("get_descriptions", "0"),
+            ])
+            .send()
+    })
+    .await;
+
+    record_request(EndpointGroup::Trading, start, &body);
+    body
+}
+
+/// Retrieves the trade offers of the account
+///
+/// - `active_only` skips the offers that are not active anymore
+/// - `sent` and `received` pick the offers created by us and the offers sent to us
+pub async fn get_trade_offers(
+    account: &SteamAccount,
+    active_only: bool,
+    sent: bool,
+    received: bool,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let url = "https://api.steampowered.com/IEconService/GetTradeOffers/v1/";
+
+    let flag = |value: bool| if value { "1" } else { "0" };
+
+    let start = Instant::now();
+    let client = get_client(None, 0)?;
+    let body = retry("steam_api | get_trade_offers()", &READ_RETRY_POLICY, || {
+        client
+            .get(url)
+            .timeout(std::time::Duration::from_secs(30))
+            .query(&[
+                ("access_token", account.webapi_token.as_str()),
+                ("active_only", flag(active_only)),
+                ("get_sent_offers", flag(sent)),
+                ("get_received_offers", flag(received)),
+                ("get_descriptions", "0"),
+            ])
+            .send()
+    })
+    .await;
+
+    record_request(EndpointGroup::Trading, start, &body);
+    body
+}
+
+/// Retrieves the web api token of the account
+///
+/// The token is only handed out to a logged in session,
+/// so the cookie of the account is what authenticates the request
+pub async fn get_steam_webapi(account: &SteamAccount) -> Result<reqwest::Response, String> {
+    let url = "https://steamcommunity.com/pointssummary/ajaxgetasyncconfig";
+
+    let mut headers = HeaderMap::new();
+    headers.insert(COOKIE, cookie_header(account)?);
+
+    let start = Instant::now();
+    let client = get_client(None, 0)
+        .map_err(|e| format!("Error occured when building the client: {:?}", e))?;
+    let body = retry("steam_api | get_steam_webapi()", &READ_RETRY_POLICY, || {
+        client
+            .get(url)
+            .timeout(std::time::Duration::from_secs(30))
+            .headers(headers.clone())
+            .send()
+    })
+    .await;
+
+    record_request(EndpointGroup::Account, start, &body);
+    body.map_err(|e| format!("Error occured when sending the request: {:?}", e))
+}
+
+/// Retrieves the Steam Community Market price overview of a CS item
+///
+/// - `currency` is the Steam currency id, 1 is USD
+/// - The endpoint is rate limited hard, so it goes through the Steam proxies
+///   and the 429 answers are retried with backoff
+pub async fn get_market_price(market_hash_name: &str, currency: u32) -> Result<reqwest::Response, String> {
+    let url = "https://steamcommunity.com/market/priceoverview/";
+    let currency = currency.to_string();
+
+    let start = Instant::now();
+    let proxy = proxy_handler::get_proxy(Market::Steam)
+        .map_err(|e| format!("steam_api | get_market_price(market_hash_name: {}) | {}", market_hash_name, e))?;
+    let client = get_client(proxy.as_ref(), 15)
+        .map_err(|e| format!("steam_api | get_market_price(market_hash_name: {}) | Error occured when building the client. E: {:?}", market_hash_name, e))?;
+    let body = retry("steam_api | get_market_price()", &READ_RETRY_POLICY, || {
+        client
+            .get(url)
+            .query(&[
+                ("appid", "730"),
+                ("currency", currency.as_str()),
+                ("market_hash_name", market_hash_name),
+            ])
+            .send()
+    })
+    .await;
+    proxy_handler::record_result(proxy.as_ref(), &body);
+
+    record_request(EndpointGroup::Prices, start, &body);
+    body.map_err(|e| format!("{:?}", e))
 }
 
 /// Fetches a user's CS:GO inventory with proper authentication
 /// 
 /// This function shows handling of Steam's cookie-based authentication
 /// and includes performance logging to track API response times
-pub async fn get_inventory(user_id: String, last_asset: &str) -> Result<reqwest::Response, String> {
+/// The account authenticates the request, user_id is the owner of the inventory
+pub async fn get_inventory(account: &SteamAccount, user_id: SteamId, last_asset: &str) -> Result<reqwest::Response, String> {
+    get_inventory_for_app(account, user_id, last_asset, SteamApp::CS).await
+}
+
+/// Fetches a page of a user's inventory of the given Steam app and context
+pub async fn get_inventory_for_app(
+    account: &SteamAccount,
+    user_id: SteamId,
+    last_asset: &str,
+    app: SteamApp,
+) -> Result<reqwest::Response, String> {
     // Start the timer and open the log file
     let start = SystemTime::now();
     let mut log = OpenOptions::new()
@@ -80,33 +247,24 @@ pub async fn get_inventory(user_id: String, last_asset: &str) -> Result<reqwest:
     };
 
     let url = format!(
-        "https://steamcommunity.com/inventory/{}/730/2?l=english&count=1000",
-        user_id
+        "https://steamcommunity.com/inventory/{}/{}/{}?l=english&count={}",
+        user_id.steam64(), app.app_id, app.context_id, config::get_bot_config().steam_inventory_page_count
     ) + &asset_str;
 
-    let cookie_ = get_steam_cookie();
-    if let Err(statics_err) = cookie_ {
-        return Err(statics_err);
-    }
-    let mut cookie = cookie_.unwrap();
-    cookie = cookie.trim().to_string();
-
     // Create the headers
     let mut headers = HeaderMap::new();
-    headers.insert(COOKIE, cookie.parse().unwrap());
-    
-    let session_id_ = get_steam_session_id();
-    if let Err(statics_err) = session_id_ {
-        return Err(statics_err);
-    }
-
-    let client = reqwest::Client::new();
-    let body = client
-        .get(url)
-        .timeout(std::time::Duration::from_secs(30))
-        .headers(headers)
-        .send()
-        .await;
+    headers.insert(COOKIE, cookie_header(account)?);
+
+    let client = get_client(None, 0)
+        .map_err(|e| format!("Error occured when building the client: {:?}", e))?;
+    let body = retry("steam_api | get_inventory()", &READ_RETRY_POLICY, || {
+        client
+            .get(&url)
+            .timeout(std::time::Duration::from_secs(30))
+            .headers(headers.clone())
+            .send()
+    })
+    .await;
 
     // After the request has been sent log the interaction
     let after = SystemTime::now();
@@ -118,6 +276,8 @@ pub async fn get_inventory(user_id: String, last_asset: &str) -> Result<reqwest:
     log.write(log_txt.as_bytes())
         .await
         .expect("Cannot write to api_log.txt file.");
+    metrics::record_request(Market::Steam, passed, metrics::is_success(&body));
+    circuit_breaker::record(Market::Steam, EndpointGroup::Account, &body);
 
     if let Err(body_err) = body {
         return Err(format!("{:?}", body_err));
@@ -125,17 +285,26 @@ pub async fn get_inventory(user_id: String, last_asset: &str) -> Result<reqwest:
     Ok(body.unwrap())
 }
 
+/// Builds the cookie header of the account, a cookie that isn't a valid header value is returned as an error
+fn cookie_header(account: &SteamAccount) -> Result<HeaderValue, String> {
+    account
+        .cookie
+        .trim()
+        .parse()
+        .map_err(|e| format!("Error occured when building the cookie header: {:?}", e))
+}
+
 /// Sends a trade offer to another Steam user
 ///
 /// This function demonstrates complex form submission with proper headers,
 /// handling of session authentication, and Steam's trading API integration
 pub async fn send_trade_offer(
-    partner_id: &str,
+    account: &SteamAccount,
+    partner: SteamId,
     partner_token: &str,
     trade_offer_message: &str,
     items: &str,
 ) -> Result<reqwest::Response, String> {
-    let steam_id: i128 = partner_id.parse::<i128>().unwrap() + 76561197960265728;
     let url = "https://steamcommunity.com/tradeoffer/new/send";
 
     // Create the headers
@@ -145,10 +314,10 @@ pub async fn send_trade_offer(
         REFERER,
         format!(
             "https://steamcommunity.com/tradeoffer/new/?partner={}&token={}",
-            partner_id, partner_token
+            partner.account_id(), partner_token
         )
         .parse()
-        .unwrap(),
+        .map_err(|e| format!("Error occured when building the referer header: {:?}", e))?,
     );
 
     headers.insert(
@@ -158,24 +327,14 @@ pub async fn send_trade_offer(
             .unwrap(),
     );
 
-    let cookie_ = get_steam_cookie();
-    if let Err(statics_err) = cookie_ {
-        return Err(statics_err);
-    }
-    let cookie = cookie_.unwrap();
-
-    headers.insert(COOKIE, cookie.parse().unwrap());
-    let session_id_ = get_steam_session_id();
-    if let Err(statics_err) = session_id_ {
-        return Err(statics_err);
-    }
-    let session_id = session_id_.unwrap();
+    headers.insert(COOKIE, cookie_header(account)?);
+    let session_id = account.session_id.clone();
 
     // Create the body (json)
     let body_obj = TradeOfferData {
         sessionid: session_id,
         serverid: 1,
-        partner: steam_id.to_string(),
+        partner: partner.steam64().to_string(),
         tradeoffermessage: trade_offer_message.to_string(),
         trade_offer_create_params: format!("{{\"trade_offer_access_token\": \"{}\"}}", partner_token),
         json_tradeoffer: format!("{{\"newversion\": true, \"version\": 2, \"me\": {{\"assets\":{}, \"currency\": [], \"ready\": false}}, \"them\": {{\"assets\":[], \"currency\": [], \"ready\": false}}}}", items),
@@ -184,15 +343,20 @@ pub async fn send_trade_offer(
 
     let data = serde_urlencoded::to_string(&body_obj).expect("serialize issue");
 
-    let client = reqwest::Client::new();
-
-    let response = client
-        .post(url)
-        .timeout(std::time::Duration::from_secs(30))
-        .headers(headers)
-        .body(data)
-        .send()
-        .await;
+    let client = get_client(None, 0)
+        .map_err(|e| format!("Error occured when building the client: {:?}", e))?;
+
+    let start = Instant::now();
+    let response = retry("steam_api | send_trade_offer()", &WRITE_RETRY_POLICY, || {
+        client
+            .post(url.clone())
+            .timeout(std::time::Duration::from_secs(30))
+            .headers(headers.clone())
+            .body(data.clone())
+            .send()
+    })
+    .await;
+    record_request(EndpointGroup::Trading, start, &response);
 
     if let Ok(body) = response {
         Ok(body)
@@ -209,6 +373,7 @@ pub async fn send_trade_offer(
 /// This function shows how to handle Steam's trade acceptance flow,
 /// demonstrating session management and proper HTTP header configuration
 pub async fn accept_trade_offer(
+    account: &SteamAccount,
     trade_offer_id: &str,
 ) -> Result<reqwest::Response, String> {
     let url = format!(
@@ -233,18 +398,8 @@ pub async fn accept_trade_offer(
             .unwrap(),
     );
 
-    let cookie_ = get_steam_cookie();
-    if let Err(statics_err) = cookie_ {
-        return Err(statics_err);
-    }
-    let cookie = cookie_.unwrap();
-
-    headers.insert(COOKIE, cookie.parse().unwrap());
-    let session_id_ = get_steam_session_id();
-    if let Err(statics_err) = session_id_ {
-        return Err(statics_err);
-    }
-    let session_id = session_id_.unwrap();
+    headers.insert(COOKIE, cookie_header(account)?);
+    let session_id = account.session_id.clone();
 
     // Create the body (json)
     let body_obj = TradeOfferAcceptData {
@@ -256,15 +411,85 @@ pub async fn accept_trade_offer(
 
     let data = serde_urlencoded::to_string(&body_obj).expect("serialize issue");
 
-    let client = reqwest::Client::new();
+    let client = get_client(None, 0)
+        .map_err(|e| format!("Error occured when building the client: {:?}", e))?;
+
+    let start = Instant::now();
+    let response = retry("steam_api | accept_trade_offer()", &WRITE_RETRY_POLICY, || {
+        client
+            .post(url.clone())
+            .timeout(std::time::Duration::from_secs(30))
+            .headers(headers.clone())
+            .body(data.clone())
+            .send()
+    })
+    .await;
+    record_request(EndpointGroup::Trading, start, &response);
+
+    if let Ok(body) = response {
+        Ok(body)
+    } else {
+        Err(format!(
+            "Error occured when sending the request: {:?}",
+            response.unwrap_err()
+        ))
+    }
+}
 
-    let response = client
-        .post(url)
-        .timeout(std::time::Duration::from_secs(30))
-        .headers(headers)
-        .body(data)
-        .send()
-        .await;
+/// Cancels an outgoing trade offer
+///
+/// This function mirrors the acceptance flow, using the session id
+/// and cookie to authenticate the cancel request on Steam's trade offer page
+pub async fn cancel_trade_offer(
+    account: &SteamAccount,
+    trade_offer_id: &str,
+) -> Result<reqwest::Response, String> {
+    let url = format!(
+        "https://steamcommunity.com/tradeoffer/{}/cancel",
+        trade_offer_id
+    );
+
+    // Create the headers
+    let mut headers = HeaderMap::new();
+
+    headers.insert(
+        REFERER,
+        format!("https://steamcommunity.com/tradeoffer/{}", trade_offer_id)
+            .parse()
+            .unwrap(),
+    );
+
+    headers.insert(
+        CONTENT_TYPE,
+        "application/x-www-form-urlencoded; charset=UTF-8"
+            .parse()
+            .unwrap(),
+    );
+
+    headers.insert(COOKIE, cookie_header(account)?);
+    let session_id = account.session_id.clone();
+
+    // Create the body
+    let body_obj = TradeOfferCancelData {
+        sessionid: session_id,
+    };
+
+    let data = serde_urlencoded::to_string(&body_obj).expect("serialize issue");
+
+    let client = get_client(None, 0)
+        .map_err(|e| format!("Error occured when building the client: {:?}", e))?;
+
+    let start = Instant::now();
+    let response = retry("steam_api | cancel_trade_offer()", &WRITE_RETRY_POLICY, || {
+        client
+            .post(url.clone())
+            .timeout(std::time::Duration::from_secs(30))
+            .headers(headers.clone())
+            .body(data.clone())
+            .send()
+    })
+    .await;
+    record_request(EndpointGroup::Trading, start, &response);
 
     if let Ok(body) = response {
         Ok(body)
@@ -275,3 +500,9 @@ pub async fn accept_trade_offer(
         ))
     }
 }
+
+/// Records the duration and the result of a Steam request in the request metrics and the circuit breaker
+fn record_request<E>(group: EndpointGroup, start: Instant, body: &Result<reqwest::Response, E>) {
+    metrics::record_request(Market::Steam, start.elapsed(), metrics::is_success(body));
+    circuit_breaker::record(Market::Steam, group, body);
+}
@@ -0,0 +1,170 @@
+// waxpeer_api.rs
+//
+// This module provides a client implementation for WaxPeer's peer-to-peer marketplace API,
+// handling item listing, price updates, listing removal and the trades that have to be sent
+// to buyers. Prices are sent and received in thousandths of a dollar.
+
+use crate::{circuit_breaker::{self, EndpointGroup}, log_functions::log_write, metrics, proxy_handler::{self, get_client, ProxyConfig}, structs::Market};
+use reqwest::header::{self, HeaderMap};
+use std::time::SystemTime;
+
+static P_KEY: &str = "XXX";
+
+/// Searches the WaxPeer listings of a CS item by name
+///
+/// - Goes through the proxy rotation since it is used while scanning prices
+/// - Returns every listing so the lowest price can be found
+pub async fn get_item_price(market_hash_name: &str) -> Result<reqwest::Response, String> {
+    let start = SystemTime::now();
+
+    let url = "https://api.waxpeer.com/v1/search-items-by-name";
+
+    let (proxy, client) = proxied_client(15)
+        .map_err(|e| format!("waxpeer_api | get_item_price(market_hash_name: {}) | {}", market_hash_name, e))?;
+    let body = client
+        .get(url)
+        .query(&[("api", P_KEY), ("game", "csgo"), ("names", market_hash_name)])
+        .send()
+        .await;
+    proxy_handler::record_result(proxy.as_ref(), &body);
+
+    log_request(&format!("get_item_price(market_hash_name: {})", market_hash_name), EndpointGroup::Prices, start, &body);
+    body.map_err(|e| format!("{:?}", e))
+}
+
+/// Checks if an item with the given name is currently listed on WaxPeer
+///
+/// - Uses the same search endpoint as the price check with a single result
+pub async fn check_item_availability(market_hash_name: &str) -> Result<reqwest::Response, String> {
+    let start = SystemTime::now();
+
+    let url = "https://api.waxpeer.com/v1/search-items-by-name";
+
+    let (proxy, client) = proxied_client(15)
+        .map_err(|e| format!("waxpeer_api | check_item_availability(market_hash_name: {}) | {}", market_hash_name, e))?;
+    let body = client
+        .get(url)
+        .query(&[("api", P_KEY), ("game", "csgo"), ("names", market_hash_name), ("minified", "1")])
+        .send()
+        .await;
+    proxy_handler::record_result(proxy.as_ref(), &body);
+
+    log_request(&format!("check_item_availability(market_hash_name: {})", market_hash_name), EndpointGroup::Prices, start, &body);
+    body.map_err(|e| format!("{:?}", e))
+}
+
+/// Lists an item from the Steam inventory for sale
+///
+/// - Price is in thousandths of a dollar
+pub async fn list_item(asset_id: &str, price: i64) -> Result<reqwest::Response, reqwest::Error> {
+    let start = SystemTime::now();
+
+    let url = format!("https://api.waxpeer.com/v1/list-items-steam?api={}", P_KEY);
+    let json_str = format!(r#"{{"items":[{{"item_id":{},"price":{}}}]}}"#, asset_id, price);
+
+    let body = send_post(&url, json_str).await;
+
+    log_request(&format!("list_item(asset_id: {}, price: {})", asset_id, price), EndpointGroup::Trading, start, &body);
+    body
+}
+
+/// Changes the price of a listed item
+pub async fn edit_price(asset_id: &str, price: i64) -> Result<reqwest::Response, reqwest::Error> {
+    let start = SystemTime::now();
+
+    let url = format!("https://api.waxpeer.com/v1/edit-items?api={}", P_KEY);
+    let json_str = format!(r#"{{"items":[{{"item_id":{},"price":{}}}]}}"#, asset_id, price);
+
+    let body = send_post(&url, json_str).await;
+
+    log_request(&format!("edit_price(asset_id: {}, price: {})", asset_id, price), EndpointGroup::Trading, start, &body);
+    body
+}
+
+/// Changes the prices of several listed items in one request
+///
+/// - Prices are in thousandths of a dollar
+pub async fn edit_prices(items: &[(String, i64)]) -> Result<reqwest::Response, reqwest::Error> {
+    let start = SystemTime::now();
+
+    let url = format!("https://api.waxpeer.com/v1/edit-items?api={}", P_KEY);
+    let entries: Vec<String> = items
+        .iter()
+        .map(|(asset_id, price)| format!(r#"{{"item_id":{},"price":{}}}"#, asset_id, price))
+        .collect();
+    let json_str = format!(r#"{{"items":[{}]}}"#, entries.join(","));
+
+    let body = send_post(&url, json_str).await;
+
+    log_request(&format!("edit_prices(items: {})", items.len()), EndpointGroup::Trading, start, &body);
+    body
+}
+
+/// Removes a listed item from sale
+pub async fn remove_item(asset_id: &str) -> Result<reqwest::Response, reqwest::Error> {
+    let start = SystemTime::now();
+
+    let url = format!("https://api.waxpeer.com/v1/remove-items?api={}&id={}", P_KEY, asset_id);
+
+    let client = get_client(None, 0)?;
+    let body = client
+        .get(url)
+        .timeout(std::time::Duration::from_secs(30))
+        .send()
+        .await;
+
+    log_request(&format!("remove_item(asset_id: {})", asset_id), EndpointGroup::Trading, start, &body);
+    body
+}
+
+/// Retrieves the sold items that are waiting for a Steam trade to the buyer
+pub async fn get_trades_to_send() -> Result<reqwest::Response, reqwest::Error> {
+    let start = SystemTime::now();
+
+    let url = format!("https://api.waxpeer.com/v1/ready-to-transfer-p2p?api={}", P_KEY);
+
+    let client = get_client(None, 0)?;
+    let body = client
+        .get(url)
+        .timeout(std::time::Duration::from_secs(30))
+        .send()
+        .await;
+
+    log_request("get_trades_to_send()", EndpointGroup::Trading, start, &body);
+    body
+}
+
+/// Builds a client that goes through the next WaxPeer proxy, the proxy is returned to record the result
+/// Fails if WaxPeer has no usable proxy, so the request is not sent from our own address
+fn proxied_client(timeout_secs: u64) -> Result<(Option<ProxyConfig>, reqwest::Client), String> {
+    let proxy = proxy_handler::get_proxy(Market::WaxPeer)?;
+    let client = get_client(proxy.as_ref(), timeout_secs)
+        .map_err(|e| format!("Error occured when building the client. E: {:?}", e))?;
+    Ok((proxy, client))
+}
+
+/// Sends an authenticated json POST request directly
+async fn send_post(url: &str, json_str: String) -> Result<reqwest::Response, reqwest::Error> {
+    let mut header = HeaderMap::new();
+    header.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_str("application/json").unwrap(),
+    );
+
+    let client = get_client(None, 0)?;
+    client
+        .post(url)
+        .timeout(std::time::Duration::from_secs(30))
+        .headers(header)
+        .body(json_str)
+        .send()
+        .await
+}
+
+/// Logs how long the request took and records it in the request metrics and the circuit breaker
+fn log_request<E>(call: &str, group: EndpointGroup, start: SystemTime, body: &Result<reqwest::Response, E>) {
+    let passed = SystemTime::now().duration_since(start).unwrap();
+    log_write(&format!("waxpeer_api | {} | The HTTP request took {:?}.\n", call, passed));
+    metrics::record_request(Market::WaxPeer, passed, metrics::is_success(body));
+    circuit_breaker::record(Market::WaxPeer, group, body);
+}
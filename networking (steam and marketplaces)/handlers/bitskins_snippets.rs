@@ -4,24 +4,60 @@
 // building on top of the API layer to handle price discovery, purchase workflows,
 // and item withdrawal processes.
 
-use super::{api::bitskins_api, steam};
+use super::{api::bitskins_api::{self, BitSkinsApiError}, steam};
 use crate::{
-    data,
+    balances::{Balance, BalanceError, BalanceProvider},
+    bankroll, cache, circuit_breaker::{self, EndpointGroup, MarketUnavailable}, config,
+    currency::Currency,
+    control::{self, CriticalOp},
+    data, dedup,
+    ledger::{self, LedgerEntry, LedgerKind},
+    log_functions,
+    metrics::{self, BuyOutcome},
+    naming, notifications, patterns, price_cache, ticket_bus,
+    price_functions::{self, from_thousandths, to_thousandths},
+    purchase_cooldown,
+    statics, stats, withdrawal_queue,
     structs::{
-        ItemData, ItemSaleStats, ItemStatus, ItemStatusChangeTicket, ItemStatusChanges, Market,
-        Price,
+        BuyOperationsReport, FloatFilter, Item, ItemData, ItemSaleStats, Listing, OrderBookDepth, ItemStatus, ItemStatusChangeTicket, ItemStatusChanges, Market,
+        Price, SteamApp, WearTier,
     },
 };
-use chrono::{Duration, Local, NaiveDate};
-use serde::Deserialize;
+use chrono::{Local, NaiveDate};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use tokio::time::sleep;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// Maximum amount of listings the buy/many endpoint accepts in one request
+const MAX_BATCH_BUY: usize = 10;
+
+// The difference between the listed and the charged price (in dollars) that gets logged as a warning
+const MAX_CHARGE_MISMATCH: f32 = 0.03;
+
+// The file the resolved skin ids are persisted to, the ids never change
+const SKIN_IDS_PATH: &str = "bitskins_skin_ids.json";
+
+// The skin ids keyed by the market hash name, loaded from the file on the first use
+static SKIN_IDS: Mutex<Option<HashMap<String, i64>>> = Mutex::new(None);
+
+// The buys (single and batch) that are being sent, and a counter bumped when one starts or ends
+// A balance is only compared with another one if no buy started or ended between the two
+static BUYS_IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+static BUY_EVENTS: AtomicU64 = AtomicU64::new(0);
+
+// The last balance that was fetched while no buy was in flight, with the buy events counter at that time
+static QUIET_BALANCE: Mutex<Option<(f32, u64)>> = Mutex::new(None);
 
 /// BitSkins inventory item structure for parsing API responses
 #[allow(dead_code)]
 #[derive(Deserialize, Clone, Debug)]
 struct InventoryEntryResult {
     id: String,
+    // Required, an item without it would be withdrawn and ticketed under an empty asset id
+    asset_id: String,
     tradehold: i32,
 }
 
@@ -42,66 +78,414 @@ struct ItemEntryResult {
     price: i64,
     name: String,
     tradehold: i64,
+    #[serde(default)]
+    float_value: Option<f64>,
+    #[serde(default)]
+    paint_seed: Option<u32>,
 }
 
-/// Structure for parsing price history statistics
-#[allow(dead_code)]
-#[derive(Deserialize, Clone, Debug)]
-struct ItemStatResult {
-    date: String,
-    price_min: i64,
-    counter: i64,
+/// Structure for parsing price history statistics, one entry is a day
+/// Public so the backtest can replay recorded days through the same stats calculation
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ItemStatResult {
+    pub date: String,
+    pub price_min: i64,
+    pub counter: i64,
+}
+
+/// The errors of a BitSkins purchase
+#[derive(Debug, Clone)]
+pub enum BuyError {
+    /// The best listing is more expensive than the price the buy decision was made with
+    PriceMoved {
+        decision_price_w_comm: f32,
+        current_price_w_comm: f32,
+        delta_perc: f32,
+    },
+    /// The api key expired, the refresh callback was called
+    AuthExpired,
+    /// The item is already held as many times as its max count allows, nothing was sent
+    CapReached {
+        held: i16,
+        max_count: i16,
+    },
+    /// The item was bought too recently, its purchase cooldown hasn't passed
+    OnCooldown,
+    /// The circuit of BitSkins is open, nothing was sent
+    MarketUnavailable(MarketUnavailable),
+    Failed(String),
 }
 
-/// Helper function to determine if a date is within the last 7 days
-fn in_the_week(date: &str) -> bool {
-    // Parse the input date string
-    let input_date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+impl From<String> for BuyError {
+    fn from(err: String) -> Self {
+        BuyError::Failed(err)
+    }
+}
 
-    // Get the current date
-    #[allow(deprecated)]
-    let today = Local::today().naive_local();
+impl From<MarketUnavailable> for BuyError {
+    fn from(err: MarketUnavailable) -> Self {
+        BuyError::MarketUnavailable(err)
+    }
+}
 
-    // Calculate the date 7 days ago
-    let seven_days_ago = today - Duration::days(7);
+impl std::fmt::Display for BuyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuyError::PriceMoved { decision_price_w_comm, current_price_w_comm, delta_perc } => write!(
+                f,
+                "Price moved from {} to {} ({:+.2}%) since the buy decision.",
+                decision_price_w_comm, current_price_w_comm, delta_perc
+            ),
+            BuyError::AuthExpired => write!(f, "The BitSkins api key expired."),
+            BuyError::CapReached { held, max_count } => write!(f, "The item is held {} times, the max count is {}.", held, max_count),
+            BuyError::OnCooldown => write!(f, "The item was bought recently, its purchase cooldown hasn't passed."),
+            BuyError::MarketUnavailable(err) => write!(f, "{}", err),
+            BuyError::Failed(err) => write!(f, "{}", err),
+        }
+    }
+}
 
-    // Check if the input date is more recent than 7 days ago
-    input_date > seven_days_ago
+/// Passes an expired api key to the refresh callback and the notifications
+fn report_auth_error(e: &BitSkinsApiError) {
+    if let BitSkinsApiError::AuthExpired = e {
+        notifications::notify_auth_error("bitskins.rs", "The BitSkins api key expired.");
+        statics::on_bitskins_auth_expired();
+    }
 }
 
-/// Retrieves current market prices for a specific CS item with trade hold filtering
+/// The BitSkins account balance
+pub struct BitSkinsBalance;
+
+impl BalanceProvider for BitSkinsBalance {
+    fn market(&self) -> Market {
+        Market::BitSkins
+    }
+
+    async fn get_balance(&self) -> Result<Balance, BalanceError> {
+        Ok(get_account_balance().await?)
+    }
+}
+
+/// Returns the spendable account balance in dollars
+pub async fn get_balance() -> Result<f32, String> {
+    get_account_balance().await.map(|balance| balance.available)
+}
+
+/// Returns the account balance with the funds that are still locked
 ///
-/// - Identifies lowest prices based on trade hold duration
-/// - Calculates buy/sell prices with marketplace commissions
-/// - Handles special item categories
-pub async fn get_item_price(
-    market_hash_name: String,
-    sale_stats_current: Option<Option<ItemSaleStats>>,
-) -> Result<Price, String> {
-    // Send the API request to search for the item
-    let res = bitskins_api::get_item_price(market_hash_name.to_string(), 7)
+/// The balance is kept as the balance before the next buy if no buy was in flight while it was fetched
+pub async fn get_account_balance() -> Result<Balance, String> {
+    circuit_breaker::check(Market::BitSkins, EndpointGroup::Account)?;
+    let quiet = BUYS_IN_FLIGHT.load(Ordering::SeqCst) == 0;
+    let events = BUY_EVENTS.load(Ordering::SeqCst);
+    let res = bitskins_api::get_balance()
         .await
-        .map_err(|e| format!(
-            "bitskins.rs | get_item_price(market_hash_name={}, sale_stats_current={:?}) | Error occured when sending the api request. E: {:?}",
-            market_hash_name, sale_stats_current, e
-        ))?;
+        .map_err(|e| {
+            report_auth_error(&e);
+            format!("bitskins.rs | get_account_balance() | Error occured when sending the api request. E: {:?}", e)
+        })?;
 
     let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!("bitskins.rs | get_account_balance() | Error occured when parsing the api request. E: {:?}", e))?;
+
+    let balance = parse_balance(&parsed_data)?;
+    if quiet && BUY_EVENTS.load(Ordering::SeqCst) == events {
+        *QUIET_BALANCE.lock().unwrap() = Some((balance.available, events));
+    }
+    Ok(balance)
+}
+
+/// Parses the answer of the balance endpoint
+///
+/// - The amounts are in thousandths of a dollar
+/// - balance_pending is only sent when a sale or withdrawal still holds funds
+pub fn parse_balance(parsed_data: &Value) -> Result<Balance, String> {
+    let available = parsed_data["balance"]
+        .as_i64()
+        .map(from_thousandths)
+        .ok_or(format!("bitskins.rs | parse_balance() | Error occured, the balance is missing. Parsed data: {:?}", parsed_data))?;
+
+    Ok(Balance {
+        available,
+        pending: parsed_data["balance_pending"].as_i64().map(from_thousandths).unwrap_or(0.0),
+        currency: Currency::USD,
+    })
+}
+
+/// A buy that is being sent, the balance can't be compared while it is in flight
+struct BuyInFlight {
+    // The balance fetched right before the buy with no other buy in flight, None if there was none
+    balance_before: Option<f32>,
+    // The buy events counter after this buy started
+    events: u64,
+}
+
+impl BuyInFlight {
+    fn start() -> BuyInFlight {
+        let alone = BUYS_IN_FLIGHT.fetch_add(1, Ordering::SeqCst) == 0;
+        let events = BUY_EVENTS.fetch_add(1, Ordering::SeqCst);
+        let balance_before = match *QUIET_BALANCE.lock().unwrap() {
+            Some((balance, balance_events)) if alone && balance_events == events => Some(balance),
+            _ => None,
+        };
+        BuyInFlight { balance_before, events: events + 1 }
+    }
+
+    /// If no other buy started or ended since this one started
+    fn is_alone(&self) -> bool {
+        BUY_EVENTS.load(Ordering::SeqCst) == self.events
+    }
+}
+
+impl Drop for BuyInFlight {
+    fn drop(&mut self) {
+        BUYS_IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+        BUY_EVENTS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Reads the charged price of a buy result entry in dollars, None if BitSkins didn't send it
+fn price_paid(buy_result: &Value) -> Option<f32> {
+    buy_result["price_paid"].as_i64().or(buy_result["price"].as_i64()).map(from_thousandths)
+}
+
+/// Returns the amount a single purchase was charged in dollars
+///
+/// - Uses the price paid in the buy result entry if BitSkins sent it, the balance is not fetched then
+/// - Otherwise compares the balance before the buy with the current balance, but only if no other buy
+///   was in flight since the balance was fetched, the change would have the other purchase in it
+async fn get_charged_price(buy_result: &Value, buy: &BuyInFlight) -> Option<f32> {
+    if let Some(price_paid) = price_paid(buy_result) {
+        return Some(price_paid);
+    }
+
+    let balance_before = buy.balance_before?;
+    if !buy.is_alone() {
+        return None;
+    }
+    let balance_after = get_balance().await.ok()?;
+    if !buy.is_alone() {
+        return None;
+    }
+    Some(((balance_before - balance_after) * 100.0).round() / 100.0)
+}
+
+/// Withdraws a bought item, returns if BitSkins confirmed the withdrawal
+pub async fn withdraw(item_id: &str) -> bool {
+    let _critical = control::begin_critical(CriticalOp::Withdraw, item_id);
+    let res_withdraw = match bitskins_api::withdraw_item(item_id.to_string()).await {
+        Ok(res_withdraw) => res_withdraw,
+        Err(e) => {
+            report_auth_error(&e);
+            return false;
+        }
+    };
+
+    match res_withdraw.json::<serde_json::Value>().await {
+        Ok(parsed_withdraw_data) => parsed_withdraw_data[0]["success"] == Value::Bool(true),
+        Err(_) => false,
+    }
+}
+
+/// Builds the ticket of a successful withdrawal
+fn withdrawal_ticket(asset_id: &str) -> ItemStatusChangeTicket {
+    ItemStatusChangeTicket::new(asset_id, ItemStatusChanges::Withdrawal)
+}
+
+/// Retries the queued withdrawals whose backoff has passed
+///
+/// - A successful retry removes the item from the queue, pushes a `Withdrawal` ticket and adds the asset id to the report
+/// - A failed retry is added to the failures of the report
+/// - After `MAX_WITHDRAW_ATTEMPTS` failed retries the item is marked as failed in the queue and a notification is sent,
+///   it is no longer retried until it is requeued by hand
+async fn retry_pending_withdrawals(report: &mut BuyOperationsReport) -> Result<(), String> {
+    for pending in withdrawal_queue::get_due()? {
+        if control::shutdown_token().is_cancelled() {
+            break;
+        }
+
+        if withdraw(&pending.item_id).await {
+            withdrawal_queue::remove(&pending.item_id)?;
+            let ticket = withdrawal_ticket(&pending.asset_id);
+            ledger::record(
+                LedgerEntry::new(LedgerKind::Withdrawal, Market::BitSkins, &pending.market_hash_name, &pending.asset_id, 0.0, 0.0)
+                    .with_ticket(&ticket),
+            );
+            ticket_bus::push(ticket);
+            report.withdrawn.push(pending.asset_id.clone());
+            continue;
+        }
+
+        let attempts = withdrawal_queue::record_failure(&pending.item_id)?;
+        report.failures.push((
+            pending.item_id.clone(),
+            format!("The queued withdrawal failed, {} failed retries.", attempts),
+        ));
+        if attempts == withdrawal_queue::MAX_WITHDRAW_ATTEMPTS {
+            notifications::notify_withdrawal_failure(
+                &pending.market_hash_name,
+                Market::BitSkins,
+                &format!("The withdrawal of item id {} failed {} times, it should be checked by hand.", pending.item_id, attempts),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Waits for the trade offer of a started withdrawal, returns the trade offer to accept
+///
+/// - An offer that doesn't arrive in `trade_offer_poll_timeout_secs` is left for the next check
+/// - Only single item withdrawals are returned, the other offers are skipped
+/// - The error is the (trade offer id, error) of an offer that couldn't be checked
+async fn withdrawal_offer(asset_id: String) -> Result<Option<String>, (String, String)> {
+    let timeout_secs = config::get_bot_config().trade_offer_poll_timeout_secs;
+    let trade_offer_id = match steam::poll_for_trade_offer(&asset_id, timeout_secs).await {
+        Ok(trade_offer_id) => trade_offer_id,
+        Err(err_str) => {
+            log_functions::log_structured("Warning", &err_str);
+            return Ok(None);
+        }
+    };
+
+    let offers = match steam::get_active_offers().await {
+        Ok(offers) => offers,
+        Err(err_str) => return Err((trade_offer_id, err_str)),
+    };
+    if !offers.iter().any(|offer| offer.trade_offer_id == trade_offer_id && steam::is_single_item_withdrawal(offer)) {
+        log_functions::log_structured(
+            "Warning",
+            &format!("bitskins.rs | withdrawal_offer(asset_id={}) | Skipped the trade offer {}, it is not a single item withdrawal.", asset_id, trade_offer_id),
+        );
+        return Ok(None);
+    }
+
+    Ok(Some(trade_offer_id))
+}
+
+/// Accepts a withdrawal trade offer and rebinds the received items to their new asset ids
+///
+/// - Every received item pushes an `AssetIdRebound` ticket from the asset id of the offer (the one the purchase is tracked with)
+///   to the asset id it got in our inventory, multi item trades rebind every item
+async fn accept_and_rebind(trade_offer_id: String) -> Result<(), String> {
+    let received = steam::accept_trade_offer_get_asset_ids(trade_offer_id).await?;
+    for (old, new) in received.into_iter().filter(|(old, new)| old != new) {
+        ticket_bus::push(ItemStatusChangeTicket::new(old.clone(), ItemStatusChanges::AssetIdRebound { old, new }));
+    }
+    Ok(())
+}
+
+/// Checks if the float of the listing is in the range of the filter
+fn passes_float_filter(item: &ItemEntryResult, float_filter: &Option<FloatFilter>) -> bool {
+    match (float_filter, item.float_value) {
+        (Some(float_filter), Some(float_value)) => float_filter.contains(float_value),
+        _ => true,
+    }
+}
+
+/// Checks if the date of a sale stats day is within the window
+///
+/// - A date that can't be parsed is logged and excluded instead of failing the stats
+fn in_window(date: &str, days: u32, today: NaiveDate) -> bool {
+    match stats::within_days(date, days, today) {
+        Ok(within) => within,
+        Err(e) => {
+            log_functions::log_structured(
+                "Warning",
+                &format!("bitskins.rs | in_window(date={}, days={}) | Excluded a sale stats day with an unparsable date. E: {:?}", date, days, e),
+            );
+            false
+        }
+    }
+}
+
+/// Searches the listings of an item up to the max trade hold
+///
+/// - Only the listings with the exact name are returned
+/// - Sorted by price, cheapest first
+async fn search_listings(app: SteamApp, market_hash_name: &str, max_trade_hold: i32) -> Result<Vec<ItemEntryResult>, String> {
+    circuit_breaker::check(Market::BitSkins, EndpointGroup::Prices)?;
+
+    // Send the API request to search for the item
+    let res = bitskins_api::get_item_price_for_app(app, market_hash_name.to_string(), max_trade_hold)
         .await
         .map_err(|e| format!(
-            "bitskins.rs | get_item_price(market_hash_name={}, sale_stats_current={:?}) | Error occured when parsing the api request. E: {:?}",
-            market_hash_name, sale_stats_current, e
+            "bitskins.rs | search_listings(market_hash_name={}, max_trade_hold={}) | Error occured when sending the api request. E: {:?}",
+            market_hash_name, max_trade_hold, e
         ))?;
 
-    // Parse search results into structured data
-    let item_data: Vec<ItemEntryResult> = serde_json::from_value(parsed_data["list"].clone())
+    let body = res.text()
+        .await
         .map_err(|e| format!(
-            "bitskins.rs | get_item_price(market_hash_name={}, sale_stats_current={:?}) | Error occured when parsing the api request to data structre. E: {:?}",
-            market_hash_name, sale_stats_current, e
+            "bitskins.rs | search_listings(market_hash_name={}, max_trade_hold={}) | Error occured when reading the api request. E: {:?}",
+            market_hash_name, max_trade_hold, e
         ))?;
 
+    // Parse search results into structured data, BitSkins answers with a message instead of the list during maintenance
+    let item_data: Vec<ItemEntryResult> = match serde_json::from_str::<Value>(&body).and_then(|parsed_data| serde_json::from_value(parsed_data["list"].clone())) {
+        Ok(item_data) => item_data,
+        Err(e) => {
+            if circuit_breaker::is_maintenance_body(&body) {
+                circuit_breaker::record_maintenance(Market::BitSkins, EndpointGroup::Prices, None);
+            }
+            return Err(format!(
+                "bitskins.rs | search_listings(market_hash_name={}, max_trade_hold={}) | Error occured when parsing the api request to data structre. E: {:?}. Body: {:?}",
+                market_hash_name, max_trade_hold, e, body
+            ));
+        }
+    };
+
+    let mut listings: Vec<ItemEntryResult> = item_data
+        .into_iter()
+        .filter(|item| naming::is_same_item(&item.name, market_hash_name))
+        .collect();
+    listings.sort_by_key(|item| item.price);
+
+    Ok(listings)
+}
+
+/// Retrieves the listings of an item up to the max trade hold
+///
+/// - Only the listings with the exact name are returned
+/// - Sorted by price, cheapest first
+pub async fn get_item_listings(market_hash_name: &str, max_trade_hold: i32) -> Result<Vec<Listing>, String> {
+    get_item_listings_for_app(SteamApp::CS, market_hash_name, max_trade_hold).await
+}
+
+/// Retrieves the listings of an item of the given Steam app up to the max trade hold
+pub async fn get_item_listings_for_app(app: SteamApp, market_hash_name: &str, max_trade_hold: i32) -> Result<Vec<Listing>, String> {
+    Ok(search_listings(app, market_hash_name, max_trade_hold)
+        .await?
+        .into_iter()
+        .map(|item| Listing {
+            id: item.id,
+            asset_id: item.asset_id,
+            price: from_thousandths(item.price),
+            trade_hold_days: item.tradehold,
+        })
+        .collect())
+}
+
+/// Retrieves current market prices for a specific CS item with trade hold filtering
+///
+/// - Identifies lowest prices based on trade hold duration
+/// - Calculates buy/sell prices with marketplace commissions
+/// - Handles special item categories
+/// - Reuses a cached price while it is still fresh
+pub async fn get_item_price(
+    market_hash_name: String,
+    sale_stats_current: Option<Option<ItemSaleStats>>,
+) -> Result<Price, String> {
+    // Skip the request if the price was fetched recently
+    if let Some(cached_price) = cache::get_cached_price(&Market::BitSkins, &market_hash_name) {
+        return Ok(cached_price);
+    }
+
+    let listings = get_item_listings(&market_hash_name, 7).await?;
+
     // Ensure we found matching items
-    if item_data.is_empty() {
+    if listings.is_empty() {
         return Err(format!(
             "bitskins.rs | get_item_price(market_hash_name={}, sale_stats_current={:?}) | Error occured while the returned item price data vector is empty.",
             market_hash_name, sale_stats_current
@@ -114,29 +498,30 @@ pub async fn get_item_price(
     let mut price_4 = 0.0;
     let mut price_7 = 0.0;
 
-    for item in item_data.iter() {
-        // Check if the name matches exactly
-        if item.name == market_hash_name && price_now.is_none() {
-            // Get the price and categorize by trade hold duration
-            let price = item.price as f32 / 1000.0;
-            if item.tradehold > 4 {
+    for listing in listings.iter() {
+        if price_now.is_some() {
+            break;
+        }
+
+        // Get the price and categorize by trade hold duration
+        let price = listing.price;
+        if listing.trade_hold_days > 4 {
+            price_7 = price;
+        } else if listing.trade_hold_days > 2 {
+            price_4 = price;
+        } else if listing.trade_hold_days >= 1 {
+            price_2 = price;
+        } else {
+            price_now = Some(price);
+            // Fill in missing price categories with the current price
+            if price_7 == 0.0 {
                 price_7 = price;
-            } else if item.tradehold > 2 {
+            }
+            if price_4 == 0.0 {
                 price_4 = price;
-            } else if item.tradehold >= 1 {
+            }
+            if price_2 == 0.0 {
                 price_2 = price;
-            } else {
-                price_now = Some(price);
-                // Fill in missing price categories with the current price
-                if price_7 == 0.0 {
-                    price_7 = price;
-                }
-                if price_4 == 0.0 {
-                    price_4 = price;
-                }
-                if price_2 == 0.0 {
-                    price_2 = price;
-                }
             }
         }
     }
@@ -144,8 +529,8 @@ pub async fn get_item_price(
     // Ensure we found a current price
     if price_now.is_none() {
         return Err(format!(
-            "bitskins.rs | get_item_price(market_hash_name={}, sale_stats_current={:?}) | Error occured price could not be fetched. Found item name: {:?}",
-            market_hash_name, sale_stats_current, item_data[0].name
+            "bitskins.rs | get_item_price(market_hash_name={}, sale_stats_current={:?}) | Error occured price could not be fetched, every listing has a trade hold.",
+            market_hash_name, sale_stats_current
         ));
     }
 
@@ -173,7 +558,7 @@ pub async fn get_item_price(
     // Create and return the Price structure with all calculated values
     let res = Price {
         market: Market::BitSkins,
-        commision: 4,
+        commision: comms.1 + comms.2,
         price_buy: price,
         price_buy_trade: (price_7, price_4, price_2),
         price_buy_w_comm,
@@ -181,66 +566,173 @@ pub async fn get_item_price(
         price_buy_trade_w_comm: (price_buy_7_w_comm, price_buy_4_w_comm, price_buy_2_w_comm),
         price_sell: price,
         sale_stats: None,
+        fetched_at_unix: Local::now().timestamp(),
+        volume_24h: None,
+        currency: config::get_bot_config().get_market_currency(&Market::BitSkins),
     };
+
+    cache::cache_price(Market::BitSkins, market_hash_name, res.clone());
     
     Ok(res)
 }
 
+/// Counts the listings near the lowest price of an item
+///
+/// - Uses the 30 cheapest listings, so the depth can't be larger than that
+/// - `depth_perc` is how far above the floor a listing still counts (5.0 = 5%)
+/// - Tells if a position can be exited quickly
+pub async fn get_order_book_depth(market_hash_name: &str, depth_perc: f32) -> Result<OrderBookDepth, String> {
+    let listings = get_item_listings(market_hash_name, 7).await?;
+    let prices: Vec<i64> = listings.iter().map(|listing| to_thousandths(listing.price)).collect();
+
+    let floor = *prices.iter().min().ok_or(format!(
+        "bitskins.rs | get_order_book_depth(market_hash_name={}, depth_perc={}) | Error occured while there are no listings of the item.",
+        market_hash_name, depth_perc
+    ))?;
+    let depth_limit = (floor as f32 * (1.0 + depth_perc / 100.0)) as i64;
+
+    let within_depth: Vec<i64> = prices.iter().copied().filter(|price| *price <= depth_limit).collect();
+
+    Ok(OrderBookDepth {
+        listings_at_floor: prices.iter().filter(|price| **price == floor).count() as u32,
+        listings_within_depth: within_depth.len() as u32,
+        price_floor: from_thousandths(floor),
+        price_at_depth: from_thousandths(*within_depth.iter().max().unwrap_or(&floor)),
+    })
+}
+
 /// Retrieves historical sales statistics for an item
 ///
-/// - Calculates weekly and monthly sales volume
+/// - The skin id of the item is resolved with get_skin_id
+/// - Calculates the sales volume of the short and the long window (7 and 30 days by default)
+/// - Days with a malformed date are logged and left out
 /// - Determines price trends
 /// - Computes weighted average prices
-pub async fn get_item_sale_stats(skin_id: &str) -> Result<ItemSaleStats, String> {
+pub async fn get_item_sale_stats(market_hash_name: &str) -> Result<ItemSaleStats, String> {
+    circuit_breaker::check(Market::BitSkins, EndpointGroup::Prices)?;
+    let skin_id = get_skin_id(market_hash_name).await?;
+
     // Retrieve historical sales data
     let res = bitskins_api::get_sale_stats(skin_id.to_string())
         .await
         .map_err(|e| format!(
-            "bitskins.rs | get_item_sale_stats(skin_id={}) | Error occured when sending the api request. E: {:?}",
-            skin_id, e
+            "bitskins.rs | get_item_sale_stats(market_hash_name={}, skin_id={}) | Error occured when sending the api request. E: {:?}",
+            market_hash_name, skin_id, e
         ))?;
 
     let parsed_data: serde_json::Value = res.json()
         .await
         .map_err(|e| format!(
-            "bitskins.rs | get_item_sale_stats(skin_id={}) | Error occured when parsing the api request. E: {:?}",
-            skin_id, e
+            "bitskins.rs | get_item_sale_stats(market_hash_name={}, skin_id={}) | Error occured when parsing the api request. E: {:?}",
+            market_hash_name, skin_id, e
         ))?;
 
     // Parse the historical data into structured format
-    let item_data: Vec<ItemStatResult> = serde_json::from_value(parsed_data.clone())
+    let item_data_raw: Vec<ItemStatResult> = serde_json::from_value(parsed_data.clone())
         .map_err(|e| format!(
-            "bitskins.rs | get_item_sale_stats(skin_id={}) | Error occured when parsing the api request to data structre. E: {:?}.\nParsed Data: {:?}",
-            skin_id, e, parsed_data
+            "bitskins.rs | get_item_sale_stats(market_hash_name={}, skin_id={}) | Error occured when parsing the api request to data structre. E: {:?}.\nParsed Data: {:?}",
+            market_hash_name, skin_id, e, parsed_data
         ))?;
+
+    sale_stats_from_days(item_data_raw, Local::now().date_naive())
+}
+
+/// Returns the BitSkins skin id of the item
+///
+/// - The ids are cached in memory and in SKIN_IDS_PATH, only an unknown name is searched on BitSkins
+/// - A file that can't be written is logged, the id is still returned
+pub async fn get_skin_id(market_hash_name: &str) -> Result<i64, String> {
+    let cached = SKIN_IDS.lock().unwrap().get_or_insert_with(read_skin_ids).get(market_hash_name).copied();
+    if let Some(skin_id) = cached {
+        return Ok(skin_id);
+    }
+
+    let skin_id = bitskins_api::search_skin_id(market_hash_name).await?;
+
+    let mut skin_ids = SKIN_IDS.lock().unwrap();
+    let skin_ids = skin_ids.get_or_insert_with(read_skin_ids);
+    skin_ids.insert(market_hash_name.to_string(), skin_id);
+    if let Err(err_str) = write_skin_ids(skin_ids) {
+        log_functions::log_err(&err_str);
+    }
+
+    Ok(skin_id)
+}
+
+// Reads the persisted skin ids, a missing or broken file starts an empty cache
+fn read_skin_ids() -> HashMap<String, i64> {
+    std::fs::read_to_string(SKIN_IDS_PATH)
+        .ok()
+        .and_then(|skin_ids_str| serde_json::from_str(&skin_ids_str).ok())
+        .unwrap_or_default()
+}
+
+// Writes the skin ids to the file
+fn write_skin_ids(skin_ids: &HashMap<String, i64>) -> Result<(), String> {
+    let skin_ids_str = serde_json::to_string_pretty(skin_ids)
+        .map_err(|e| format!("bitskins.rs | write_skin_ids() | Error occured when serializing the skin ids. E: {:?}", e))?;
+    std::fs::write(SKIN_IDS_PATH, skin_ids_str)
+        .map_err(|e| format!("bitskins.rs | write_skin_ids() | Error occured when writing the file. E: {:?}", e))
+}
+
+/// Calculates the sale stats from the daily price history as if today was the given day
+///
+/// - The days after today are not expected, the caller only passes the days it has seen
+/// - Used by get_item_sale_stats and by the backtest with the recorded days
+pub fn sale_stats_from_days(item_data_raw: Vec<ItemStatResult>, today: NaiveDate) -> Result<ItemSaleStats, String> {
+    // Drop the days with mispriced listings before averaging, by the median and by the Z-score
+    let raw_days = item_data_raw.len();
+    let mad_multiple = config::get_bot_config().sale_stats_mad_multiple;
+    let item_data = stats::filter_outliers(item_data_raw, |a| a.price_min as f32, mad_multiple);
+    let outlier_std_devs = config::get_bot_config().sale_stats_outlier_std_devs;
+    let item_data = stats::filter_outliers_z(item_data, |a| a.price_min as f32, outlier_std_devs);
+    let outliers_removed = (raw_days - item_data.len()) as u32;
     
-    // Filter data for weekly analysis
+    // Split the data into the short (weekly) and the long (monthly) window
+    let short_window_days = config::get_bot_config().sale_stats_short_window_days;
+    let long_window_days = config::get_bot_config().sale_stats_long_window_days;
+    let mut item_data = item_data;
+    item_data.retain(|a| in_window(&a.date, long_window_days, today));
     let mut weekly_data = item_data.clone();
-    weekly_data.retain(|a| in_the_week(&a.date));
+    weekly_data.retain(|a| in_window(&a.date, short_window_days, today));
 
     // Calculate sales metrics
     let weekly_sales_count: f32 = weekly_data.iter().map(|a| a.counter as f32).sum::<f32>();
     let monthly_sales_count: f32 = item_data.iter().map(|a| a.counter as f32).sum::<f32>();
     
-    // Calculate weighted average prices (price × quantity)
-    let weekly_avg_price: f32 = if !weekly_data.is_empty() {
+    // Calculate the volume weighted average price (price × quantity)
+    let weekly_vwap: f32 = if !weekly_data.is_empty() {
         weekly_data
             .iter()
-            .map(|a| (a.price_min as f32 / 1000.0) * a.counter as f32)
+            .map(|a| from_thousandths(a.price_min) * a.counter as f32)
             .sum::<f32>()
             / weekly_sales_count
     } else {
         0.0
     };
+
+    // Calculate the unweighted average and the volatility of the daily prices
+    let weekly_daily_prices: Vec<f32> = weekly_data.iter().map(|a| from_thousandths(a.price_min)).collect();
+    let weekly_simple_avg: f32 = if !weekly_daily_prices.is_empty() {
+        weekly_daily_prices.iter().sum::<f32>() / weekly_daily_prices.len() as f32
+    } else {
+        0.0
+    };
+    let price_volatility_std_dev = stats::std_dev(&weekly_daily_prices);
     
     // Apply commission to get effective sell price
-    let weekly_avg_price_w_comm = (weekly_avg_price * 0.88 * 100.0).ceil() / 100.0;
+    let comms = data::get_market_commisions(Market::BitSkins, "")
+        .map_err(|e| format!(
+            "bitskins.rs | sale_stats_from_days(today={}) | Error occured when trying to get the commisions of the market. E: {:?}",
+            today, e
+        ))?;
+    let weekly_vwap_w_comm = (weekly_vwap * (1.0 - ((comms.1 + comms.2) as f32 / 100.0)) * 100.0).ceil() / 100.0;
     
     // Calculate monthly average for trend analysis
     let monthly_avg_price = if !item_data.is_empty() {
         item_data
             .iter()
-            .map(|a| (a.price_min as f32 / 1000.0) * a.counter as f32)
+            .map(|a| from_thousandths(a.price_min) * a.counter as f32)
             .sum::<f32>()
             / monthly_sales_count
     } else {
@@ -249,21 +741,33 @@ pub async fn get_item_sale_stats(skin_id: &str) -> Result<ItemSaleStats, String>
 
     // Calculate price trend (percentage change week over month)
     let one_week_price_diff_perc = if monthly_avg_price != 0.0 {
-        ((weekly_avg_price / monthly_avg_price) - 1.0) * 100.0
+        ((weekly_vwap / monthly_avg_price) - 1.0) * 100.0
     } else {
         0.0
     };
 
+    // Calculate the spread of the daily prices
+    let daily_prices: Vec<f32> = item_data.iter().map(|a| from_thousandths(a.price_min)).collect();
+    let median_price = stats::median(&daily_prices);
+    let price_std_dev = stats::std_dev(&daily_prices);
+
     // Create the sales statistics structure
     let res = ItemSaleStats {
         name: "".to_string(),
-        weekly_avg_price: weekly_avg_price as f32,
-        weekly_avg_price_w_comm: weekly_avg_price_w_comm as f32,
+        short_window_days,
+        long_window_days,
+        weekly_vwap: weekly_vwap as f32,
+        weekly_vwap_w_comm: weekly_vwap_w_comm as f32,
+        weekly_simple_avg,
+        price_volatility_std_dev,
         monthly_avg_price: monthly_avg_price as f32,
         weekly_sale_count: weekly_sales_count as i32,
         monthly_sale_count: monthly_sales_count as i32,
         weekly_price_change: one_week_price_diff_perc as f32,
         projected_price_next_week: 0.0,
+        median_price,
+        price_std_dev,
+        outliers_removed,
     };
 
     Ok(res)
@@ -274,13 +778,38 @@ pub async fn get_item_sale_stats(skin_id: &str) -> Result<ItemSaleStats, String>
 /// - Finds the lowest priced matching item within constraints
 /// - Executes the purchase transaction
 /// - Initiates withdrawal to Steam inventory
+/// - In dry run mode only logs the purchase and returns it as if it succeeded
+/// - Aborts with `BuyError::PriceMoved` if the best listing got more expensive than
+///   the price (with commission) the buy decision was made with
+/// - Skips the listings whose float is outside the float filter, listings without a float are not filtered
+/// - Listings with a high value pattern can cost up to the price times their pattern value multiplier
+/// - A listing whose float is in a worse wear tier than the name gets a lower max price
+/// - Returns the listed price and the amount that was actually charged, read from the buy response
+///   or from the balance change when the response doesn't have it and no other buy was in flight
+/// - Skips the withdrawal if the shutdown started after the purchase, the ticket is still recorded
 pub async fn buy_item(
     market_hash_name: String,
     price: f32,
     trade_hold: i32,
-) -> Result<(ItemStatusChangeTicket, (String, ItemData), f32), String> {
+    decision_price_w_comm: f32,
+    float_filter: Option<FloatFilter>,
+) -> Result<(ItemStatusChangeTicket, (String, ItemData), f32, f32), BuyError> {
+    buy_item_for_app(SteamApp::CS, market_hash_name, price, trade_hold, decision_price_w_comm, float_filter).await
+}
+
+/// Executes a buy operation for an item of the given Steam app on BitSkins, see `buy_item`
+pub async fn buy_item_for_app(
+    app: SteamApp,
+    market_hash_name: String,
+    price: f32,
+    trade_hold: i32,
+    decision_price_w_comm: f32,
+    float_filter: Option<FloatFilter>,
+) -> Result<(ItemStatusChangeTicket, (String, ItemData), f32, f32), BuyError> {
+    circuit_breaker::check(Market::BitSkins, EndpointGroup::Trading)?;
+
     // Search for matching items within price range and trade hold constraints
-    let res = bitskins_api::get_item_price(market_hash_name.to_string(), trade_hold)
+    let res = bitskins_api::get_item_price_for_app(app, market_hash_name.to_string(), trade_hold)
         .await
         .map_err(|e| format!(
             "bitskins.rs | buy_item(market_hash_name={}, price={:?}) | Error occured when sending the get_item_price api request. E: {:?}",
@@ -303,69 +832,160 @@ pub async fn buy_item(
 
     // Ensure we found matching items
     if item_data.is_empty() {
-        return Err(format!(
+        return Err(BuyError::Failed(format!(
             "bitskins.rs | buy_item(market_hash_name={}, price={:?}) | Error occured while the returned item price data vector is empty.",
             market_hash_name, price
-        ));
+        )));
+    }
+
+    // Re-verify the price right before buying, the listing the decision was based on might be gone
+    if let Some(best_item) = item_data.iter().find(|item| naming::is_same_item(&item.name, &market_hash_name) && passes_float_filter(item, &float_filter)) {
+        let comms = data::get_market_commisions(Market::BitSkins, "")
+            .map_err(|e| format!(
+                "bitskins.rs | buy_item(market_hash_name={}, price={:?}) | Error occured when trying to get the commisions of the market. E: {:?}",
+                market_hash_name, price, e
+            ))?;
+
+        let current_price = from_thousandths(best_item.price);
+        let current_price_w_comm = ((current_price / ((100 - comms.0) as f32 / 100.0)) * 100.0).ceil() / 100.0;
+        let delta_perc = ((current_price_w_comm / decision_price_w_comm) - 1.0) * 100.0;
+
+        if delta_perc > config::get_bot_config().max_price_slippage_perc {
+            return Err(BuyError::PriceMoved {
+                decision_price_w_comm,
+                current_price_w_comm,
+                delta_perc,
+            });
+        }
     }
 
     // Try to find and purchase an item within our constraints
     for item in item_data.iter() {
-        // Check for name match and also price match
-        let max_buy_price: i64 = (price * 1000.0) as i64;
-        if item.name == market_hash_name && item.price < max_buy_price {
-            // Execute purchase transaction
-            let res_buy = bitskins_api::buy_item(item.id.clone(), item.price)
-                .await
+        // Check for name match and also price match, a high value pattern is worth more than the base price
+        let effective_price = price * patterns::value_multiplier(&market_hash_name, item.paint_seed);
+        // A listing whose float is in a worse wear tier than the name is worth less
+        let effective_price = price_functions::wear_adjusted_max_price(effective_price, &market_hash_name, item.float_value);
+        let max_buy_price: i64 = (effective_price * 1000.0) as i64;
+        if naming::is_same_item(&item.name, &market_hash_name) && item.price < max_buy_price && passes_float_filter(item, &float_filter) {
+            // Simulate the purchase so the strategy can be evaluated without spending money
+            if config::get_bot_config().dry_run {
+                log_functions::log_dry_run(&format!(
+                    "Would buy {} at {} on {:?}",
+                    market_hash_name, from_thousandths(item.price), Market::BitSkins
+                ));
+                let (ticket, bought_item, buy_price) = bought_item_result(&market_hash_name, item);
+                return Ok((ticket, bought_item, buy_price, buy_price));
+            }
+
+            // The shutdown waits for the buy to finish so the bought item is always recorded
+            let _critical = control::begin_critical(CriticalOp::Buy, &market_hash_name);
+
+            // A buy of the listing that timed out might still go through, don't send a second one
+            dedup::try_acquire_buy(&Market::BitSkins, &item.id)
                 .map_err(|e| format!(
-                    "bitskins.rs | buy_item(market_hash_name={}, price={:?}) | Error occured when sending the buy_item api request. E: {:?}",
+                    "bitskins.rs | buy_item(market_hash_name={}, price={:?}) | Error occured, the listing is already being bought. E: {:?}",
                     market_hash_name, price, e
                 ))?;
 
-            let parsed_buy_data: serde_json::Value = res_buy.json()
-                .await
+            // Reserve the funds so the spend caps can't be exceeded
+            let reservation = bankroll::reserve(Market::BitSkins, from_thousandths(item.price))
                 .map_err(|e| format!(
-                    "bitskins.rs | buy_item(market_hash_name={}, price={:?}) | Error occured when parsing the buy_item api request. E: {:?}",
+                    "bitskins.rs | buy_item(market_hash_name={}, price={:?}) | Error occured, the budget doesn't allow the buy. E: {:?}",
                     market_hash_name, price, e
                 ))?;
 
+            // Keeps the balance from being compared with another one while the buy is in flight
+            let buy_in_flight = BuyInFlight::start();
+
+            // Record how old the price the buy is based on is
+            if let Some(cached_price) = cache::get_cached_price(&Market::BitSkins, &market_hash_name) {
+                metrics::observe_price_to_buy(&Market::BitSkins, Local::now().timestamp() - cached_price.fetched_at_unix);
+            }
+
+            // Execute purchase transaction
+            metrics::record_buy(&Market::BitSkins, BuyOutcome::Attempted);
+            let res_buy = match bitskins_api::buy_item_for_app(app, item.id.clone(), item.price).await {
+                Ok(res_buy) => res_buy,
+                Err(BitSkinsApiError::AuthExpired) => {
+                    let _ = bankroll::release(reservation);
+                    metrics::record_buy(&Market::BitSkins, BuyOutcome::Failed);
+                    report_auth_error(&BitSkinsApiError::AuthExpired);
+                    return Err(BuyError::AuthExpired);
+                }
+                Err(e) => {
+                    let _ = bankroll::release(reservation);
+                    metrics::record_buy(&Market::BitSkins, BuyOutcome::Failed);
+                    return Err(BuyError::Failed(format!(
+                        "bitskins.rs | buy_item(market_hash_name={}, price={:?}) | Error occured when sending the buy_item api request. E: {:?}",
+                        market_hash_name, price, e
+                    )));
+                }
+            };
+
+            // The request went out, commit the listed price if the answer can't be read since the buy might have happened
+            // The capital is returned by the refund path if the buy didn't happen
+            let parsed_buy_data: serde_json::Value = res_buy.json()
+                .await
+                .map_err(|e| {
+                    let _ = bankroll::commit(reservation, from_thousandths(item.price));
+                    metrics::record_buy(&Market::BitSkins, BuyOutcome::Failed);
+                    format!(
+                        "bitskins.rs | buy_item(market_hash_name={}, price={:?}) | Error occured when parsing the buy_item api request. E: {:?}",
+                        market_hash_name, price, e
+                    )
+                })?;
+
             let success_ = &parsed_buy_data["result"][0]["success"];
 
             if let Value::Bool(success) = success_ {
                 if *success {
-                    // Purchase successful - allow inventory to update
-                    sleep(tokio::time::Duration::from_secs(2)).await;
-
-                    // Create item tracking data
-                    let new_item = ItemData {
-                        asset_id: item.asset_id.clone(),
-                        trade_offer_id: "".to_string(),
-                        instance_id: "".to_string(),
-                        class_id: "".to_string(),
-                        market: Market::Steam,
-                        status: ItemStatus::OnHold,
-                        marketcsgo_item_id: "0".to_string(),
-                        dmarket_item_id: "0".to_string(),
-                        csmoney_item_id: "0".to_string(),
-                        csfloat_offer_id: "0".to_string(),
-                        timestamp_unix: None,
-                    };
-                    
-                    // Create status change ticket for tracking
-                    let ticket = ItemStatusChangeTicket {
-                        csmoney_item_id: "0".to_string(),
-                        marketcsgo_item_id: "0".to_string(),
-                        dmarket_item_id: "0".to_string(),
-                        csfloat_offer_id: "0".to_string(),
-                        change: ItemStatusChanges::BuySuccessBitSkins,
-                        asset_id: item.asset_id.clone(),
-                    };
-                    
-                    // Calculate actual buy price
-                    let buy_price = (item.price as f32 / 10.0).ceil() / 100.0;
+                    let charged_price = get_charged_price(&parsed_buy_data["result"][0], &buy_in_flight)
+                        .await
+                        .unwrap_or(from_thousandths(item.price));
+                    drop(buy_in_flight);
+                    let _ = bankroll::commit(reservation, charged_price);
+                    dedup::release_buy(&Market::BitSkins, &item.id);
+                    metrics::record_buy(&Market::BitSkins, BuyOutcome::Succeeded);
+
+                    // The bought listing was the cheapest one, the cached price is outdated
+                    price_cache::invalidate_after_buy(&Market::BitSkins, &market_hash_name);
+
+                    // Create the item tracking data, the ticket and the actual buy price
+                    let (ticket, (_, new_item), buy_price) = bought_item_result(&market_hash_name, item);
+
+                    // A fee that is more than a few cents off means the profit accounting would drift
+                    if (charged_price - buy_price).abs() > MAX_CHARGE_MISMATCH {
+                        log_functions::log_structured(
+                            "Warning",
+                            &format!(
+                                "bitskins.rs | buy_item(market_hash_name={}) | Charged {} instead of the listed {}. Buy response: {}",
+                                market_hash_name, charged_price, buy_price, parsed_buy_data
+                            ),
+                        );
+                    }
+
+                    ledger::record(
+                        LedgerEntry::new(LedgerKind::Buy, Market::BitSkins, &market_hash_name, &ticket.asset_id, charged_price, (charged_price - buy_price).max(0.0))
+                            .with_ticket(&ticket),
+                    );
+
+                    // Don't start the withdrawal during the shutdown, record the bought item so it is withdrawn after the restart
+                    if control::shutdown_token().is_cancelled() {
+                        ticket_bus::record(&ticket);
+                        log_functions::log_structured(
+                            "Warning",
+                            &format!("Shutdown requested, skipping the withdrawal of {} (asset id {}).", market_hash_name, ticket.asset_id),
+                        );
+                        notifications::notify_buy(&market_hash_name, Market::BitSkins, buy_price);
+                        return Ok((ticket, (market_hash_name, new_item), buy_price, charged_price));
+                    }
 
                     // Initiate withdrawal to Steam inventory
-                    let res_withdraw_ = bitskins_api::withdraw_item(item.id.clone()).await;
+                    let _critical_withdraw = control::begin_critical(CriticalOp::Withdraw, &item.id);
+                    let res_withdraw_ = bitskins_api::withdraw_item_for_app(app, item.id.clone()).await;
+                    if let Err(e) = &res_withdraw_ {
+                        report_auth_error(e);
+                    }
 
                     if let Ok(res_withdraw) = res_withdraw_ {
                         let parsed_withdraw_data_: Result<serde_json::Value, reqwest::Error> = res_withdraw.json().await;
@@ -374,53 +994,351 @@ pub async fn buy_item(
                             if let Value::Bool(success_withdrawal) = success_withdrawal_ {
                                 if *success_withdrawal {
                                     // Withdrawal successful, complete buy operation
-                                    return Ok((ticket, (market_hash_name, new_item), buy_price));
+                                    ledger::record(LedgerEntry::new(LedgerKind::Withdrawal, Market::BitSkins, &market_hash_name, &ticket.asset_id, 0.0, 0.0));
+                                    notifications::notify_buy(&market_hash_name, Market::BitSkins, buy_price);
+                                    return Ok((ticket, (market_hash_name, new_item), buy_price, charged_price));
                                 }
                             }
                         }
                     }
 
-                    // Withdrawal not confirmed but purchase succeeded
-                    println!("Could not withdraw the item: {:?}", market_hash_name);
-                    return Ok((ticket, (market_hash_name, new_item), buy_price));
+                    // Withdrawal not confirmed but purchase succeeded, queue it so check_buy_operations retries it
+                    log_functions::log_structured(
+                        "Warning",
+                        &format!("Could not withdraw {} (item id {}), queued for a retry.", market_hash_name, item.id),
+                    );
+                    if let Err(err_str) = withdrawal_queue::enqueue(&item.id, &item.asset_id, &market_hash_name) {
+                        log_functions::log_err(&err_str);
+                    }
+                    notifications::notify_buy(&market_hash_name, Market::BitSkins, buy_price);
+
+                    let ticket = ItemStatusChangeTicket { change: ItemStatusChanges::WithdrawFailed, ..ticket };
+                    let new_item = ItemData { status: ItemStatus::PendingWithdrawal, ..new_item };
+                    return Ok((ticket, (market_hash_name, new_item), buy_price, charged_price));
                 }
             }
 
             // Purchase API call was unsuccessful
-            return Err(format!(
+            let _ = bankroll::release(reservation);
+            metrics::record_buy(&Market::BitSkins, BuyOutcome::Failed);
+            return Err(BuyError::Failed(format!(
                 "bitskins.rs | buy_item(market_hash_name={}, price={:?}) | Error occured, the buy_item api call was not successfull. Parsed buy data: {:?}", 
                 market_hash_name, price, parsed_buy_data
-            ));
+            )));
         }
     }
 
     // No matching items found at the desired price
-    Err(format!(
+    Err(BuyError::Failed(format!(
         "bitskins.rs | buy_item(market_hash_name={}, price={:?}) | Error occured, could not find the given item for the desired price.", 
         market_hash_name, price
-    ))
+    )))
+}
+
+/// Buys several BitSkins listings with batched buy requests
+///
+/// - Sends at most 10 listings per request
+/// - Takes (bitskins item id, asset id, max price) for every listing
+/// - Returns one result per listing so a failed item doesn't lose the successful ones
+pub async fn buy_items_batch(
+    items: &[(String, String, f32)],
+) -> Result<Vec<Result<ItemStatusChangeTicket, String>>, String> {
+    circuit_breaker::check(Market::BitSkins, EndpointGroup::Trading)?;
+    let mut results: Vec<Result<ItemStatusChangeTicket, String>> = Vec::new();
+
+    for chunk_all in items.chunks(MAX_BATCH_BUY) {
+        if config::get_bot_config().dry_run {
+            for (item_id, asset_id, price) in chunk_all {
+                log_functions::log_dry_run(&format!("Would buy {} at {} on {:?}", item_id, price, Market::BitSkins));
+                results.push(Ok(bought_ticket(asset_id)));
+            }
+            continue;
+        }
+
+        // One slot per item so the results stay in the order of the input
+        let mut slots: Vec<Option<Result<ItemStatusChangeTicket, String>>> = vec![None; chunk_all.len()];
+
+        // Reserve the funds of every listing, the ones the budget doesn't allow are not sent
+        let mut chunk: Vec<(usize, bankroll::ReservationId, &(String, String, f32))> = Vec::new();
+        for (slot, item) in chunk_all.iter().enumerate() {
+            if let Err(e) = dedup::try_acquire_buy(&Market::BitSkins, &item.0) {
+                slots[slot] = Some(Err(format!(
+                    "bitskins.rs | buy_items_batch(item_id={}, price={:?}) | Error occured, the listing is already being bought. E: {:?}",
+                    item.0, item.2, e
+                )));
+                continue;
+            }
+            match bankroll::reserve(Market::BitSkins, item.2) {
+                Ok(reservation) => chunk.push((slot, reservation, item)),
+                Err(e) => {
+                    slots[slot] = Some(Err(format!(
+                        "bitskins.rs | buy_items_batch(item_id={}, price={:?}) | Error occured, the budget doesn't allow the buy. E: {:?}",
+                        item.0, item.2, e
+                    )))
+                }
+            }
+        }
+
+        if !chunk.is_empty() {
+            let batch: Vec<(String, i64)> = chunk
+                .iter()
+                .map(|(_, _, (item_id, _, price))| (item_id.clone(), to_thousandths(*price)))
+                .collect();
+
+            // The balance change of a batch has every purchase in it, it is never used for the charged prices
+            let _buy_in_flight = BuyInFlight::start();
+            let res_buy = bitskins_api::buy_items_batch(&batch)
+                .await
+                .map_err(|e| {
+                    report_auth_error(&e);
+                    format!(
+                        "bitskins.rs | buy_items_batch(items={:?}) | Error occured when sending the buy_items_batch api request. E: {:?}",
+                        batch, e
+                    )
+                });
+
+            let parsed_buy_data: Result<serde_json::Value, String> = match res_buy {
+                Ok(res_buy) => res_buy.json().await.map_err(|e| {
+                    // The request went out, the purchases might have happened so the listed prices are committed
+                    for (_, reservation, (_, _, price)) in &chunk {
+                        let _ = bankroll::commit(*reservation, *price);
+                    }
+                    format!(
+                        "bitskins.rs | buy_items_batch(items={:?}) | Error occured when parsing the buy_items_batch api request. E: {:?}",
+                        batch, e
+                    )
+                }),
+                Err(err_str) => {
+                    for (_, reservation, _) in &chunk {
+                        let _ = bankroll::release(*reservation);
+                    }
+                    Err(err_str)
+                }
+            };
+
+            match parsed_buy_data {
+                Ok(parsed_buy_data) => {
+                    for (slot, reservation, (item_id, asset_id, price)) in chunk.iter() {
+                        // Find the result entry of the item by its id, the order of the response is not trusted
+                        let entry = match &parsed_buy_data["result"] {
+                            Value::Array(entries) => entries.iter().find(|entry| entry["id"] == Value::String(item_id.clone())),
+                            _ => None,
+                        };
+                        let entry = match entry {
+                            Some(entry) => entry,
+                            None => {
+                                // The buy might have happened, the listed price is committed like an unreadable response
+                                let _ = bankroll::commit(*reservation, *price);
+                                slots[*slot] = Some(Err(format!(
+                                    "bitskins.rs | buy_items_batch(item_id={}, price={:?}) | Error occured, the response has no result for the item. Parsed buy data: {:?}",
+                                    item_id, price, parsed_buy_data
+                                )));
+                                continue;
+                            }
+                        };
+
+                        if entry["success"] == Value::Bool(true) {
+                            let _ = bankroll::commit(*reservation, price_paid(entry).unwrap_or(*price));
+                            dedup::release_buy(&Market::BitSkins, item_id);
+                            slots[*slot] = Some(Ok(bought_ticket(asset_id)));
+                        } else {
+                            let _ = bankroll::release(*reservation);
+                            slots[*slot] = Some(Err(format!(
+                                "bitskins.rs | buy_items_batch(item_id={}, price={:?}) | Error occured, the item was not bought. Result entry: {:?}",
+                                item_id, price, entry
+                            )));
+                        }
+                    }
+                }
+                Err(err_str) => {
+                    for (slot, _, _) in &chunk {
+                        slots[*slot] = Some(Err(err_str.clone()));
+                    }
+                }
+            }
+        }
+
+        // The bought listings were the cheapest ones, the cached prices of the market are outdated
+        if slots.iter().any(|slot| matches!(slot, Some(Ok(_)))) {
+            cache::invalidate_market(&Market::BitSkins);
+        }
+
+        results.extend(slots.into_iter().flatten());
+    }
+
+    Ok(results)
+}
+
+/// Buys an item for the tracked inventory entry, only if it is below its max count
+///
+/// - The max count is updated from the sales velocity of the item first
+/// - Returns `BuyError::CapReached` without sending anything when `count.total >= max_count`
+/// - Returns `BuyError::OnCooldown` if the item was bought less than its purchase cooldown ago
+/// - A sold item is not counted, so selling frees the capacity again
+pub async fn buy_item_for(
+    item: &mut Item,
+    price: f32,
+    trade_hold: i32,
+    decision_price_w_comm: f32,
+    float_filter: Option<FloatFilter>,
+) -> Result<(ItemStatusChangeTicket, (String, ItemData), f32, f32), BuyError> {
+    price_functions::update_velocity_max_count(item);
+    let max_count = price_functions::effective_max_count(item);
+    if !price_functions::item_has_capacity(item) {
+        log_functions::log_structured(
+            "Info",
+            &format!("Skipped buying {}, it is held {} times and the max count is {}.", item.name, item.count.total, max_count),
+        );
+        return Err(BuyError::CapReached { held: item.count.total, max_count });
+    }
+    if purchase_cooldown::is_on_cooldown(&item.name) {
+        return Err(BuyError::OnCooldown);
+    }
+
+    let res = buy_item(item.name.clone(), price, trade_hold, decision_price_w_comm, float_filter).await?;
+    purchase_cooldown::record_purchase(&item.name);
+    Ok(res)
+}
+
+/// Buys several listings of an item in one buy/many request
+///
+/// - Picks the cheapest listings without a trade hold up to the max unit price
+/// - Some listings can be sniped by other buyers, the ones that were bought are returned with the total spent
+/// - Only fails if no listing could be bought
+/// - The bought items are withdrawn by `check_buy_operations`
+pub async fn buy_items(
+    market_hash_name: String,
+    max_unit_price: f32,
+    quantity: usize,
+) -> Result<(Vec<(ItemStatusChangeTicket, (String, ItemData))>, f32), String> {
+    let max_price = to_thousandths(max_unit_price);
+    let listings: Vec<ItemEntryResult> = search_listings(SteamApp::CS, &market_hash_name, 0)
+        .await?
+        .into_iter()
+        .filter(|item| item.price <= max_price)
+        .take(quantity)
+        .collect();
+
+    if listings.is_empty() {
+        return Err(format!(
+            "bitskins.rs | buy_items(market_hash_name={}, max_unit_price={:?}, quantity={}) | Error occured, could not find the given item for the desired price.",
+            market_hash_name, max_unit_price, quantity
+        ));
+    }
+
+    let batch: Vec<(String, String, f32)> = listings
+        .iter()
+        .map(|item| (item.id.clone(), item.asset_id.clone(), from_thousandths(item.price)))
+        .collect();
+
+    // Simulate the purchase so the strategy can be evaluated without spending money
+    if config::get_bot_config().dry_run {
+        let mut bought: Vec<(ItemStatusChangeTicket, (String, ItemData))> = Vec::new();
+        let mut total_spent = 0.0;
+        for item in listings.iter() {
+            log_functions::log_dry_run(&format!(
+                "Would buy {} at {} on {:?}",
+                market_hash_name, from_thousandths(item.price), Market::BitSkins
+            ));
+            let (ticket, bought_item, buy_price) = bought_item_result(&market_hash_name, item);
+            total_spent += buy_price;
+            bought.push((ticket, bought_item));
+        }
+        return Ok((bought, total_spent));
+    }
+
+    // The shutdown waits for the buy to finish so the bought items are always recorded
+    let _critical = control::begin_critical(CriticalOp::Buy, &market_hash_name);
+    for _ in listings.iter() {
+        metrics::record_buy(&Market::BitSkins, BuyOutcome::Attempted);
+    }
+    let results = buy_items_batch(&batch).await?;
+
+    let mut bought: Vec<(ItemStatusChangeTicket, (String, ItemData))> = Vec::new();
+    let mut total_spent = 0.0;
+    for (item, result) in listings.iter().zip(results.into_iter()) {
+        match result {
+            Ok(_) => {
+                metrics::record_buy(&Market::BitSkins, BuyOutcome::Succeeded);
+                let (ticket, bought_item, buy_price) = bought_item_result(&market_hash_name, item);
+                ledger::record(
+                    LedgerEntry::new(LedgerKind::Buy, Market::BitSkins, &market_hash_name, &ticket.asset_id, buy_price, 0.0).with_ticket(&ticket),
+                );
+                notifications::notify_buy(&market_hash_name, Market::BitSkins, buy_price);
+                total_spent += buy_price;
+                bought.push((ticket, bought_item));
+            }
+            Err(err_str) => {
+                metrics::record_buy(&Market::BitSkins, BuyOutcome::Failed);
+                log_functions::log_structured("Warning", &format!("A listing of {} was not bought. E: {}", market_hash_name, err_str));
+            }
+        }
+    }
+
+    if bought.is_empty() {
+        return Err(format!(
+            "bitskins.rs | buy_items(market_hash_name={}, max_unit_price={:?}, quantity={}) | Error occured, none of the {} listings were bought.",
+            market_hash_name, max_unit_price, quantity, listings.len()
+        ));
+    }
+
+    Ok((bought, total_spent))
+}
+
+/// Builds the ticket of a bought listing
+fn bought_ticket(asset_id: &str) -> ItemStatusChangeTicket {
+    ItemStatusChangeTicket::new(asset_id, ItemStatusChanges::BuySuccessBitSkins)
+}
+
+/// Builds the ticket, the tracked item data and the buy price of a bought listing
+fn bought_item_result(
+    market_hash_name: &str,
+    item: &ItemEntryResult,
+) -> (ItemStatusChangeTicket, (String, ItemData), f32) {
+    // Create item tracking data
+    let new_item = ItemData::builder()
+        .asset_id(item.asset_id.clone())
+        .market(Market::Steam)
+        .status(ItemStatus::OnHold)
+        .pattern_index(item.paint_seed)
+        .exterior(WearTier::from_market_hash_name(market_hash_name))
+        .build();
+
+    // Create status change ticket for tracking
+    let ticket = bought_ticket(&item.asset_id);
+
+    // Calculate actual buy price
+    let buy_price = (item.price as f32 / 10.0).ceil() / 100.0;
+
+    (ticket, (market_hash_name.to_string(), new_item), buy_price)
 }
 
 /// Checks and processes pending buy operations and trade offers
 ///
 /// - Identifies items ready for withdrawal from BitSkins
-/// - Processes active Steam trade offers
+/// - Processes active Steam trade offers, only the ones Steam shows as a single item withdrawal are accepted
+/// - The accepted items are rebound to the asset ids they got in the Steam inventory with `AssetIdRebound` tickets
 /// - Ensures withdrawals complete successfully
-pub async fn check_buy_operations() -> Result<(), String> {
+/// - Returns the withdrawn asset ids, the accepted trade offers and the failed operations,
+///   a failed withdrawal or accept doesn't stop the others
+pub async fn check_buy_operations() -> Result<BuyOperationsReport, String> {
+    circuit_breaker::check(Market::BitSkins, EndpointGroup::Trading)?;
+    let mut report = BuyOperationsReport::default();
+
     // Retrieve current inventory and active trades data
     let res_inv = bitskins_api::get_buy_inventory()
         .await
-        .map_err(|e| format!(
-            "bitskins.rs | check_buy_operations() | Error occured when sending the inventory api request. E: {:?}", 
-            e
-        ))?;
+        .map_err(|e| {
+            report_auth_error(&e);
+            format!("bitskins.rs | check_buy_operations() | Error occured when sending the inventory api request. E: {:?}", e)
+        })?;
     
     let res_trades = bitskins_api::get_active_trades()
         .await
-        .map_err(|e| format!(
-            "bitskins.rs | check_buy_operations() | Error occured when sending the active_trades api request. E: {:?}", 
-            e
-        ))?;
+        .map_err(|e| {
+            report_auth_error(&e);
+            format!("bitskins.rs | check_buy_operations() | Error occured when sending the active_trades api request. E: {:?}", e)
+        })?;
 
     let parsed_inv_data: serde_json::Value = res_inv.json()
         .await
@@ -437,6 +1355,117 @@ pub async fn check_buy_operations() -> Result<(), String> {
         ))?;
 
     // Parse structured data
+    let (inv_data, trades_data) = parse_buy_operations(&parsed_inv_data, &parsed_trades_data)?;
+
+    // Retry the withdrawals that failed before, the queue has the backoff of every item
+    retry_pending_withdrawals(&mut report).await?;
+    let queued = withdrawal_queue::queued_item_ids()?;
+
+    // Cross-check the BitSkins trades with the offers Steam has
+    let offers = if trades_data.is_empty() {
+        Vec::new()
+    } else {
+        steam::get_active_offers().await?
+    };
+
+    process_buy_operations(
+        inv_data,
+        &queued,
+        trades_data,
+        &offers,
+        &mut report,
+        |item: InventoryEntryResult| async move {
+            let withdrawn = withdraw(&item.id).await;
+            if withdrawn {
+                ticket_bus::push(withdrawal_ticket(&item.asset_id));
+            }
+            withdrawn
+        },
+        withdrawal_offer,
+        accept_and_rebind,
+    )
+    .await;
+
+    Ok(report)
+}
+
+/// Withdraws the inventory items and accepts the trade offers of a buy operations check, fills the report
+///
+/// - withdraw_item starts the withdrawal of an item, find_offer returns the checked trade offer of a withdrawn asset id
+///   and accept_offer accepts a trade offer
+/// - The queued item ids are left to retry_pending_withdrawals, the items on trade hold are left for a later check
+/// - A failed withdrawal or accept is added to the failures and doesn't stop the others
+/// - No new withdrawals or accepts are started during the shutdown
+#[allow(clippy::too_many_arguments)]
+async fn process_buy_operations<W, WFut, F, FFut, A, AFut>(
+    inv_data: Vec<InventoryEntryResult>,
+    queued: &[String],
+    trades_data: Vec<ActiveTradesEntryResult>,
+    offers: &[steam::TradeOffer],
+    report: &mut BuyOperationsReport,
+    mut withdraw_item: W,
+    mut find_offer: F,
+    mut accept_offer: A,
+) where
+    W: FnMut(InventoryEntryResult) -> WFut,
+    WFut: Future<Output = bool>,
+    F: FnMut(String) -> FFut,
+    FFut: Future<Output = Result<Option<String>, (String, String)>>,
+    A: FnMut(String) -> AFut,
+    AFut: Future<Output = Result<(), String>>,
+{
+    // Process inventory items with no trade hold
+    for item in inv_data {
+        if control::shutdown_token().is_cancelled() {
+            return;
+        }
+        if queued.contains(&item.id) || item.tradehold != 0 {
+            continue;
+        }
+
+        let (item_id, asset_id) = (item.id.clone(), item.asset_id.clone());
+        if !withdraw_item(item).await {
+            report.failures.push((item_id, "The withdrawal failed.".to_string()));
+            continue;
+        }
+        report.withdrawn.push(asset_id.clone());
+
+        // Accept the trade offer of the withdrawal right away
+        match find_offer(asset_id).await {
+            Ok(Some(trade_offer_id)) => match accept_offer(trade_offer_id.clone()).await {
+                Ok(()) => report.accepted_offers.push(trade_offer_id),
+                Err(err_str) => report.failures.push((trade_offer_id, err_str)),
+            },
+            Ok(None) => {}
+            Err(failure) => report.failures.push(failure),
+        }
+    }
+
+    // Process active trade offers
+    let (to_accept, skipped) = offers_to_accept(trades_data, offers, &report.accepted_offers);
+    for trade_offer_id in skipped {
+        let offer = offers.iter().find(|offer| offer.trade_offer_id == trade_offer_id);
+        log_functions::log_structured(
+            "Warning",
+            &format!(
+                "bitskins.rs | check_buy_operations() | Skipped the trade offer {}, it is not a single item withdrawal. Offer: {:?}",
+                trade_offer_id, offer
+            ),
+        );
+    }
+    for trade_offer_id in to_accept {
+        if control::shutdown_token().is_cancelled() {
+            return;
+        }
+        match accept_offer(trade_offer_id.clone()).await {
+            Ok(()) => report.accepted_offers.push(trade_offer_id),
+            Err(err_str) => report.failures.push((trade_offer_id, err_str)),
+        }
+    }
+}
+
+/// Parses the inventory and the active trades responses of a buy operations check
+fn parse_buy_operations(parsed_inv_data: &Value, parsed_trades_data: &Value) -> Result<(Vec<InventoryEntryResult>, Vec<ActiveTradesEntryResult>), String> {
     let inv_data: Vec<InventoryEntryResult> = serde_json::from_value(parsed_inv_data["list"].clone())
         .map_err(|e| format!(
             "bitskins.rs | check_buy_operations() | Error occured when parsing the inventory api request into the data structre. E: {:?}", 
@@ -449,22 +1478,215 @@ pub async fn check_buy_operations() -> Result<(), String> {
             e
         ))?;
 
-    // Process inventory items with no trade hold
-    for item in inv_data {
-        if item.tradehold == 0 {
-            // Initiate withdrawal for items ready to trade
-            let _ = bitskins_api::withdraw_item(item.id).await;
+    Ok((inv_data, trades_data))
+}
+
+/// Splits the active BitSkins trades into the offers to accept and the skipped ones
+///
+/// - The offers accepted right after their withdrawal are left out
+/// - Only the offers Steam shows as a single item withdrawal are accepted, the rest are skipped
+fn offers_to_accept(trades_data: Vec<ActiveTradesEntryResult>, offers: &[steam::TradeOffer], accepted_offers: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut to_accept = Vec::new();
+    let mut skipped = Vec::new();
+
+    for trade in trades_data {
+        if accepted_offers.contains(&trade.tradeofferid) {
+            continue;
+        }
+        match offers.iter().find(|offer| offer.trade_offer_id == trade.tradeofferid) {
+            Some(offer) if steam::is_single_item_withdrawal(offer) => to_accept.push(trade.tradeofferid),
+            _ => skipped.push(trade.tradeofferid),
         }
     }
 
-    // Process active trade offers
-    for trade in trades_data {
-        // Accept each active trade offer
-        let res = steam::accept_trade_offer(trade.tradeofferid.clone()).await;
-        if let Err(err_str) = res {
-            println!("bitskins.rs | check_buy_operations() | Error occured when accepting the trade offer. E: {:?}", err_str);
+    (to_accept, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use steam::{TradeOffer, TradeOfferState};
+
+    fn offer(trade_offer_id: &str, give: &[&str], receive: &[&str], state: TradeOfferState) -> TradeOffer {
+        TradeOffer {
+            trade_offer_id: trade_offer_id.to_string(),
+            partner: crate::structs::SteamId::from_account_id(1),
+            items_to_give: give.iter().map(|id| id.to_string()).collect(),
+            items_to_receive: receive.iter().map(|id| id.to_string()).collect(),
+            state,
+            is_our_offer: false,
         }
     }
 
-    Ok(())
+    fn trades(ids: &[&str]) -> Vec<ActiveTradesEntryResult> {
+        ids.iter().map(|id| ActiveTradesEntryResult { tradeofferid: id.to_string() }).collect()
+    }
+
+    #[test]
+    fn fixture_responses_are_parsed() {
+        let inv = json!({"list": [
+            {"id": "9001", "asset_id": "101", "tradehold": 0, "name": "AK-47 | Redline (Field-Tested)"},
+            {"id": "9002", "asset_id": "102", "tradehold": 3},
+        ]});
+        let active = json!({"list": [{"tradeofferid": "5001", "state": 2}]});
+
+        let (inv_data, trades_data) = parse_buy_operations(&inv, &active).unwrap();
+
+        assert_eq!(inv_data.len(), 2);
+        assert_eq!((inv_data[0].id.as_str(), inv_data[0].asset_id.as_str(), inv_data[0].tradehold), ("9001", "101", 0));
+        assert_eq!(inv_data[1].tradehold, 3);
+        assert_eq!(trades_data[0].tradeofferid, "5001");
+    }
+
+    #[test]
+    fn an_error_response_is_a_parse_error() {
+        let error = json!({"code": "UNAUTHORIZED", "message": "Invalid api key"});
+
+        let res = parse_buy_operations(&error, &json!({"list": []}));
+        assert!(res.unwrap_err().contains("parsing the inventory api request"));
+        let res = parse_buy_operations(&json!({"list": []}), &error);
+        assert!(res.unwrap_err().contains("parsing the trades api request"));
+    }
+
+    #[test]
+    fn inventory_item_without_an_asset_id_is_a_parse_error() {
+        let inv = json!({"list": [
+            {"id": "9001", "asset_id": "101", "tradehold": 0},
+            {"id": "9002", "tradehold": 0},
+        ]});
+
+        let err = parse_buy_operations(&inv, &json!({"list": []})).unwrap_err();
+        assert!(err.contains("asset_id"), "{}", err);
+    }
+
+    #[test]
+    fn balance_is_read_in_thousandths() {
+        let balance = parse_balance(&json!({"balance": 152340, "balance_pending": 8500})).unwrap();
+        assert!((balance.available - 152.34).abs() < 1e-4);
+        assert!((balance.pending - 8.5).abs() < 1e-4);
+        assert!((balance.total() - 160.84).abs() < 1e-4);
+        assert_eq!(balance.currency, Currency::USD);
+
+        // balance_pending is only sent while a sale or withdrawal holds funds
+        assert_eq!(parse_balance(&json!({"balance": 0})).unwrap().pending, 0.0);
+    }
+
+    #[test]
+    fn balance_without_an_amount_is_an_error() {
+        for body in [json!({"balance_pending": 8500}), json!({"balance": "152340"}), json!({"code": "UNAUTHORIZED"})] {
+            assert!(parse_balance(&body).is_err(), "{}", body);
+        }
+    }
+
+    #[test]
+    fn only_single_item_withdrawals_are_accepted() {
+        let offers = vec![
+            offer("1", &[], &["201"], TradeOfferState::Active),
+            // Takes one of our items
+            offer("2", &["202"], &[], TradeOfferState::Active),
+            // Gives two items
+            offer("3", &[], &["203", "204"], TradeOfferState::Active),
+            // Not active anymore
+            offer("4", &[], &["205"], TradeOfferState::Canceled),
+            // Accepted right after its withdrawal
+            offer("5", &[], &["206"], TradeOfferState::Active),
+        ];
+
+        // 6 is a BitSkins trade Steam doesn't show
+        let (to_accept, skipped) = offers_to_accept(trades(&["1", "2", "3", "4", "5", "6"]), &offers, &["5".to_string()]);
+
+        assert_eq!(to_accept, vec!["1".to_string()]);
+        assert_eq!(skipped, vec!["2", "3", "4", "6"]);
+    }
+
+    #[test]
+    fn report_has_the_mixed_withdrawals_and_accepts() {
+        let inv_data: Vec<InventoryEntryResult> = serde_json::from_value(json!([
+            {"id": "9001", "asset_id": "101", "tradehold": 0},
+            // The withdrawal fails
+            {"id": "9002", "asset_id": "102", "tradehold": 0},
+            // Withdrawn, but the accept of its offer fails
+            {"id": "9003", "asset_id": "103", "tradehold": 0},
+            // On trade hold and queued, neither is withdrawn
+            {"id": "9004", "asset_id": "104", "tradehold": 3},
+            {"id": "9005", "asset_id": "105", "tradehold": 0},
+        ]))
+        .unwrap();
+        let queued = vec!["9005".to_string()];
+        // 5101 is the offer of 9001 and is accepted with the withdrawal, 5201 is an older withdrawal
+        let offers = vec![offer("5101", &[], &["101"], TradeOfferState::Active), offer("5201", &[], &["201"], TradeOfferState::Active)];
+
+        let mut withdrawn_ids = Vec::new();
+        let mut accepted_ids = Vec::new();
+        let mut report = BuyOperationsReport::default();
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(process_buy_operations(
+            inv_data,
+            &queued,
+            trades(&["5101", "5201"]),
+            &offers,
+            &mut report,
+            |item: InventoryEntryResult| {
+                withdrawn_ids.push(item.id.clone());
+                async move { item.id != "9002" }
+            },
+            |asset_id: String| async move { Ok(Some(format!("5{}", asset_id))) },
+            |trade_offer_id: String| {
+                accepted_ids.push(trade_offer_id.clone());
+                async move {
+                    if trade_offer_id == "5103" {
+                        return Err("The trade offer is no longer active.".to_string());
+                    }
+                    Ok(())
+                }
+            },
+        ));
+
+        assert_eq!(withdrawn_ids, vec!["9001", "9002", "9003"]);
+        // 5101 is not accepted a second time from the active trades
+        assert_eq!(accepted_ids, vec!["5101", "5103", "5201"]);
+
+        assert_eq!(report.withdrawn, vec!["101", "103"]);
+        assert_eq!(report.accepted_offers, vec!["5101", "5201"]);
+        assert_eq!(
+            report.failures,
+            vec![
+                ("9002".to_string(), "The withdrawal failed.".to_string()),
+                ("5103".to_string(), "The trade offer is no longer active.".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn offer_check_errors_are_failures_and_missing_offers_are_not() {
+        let inv_data: Vec<InventoryEntryResult> = serde_json::from_value(json!([
+            {"id": "9001", "asset_id": "101", "tradehold": 0},
+            {"id": "9002", "asset_id": "102", "tradehold": 0},
+        ]))
+        .unwrap();
+
+        let mut report = BuyOperationsReport::default();
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(process_buy_operations(
+            inv_data,
+            &[],
+            Vec::new(),
+            &[],
+            &mut report,
+            |_: InventoryEntryResult| async { true },
+            |asset_id: String| async move {
+                match asset_id.as_str() {
+                    // No offer arrived in the poll timeout, it is accepted by a later check
+                    "101" => Ok(None),
+                    _ => Err(("5102".to_string(), "The active offers can't be read.".to_string())),
+                }
+            },
+            |_: String| async { Ok(()) },
+        ));
+
+        assert_eq!(report.withdrawn, vec!["101", "102"]);
+        assert!(report.accepted_offers.is_empty());
+        assert_eq!(report.failures, vec![("5102".to_string(), "The active offers can't be read.".to_string())]);
+    }
 }
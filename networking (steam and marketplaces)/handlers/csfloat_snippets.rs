@@ -0,0 +1,413 @@
+// csfloat.rs
+//
+// This module provides logic for CSFloat marketplace operations,
+// building on top of the API layer to handle price discovery and buying listings
+// with their float values and pattern indexes.
+
+use super::{api::csfloat_api, steam};
+use crate::{
+    bankroll, circuit_breaker::{self, EndpointGroup}, config,
+    control::{self, CriticalOp},
+    data, dedup, log_functions, naming, notifications, patterns, price_functions,
+    structs::{FloatFilter, ItemData, ItemStatus, ItemStatusChangeTicket, ItemStatusChanges, Market, Price, StickerInfo, WearTier},
+};
+use chrono::Local;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Structure for parsing the item of a listing
+#[allow(dead_code)]
+#[derive(Deserialize, Clone, Debug)]
+struct ListingItemResult {
+    asset_id: String,
+    market_hash_name: String,
+    float_value: Option<f64>,
+    paint_seed: Option<u32>,
+}
+
+/// Structure for parsing listing data from CSFloat
+#[allow(dead_code)]
+#[derive(Deserialize, Clone, Debug)]
+struct ListingResult {
+    id: String,
+    price: i64,
+    item: ListingItemResult,
+}
+
+/// Structure for parsing the item of a webhook contract
+#[derive(Deserialize, Clone, Debug)]
+struct WebhookItemResult {
+    asset_id: String,
+    market_hash_name: String,
+}
+
+/// Structure for parsing the contract (the sold listing) of a webhook
+#[derive(Deserialize, Clone, Debug)]
+struct WebhookContractResult {
+    id: String,
+    item: WebhookItemResult,
+}
+
+/// Structure for parsing the data of an "offer.created" webhook
+#[derive(Deserialize, Clone, Debug)]
+struct WebhookOfferCreatedResult {
+    contract: WebhookContractResult,
+    steam_offer_id: String,
+}
+
+/// Structure for parsing the Steam offer of an accepted CSFloat offer, the seller may not have sent it yet
+#[derive(Deserialize, Clone, Debug)]
+struct SteamOfferResult {
+    id: String,
+}
+
+/// Structure for parsing the trade of an accepted CSFloat offer
+#[derive(Deserialize, Clone, Debug)]
+struct AcceptedOfferResult {
+    contract: WebhookContractResult,
+    #[serde(default)]
+    steam_offer: Option<SteamOfferResult>,
+}
+
+/// Structure for parsing a sticker of an inspected item
+#[derive(Deserialize, Clone, Debug)]
+struct InspectStickerResult {
+    slot: u8,
+    #[serde(default)]
+    wear: Option<f32>,
+    #[serde(default)]
+    name: Option<String>,
+}
+
+/// Structure for parsing the answer of the inspect service
+#[derive(Deserialize, Clone, Debug)]
+struct InspectItemResult {
+    floatvalue: Option<f32>,
+    paintseed: Option<u32>,
+    #[serde(default)]
+    stickers: Vec<InspectStickerResult>,
+}
+
+/// The float value, paint seed and stickers of an inspected item
+#[derive(Clone, Debug, PartialEq)]
+pub struct InspectInfo {
+    pub float_value: Option<f32>,
+    pub paint_seed: Option<u32>,
+    /// The stickers are not priced yet
+    pub stickers: Vec<StickerInfo>,
+}
+
+/// Filters listings by float value, listings without a float are not filtered
+fn passes_float_filter(listing: &ListingResult, float_filter: &Option<FloatFilter>) -> bool {
+    match (float_filter, listing.item.float_value) {
+        (Some(float_filter), Some(float_value)) => float_filter.contains(float_value),
+        _ => true,
+    }
+}
+
+/// Fetches the buy now listings of an item, cheapest first
+async fn get_listings(market_hash_name: &str) -> Result<Vec<ListingResult>, String> {
+    let res = csfloat_api::get_listings(market_hash_name)
+        .await
+        .map_err(|e| format!(
+            "csfloat.rs | get_listings(market_hash_name={}) | Error occured when sending the api request. E: {:?}",
+            market_hash_name, e
+        ))?;
+
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!(
+            "csfloat.rs | get_listings(market_hash_name={}) | Error occured when parsing the api request. E: {:?}",
+            market_hash_name, e
+        ))?;
+
+    serde_json::from_value(parsed_data["data"].clone())
+        .map_err(|e| format!(
+            "csfloat.rs | get_listings(market_hash_name={}) | Error occured when parsing the api request to data structre. E: {:?}",
+            market_hash_name, e
+        ))
+}
+
+/// Retrieves the current CSFloat price of a CS item
+///
+/// - Uses the cheapest buy now listing with a matching name
+/// - CSFloat listings have no trade hold so every hold bucket has the same price
+pub async fn get_item_price(market_hash_name: String) -> Result<Price, String> {
+    circuit_breaker::check(Market::CSFloat, EndpointGroup::Prices)?;
+
+    let listings = get_listings(&market_hash_name).await?;
+
+    let lowest = listings
+        .iter()
+        .filter(|listing| naming::is_same_item(&listing.item.market_hash_name, &market_hash_name))
+        .map(|listing| listing.price)
+        .min()
+        .ok_or(format!(
+            "csfloat.rs | get_item_price(market_hash_name={}) | Error occured while the returned listing vector is empty.",
+            market_hash_name
+        ))?;
+
+    let comms = data::get_market_commisions(Market::CSFloat, "")
+        .map_err(|e| format!(
+            "csfloat.rs | get_item_price(market_hash_name={}) | Error occured when trying to get the commisions of the market. E: {:?}",
+            market_hash_name, e
+        ))?;
+
+    let price = lowest as f32 / 100.0;
+    let price_buy_w_comm: f32 = ((price / ((100 - comms.0) as f32 / 100.0)) * 100.0).ceil() / 100.0;
+    let price_sell_w_comm: f32 = ((price * (1.0 - ((comms.1 + comms.2) as f32 / 100.0))) * 100.0).ceil() / 100.0;
+
+    Ok(Price {
+        market: Market::CSFloat,
+        commision: comms.1 + comms.2,
+        price_buy: price,
+        price_buy_trade: (price, price, price),
+        price_buy_w_comm,
+        price_sell_w_comm,
+        price_buy_trade_w_comm: (price_buy_w_comm, price_buy_w_comm, price_buy_w_comm),
+        price_sell: price,
+        sale_stats: None,
+        fetched_at_unix: Local::now().timestamp(),
+        volume_24h: None,
+        currency: config::get_bot_config().get_market_currency(&Market::CSFloat),
+    })
+}
+
+/// Buys the cheapest matching CSFloat listing
+///
+/// - The max price is for the base item, a listing with a high value pattern can cost up to
+///   `effective_price = price * pattern value multiplier`
+/// - A listing whose float is in a worse wear tier than the name gets a lower max price
+/// - Skips the listings whose float is outside the float filter
+/// - The seller sends the item afterwards, so a `BuyStartCSFloat` ticket is returned
+/// - Returns the listed and the charged price
+pub async fn buy_item(
+    market_hash_name: String,
+    price: f32,
+    _trade_hold: i32,
+    float_filter: Option<FloatFilter>,
+) -> Result<(ItemStatusChangeTicket, (String, ItemData), f32, f32), String> {
+    circuit_breaker::check(Market::CSFloat, EndpointGroup::Trading)?;
+
+    let listings = get_listings(&market_hash_name).await?;
+
+    let listing = listings
+        .iter()
+        .find(|listing| {
+            let effective_price = price * patterns::value_multiplier(&market_hash_name, listing.item.paint_seed);
+            let effective_price = price_functions::wear_adjusted_max_price(effective_price, &market_hash_name, listing.item.float_value);
+            naming::is_same_item(&listing.item.market_hash_name, &market_hash_name)
+                && (listing.price as f32 / 100.0) <= effective_price
+                && passes_float_filter(listing, &float_filter)
+        })
+        .ok_or(format!(
+            "csfloat.rs | buy_item(market_hash_name={}, price={:?}) | Error occured, could not find the given item for the desired price.",
+            market_hash_name, price
+        ))?;
+    let buy_price = listing.price as f32 / 100.0;
+
+    // Simulate the purchase so the strategy can be evaluated without spending money
+    if config::get_bot_config().dry_run {
+        log_functions::log_dry_run(&format!("Would buy {} at {} on {:?}", market_hash_name, buy_price, Market::CSFloat));
+        let (ticket, new_item) = bought_listing_result(listing);
+        return Ok((ticket, (market_hash_name, new_item), buy_price, buy_price));
+    }
+
+    // The shutdown waits for the buy so it is always recorded
+    let _critical = control::begin_critical(CriticalOp::Buy, &market_hash_name);
+
+    // A buy of the listing that timed out might still go through, don't send a second one
+    dedup::try_acquire_buy(&Market::CSFloat, &listing.id)
+        .map_err(|e| format!(
+            "csfloat.rs | buy_item(market_hash_name={}, price={:?}) | Error occured, the listing is already being bought. E: {:?}",
+            market_hash_name, price, e
+        ))?;
+
+    // Reserve the funds so the spend caps can't be exceeded
+    let reservation = bankroll::reserve(Market::CSFloat, buy_price)
+        .map_err(|e| format!(
+            "csfloat.rs | buy_item(market_hash_name={}, price={:?}) | Error occured, the budget doesn't allow the buy. E: {:?}",
+            market_hash_name, price, e
+        ))?;
+
+    let res = match csfloat_api::buy_listing(&listing.id, listing.price).await {
+        Ok(res) => res,
+        Err(e) => {
+            let _ = bankroll::release(reservation);
+            return Err(format!(
+                "csfloat.rs | buy_item(market_hash_name={}, price={:?}) | Error occured when sending the buy_listing api request. E: {:?}",
+                market_hash_name, price, e
+            ));
+        }
+    };
+
+    if !res.status().is_success() {
+        let _ = bankroll::release(reservation);
+        return Err(format!(
+            "csfloat.rs | buy_item(market_hash_name={}, price={:?}) | Error occured, the listing was not bought. Status: {:?}",
+            market_hash_name, price, res.status()
+        ));
+    }
+
+    let _ = bankroll::commit(reservation, buy_price);
+    dedup::release_buy(&Market::CSFloat, &listing.id);
+    notifications::notify_buy(&market_hash_name, Market::CSFloat, buy_price);
+
+    let (ticket, new_item) = bought_listing_result(listing);
+    Ok((ticket, (market_hash_name, new_item), buy_price, buy_price))
+}
+
+/// Builds the ticket and the tracked item data of a bought listing
+fn bought_listing_result(listing: &ListingResult) -> (ItemStatusChangeTicket, ItemData) {
+    let new_item = ItemData::builder()
+        .asset_id(listing.item.asset_id.clone())
+        .market(Market::CSFloat)
+        .status(ItemStatus::OnBuyOfferWaitingSeller)
+        .market_id(Market::CSFloat, listing.id.clone())
+        .pattern_index(listing.item.paint_seed)
+        .exterior(WearTier::from_market_hash_name(&listing.item.market_hash_name))
+        .build();
+
+    let ticket = ItemStatusChangeTicket::new(listing.item.asset_id.clone(), ItemStatusChanges::BuyStartCSFloat)
+        .with_market_id(Market::CSFloat, listing.id.clone());
+
+    (ticket, new_item)
+}
+
+/// Accepts a CSFloat offer and returns the Steam trade offer id of the trade it started
+///
+/// - The id is taken from the answer if the seller already sent the Steam offer,
+///   otherwise the active offers are polled for the asset for trade_offer_poll_timeout_secs
+pub async fn accept_offer(offer_id: &str) -> Result<String, String> {
+    circuit_breaker::check(Market::CSFloat, EndpointGroup::Trading)?;
+
+    let res = csfloat_api::accept_offer(offer_id)
+        .await
+        .map_err(|e| format!("csfloat.rs | accept_offer(offer_id={}) | Error occured when sending the api request. E: {:?}", offer_id, e))?;
+
+    if !res.status().is_success() {
+        return Err(format!(
+            "csfloat.rs | accept_offer(offer_id={}) | Error occured, the offer was not accepted. Status: {:?}",
+            offer_id, res.status()
+        ));
+    }
+
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!("csfloat.rs | accept_offer(offer_id={}) | Error occured when parsing the api request. E: {:?}", offer_id, e))?;
+
+    let trade: AcceptedOfferResult = serde_json::from_value(parsed_data.clone())
+        .map_err(|e| format!(
+            "csfloat.rs | accept_offer(offer_id={}) | Error occured when parsing the api request to data structre. E: {:?}. Parsed data: {:?}",
+            offer_id, e, parsed_data
+        ))?;
+
+    match trade.steam_offer {
+        Some(steam_offer) => Ok(steam_offer.id),
+        None => steam::poll_for_trade_offer(&trade.contract.item.asset_id, config::get_bot_config().trade_offer_poll_timeout_secs).await,
+    }
+}
+
+/// Finishes a CSFloat buy that is waiting for its offer to be accepted
+///
+/// - Accepts the offer, then accepts the Steam trade offer the seller sent for the asset
+/// - Returns the BuySuccessCSFloat ticket and, if Steam gave the item a new asset id,
+///   the AssetIdRebound ticket that updates the asset id of the tracked item
+pub async fn complete_offer(asset_id: &str, offer_id: &str) -> Result<Vec<ItemStatusChangeTicket>, String> {
+    let trade_offer_id = accept_offer(offer_id).await?;
+
+    let new_asset_id = steam::accept_trade_offer_get_asset_id(trade_offer_id.clone())
+        .await
+        .map_err(|e| format!(
+            "csfloat.rs | complete_offer(asset_id={}, offer_id={}) | Error occured when accepting the trade offer {}. E: {:?}",
+            asset_id, offer_id, trade_offer_id, e
+        ))?;
+
+    log_functions::log_structured(
+        "Info",
+        &format!("csfloat.rs | complete_offer() | Accepted the trade offer {} of the CSFloat offer {}.", trade_offer_id, offer_id),
+    );
+    Ok(received_tickets(asset_id, offer_id, vec![(asset_id.to_string(), new_asset_id)]))
+}
+
+/// Builds the tickets of a CSFloat buy whose Steam trade offer was accepted
+/// received has the (asset id in the offer, asset id in our inventory) of the received items
+fn received_tickets(asset_id: &str, contract_id: &str, received: Vec<(String, String)>) -> Vec<ItemStatusChangeTicket> {
+    let mut tickets = vec![ItemStatusChangeTicket::new(asset_id.to_string(), ItemStatusChanges::BuySuccessCSFloat)
+        .with_market_id(Market::CSFloat, contract_id)];
+    for (old, new) in received {
+        if old == asset_id && old != new {
+            tickets.push(ItemStatusChangeTicket::new(old.clone(), ItemStatusChanges::AssetIdRebound { old, new }));
+        }
+    }
+    tickets
+}
+
+/// Inspects an item with its inspect link and returns its float value, paint seed and stickers
+/// The stickers without a name (the service doesn't know them) are left out
+pub async fn inspect_item(inspect_link: &str) -> Result<InspectInfo, String> {
+    let res = csfloat_api::get_inspect_info(inspect_link)
+        .await
+        .map_err(|e| format!("csfloat.rs | inspect_item(inspect_link={}) | Error occured when sending the api request. E: {:?}", inspect_link, e))?;
+
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!("csfloat.rs | inspect_item(inspect_link={}) | Error occured when parsing the api request. E: {:?}", inspect_link, e))?;
+
+    let item: InspectItemResult = serde_json::from_value(parsed_data["iteminfo"].clone())
+        .map_err(|e| format!(
+            "csfloat.rs | inspect_item(inspect_link={}) | Error occured when parsing the api request to data structre. E: {:?}. Parsed data: {:?}",
+            inspect_link, e, parsed_data
+        ))?;
+
+    Ok(InspectInfo {
+        float_value: item.floatvalue,
+        paint_seed: item.paintseed,
+        stickers: item
+            .stickers
+            .into_iter()
+            .filter_map(|sticker| {
+                Some(StickerInfo {
+                    name: sticker.name?,
+                    slot: sticker.slot,
+                    wear: sticker.wear,
+                    price: None,
+                })
+            })
+            .collect(),
+    })
+}
+
+/// Handles a webhook of CSFloat, returns the tickets of the status changes
+///
+/// - "offer.created" means the seller of a bought listing sent the Steam trade offer, it is accepted
+///   and the item is rebound to the asset id it got in our inventory
+/// - The other events are logged and ignored
+pub async fn handle_webhook(payload: Value) -> Result<Vec<ItemStatusChangeTicket>, String> {
+    let event = payload["event"].as_str().unwrap_or_default().to_string();
+    if event != "offer.created" {
+        log_functions::log_structured("Info", &format!("csfloat.rs | handle_webhook() | Ignored the {:?} event.", event));
+        return Ok(Vec::new());
+    }
+
+    let data: WebhookOfferCreatedResult = serde_json::from_value(payload["data"].clone())
+        .map_err(|e| format!(
+            "csfloat.rs | handle_webhook(event={}) | Error occured when parsing the payload to data structre. E: {:?}",
+            event, e
+        ))?;
+
+    let received = steam::accept_trade_offer_get_asset_ids(data.steam_offer_id.clone())
+        .await
+        .map_err(|e| format!(
+            "csfloat.rs | handle_webhook(event={}, steam_offer_id={}) | Error occured when accepting the trade offer. E: {:?}",
+            event, data.steam_offer_id, e
+        ))?;
+
+    let tickets = received_tickets(&data.contract.item.asset_id, &data.contract.id, received);
+
+    log_functions::log_structured(
+        "Info",
+        &format!("csfloat.rs | handle_webhook() | Accepted the trade offer {} of {}.", data.steam_offer_id, data.contract.item.market_hash_name),
+    );
+    Ok(tickets)
+}
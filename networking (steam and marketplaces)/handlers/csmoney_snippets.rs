@@ -0,0 +1,79 @@
+// csmoney.rs
+//
+// This module provides logic for CS.Money sell operations,
+// building on top of the API layer to list the bought items for sale
+// and to take the listings down again.
+
+use super::api::csmoney_api;
+use crate::{
+    config, log_functions,
+    structs::{ItemStatusChangeTicket, ItemStatusChanges, Market},
+};
+use serde_json::Value;
+
+/// Lists an item for sale on CS.Money
+///
+/// - Has to be called once the item is `Available` in the Steam inventory after the trade was received
+/// - Returns a `SellOfferCreated(Market::CSMoney)` ticket with the CS.Money listing id in its `market_ids`
+/// - In dry run mode nothing is listed and the ticket has no listing id
+pub async fn list_item_for_sale(asset_id: &str, instance_id: &str, class_id: &str, price: f32) -> Result<ItemStatusChangeTicket, String> {
+    // Simulate the listing so the strategy can be evaluated without selling
+    if config::get_bot_config().dry_run {
+        log_functions::log_dry_run(&format!("Would list {} at {} on {:?}", asset_id, price, Market::CSMoney));
+        return Ok(ItemStatusChangeTicket::new(asset_id, ItemStatusChanges::SellOfferCreated(Market::CSMoney)));
+    }
+
+    let res = csmoney_api::list_item(asset_id, instance_id, class_id, price)
+        .await
+        .map_err(|e| format!(
+            "csmoney.rs | list_item_for_sale(asset_id={}, price={}) | Error occured when sending the api request. E: {:?}",
+            asset_id, price, e
+        ))?;
+
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!(
+            "csmoney.rs | list_item_for_sale(asset_id={}, price={}) | Error occured when parsing the api request. E: {:?}",
+            asset_id, price, e
+        ))?;
+
+    // Find the listing id of the item in the created listings
+    let listing_id = match &parsed_data["items"] {
+        Value::Array(items) => items
+            .iter()
+            .find(|item| item["assetId"] == Value::String(asset_id.to_string()))
+            .and_then(|item| match &item["id"] {
+                Value::String(id) => Some(id.clone()),
+                Value::Number(id) => Some(id.to_string()),
+                _ => None,
+            }),
+        _ => None,
+    };
+
+    let csmoney_item_id = listing_id.ok_or(format!(
+        "csmoney.rs | list_item_for_sale(asset_id={}, price={}) | Error occured, the item was not listed. Parsed data: {:?}",
+        asset_id, price, parsed_data
+    ))?;
+
+    Ok(ItemStatusChangeTicket::new(asset_id, ItemStatusChanges::SellOfferCreated(Market::CSMoney))
+        .with_market_id(Market::CSMoney, csmoney_item_id))
+}
+
+/// Removes a CS.Money sell listing
+pub async fn remove_listing(csmoney_item_id: &str) -> Result<(), String> {
+    let res = csmoney_api::remove_listing(csmoney_item_id)
+        .await
+        .map_err(|e| format!(
+            "csmoney.rs | remove_listing(csmoney_item_id={}) | Error occured when sending the api request. E: {:?}",
+            csmoney_item_id, e
+        ))?;
+
+    if !res.status().is_success() {
+        return Err(format!(
+            "csmoney.rs | remove_listing(csmoney_item_id={}) | Error occured, the listing was not removed. Status: {:?}",
+            csmoney_item_id, res.status()
+        ));
+    }
+
+    Ok(())
+}
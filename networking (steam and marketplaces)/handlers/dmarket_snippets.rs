@@ -0,0 +1,92 @@
+// dmarket.rs
+//
+// This module provides logic for DMarket account operations,
+// building on top of the signed API layer to read the balance
+// and to stop purchases the balance can't cover.
+
+use super::api::dmarket_api;
+use crate::balances::{Balance, BalanceError, BalanceProvider};
+use crate::currency::Currency;
+use crate::structs::Market;
+use crate::{config, log_functions};
+
+/// The DMarket account balance
+pub struct DMarketBalance;
+
+impl BalanceProvider for DMarketBalance {
+    fn market(&self) -> Market {
+        Market::DMarket
+    }
+
+    async fn get_balance(&self) -> Result<Balance, BalanceError> {
+        let available = get_balance().await?;
+        Ok(Balance { available, pending: 0.0, currency: Currency::USD })
+    }
+}
+
+/// Retrieves the spendable USD balance of the account in dollars
+///
+/// - DMarket returns the amount as a string of cents
+pub async fn get_balance() -> Result<f32, String> {
+    let res = dmarket_api::get_balance()
+        .await
+        .map_err(|e| format!("dmarket.rs | get_balance() | Error occured when sending the api request. E: {:?}", e))?;
+
+    if !res.status().is_success() {
+        return Err(format!("dmarket.rs | get_balance() | Error occured, DMarket returned an error status: {}", res.status()));
+    }
+
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!("dmarket.rs | get_balance() | Error occured when parsing the api request. E: {:?}", e))?;
+
+    parse_balance(&parsed_data)
+}
+
+/// Parses the answer of the balance endpoint, the usd amount is a string of cents
+fn parse_balance(parsed_data: &serde_json::Value) -> Result<f32, String> {
+    parsed_data["usd"]
+        .as_str()
+        .and_then(|usd| usd.parse::<f64>().ok())
+        .map(|cents| (cents / 100.0) as f32)
+        .ok_or(format!("dmarket.rs | get_balance() | Error occured, the usd balance is missing. Parsed data: {:?}", parsed_data))
+}
+
+/// Checks that the balance covers the buy price plus `dmarket_balance_buffer_usd`
+///
+/// - Has to be called before a DMarket purchase, returns an error that tells the missing amount
+pub async fn check_balance_for_buy(buy_price: f32) -> Result<f32, String> {
+    let balance = get_balance().await?;
+    let required = buy_price + config::get_bot_config().dmarket_balance_buffer_usd;
+
+    if balance < required {
+        let err_str = format!(
+            "dmarket.rs | check_balance_for_buy(buy_price={}) | Error occured, the balance ${:.2} is below the required ${:.2} (${:.2} missing).",
+            buy_price, balance, required, required - balance
+        );
+        log_functions::log_structured("Warning", &err_str);
+        return Err(err_str);
+    }
+
+    Ok(balance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn balance_is_read_from_the_usd_cents() {
+        let body = json!({"usd": "123456", "dmc": "0", "usdAvailableToWithdraw": "100000", "dmcAvailableToWithdraw": "0"});
+        assert!((parse_balance(&body).unwrap() - 1234.56).abs() < 1e-3);
+        assert_eq!(parse_balance(&json!({"usd": "0"})), Ok(0.0));
+    }
+
+    #[test]
+    fn balance_without_usd_cents_is_an_error() {
+        for body in [json!({"dmc": "100"}), json!({"usd": 123456}), json!({"usd": "12.3.4"}), json!({"error": "Unauthorized"})] {
+            assert!(parse_balance(&body).is_err(), "{}", body);
+        }
+    }
+}
@@ -0,0 +1,352 @@
+// lisskins.rs
+//
+// This module provides logic for LisSkins marketplace operations,
+// building on top of the API layer to handle price discovery, purchases
+// and accepting the Steam trade offers of the bought items.
+
+use super::{api::lisskins_api, steam};
+use crate::{
+    bankroll, circuit_breaker::{self, EndpointGroup}, config,
+    control::{self, CriticalOp},
+    data, log_functions, naming, notifications, ticket_bus,
+    structs::{FloatFilter, Item, ItemData, ItemStatus, ItemStatusChangeTicket, ItemStatusChanges, Market, Price},
+};
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+// The purchase statuses that mean the item will never arrive
+const FAILED_STATUSES: [&str; 3] = ["canceled", "failed", "refunded"];
+
+// The custom ids whose failure was already pushed, so a purchase that stays failed isn't reported every poll
+static REPORTED: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+/// Structure for parsing listing data from LisSkins
+#[allow(dead_code)]
+#[derive(Deserialize, Clone, Debug)]
+struct ItemEntryResult {
+    id: i64,
+    name: String,
+    price: f32,
+    unlock_at: Option<String>,
+    #[serde(default)]
+    item_float: Option<String>,
+}
+
+/// Structure for parsing the status of a purchase
+#[allow(dead_code)]
+#[derive(Deserialize, Clone, Debug)]
+struct PurchaseInfoResult {
+    custom_id: String,
+    status: String,
+    steam_trade_offer_id: Option<String>,
+}
+
+/// Returns the days until the listing can be traded, 0 if it is not trade locked
+fn get_trade_hold_days(item: &ItemEntryResult) -> i64 {
+    let unlock_at = match &item.unlock_at {
+        Some(unlock_at) => unlock_at,
+        None => return 0,
+    };
+
+    match DateTime::parse_from_rfc3339(unlock_at) {
+        Ok(unlock_at) => {
+            let secs = unlock_at.timestamp() - Local::now().timestamp();
+            if secs <= 0 { 0 } else { (secs + 86399) / 86400 }
+        }
+        // Unknown date format, treat it as the longest hold
+        Err(_) => 7,
+    }
+}
+
+/// Filters listings by float value, listings without a float are not filtered
+fn passes_float_filter(item: &ItemEntryResult, float_filter: &Option<FloatFilter>) -> bool {
+    let float_value = item.item_float.as_ref().and_then(|float_value| float_value.parse::<f64>().ok());
+    match (float_filter, float_value) {
+        (Some(float_filter), Some(float_value)) => float_filter.contains(float_value),
+        _ => true,
+    }
+}
+
+/// Searches the listings of an item, cheapest first
+async fn search_items(market_hash_name: &str) -> Result<Vec<ItemEntryResult>, String> {
+    let res = lisskins_api::search_items(market_hash_name)
+        .await
+        .map_err(|e| format!(
+            "lisskins.rs | search_items(market_hash_name={}) | Error occured when sending the api request. E: {:?}",
+            market_hash_name, e
+        ))?;
+
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!(
+            "lisskins.rs | search_items(market_hash_name={}) | Error occured when parsing the api request. E: {:?}",
+            market_hash_name, e
+        ))?;
+
+    serde_json::from_value(parsed_data["data"].clone())
+        .map_err(|e| format!(
+            "lisskins.rs | search_items(market_hash_name={}) | Error occured when parsing the api request to data structre. E: {:?}",
+            market_hash_name, e
+        ))
+}
+
+/// Retrieves the current LisSkins price of a CS item
+///
+/// - Identifies lowest prices based on trade hold duration
+/// - Calculates buy/sell prices with marketplace commissions
+/// - Goes out without a proxy, LisSkins doesn't need them
+pub async fn get_item_price(market_hash_name: String) -> Result<Price, String> {
+    circuit_breaker::check(Market::LisSkins, EndpointGroup::Prices)?;
+
+    let item_data = search_items(&market_hash_name).await?;
+
+    // Process pricing data with trade hold categories, the listings are sorted by price
+    let mut price_now = None;
+    let mut price_2 = 0.0;
+    let mut price_4 = 0.0;
+    let mut price_7 = 0.0;
+
+    for item in item_data.iter() {
+        if !naming::is_same_item(&item.name, &market_hash_name) || price_now.is_some() {
+            continue;
+        }
+
+        let trade_hold = get_trade_hold_days(item);
+        if trade_hold > 4 {
+            if price_7 == 0.0 {
+                price_7 = item.price;
+            }
+        } else if trade_hold > 2 {
+            if price_4 == 0.0 {
+                price_4 = item.price;
+            }
+        } else if trade_hold >= 1 {
+            if price_2 == 0.0 {
+                price_2 = item.price;
+            }
+        } else {
+            price_now = Some(item.price);
+            // Fill in missing price categories with the current price
+            if price_7 == 0.0 {
+                price_7 = item.price;
+            }
+            if price_4 == 0.0 {
+                price_4 = item.price;
+            }
+            if price_2 == 0.0 {
+                price_2 = item.price;
+            }
+        }
+    }
+
+    let price = price_now.ok_or(format!(
+        "lisskins.rs | get_item_price(market_hash_name={}) | Error occured, there are no tradable listings.",
+        market_hash_name
+    ))?;
+
+    let comms = data::get_market_commisions(Market::LisSkins, "")
+        .map_err(|e| format!(
+            "lisskins.rs | get_item_price(market_hash_name={}) | Error occured when trying to get the commisions of the market. E: {:?}",
+            market_hash_name, e
+        ))?;
+
+    // Calculate effective buy and sell prices with commissions
+    let with_buy_comm = |price: f32| ((price / ((100 - comms.0) as f32 / 100.0)) * 100.0).ceil() / 100.0;
+    let price_buy_w_comm = with_buy_comm(price);
+    let price_sell_w_comm: f32 = ((price * (1.0 - ((comms.1 + comms.2) as f32 / 100.0))) * 100.0).ceil() / 100.0;
+
+    Ok(Price {
+        market: Market::LisSkins,
+        commision: comms.1 + comms.2,
+        price_buy: price,
+        price_buy_trade: (price_7, price_4, price_2),
+        price_buy_w_comm,
+        price_sell_w_comm,
+        price_buy_trade_w_comm: (with_buy_comm(price_7), with_buy_comm(price_4), with_buy_comm(price_2)),
+        price_sell: price,
+        sale_stats: None,
+        fetched_at_unix: Local::now().timestamp(),
+        volume_24h: None,
+        currency: config::get_bot_config().get_market_currency(&Market::LisSkins),
+    })
+}
+
+/// Creates a LisSkins purchase of the cheapest matching listing
+///
+/// - Only buys listings whose trade hold is not longer than `trade_hold` days
+/// - The asset id is not known until the trade offer arrives, so the item is tracked
+///   with the purchase custom id until `check_buy_operations` rebinds it
+/// - Returns a `BuyStartLisSkins` ticket, the item data, the listed and the charged price
+pub async fn buy_item(
+    market_hash_name: String,
+    price: f32,
+    trade_hold: i32,
+    float_filter: Option<FloatFilter>,
+) -> Result<(ItemStatusChangeTicket, (String, ItemData), f32, f32), String> {
+    circuit_breaker::check(Market::LisSkins, EndpointGroup::Trading)?;
+
+    let item_data = search_items(&market_hash_name).await?;
+
+    let item = item_data
+        .iter()
+        .find(|item| {
+            naming::is_same_item(&item.name, &market_hash_name)
+                && item.price <= price
+                && get_trade_hold_days(item) <= trade_hold as i64
+                && passes_float_filter(item, &float_filter)
+        })
+        .ok_or(format!(
+            "lisskins.rs | buy_item(market_hash_name={}, price={:?}) | Error occured, could not find the given item for the desired price.",
+            market_hash_name, price
+        ))?;
+
+    let custom_id = format!("lis-{}-{}", item.id, Local::now().timestamp_millis());
+
+    // Simulate the purchase so the strategy can be evaluated without spending money
+    if config::get_bot_config().dry_run {
+        log_functions::log_dry_run(&format!("Would buy {} at {} on {:?}", market_hash_name, item.price, Market::LisSkins));
+        let (ticket, new_item) = purchase_started_result(&custom_id);
+        return Ok((ticket, (market_hash_name, new_item), item.price, item.price));
+    }
+
+    // The shutdown waits for the purchase so it is always recorded
+    let _critical = control::begin_critical(CriticalOp::Buy, &market_hash_name);
+
+    // Reserve the funds so the spend caps can't be exceeded
+    let reservation = bankroll::reserve(Market::LisSkins, item.price)
+        .map_err(|e| format!(
+            "lisskins.rs | buy_item(market_hash_name={}, price={:?}) | Error occured, the budget doesn't allow the buy. E: {:?}",
+            market_hash_name, price, e
+        ))?;
+
+    let res = match lisskins_api::create_purchase(item.id, price, &custom_id).await {
+        Ok(res) => res,
+        Err(e) => {
+            let _ = bankroll::release(reservation);
+            return Err(format!(
+                "lisskins.rs | buy_item(market_hash_name={}, price={:?}) | Error occured when sending the create_purchase api request. E: {:?}",
+                market_hash_name, price, e
+            ));
+        }
+    };
+
+    // The request went out, commit the listed price if the answer can't be read since the purchase might exist
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| {
+            let _ = bankroll::commit(reservation, item.price);
+            format!(
+                "lisskins.rs | buy_item(market_hash_name={}, price={:?}) | Error occured when parsing the create_purchase api request. E: {:?}",
+                market_hash_name, price, e
+            )
+        })?;
+
+    if parsed_data["data"]["custom_id"].as_str() != Some(custom_id.as_str()) {
+        let _ = bankroll::release(reservation);
+        return Err(format!(
+            "lisskins.rs | buy_item(market_hash_name={}, price={:?}) | Error occured, the purchase was not created. Parsed data: {:?}",
+            market_hash_name, price, parsed_data
+        ));
+    }
+
+    let charged_price = parsed_data["data"]["price"].as_f64().map(|price| price as f32).unwrap_or(item.price);
+    let _ = bankroll::commit(reservation, charged_price);
+
+    notifications::notify_buy(&market_hash_name, Market::LisSkins, item.price);
+
+    let (ticket, new_item) = purchase_started_result(&custom_id);
+    Ok((ticket, (market_hash_name, new_item), item.price, charged_price))
+}
+
+/// Builds the ticket and the tracked item data of a started purchase
+fn purchase_started_result(custom_id: &str) -> (ItemStatusChangeTicket, ItemData) {
+    let new_item = ItemData::builder()
+        .asset_id(custom_id)
+        .market(Market::LisSkins)
+        .status(ItemStatus::OnBuyOfferWaitingSeller)
+        .build();
+
+    (purchase_ticket(custom_id, ItemStatusChanges::BuyStartLisSkins), new_item)
+}
+
+fn purchase_ticket(custom_id: &str, change: ItemStatusChanges) -> ItemStatusChangeTicket {
+    ItemStatusChangeTicket::new(custom_id, change)
+}
+
+/// Checks the pending LisSkins purchases and accepts their trade offers
+///
+/// - Polls the status of every item that is still waiting for the seller
+/// - Accepts the trade offer and pushes a `BuySuccessLisSkins(new asset id)` ticket,
+///   applying it calls `update_asset_id` to rebind the ItemData from the custom id to the real asset id
+/// - Pushes a `BuyFailure` ticket once for the purchases LisSkins canceled
+pub async fn check_buy_operations(inv: &HashMap<String, Item>) -> Result<(), String> {
+    let custom_ids: Vec<String> = inv
+        .values()
+        .flat_map(|item| item.data.iter())
+        .filter(|data| data.market == Market::LisSkins && data.status == ItemStatus::OnBuyOfferWaitingSeller)
+        .map(|data| data.asset_id.clone())
+        .collect();
+
+    REPORTED
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashSet::new)
+        .retain(|custom_id| custom_ids.contains(custom_id));
+
+    if custom_ids.is_empty() {
+        return Ok(());
+    }
+
+    let res = lisskins_api::get_purchase_status(&custom_ids)
+        .await
+        .map_err(|e| format!("lisskins.rs | check_buy_operations() | Error occured when sending the api request. E: {:?}", e))?;
+
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!("lisskins.rs | check_buy_operations() | Error occured when parsing the api request. E: {:?}", e))?;
+
+    let purchases: Vec<PurchaseInfoResult> = serde_json::from_value(parsed_data["data"].clone())
+        .map_err(|e| format!(
+            "lisskins.rs | check_buy_operations() | Error occured when parsing the api request into the data structre. E: {:?}",
+            e
+        ))?;
+
+    for purchase in purchases {
+        // No new trade accepts during the shutdown
+        if control::shutdown_token().is_cancelled() {
+            break;
+        }
+
+        if FAILED_STATUSES.contains(&purchase.status.as_str()) {
+            let first_report = REPORTED
+                .lock()
+                .unwrap()
+                .get_or_insert_with(HashSet::new)
+                .insert(purchase.custom_id.clone());
+            if first_report {
+                ticket_bus::push(purchase_ticket(&purchase.custom_id, ItemStatusChanges::BuyFailure));
+            }
+            continue;
+        }
+
+        let trade_offer_id = match purchase.steam_trade_offer_id {
+            Some(trade_offer_id) if !trade_offer_id.is_empty() => trade_offer_id,
+            _ => continue,
+        };
+
+        match steam::accept_trade_offer_get_asset_id(trade_offer_id).await {
+            Ok(new_asset_id) => ticket_bus::push(purchase_ticket(
+                &purchase.custom_id,
+                ItemStatusChanges::BuySuccessLisSkins(new_asset_id),
+            )),
+            Err(err_str) => log_functions::log_err(&format!(
+                "lisskins.rs | check_buy_operations() | Error occured when accepting the trade offer of {}. E: {:?}",
+                purchase.custom_id, err_str
+            )),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,139 @@
+// marketcsgo.rs
+//
+// This module provides logic for MarketCSGO sell operations,
+// building on top of the API layer to follow the listings of the account
+// and to detect when the listed items are sold.
+
+use super::api::marketcsgo_api;
+use crate::{
+    config, control, data, log_functions, ticket_bus,
+    structs::{ItemStatusChangeTicket, ItemStatusChanges, ListingState, ListingStatus, Market},
+};
+use chrono::Local;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use tokio::time::{sleep, Duration};
+
+// The item ids whose sale or cancel was already pushed, so a listing that stays sold isn't reported twice
+static REPORTED: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+/// Structure for parsing an item of the account from MarketCSGO
+#[allow(dead_code)]
+#[derive(Deserialize, Clone, Debug)]
+struct ItemEntryResult {
+    item_id: String,
+    assetid: String,
+    market_hash_name: String,
+    price: f32,
+    status: String,
+}
+
+/// Maps the MarketCSGO item status to the listing state
+///
+/// - "1" is for sale
+/// - "2" is sold and waiting for the trade, "4" is sold and the trade is done
+/// - Everything else means the listing was taken down or failed
+fn listing_state(item: &ItemEntryResult, sell_comm_perc: i32) -> ListingState {
+    match item.status.as_str() {
+        "1" => ListingState::Active,
+        "2" | "4" => ListingState::Sold {
+            at_price: ((item.price * (1.0 - sell_comm_perc as f32 / 100.0)) * 100.0).floor() / 100.0,
+        },
+        _ => ListingState::Canceled,
+    }
+}
+
+/// Retrieves the state of every listing of the account on MarketCSGO
+///
+/// - The price of a sold listing is the amount received after MarketCSGO's commission
+pub async fn poll_active_listings() -> Result<Vec<ListingStatus>, String> {
+    let res = marketcsgo_api::get_items()
+        .await
+        .map_err(|e| format!("marketcsgo.rs | poll_active_listings() | Error occured when sending the api request. E: {:?}", e))?;
+
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!("marketcsgo.rs | poll_active_listings() | Error occured when parsing the api request. E: {:?}", e))?;
+
+    if parsed_data["success"] != serde_json::Value::Bool(true) {
+        return Err(format!(
+            "marketcsgo.rs | poll_active_listings() | Error occured, the request was not successful. Parsed data: {:?}",
+            parsed_data
+        ));
+    }
+
+    // An account without listings has no items array
+    let items: Vec<ItemEntryResult> = match &parsed_data["items"] {
+        serde_json::Value::Null => Vec::new(),
+        items => serde_json::from_value(items.clone())
+            .map_err(|e| format!(
+                "marketcsgo.rs | poll_active_listings() | Error occured when parsing the api request into the data structre. E: {:?}",
+                e
+            ))?,
+    };
+
+    let comms = data::get_market_commisions(Market::MarketCSGO, "")
+        .map_err(|e| format!(
+            "marketcsgo.rs | poll_active_listings() | Error occured when trying to get the commisions of the market. E: {:?}",
+            e
+        ))?;
+
+    Ok(items
+        .iter()
+        .map(|item| ListingStatus {
+            asset_id: item.assetid.clone(),
+            marketcsgo_item_id: item.item_id.clone(),
+            status: listing_state(item, comms.1 + comms.2),
+        })
+        .collect())
+}
+
+/// Polls the listings and pushes the tickets of the ones that changed
+///
+/// - Sold listings push a `SellSuccess(Market::MarketCSGO, received price)` ticket
+/// - Canceled listings push a `SellError(timestamp)` ticket
+/// - Every listing is reported once, the item ids MarketCSGO doesn't return anymore are forgotten
+pub async fn check_sell_operations() -> Result<(), String> {
+    let listings = poll_active_listings().await?;
+
+    let mut reported = REPORTED.lock().unwrap();
+    let reported = reported.get_or_insert_with(HashSet::new);
+    reported.retain(|item_id| listings.iter().any(|listing| listing.marketcsgo_item_id == *item_id));
+
+    for listing in listings.iter() {
+        let change = match listing.status {
+            ListingState::Active => continue,
+            ListingState::Sold { at_price } => ItemStatusChanges::SellSuccess(Market::MarketCSGO, at_price),
+            ListingState::Canceled => ItemStatusChanges::SellError(Local::now().timestamp()),
+        };
+        if !reported.insert(listing.marketcsgo_item_id.clone()) {
+            continue;
+        }
+
+        ticket_bus::push(
+            ItemStatusChangeTicket::new(listing.asset_id.clone(), change)
+                .with_market_id(Market::MarketCSGO, listing.marketcsgo_item_id.clone()),
+        );
+    }
+
+    Ok(())
+}
+
+/// Checks the MarketCSGO listings right away and then every `marketcsgo_poll_secs` until the shutdown
+pub fn spawn_listings_poller() -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let token = control::shutdown_token();
+        loop {
+            if let Err(err_str) = check_sell_operations().await {
+                log_functions::log_err(&err_str);
+            }
+
+            let interval = Duration::from_secs(config::get_bot_config().marketcsgo_poll_secs);
+            tokio::select! {
+                _ = token.cancelled() => break,
+                _ = sleep(interval) => {}
+            }
+        }
+    })
+}
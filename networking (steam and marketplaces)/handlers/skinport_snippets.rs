@@ -0,0 +1,144 @@
+// skinport.rs
+//
+// This module provides logic for Skinport price signals,
+// building on top of the API layer to turn Skinport's item prices into
+// the Price structure used by the arbitrage calculations.
+
+use super::api::skinport_api;
+use crate::{
+    config, control, data, log_functions,
+    currency::Currency,
+    structs::{FloatFilter, ItemData, ItemStatusChangeTicket, Market, Price},
+};
+use chrono::Local;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+use tokio::time::{sleep, Duration};
+
+// The prices of every Skinport item keyed by market hash name, rebuilt by the refresher
+static PRICE_MAP: OnceLock<RwLock<HashMap<String, Price>>> = OnceLock::new();
+
+/// Structure for parsing item price data from Skinport
+#[allow(dead_code)]
+#[derive(Deserialize, Clone, Debug)]
+struct ItemEntryResult {
+    market_hash_name: String,
+    min_price: Option<f32>,
+    mean_price: Option<f32>,
+    suggested_price: Option<f32>,
+    quantity: i64,
+}
+
+/// Returns the Skinport price map, empty until the first refresh
+fn price_map() -> &'static RwLock<HashMap<String, Price>> {
+    PRICE_MAP.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// Fetches the prices of every item in one request and builds the price map
+///
+/// - Uses the lowest listing as the buy price
+/// - Uses Skinport's suggested price as the sell price when it exists
+/// - Calculates the sell price after Skinport's commission
+/// - Items without listings are left out
+pub async fn build_price_map() -> Result<HashMap<String, Price>, String> {
+    let res = skinport_api::get_all_prices()
+        .await
+        .map_err(|e| format!("skinport.rs | build_price_map() | Error occured when sending the api request. E: {:?}", e))?;
+
+    let item_data: Vec<ItemEntryResult> = res.json()
+        .await
+        .map_err(|e| format!(
+            "skinport.rs | build_price_map() | Error occured when parsing the api request to data structre. E: {:?}",
+            e
+        ))?;
+
+    let comms = data::get_market_commisions(Market::Skinport, "")
+        .map_err(|e| format!(
+            "skinport.rs | build_price_map() | Error occured when trying to get the commisions of the market. E: {:?}",
+            e
+        ))?;
+    let fetched_at_unix = Local::now().timestamp();
+
+    let mut price_map: HashMap<String, Price> = HashMap::new();
+    for item in item_data {
+        // Items without listings have no minimum price
+        let price = match item.min_price {
+            Some(price) if item.quantity > 0 => price,
+            _ => continue,
+        };
+        let price_sell = item.suggested_price.unwrap_or(price);
+
+        // Calculate effective buy and sell prices with commissions
+        let price_buy_w_comm: f32 = ((price / ((100 - comms.0) as f32 / 100.0)) * 100.0).ceil() / 100.0;
+        let price_sell_w_comm: f32 = ((price_sell * (1.0 - ((comms.1 + comms.2) as f32 / 100.0))) * 100.0).ceil() / 100.0;
+
+        price_map.insert(item.market_hash_name, Price {
+            market: Market::Skinport,
+            commision: comms.1 + comms.2,
+            price_buy: price,
+            price_buy_trade: (price, price, price),
+            price_buy_w_comm,
+            price_sell_w_comm,
+            price_buy_trade_w_comm: (price_buy_w_comm, price_buy_w_comm, price_buy_w_comm),
+            price_sell,
+            sale_stats: None,
+            fetched_at_unix,
+            volume_24h: None,
+            // Requested with currency=USD
+            currency: Currency::USD,
+        });
+    }
+
+    Ok(price_map)
+}
+
+/// Rebuilds the price map, the old one is kept if the fetch fails
+pub async fn refresh_price_map() -> Result<usize, String> {
+    let new_map = build_price_map().await?;
+    let count = new_map.len();
+    *price_map().write().unwrap() = new_map;
+    Ok(count)
+}
+
+/// Refreshes the price map right away and then every `skinport_refresh_secs` until the shutdown
+pub fn spawn_price_map_refresher() -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let token = control::shutdown_token();
+        loop {
+            match refresh_price_map().await {
+                Ok(count) => log_functions::log_structured("Info", &format!("Refreshed the Skinport prices of {} items.", count)),
+                Err(err_str) => log_functions::log_err(&err_str),
+            }
+
+            let interval = Duration::from_secs(config::get_bot_config().skinport_refresh_secs);
+            tokio::select! {
+                _ = token.cancelled() => break,
+                _ = sleep(interval) => {}
+            }
+        }
+    })
+}
+
+/// Retrieves the current Skinport price of a CS item from the price map, no request is sent
+pub async fn get_item_price(market_hash_name: String) -> Result<Price, String> {
+    price_map()
+        .read()
+        .unwrap()
+        .get(&market_hash_name)
+        .cloned()
+        .ok_or(format!(
+            "skinport.rs | get_item_price(market_hash_name={}) | Error occured, the item has no listings on Skinport or the prices are not fetched yet.",
+            market_hash_name
+        ))
+}
+
+/// Skinport has no bot api for buying, the prices are only used as a signal
+pub async fn buy_item(
+    _market_hash_name: String,
+    _price: f32,
+    _trade_hold: i32,
+    _float_filter: Option<FloatFilter>,
+) -> Result<(ItemStatusChangeTicket, (String, ItemData), f32, f32), String> {
+    Err("Skinport is read-only".to_string())
+}
@@ -4,24 +4,189 @@
 // building on top of the API layer to handle inventory management, trade offers,
 // and trade lock status tracking.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::OnceLock;
 
+use crate::config;
+use crate::currency::Currency;
+use crate::data;
+use crate::log_functions;
+use crate::control::{self, CriticalOp};
 use crate::markets::api::steam_api;
-use crate::structs::{ItemData, ItemCount, Item, ItemStatus, ItemStatusChangeTicket, ItemStatusChanges, Market};
+use crate::metrics;
+use crate::naming;
+use crate::notifications;
+use crate::ticket_bus;
+use crate::structs::{ItemData, ItemCount, Item, ItemStatus, ItemStatusChangeTicket, ItemStatusChanges, Market, Price, SteamAccount, SteamApp, SteamId, WearTier};
 
+use reqwest::header::RETRY_AFTER;
 use serde_json;
 use serde_json::Value;
 use serde::Deserialize;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::time::{sleep, Duration, Instant};
 
-// Items to ignore when processing inventory
-const IGNORE: [&'static str; 5] = ["Loyalty Badge", "5 Year Veteran Coin", "Music Kit", "Graffiti |", "Global Offensive Badge"];
+// How long to wait when Steam rate limits without a Retry-After header
+const DEFAULT_RATE_LIMIT_SECS: u64 = 900;
+
+// How many items a Steam inventory can hold
+const STEAM_INVENTORY_LIMIT: i32 = 1000;
+
+// How often poll_for_trade_offer checks the offers of the account
+const TRADE_OFFER_POLL_SECS: u64 = 5;
+
+// The Steam currency id of USD, every price of the bot is in dollars
+const STEAM_CURRENCY_USD: u32 = 1;
+
+// How many of the most recent trades are searched for a missing item
+const TRADE_HISTORY_MAX_TRADES: u32 = 100;
+
+// How many inventory page requests can run at the same time across every account
+const MAX_CONCURRENT_INVENTORY_REQUESTS: usize = 3;
+
+// How many fetched inventory pages can wait for the parsing before the next request waits
+const INVENTORY_PIPELINE_DEPTH: usize = 2;
+
+// The permits of the inventory page requests
+static INVENTORY_PERMITS: OnceLock<Semaphore> = OnceLock::new();
+
+/// Structure for parsing the price overview of the Steam Community Market
+#[derive(Deserialize, Debug, Clone)]
+struct PriceOverviewReturn {
+    success: bool,
+    #[serde(default)]
+    lowest_price: Option<String>,
+    #[serde(default)]
+    median_price: Option<String>,
+    #[serde(default)]
+    volume: Option<String>,
+}
 
 /// Structure for parsing trade offer data from Steam API
 #[derive(Deserialize, Debug, Clone)]
 #[allow(dead_code)]
 struct SteamTradeOfferData {
     tradeofferid: String,
+    // Steam leaves out the side of the offer that has no items
+    #[serde(default)]
+    items_to_give: Vec<InventoryReturn>,
+    #[serde(default)]
+    items_to_receive: Vec<InventoryReturn>,
+    #[serde(default)]
+    tradeid: Option<String>,
+}
+
+impl SteamTradeOfferData {
+    // The asset ids of the items we give in the offer
+    fn give_asset_ids(&self) -> Vec<String> {
+        self.items_to_give.iter().map(|item| item.assetid.clone()).collect()
+    }
+}
+
+/// Structure for parsing a received asset of a trade status from Steam API
+#[derive(Deserialize, Debug, Clone)]
+struct TradeStatusAssetReturn {
+    assetid: String,
+    new_assetid: String,
+}
+
+/// Structure for parsing a trade of the trade history from Steam API
+#[derive(Deserialize, Debug, Clone)]
+#[allow(dead_code)]
+struct TradeHistoryReturn {
+    tradeid: String,
+    steamid_other: String,
+    #[serde(default)]
+    assets_given: Vec<TradeHistoryAssetReturn>,
+    #[serde(default)]
+    assets_received: Vec<TradeHistoryAssetReturn>,
+}
+
+/// Structure for parsing an asset of a trade of the trade history from Steam API
+#[derive(Deserialize, Debug, Clone)]
+struct TradeHistoryAssetReturn {
+    assetid: String,
+}
+
+/// Structure for parsing an entry of the trade offer list from Steam API
+#[derive(Deserialize, Debug, Clone)]
+#[allow(dead_code)]
+struct TradeOfferListReturn {
+    tradeofferid: String,
+    accountid_other: u32,
+    #[serde(default)]
+    items_to_give: Vec<InventoryReturn>,
+    #[serde(default)]
     items_to_receive: Vec<InventoryReturn>,
+    trade_offer_state: i32,
+    #[serde(default)]
+    is_our_offer: bool,
+}
+
+/// The states of a Steam trade offer (ETradeOfferState)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TradeOfferState {
+    Invalid,
+    Active,
+    Accepted,
+    Countered,
+    Expired,
+    Canceled,
+    Declined,
+    InvalidItems,
+    CreatedNeedsConfirmation,
+    CanceledBySecondFactor,
+    InEscrow,
+    Unknown(i32),
+}
+
+impl From<i32> for TradeOfferState {
+    fn from(state: i32) -> Self {
+        match state {
+            1 => TradeOfferState::Invalid,
+            2 => TradeOfferState::Active,
+            3 => TradeOfferState::Accepted,
+            4 => TradeOfferState::Countered,
+            5 => TradeOfferState::Expired,
+            6 => TradeOfferState::Canceled,
+            7 => TradeOfferState::Declined,
+            8 => TradeOfferState::InvalidItems,
+            9 => TradeOfferState::CreatedNeedsConfirmation,
+            10 => TradeOfferState::CanceledBySecondFactor,
+            11 => TradeOfferState::InEscrow,
+            other => TradeOfferState::Unknown(other),
+        }
+    }
+}
+
+/// A Steam trade offer of the account
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradeOffer {
+    pub trade_offer_id: String,
+    pub partner: SteamId,
+    /// Asset ids of the items we give
+    pub items_to_give: Vec<String>,
+    /// Asset ids of the items we receive
+    pub items_to_receive: Vec<String>,
+    pub state: TradeOfferState,
+    /// True if the offer was created by us
+    pub is_our_offer: bool,
+}
+
+/// The kinds of trade offers, used to decide if an offer can be accepted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TradeOfferClass {
+    /// We only receive items (withdrawals, purchases)
+    Incoming,
+    /// We only give items and we created the offer (sales)
+    Outgoing,
+    /// Both sides give items
+    Exchange,
+    /// Someone else created an offer that takes our items and gives nothing, never accept it
+    PossibleHijack,
+    /// No items on either side
+    Empty,
 }
 
 /// Structure for parsing inventory item data from Steam API
@@ -37,12 +202,39 @@ struct InventoryReturn {
 }
 
 /// Structure for parsing item description data from Steam API
+///
+/// - `cache_expiration` is the RFC 3339 datetime the trade hold ends, only sent for held items
+/// - `owner_descriptions` has the same date as text ("Tradable/Marketable After ...")
 #[derive(Deserialize, Debug)]
 struct DescriptionsReturn {
     classid: String,
     instanceid: String,
     market_name: String,
     tradable: i32,
+    #[serde(default)]
+    cache_expiration: Option<String>,
+    #[serde(default)]
+    owner_descriptions: Vec<OwnerDescriptionReturn>,
+    #[serde(default)]
+    actions: Vec<ActionReturn>,
+}
+
+/// Structure for parsing the actions of an item, the inspect link is one of them
+///
+/// - The link has %owner_steamid% and %assetid% in it, they are filled in by inspect_link
+#[derive(Deserialize, Debug, Clone)]
+struct ActionReturn {
+    #[serde(default)]
+    link: String,
+    #[serde(default)]
+    name: String,
+}
+
+/// Structure for parsing the owner only description lines of an item
+#[derive(Deserialize, Debug, Clone)]
+struct OwnerDescriptionReturn {
+    #[serde(default)]
+    value: String,
 }
 
 /// Internal structure for processing inventory data
@@ -52,53 +244,149 @@ struct InventoryRequestReturn {
     id_data: Vec<InventoryReturn>,
     names: Vec<String>,
     tradable: Vec<bool>,
+    unlock_unix: Vec<Option<i64>>,
+    untradable: Vec<bool>,
+    inspect_links: Vec<Option<String>>,
+}
+
+impl InventoryRequestReturn {
+    fn new() -> Self {
+        InventoryRequestReturn {
+            total_count: 0,
+            id_data: Vec::new(),
+            names: Vec::new(),
+            tradable: Vec::new(),
+            unlock_unix: Vec::new(),
+            untradable: Vec::new(),
+            inspect_links: Vec::new(),
+        }
+    }
+
+    // Appends the assets of a page, an asset that was on an earlier page is skipped
+    fn append_page(&mut self, page: InventoryRequestReturn, seen_asset_ids: &mut HashSet<String>) {
+        self.total_count = page.total_count;
+
+        for (i, entry) in page.id_data.into_iter().enumerate() {
+            if !seen_asset_ids.insert(entry.assetid.clone()) {
+                continue;
+            }
+            self.id_data.push(entry);
+            self.names.push(page.names[i].clone());
+            self.tradable.push(page.tradable[i]);
+            self.unlock_unix.push(page.unlock_unix[i]);
+            self.untradable.push(page.untradable[i]);
+            self.inspect_links.push(page.inspect_links[i].clone());
+        }
+    }
+}
+
+/// The errors of a Steam inventory fetch
+#[derive(Debug, Clone, PartialEq)]
+pub enum InventoryFetchError {
+    RateLimited { retry_after_secs: u64 },
+    AuthenticationFailed,
+    ParseError(String),
+    NetworkError(String),
+    // Steam answered with a page without assets, the inventory has reached the end
+    EndOfInventory,
+}
+
+impl std::fmt::Display for InventoryFetchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InventoryFetchError::RateLimited { retry_after_secs } => write!(f, "Steam inventory rate limited, retry after {} seconds.", retry_after_secs),
+            InventoryFetchError::AuthenticationFailed => write!(f, "Steam inventory authentication failed. Fix steam cookie!!!"),
+            InventoryFetchError::ParseError(err_str) => write!(f, "{}", err_str),
+            InventoryFetchError::NetworkError(err_str) => write!(f, "{}", err_str),
+            InventoryFetchError::EndOfInventory => write!(f, "Steam inventory has no more pages."),
+        }
+    }
+}
+
+// The id Steam gives a created trade offer, the same as in the SellTradeSent tickets
+pub type TradeOfferId = i64;
+
+/// The errors of sending a Steam trade offer
+#[derive(Debug, Clone, PartialEq)]
+pub enum SendTradeOfferError {
+    /// The inventory of the partner (or ours) is private, Steam can't see the items
+    InventoryPrivate,
+    /// One of the accounts has a trade ban or can't trade right now
+    TradeBan(String),
+    /// The offer would be held in escrow, the account has no mobile authenticator or it is too new
+    Escrow(String),
+    /// The cookie of the account is not valid anymore
+    AuthenticationFailed,
+    /// Steam refused the offer for another reason, has the strError of the response
+    Rejected(String),
+    Failed(String),
+}
+
+impl From<String> for SendTradeOfferError {
+    fn from(err: String) -> Self {
+        SendTradeOfferError::Failed(err)
+    }
+}
+
+impl std::fmt::Display for SendTradeOfferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendTradeOfferError::InventoryPrivate => write!(f, "The trade offer can't be sent, the inventory is private."),
+            SendTradeOfferError::TradeBan(err_str) => write!(f, "The trade offer can't be sent, the account can't trade: {}", err_str),
+            SendTradeOfferError::Escrow(err_str) => write!(f, "The trade offer would be held in escrow: {}", err_str),
+            SendTradeOfferError::AuthenticationFailed => write!(f, "The steam authentication is not working."),
+            SendTradeOfferError::Rejected(err_str) => write!(f, "Steam rejected the trade offer: {}", err_str),
+            SendTradeOfferError::Failed(err_str) => write!(f, "{}", err_str),
+        }
+    }
+}
+
+/// Retrieves the complete inventory of the user with the account the user id belongs to,
+/// the default account if it is not one of ours
+pub async fn get_inventory(user_id: SteamId) -> Result<HashMap<String, Item>, InventoryFetchError> {
+    let account = account_for(user_id).map_err(|_| InventoryFetchError::AuthenticationFailed)?;
+    get_inventory_for(&account, user_id).await
 }
 
 /// Retrieves and processes a user's complete Steam CS:GO inventory
 ///
+/// - The account authenticates the requests, user_id is the owner of the inventory
 /// - Handles paginated inventory retrieval for large inventories
 /// - Processes complex nested item data structures
 /// - Properly categorizes items by trade status
-pub async fn get_inventory(user_id: String) -> Result<HashMap<String, Item>, String> {
+pub async fn get_inventory_for(account: &SteamAccount, user_id: SteamId) -> Result<HashMap<String, Item>, InventoryFetchError> {
+    get_inventory_for_app(account, user_id, SteamApp::CS).await
+}
+
+/// Retrieves and processes a user's complete Steam inventory of the given app
+///
+/// - The ignored items come from the app config
+pub async fn get_inventory_for_app(account: &SteamAccount, user_id: SteamId, app: SteamApp) -> Result<HashMap<String, Item>, InventoryFetchError> {
     let mut inv: HashMap<String, Item> = HashMap::new();
-    let mut temp_data = InventoryRequestReturn {
-        total_count: 100,
-        id_data: Vec::new(),
-        names: Vec::new(),
-        tradable: Vec::new(),
-    };
-    let mut last_asset_id = "".to_string();
-    let storage_container_amount = 12;
 
     // Retrieve inventory in batches until we have all items
-    while temp_data.total_count - storage_container_amount > temp_data.names.len() as i32 {
-        let res = get_inventory_request(user_id.clone(), &last_asset_id).await;
-        if let Ok(mut data) = res {
-            last_asset_id = data.id_data[data.id_data.len()-1].assetid.clone();
-            temp_data.total_count = data.total_count;
-            temp_data.id_data.append(&mut data.id_data);
-            temp_data.names.append(&mut data.names);
-            temp_data.tradable.append(&mut data.tradable);
-        } else {
-            let err_str = res.unwrap_err();
-            return Err(format!("Fix steam cookie!!! {:?}", err_str));
+    let start = Instant::now();
+    let temp_data = get_inventory_all_pages(account, user_id, app).await;
+    metrics::observe_request("Steam", "inventory", start.elapsed());
+    let temp_data = temp_data.map_err(|e| {
+        if e == InventoryFetchError::AuthenticationFailed {
+            notifications::notify_auth_error("steam.rs | get_inventory()", "Fix steam cookie!!!");
         }
-    }
+        e
+    })?;
 
     // Process inventory data into a structured format
     for i in 0..temp_data.names.len() {
         let item_name = &temp_data.names[i];
         let ids = &temp_data.id_data[i];
         let tradable = &temp_data.tradable[i];
+        let unlock_unix = temp_data.unlock_unix[i];
+        let untradable = temp_data.untradable[i];
+        let inspect_link = temp_data.inspect_links[i].clone();
+        let exterior = WearTier::from_market_hash_name(item_name);
 
         // Skip items we don't want to track
-        let mut ignored = false;
-        for ignore_str in IGNORE {
-            if item_name.contains(ignore_str) {
-                ignored = true;
-            }
-        }
-        if ignored {
+        if is_ignored(app, item_name) {
             continue;
         }
         
@@ -116,262 +404,1711 @@ pub async fn get_inventory(user_id: String) -> Result<HashMap<String, Item>, Str
         if *tradable {
             // Item is available for trading
             entry.count.available += 1;
-            entry.data.push(ItemData{
-                asset_id: ids.assetid.clone(), 
-                instance_id: ids.instanceid.clone(), 
-                class_id: ids.classid.clone(), 
-                market: Market::Steam, 
-                status: ItemStatus::Available, 
-                marketcsgo_item_id: "".to_string(), 
-                trade_offer_id: "0".to_string(), 
-                dmarket_item_id: "0".to_string(), 
-                csmoney_item_id: "0".to_string(), 
-                csfloat_offer_id: "0".to_string(), 
-                timestamp_unix: None
-            });
+            entry.data.push(ItemData::builder()
+                .asset_id(ids.assetid.clone())
+                .instance_id(ids.instanceid.clone())
+                .class_id(ids.classid.clone())
+                .market(Market::Steam)
+                .status(ItemStatus::Available)
+                .exterior(exterior)
+                .inspect_link(inspect_link)
+                .build());
+        } else if untradable {
+            // Item can never be traded, no need to check its trade lock
+            entry.data.push(ItemData::builder()
+                .asset_id(ids.assetid.clone())
+                .instance_id(ids.instanceid.clone())
+                .class_id(ids.classid.clone())
+                .market(Market::Steam)
+                .status(ItemStatus::Untradable)
+                .exterior(exterior)
+                .inspect_link(inspect_link)
+                .build());
         } else {
-            // Item is on trade hold
+            // Item is on trade hold, the timestamp is when it unlocks
             entry.count.on_hold += 1;
-            entry.data.push(ItemData{
-                asset_id: ids.assetid.clone(), 
-                instance_id: ids.instanceid.clone(), 
-                class_id: ids.classid.clone(), 
-                market: Market::Steam, 
-                status: ItemStatus::OnHold, 
-                marketcsgo_item_id: "".to_string(), 
-                trade_offer_id: "0".to_string(), 
-                dmarket_item_id: "0".to_string(), 
-                csmoney_item_id: "0".to_string(), 
-                csfloat_offer_id: "0".to_string(), 
-                timestamp_unix: None
-            });
+            entry.data.push(ItemData::builder()
+                .asset_id(ids.assetid.clone())
+                .instance_id(ids.instanceid.clone())
+                .class_id(ids.classid.clone())
+                .market(Market::Steam)
+                .status(ItemStatus::OnHold)
+                .timestamp_unix(unlock_unix)
+                .exterior(exterior)
+                .inspect_link(inspect_link)
+                .build());
         }
     }
 
+    metrics::set_items_on_hold(inv.values().map(|item| item.count.on_hold as i64).sum());
+
     Ok(inv)
 }
 
+/// Retrieves the inventories of every account at the same time, keyed by the steam id of the account
+///
+/// - Every account gets its own task, the page requests still wait for one of the MAX_CONCURRENT_INVENTORY_REQUESTS permits
+/// - An account whose inventory can't be fetched is logged and left out
+pub async fn get_all_inventories(accounts: &[SteamAccount]) -> HashMap<String, HashMap<String, Item>> {
+    let handles: Vec<(String, tokio::task::JoinHandle<Result<HashMap<String, Item>, InventoryFetchError>>)> = accounts
+        .iter()
+        .map(|account| {
+            let account = account.clone();
+            let steam_id = account.steam_id.to_string();
+            let handle = tokio::spawn(async move { get_inventory_for(&account, account.steam_id).await });
+            (steam_id, handle)
+        })
+        .collect();
+
+    let mut res = HashMap::new();
+    for (steam_id, handle) in handles {
+        match handle.await {
+            Ok(Ok(inv)) => {
+                res.insert(steam_id, inv);
+            }
+            Ok(Err(e)) => log_functions::log_err(&format!(
+                "steam.rs | get_all_inventories() | Error occured when getting the inventory of {}. E: {}",
+                steam_id, e
+            )),
+            Err(e) => log_functions::log_err(&format!(
+                "steam.rs | get_all_inventories() | Error occured, the inventory task of {} panicked. E: {:?}",
+                steam_id, e
+            )),
+        }
+    }
+
+    res
+}
+
+/// Merges the inventories of several accounts into one
+///
+/// - The counts of the same item are summed and its data entries are put together, an asset id is only kept once
+/// - The prices of the first inventory that has them are kept, the histories are put together
+pub fn merge_inventories(inventories: impl IntoIterator<Item = HashMap<String, Item>>) -> HashMap<String, Item> {
+    let mut res: HashMap<String, Item> = HashMap::new();
+
+    for inv in inventories {
+        for (name, item) in inv {
+            let entry = match res.get_mut(&name) {
+                Some(entry) => entry,
+                None => {
+                    res.insert(name, item);
+                    continue;
+                }
+            };
+
+            entry.count.total += item.count.total;
+            entry.count.available += item.count.available;
+            entry.count.on_offer += item.count.on_offer;
+            entry.count.on_hold += item.count.on_hold;
+            entry.count.max_count = entry.count.max_count.max(item.count.max_count);
+
+            for data in item.data {
+                if !entry.data.iter().any(|existing| existing.asset_id == data.asset_id) {
+                    entry.data.push(data);
+                }
+            }
+            if entry.price.is_empty() {
+                entry.price = item.price;
+            }
+            entry.history.extend(item.history);
+        }
+    }
+
+    res
+}
+
 /// Checks for items that have completed their trade hold period
 ///
-/// - Identifies newly tradable items and changes their status
-/// - Creates status change tickets for trading system to process
-pub async fn check_trade_lock(user_id: String) -> Result<Vec<ItemStatusChangeTicket>, String> {
-    let mut tickets_vec: Vec<ItemStatusChangeTicket> = Vec::new();
-    let mut temp_data = InventoryRequestReturn {
-        total_count: 100,
-        id_data: Vec::new(),
-        names: Vec::new(),
-        tradable: Vec::new(),
-    };
-    let mut last_asset_id = "".to_string();
-    let storage_container_amount = 12;
+/// - Only looks at the assets that are tracked as OnHold, Untradable items are never polled
+/// - Only queries Steam if an unlock timestamp has passed (or an item has no timestamp)
+/// - Pushes TradeLockDone tickets to the ticket bus for the ones that became tradable
+/// - Returns the asset ids that are still locked so the next poll can be scheduled
+pub async fn check_trade_lock(user_id: SteamId, inv: &HashMap<String, Item>) -> Result<Vec<String>, String> {
+    let (on_hold, mut still_locked) = held_assets(inv, chrono::Utc::now().timestamp());
+
+    if on_hold.is_empty() {
+        return Ok(still_locked);
+    }
 
     // Retrieve complete inventory in batches
-    while temp_data.total_count - storage_container_amount > temp_data.names.len() as i32 {
-        let res = get_inventory_request(user_id.clone(), &last_asset_id).await;
-        if let Ok(mut data) = res {
-            last_asset_id = data.id_data[data.id_data.len()-1].assetid.clone();
-            temp_data.total_count = data.total_count;
-            temp_data.id_data.append(&mut data.id_data);
-            temp_data.names.append(&mut data.names);
-            temp_data.tradable.append(&mut data.tradable);
-        } else {
-            let err_str = res.unwrap_err();
-            if err_str.contains("Error occured while trying to parse the response body.") {
-                // The inventory has reached the end
-                break;
-            } 
-            return Err(err_str);
-        }
+    let account = account_for(user_id)?;
+    let temp_data = get_inventory_all_pages(&account, user_id, SteamApp::CS).await.map_err(|e| e.to_string())?;
+
+    // Push the status change tickets of the held items that are tradable now
+    let (unlocked, locked) = split_unlocked(&temp_data, &on_hold);
+    for asset_id in unlocked {
+        ticket_bus::push(ItemStatusChangeTicket::new(asset_id, ItemStatusChanges::TradeLockDone));
     }
+    still_locked.extend(locked);
 
-    // Find tradable items and create status change tickets
-    for i in 0..temp_data.names.len() {
-        let item_name = &temp_data.names[i];
-        let ids = &temp_data.id_data[i];
-        let tradable = &temp_data.tradable[i];
+    Ok(still_locked)
+}
+
+// Returns the OnHold assets whose trade hold should have ended by now (or have no unlock time)
+// and the ones that are still locked, every other status is left out
+fn held_assets(inv: &HashMap<String, Item>, now: i64) -> (HashSet<&str>, Vec<String>) {
+    let mut on_hold: HashSet<&str> = HashSet::new();
+    let mut still_locked: Vec<String> = Vec::new();
 
-        // Skip non-tradable items
-        let mut ignored = false;
-        for ignore_str in IGNORE {
-            if item_name.contains(ignore_str) {
-                ignored = true;
+    for data in inv.values().flat_map(|item| item.data.iter()) {
+        if data.status != ItemStatus::OnHold {
+            continue;
+        }
+        match data.timestamp_unix {
+            Some(unlock_unix) if unlock_unix > now => still_locked.push(data.asset_id.clone()),
+            _ => {
+                on_hold.insert(data.asset_id.as_str());
             }
         }
-        if ignored {
+    }
+
+    (on_hold, still_locked)
+}
+
+// Splits the held assets found in the live inventory into (tradable now, still locked)
+fn split_unlocked(temp_data: &InventoryRequestReturn, on_hold: &HashSet<&str>) -> (Vec<String>, Vec<String>) {
+    let mut unlocked: Vec<String> = Vec::new();
+    let mut locked: Vec<String> = Vec::new();
+
+    for (ids, tradable) in temp_data.id_data.iter().zip(temp_data.tradable.iter()) {
+        if !on_hold.contains(ids.assetid.as_str()) {
             continue;
         }
-               
-        // Create status change tickets for tradable items
         if *tradable {
-            let entry = ItemStatusChangeTicket{
-                asset_id: ids.assetid.clone(),
-                csfloat_offer_id: "0".to_string(), 
-                marketcsgo_item_id: "0".to_string(), 
-                dmarket_item_id: "0".to_string(), 
-                csmoney_item_id: "0".to_string(), 
-                change: ItemStatusChanges::TradeLockDone
-            };
-            tickets_vec.push(entry);
+            unlocked.push(ids.assetid.clone());
+        } else {
+            locked.push(ids.assetid.clone());
         }
     }
 
-    Ok(tickets_vec)
+    (unlocked, locked)
 }
 
-/// Internal function to handle inventory data retrieval and parsing
-async fn get_inventory_request(user_id: String, last_asset_id: &str) -> Result<InventoryRequestReturn, String> {
-    let res = steam_api::get_inventory(user_id.clone(), last_asset_id).await;
+/// The difference between the tracked inventory and the live Steam inventory
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InventoryDiff {
+    /// (asset_id, item name, status in the inventory) of the assets that are not tracked yet,
+    /// the status is Available, OnHold or Untradable
+    pub new_assets: Vec<(String, String, ItemStatus)>,
+    /// (asset_id, item name) of the tracked assets that are not in the inventory anymore
+    pub vanished_assets: Vec<(String, String)>,
+    /// (asset_id, item name, tradable now) of the assets whose tradable flag changed
+    pub tradable_flipped: Vec<(String, String, bool)>,
+}
 
-    // A hashmap that contains the classid as the key and the item name as the value
-    let mut name_map: HashMap<(String, String), (String, i32)> = HashMap::new();
-    
-    // The Result
-    let mut result: InventoryRequestReturn = InventoryRequestReturn{
-        total_count: 0,
-        id_data: Vec::new(),
-        names: Vec::new(),
-        tradable: Vec::new(),
-    };
+/// What the reconciliation changed in the tracked inventory
+#[derive(Debug, Clone, Default)]
+pub struct ReconciliationReport {
+    /// TradeLockDone tickets of the assets that became tradable
+    pub tickets: Vec<ItemStatusChangeTicket>,
+    /// (item name, removed data) of the tracked assets that are not in the inventory anymore
+    pub missing_items: Vec<(String, ItemData)>,
+}
 
-    match res {
-        Ok(val) => {
-            let parsed_data: Result<serde_json::Value, reqwest::Error> = val.json().await;
-
-            match parsed_data {
-                Ok(json) => {
-                    // Get the data from the json
-                    let total_count = &json["total_inventory_count"];
-                    let assets = &json["assets"];
-                    let descriptions = &json["descriptions"];
-
-                    // Break and return error if somehow the json is empty
-                    if assets == &Value::Null || total_count == &Value::Null || descriptions == &Value::Null {
-                        // Assume that we reached the end 
-                        Err(format!("steam.rs | get_inventory() | user_id = {} | Error occured while trying to parse the response body.", &user_id))
-                    } else {
-                        // Process the data if everything checks out
-                        let des_res: Vec<DescriptionsReturn> = serde_json::from_value(descriptions.clone()).unwrap();
-                        let inv_res: Vec<InventoryReturn> = serde_json::from_value(assets.clone()).unwrap(); 
-
-                        // Map all the names for classids
-                        for i in 0..des_res.len() {
-                            name_map.insert(
-                                (des_res[i].classid.clone(), des_res[i].instanceid.clone()), 
-                                (des_res[i].market_name.clone(), des_res[i].tradable.clone())
-                            );
-                        }
-
-                        // Find the total count and write it to the result 
-                        let total_c = &json["total_inventory_count"].as_i64();
-                        if let Some(n) = total_c {
-                            result.total_count = *n as i32;
-                        } else {
-                            return Err(format!("steam.rs | get_inventory() | user_id = {} | Error occured while trying to parse the response body. | Toal Count", &user_id));
-                        } 
-
-                        // Go through all the inv data and return
-                        for i in 0..inv_res.len() {
-                            let entry = &inv_res[i];
-                            if let Some(s) = name_map.get(&(entry.classid.clone(), entry.instanceid.clone())) {
-                                result.id_data.push(entry.clone());
-                                result.names.push(s.0.to_string());
-                                let tradable = if s.1 == 1 { true} else {false};
-                                result.tradable.push(tradable);
-                            } else {
-                                return Err(format!("steam.rs | get_inventory() | user_id = {} | Error occured while trying to parse the response body. | name_map", &user_id));
-                            }
-                        }
-
-                        return Ok(result);
-                    }
-                },
-                Err(e) => {
-                    Err(format!("steam.rs | get_inventory() | user_id = {} | Error occured while trying to parse the response body.| {}", user_id, e))
+/// Why a tracked asset is not in the Steam inventory anymore
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingItemReason {
+    /// Given away in a trade that gave nothing back, what a market sale looks like
+    Sold,
+    /// Given to one of our own accounts or exchanged for other items
+    TransferredOut,
+    /// Not in any recent trade, the tracking may be wrong and it has to be checked by hand
+    Unknown,
+}
+
+/// Compares the tracked inventory with the live Steam inventory
+///
+/// - Finds assets that appeared or disappeared outside the bot
+/// - Finds assets whose trade hold started or ended
+pub async fn diff_inventory(previous: &HashMap<String, Item>, user_id: SteamId) -> Result<InventoryDiff, String> {
+    let account = account_for(user_id)?;
+    let temp_data = get_inventory_all_pages(&account, user_id, SteamApp::CS).await.map_err(|e| e.to_string())?;
+
+    // The live inventory keyed by asset id
+    let mut current: HashMap<String, (String, ItemStatus)> = HashMap::new();
+    for i in 0..temp_data.names.len() {
+        let item_name = &temp_data.names[i];
+        if is_ignored(SteamApp::CS, item_name) {
+            continue;
+        }
+        let status = live_status(temp_data.tradable[i], temp_data.untradable[i]);
+        current.insert(temp_data.id_data[i].assetid.clone(), (item_name.clone(), status));
+    }
+
+    Ok(compute_inventory_diff(previous, &current))
+}
+
+/// The status of an asset of the live inventory, like get_inventory tracks it
+fn live_status(tradable: bool, untradable: bool) -> ItemStatus {
+    if tradable {
+        ItemStatus::Available
+    } else if untradable {
+        ItemStatus::Untradable
+    } else {
+        ItemStatus::OnHold
+    }
+}
+
+/// Compares the tracked inventory with the live inventory keyed by asset id
+fn compute_inventory_diff(previous: &HashMap<String, Item>, current: &HashMap<String, (String, ItemStatus)>) -> InventoryDiff {
+    let mut diff = InventoryDiff::default();
+    let mut tracked: HashMap<&str, (&str, bool)> = HashMap::new();
+
+    for item in previous.values() {
+        for data in item.data.iter().filter(|data| data.market == Market::Steam) {
+            let tradable = !matches!(data.status, ItemStatus::OnHold | ItemStatus::Untradable);
+            tracked.insert(&data.asset_id, (&item.name, tradable));
+
+            match current.get(&data.asset_id) {
+                None => diff.vanished_assets.push((data.asset_id.clone(), item.name.clone())),
+                Some((_, status_now)) if (*status_now == ItemStatus::Available) != tradable => {
+                    diff.tradable_flipped.push((data.asset_id.clone(), item.name.clone(), !tradable))
                 }
+                Some(_) => {}
             }
         }
-        Err(e) => {
-            Err(format!("steam.rs | get_inventory() | user_id = {} | Error occured while trying to get the inventory data.| {}", user_id, e))
+    }
+
+    for (asset_id, (name, status)) in current {
+        if !tracked.contains_key(asset_id.as_str()) {
+            diff.new_assets.push((asset_id.clone(), name.clone(), status.clone()));
         }
     }
+
+    diff
 }
 
-/// Accepts a trade offer and retrieves the received item's asset ID
+/// Applies an inventory diff to the tracked inventory
 ///
-/// - Fetches trade offer details to identify incoming items
-/// - Accepts the trade offer
-/// - Returns the new asset ID for inventory tracking
-pub async fn accept_trade_offer_get_asset_id(trade_offer_id: String) -> Result<String, String> {
-    // Get trade offer details to identify the item being received
-    let res = steam_api::get_trade_offer(trade_offer_id.clone())
-        .await
-        .map_err(|e| format!(
-            "steam.rs | accept_trade_offer_get_asset_id(tradeofferid={}) | Error occured when getting the trade offer. | {:?}", 
-            trade_offer_id.clone(), e
-        ))?;
+/// - Adds the new assets as Available, OnHold or Untradable, the Untradable ones are in no count bucket
+/// - Removes the vanished assets, they are returned as the missing items to be classified
+///   by review_missing_items
+/// - Returns TradeLockDone tickets for the assets that became tradable
+pub fn reconcile_inventory(inv: &mut HashMap<String, Item>, diff: &InventoryDiff) -> ReconciliationReport {
+    let mut report = ReconciliationReport::default();
 
-    let parsed_data: serde_json::Value = res.json()
-        .await
-        .map_err(|e| format!(
-            "steam.rs | accept_trade_offer_get_asset_id(tradeofferid={}) | Error occured when parsing the data into json. | {:?}", 
-            trade_offer_id.clone(), e
-        ))?;
+    for (asset_id, item_name, status) in &diff.new_assets {
+        let entry = inv.entry(item_name.to_string()).or_insert(Item{
+            name: item_name.to_string(),
+            count: ItemCount{total: 0, available: 0, on_offer: 0, on_hold: 0, max_count: 0},
+            data: Vec::new(),
+            price: Vec::new(),
+            history: Vec::new()
+        });
 
-    let offer_data: SteamTradeOfferData = serde_json::from_value(parsed_data["response"]["offer"].clone())
-        .map_err(|e| format!(
-            "steam.rs | accept_trade_offer_get_asset_id(tradeofferid={}) | Error occured when parsing the data into the data structre. | {:?}", 
-            trade_offer_id.clone(), e
-        ))?;
+        entry.count.total += 1;
+        match status {
+            ItemStatus::Available => entry.count.available += 1,
+            ItemStatus::OnHold => entry.count.on_hold += 1,
+            _ => {}
+        }
+        entry.data.push(ItemData::builder()
+            .asset_id(asset_id.clone())
+            .market(Market::Steam)
+            .status(status.clone())
+            .build());
+    }
 
-    if offer_data.items_to_receive.is_empty() {
-        return Err(format!(
-            "steam.rs | accept_trade_offer_get_asset_id(tradeofferid={}) | Error occured while the items_to_receive array is empty.", 
-            trade_offer_id.clone()
-        ));
+    for (asset_id, item_name) in &diff.vanished_assets {
+        if let Some(entry) = inv.get_mut(item_name) {
+            if let Some(pos) = entry.data.iter().position(|data| data.asset_id == *asset_id) {
+                let removed = entry.data.remove(pos);
+                entry.count.total -= 1;
+                match removed.status {
+                    ItemStatus::Available => entry.count.available -= 1,
+                    ItemStatus::OnHold => entry.count.on_hold -= 1,
+                    ItemStatus::Untradable => {}
+                    _ => entry.count.on_offer -= 1,
+                }
+                report.missing_items.push((item_name.clone(), removed));
+            }
+            if entry.data.is_empty() && entry.history.is_empty() {
+                inv.remove(item_name);
+            }
+        }
     }
 
-    // Extract the asset ID of the item we'll receive
-    let asset_id = offer_data.items_to_receive[0].assetid.clone();
+    for (asset_id, _, tradable_now) in &diff.tradable_flipped {
+        if *tradable_now {
+            report.tickets.push(ItemStatusChangeTicket::new(asset_id.clone(), ItemStatusChanges::TradeLockDone));
+        }
+    }
 
-    // Accept the trade offer
-    accept_trade_offer(trade_offer_id.clone()).await?;
+    report
+}
+
+/// Finds out why a tracked asset is not in the Steam inventory anymore
+///
+/// - Searches the last TRADE_HISTORY_MAX_TRADES trades of every account for the asset
+/// - A trade history that can't be read is logged and the asset is Unknown
+pub async fn classify_missing_item(asset_id: &str) -> MissingItemReason {
+    let trades = match get_all_trade_history().await {
+        Ok(trades) => trades,
+        Err(err_str) => {
+            log_functions::log_err(&err_str);
+            return MissingItemReason::Unknown;
+        }
+    };
 
-    Ok(asset_id)
+    classify_in_trade_history(&trades, asset_id)
 }
 
-/// Accepts a Steam trade offer
-pub async fn accept_trade_offer(trade_offer_id: String) -> Result<(), String> {
-    let res = steam_api::accept_trade_offer(&trade_offer_id)
-        .await
-        .map_err(|e| format!("Steam accept trade api error: {:?}", e))?;
-    
-    let status = res.status();
+/// Classifies the missing items of a reconciliation with a single read of the trade history
+///
+/// - The Unknown items are logged as possible data integrity issues to be reviewed by hand
+pub async fn review_missing_items(missing_items: &[(String, ItemData)]) -> Vec<(String, ItemData, MissingItemReason)> {
+    if missing_items.is_empty() {
+        return Vec::new();
+    }
+    let trades = match get_all_trade_history().await {
+        Ok(trades) => trades,
+        Err(err_str) => {
+            log_functions::log_err(&err_str);
+            Vec::new()
+        }
+    };
 
-    if status == 403 {
-        return Err("The steam authentication is not working.".to_string());
+    let mut res = Vec::new();
+    for (item_name, data) in missing_items {
+        let reason = classify_in_trade_history(&trades, &data.asset_id);
+        if reason == MissingItemReason::Unknown {
+            log_functions::log_structured(
+                "Error",
+                &format!(
+                    "Possible data integrity issue, {} ({}) left the inventory without a trade. Status: {:?}. Review it by hand.",
+                    item_name, data.asset_id, data.status
+                ),
+            );
+        }
+        res.push((item_name.clone(), data.clone(), reason));
     }
 
-    if status == 200 {
-        return Ok(());
-    } 
+    res
+}
 
-    Err(format!("The steam accept trade returned an error status: {}", status))
+/// Classifies an asset by the trade it was given away in
+fn classify_in_trade_history(trades: &[TradeHistoryReturn], asset_id: &str) -> MissingItemReason {
+    let trade = match trades.iter().find(|trade| trade.assets_given.iter().any(|asset| asset.assetid == asset_id)) {
+        Some(trade) => trade,
+        None => return MissingItemReason::Unknown,
+    };
+
+    let to_own_account = SteamId::parse(&trade.steamid_other)
+        .is_ok_and(|partner| config::get_bot_config().steam_accounts.iter().any(|account| account.steam_id == partner));
+    if to_own_account || !trade.assets_received.is_empty() {
+        MissingItemReason::TransferredOut
+    } else {
+        MissingItemReason::Sold
+    }
 }
 
-/// Retrieves the Steam Web API token needed for API operations
-pub async fn get_webapi() -> Result<String, String> {
-    let res = steam_api::get_steam_webapi()
+/// Retrieves the recent trades of every account, the default account if none are listed
+async fn get_all_trade_history() -> Result<Vec<TradeHistoryReturn>, String> {
+    let mut accounts = config::get_bot_config().steam_accounts.clone();
+    if accounts.is_empty() {
+        accounts.push(default_account()?);
+    }
+
+    let mut trades = Vec::new();
+    for account in accounts.iter() {
+        trades.extend(get_trade_history(account).await?);
+    }
+
+    Ok(trades)
+}
+
+/// Retrieves the last TRADE_HISTORY_MAX_TRADES trades of the account
+async fn get_trade_history(account: &SteamAccount) -> Result<Vec<TradeHistoryReturn>, String> {
+    let res = steam_api::get_trade_history(account, TRADE_HISTORY_MAX_TRADES)
         .await
-        .map_err(|e| format!("steam.rs | get_webapi() | Error occured when sending the api request. E: {:?}", e))?;
+        .map_err(|e| format!("steam.rs | get_trade_history(steam_id={}) | Error occured when sending the api request. E: {:?}", account.steam_id, e))?;
 
     let parsed_data: serde_json::Value = res.json()
         .await
-        .map_err(|e| format!("steam.rs | get_webapi() | Error occured while parsing the api request. E: {:?}", e))?;
+        .map_err(|e| format!("steam.rs | get_trade_history(steam_id={}) | Error occured when parsing the data into json. E: {:?}", account.steam_id, e))?;
 
-    if let Value::String(webapi) = &parsed_data["data"]["webapi_token"] {
-        return Ok(webapi.to_string());
+    // Steam leaves out the list when there are no trades
+    if parsed_data["response"]["trades"].is_null() {
+        return Ok(Vec::new());
+    }
+
+    serde_json::from_value(parsed_data["response"]["trades"].clone())
+        .map_err(|e| format!("steam.rs | get_trade_history(steam_id={}) | Error occured when parsing the trades into the data structre. E: {:?}", account.steam_id, e))
+}
+
+/// Checks if the item matches one of the ignored inventory patterns of the app
+fn is_ignored(app: SteamApp, item_name: &str) -> bool {
+    naming::matches_any(&config::get_bot_config().get_app_config(app).inventory_ignore, item_name)
+}
+
+/// Returns the inspect link of the description with the placeholders still in it
+fn inspect_link_template(des: &DescriptionsReturn) -> Option<String> {
+    des.actions
+        .iter()
+        .find(|action| action.link.contains("%assetid%") || action.name.to_lowercase().contains("inspect"))
+        .map(|action| action.link.clone())
+}
+
+/// Fills the owner and the asset id into the inspect link of a description
+fn inspect_link(template: &str, owner: SteamId, asset_id: &str) -> String {
+    template
+        .replace("%owner_steamid%", &owner.steam64().to_string())
+        .replace("%assetid%", asset_id)
+}
+
+/// Reads the trade hold end of an item from its description
+///
+/// - Prefers `cache_expiration`, falls back to the "Tradable/Marketable After" owner description
+/// - Returns None for tradable items and for items that can never be traded
+fn parse_unlock_timestamp(des: &DescriptionsReturn) -> Option<i64> {
+    if let Some(cache_expiration) = &des.cache_expiration {
+        if let Ok(unlock) = chrono::DateTime::parse_from_rfc3339(cache_expiration) {
+            return Some(unlock.timestamp());
+        }
+    }
+
+    // "Tradable/Marketable After Oct 23, 2025 (7:00:00) GMT"
+    for owner_des in des.owner_descriptions.iter() {
+        if let Some((_, date_str)) = owner_des.value.split_once("After ") {
+            let date_str = date_str.trim().trim_end_matches("GMT").trim();
+            if let Ok(unlock) = chrono::NaiveDateTime::parse_from_str(date_str, "%b %d, %Y (%H:%M:%S)") {
+                return Some(unlock.and_utc().timestamp());
+            }
+        }
+    }
+
+    None
+}
+
+/// Internal function to retrieve every page of the inventory
+async fn get_inventory_all_pages(account: &SteamAccount, user_id: SteamId, app: SteamApp) -> Result<InventoryRequestReturn, InventoryFetchError> {
+    pipeline_inventory_pages(user_id, |last_asset_id: String| async move {
+        get_inventory_page(account, user_id, &last_asset_id, app).await
+    })
+    .await
+}
+
+/// Fetches the pages with fetch_page and joins them
+///
+/// - The pages are fetched and parsed in a pipeline, the next page is requested while the last one is parsed
+/// - Waits out a rate limit once per fetch, a second rate limit is returned to the caller
+/// - An asset that is on more than one page is only kept once
+async fn pipeline_inventory_pages<F, Fut>(user_id: SteamId, mut fetch_page: F) -> Result<InventoryRequestReturn, InventoryFetchError>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<Value, InventoryFetchError>>,
+{
+    let (tx, mut rx) = mpsc::channel::<Result<Value, InventoryFetchError>>(INVENTORY_PIPELINE_DEPTH);
+
+    let fetch = async move {
+        let mut last_asset_id = "".to_string();
+        let mut fetched_assets = 0;
+        let mut rate_limit_retried = false;
+
+        loop {
+            let json = match fetch_page(last_asset_id.clone()).await {
+                Ok(json) => json,
+                Err(InventoryFetchError::RateLimited { retry_after_secs }) if !rate_limit_retried => {
+                    // Wait for the rate limit to end and retry the same page, unless the parsing already failed
+                    rate_limit_retried = true;
+                    tokio::select! {
+                        _ = tx.closed() => break,
+                        _ = sleep(Duration::from_secs(retry_after_secs)) => continue,
+                    }
+                }
+                Err(InventoryFetchError::EndOfInventory) => {
+                    // The inventory has reached the end
+                    break;
+                }
+                Err(e) => {
+                    let _ = tx.send(Err(e)).await;
+                    break;
+                }
+            };
+
+            fetched_assets += json["assets"].as_array().map(|assets| assets.len()).unwrap_or(0);
+            let next_asset_id = next_inventory_page(&json, fetched_assets);
+            if tx.send(Ok(json)).await.is_err() {
+                break;
+            }
+            match next_asset_id {
+                Some(asset_id) if asset_id != last_asset_id => last_asset_id = asset_id,
+                _ => break,
+            }
+        }
+    };
+
+    let parse = async move {
+        let mut temp_data = InventoryRequestReturn::new();
+        let mut seen_asset_ids: HashSet<String> = HashSet::new();
+
+        while let Some(page) = rx.recv().await {
+            temp_data.append_page(parse_inventory_page(user_id, &page?)?, &mut seen_asset_ids);
+        }
+
+        Ok::<InventoryRequestReturn, InventoryFetchError>(temp_data)
+    };
+
+    let ((), res) = tokio::join!(fetch, parse);
+    res
+}
+
+/// Returns the asset id the page after this one starts from, None if this was the last page
+///
+/// - Steam sends more_items and last_assetid while there are more pages
+/// - A page without them is continued from its last asset until fetched_assets reaches the total count,
+///   less the storage containers which are not sent
+fn next_inventory_page(json: &Value, fetched_assets: usize) -> Option<String> {
+    let storage_container_amount = 12;
+
+    if let Some(more_items) = json["more_items"].as_i64() {
+        if more_items != 1 {
+            return None;
+        }
+        if let Some(last_asset_id) = json["last_assetid"].as_str() {
+            return Some(last_asset_id.to_string());
+        }
+    }
+
+    let total_count = json["total_inventory_count"].as_i64()?;
+    if total_count - storage_container_amount <= fetched_assets as i64 {
+        return None;
+    }
+    json["assets"].as_array()?.last()?["assetid"].as_str().map(|asset_id| asset_id.to_string())
+}
+
+/// Internal function to fetch a page of the inventory, the body is parsed by parse_inventory_page
+async fn get_inventory_page(account: &SteamAccount, user_id: SteamId, last_asset_id: &str, app: SteamApp) -> Result<Value, InventoryFetchError> {
+    // Held until the body is read, so at most MAX_CONCURRENT_INVENTORY_REQUESTS pages are fetched at once
+    let _permit = INVENTORY_PERMITS
+        .get_or_init(|| Semaphore::new(MAX_CONCURRENT_INVENTORY_REQUESTS))
+        .acquire()
+        .await
+        .map_err(|e| InventoryFetchError::NetworkError(format!(
+            "steam.rs | get_inventory() | user_id = {} | Error occured while waiting for a request permit.| {}", user_id, e
+        )))?;
+
+    let res = steam_api::get_inventory_for_app(account, user_id, last_asset_id, app)
+        .await
+        .map_err(|e| InventoryFetchError::NetworkError(format!(
+            "steam.rs | get_inventory() | user_id = {} | Error occured while trying to get the inventory data.| {}", user_id, e
+        )))?;
+
+    // Check the status before parsing the body
+    let status = res.status();
+    if status == 429 {
+        let retry_after_secs = res
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .unwrap_or(DEFAULT_RATE_LIMIT_SECS);
+        return Err(InventoryFetchError::RateLimited { retry_after_secs });
+    }
+    if status == 401 || status == 403 {
+        return Err(InventoryFetchError::AuthenticationFailed);
+    }
+    // A 5xx or any other failed status has no inventory in its body, it is not the end of the inventory
+    if !status.is_success() {
+        return Err(InventoryFetchError::NetworkError(format!(
+            "steam.rs | get_inventory() | user_id = {} | Error occured, Steam answered with status {}.", user_id, status
+        )));
+    }
+
+    let json: serde_json::Value = res.json()
+        .await
+        .map_err(|e| InventoryFetchError::ParseError(format!(
+            "steam.rs | get_inventory() | user_id = {} | Error occured while trying to parse the response body.| {}", user_id, e
+        )))?;
+
+    // A successful page without assets means the inventory has reached the end, anything else is an error
+    if json["assets"] == Value::Null || json["total_inventory_count"] == Value::Null || json["descriptions"] == Value::Null {
+        if json["success"].as_i64() == Some(1) {
+            return Err(InventoryFetchError::EndOfInventory);
+        }
+        return Err(InventoryFetchError::ParseError(format!(
+            "steam.rs | get_inventory() | user_id = {} | Error occured, the page has no assets and is not successful. Body: {}", user_id, json
+        )));
+    }
+
+    Ok(json)
+}
+
+/// Internal function to parse a page of the inventory
+fn parse_inventory_page(user_id: SteamId, json: &Value) -> Result<InventoryRequestReturn, InventoryFetchError> {
+    // A hashmap that contains the classid as the key and the item name as the value
+    let mut name_map: HashMap<(String, String), (String, i32, Option<i64>, Option<String>)> = HashMap::new();
+    
+    // The Result
+    let mut result = InventoryRequestReturn::new();
+
+    // Get the data from the json
+    let assets = &json["assets"];
+    let descriptions = &json["descriptions"];
+
+    // Process the data if everything checks out
+    let des_res: Vec<DescriptionsReturn> = serde_json::from_value(descriptions.clone())
+        .map_err(|e| InventoryFetchError::ParseError(format!(
+            "steam.rs | get_inventory() | user_id = {} | Error occured while trying to parse the descriptions. | {}", user_id, e
+        )))?;
+    let inv_res: Vec<InventoryReturn> = serde_json::from_value(assets.clone())
+        .map_err(|e| InventoryFetchError::ParseError(format!(
+            "steam.rs | get_inventory() | user_id = {} | Error occured while trying to parse the assets. | {}", user_id, e
+        )))?;
+
+    // Map all the names for classids
+    for i in 0..des_res.len() {
+        name_map.insert(
+            (des_res[i].classid.clone(), des_res[i].instanceid.clone()), 
+            (des_res[i].market_name.clone(), des_res[i].tradable.clone(), parse_unlock_timestamp(&des_res[i]), inspect_link_template(&des_res[i]))
+        );
+    }
+
+    // Find the total count and write it to the result 
+    let total_c = &json["total_inventory_count"].as_i64();
+    if let Some(n) = total_c {
+        result.total_count = *n as i32;
+    } else {
+        return Err(InventoryFetchError::ParseError(format!("steam.rs | get_inventory() | user_id = {} | Error occured while trying to parse the response body. | Toal Count", &user_id)));
+    } 
+
+    // Go through all the inv data and return
+    for i in 0..inv_res.len() {
+        let entry = &inv_res[i];
+        if let Some(s) = name_map.get(&(entry.classid.clone(), entry.instanceid.clone())) {
+            result.id_data.push(entry.clone());
+            result.names.push(s.0.to_string());
+            let tradable = if s.1 == 1 { true} else {false};
+            result.tradable.push(tradable);
+            result.unlock_unix.push(s.2);
+            // A non-tradable item without an unlock date will never become tradable
+            result.untradable.push(!tradable && s.2.is_none());
+            result.inspect_links.push(s.3.as_ref().map(|template| inspect_link(template, user_id, &entry.assetid)));
+        } else {
+            return Err(InventoryFetchError::ParseError(format!("steam.rs | get_inventory() | user_id = {} | Error occured while trying to parse the response body. | name_map", &user_id)));
+        }
+    }
+
+    Ok(result)
+}
+
+/// Returns the default Steam account from the config, the one single account setups use
+pub fn default_account() -> Result<SteamAccount, String> {
+    config::get_bot_config()
+        .get_default_steam_account()
+        .cloned()
+        .ok_or("steam.rs | default_account() | Error occured, no Steam account is set in the config.".to_string())
+}
+
+/// Returns our account with the given id, the default account if the id is not one of ours
+fn account_for(user_id: SteamId) -> Result<SteamAccount, String> {
+    match config::get_bot_config().steam_accounts.iter().find(|account| account.steam_id == user_id) {
+        Some(account) => Ok(account.clone()),
+        None => default_account(),
+    }
+}
+
+/// The Steam accounts of the bot, used to spread the bought items and their trade holds
+#[derive(Debug, Clone, Default)]
+pub struct AccountPool {
+    accounts: Vec<SteamAccount>,
+}
+
+impl AccountPool {
+    pub fn new(accounts: Vec<SteamAccount>) -> Self {
+        AccountPool { accounts }
+    }
+
+    /// Builds the pool from the accounts in the config
+    pub fn from_config() -> Self {
+        AccountPool::new(config::get_bot_config().steam_accounts.clone())
+    }
+
+    pub fn accounts(&self) -> &[SteamAccount] {
+        &self.accounts
+    }
+
+    pub fn get(&self, steam_id: SteamId) -> Option<&SteamAccount> {
+        self.accounts.iter().find(|account| account.steam_id == steam_id)
+    }
+
+    /// Picks the account with the most free inventory slots
+    ///
+    /// - `inventories` has the tracked inventory of each account, an account without one counts as empty
+    /// - Returns None if every inventory is full
+    pub fn pick_most_free_slots(&self, inventories: &HashMap<SteamId, HashMap<String, Item>>) -> Option<&SteamAccount> {
+        self.accounts
+            .iter()
+            .map(|account| {
+                let used: i32 = inventories
+                    .get(&account.steam_id)
+                    .map(|inv| inv.values().map(|item| item.count.total as i32).sum())
+                    .unwrap_or(0);
+                (account, STEAM_INVENTORY_LIMIT - used)
+            })
+            .filter(|(_, free)| *free > 0)
+            .max_by_key(|(_, free)| *free)
+            .map(|(account, _)| account)
+    }
+
+    /// Picks the account with the fewest trade locked items
+    ///
+    /// - `inventories` has the tracked inventory of each account, an account without one has none on hold
+    pub fn pick_fewest_on_hold(&self, inventories: &HashMap<SteamId, HashMap<String, Item>>) -> Option<&SteamAccount> {
+        self.accounts.iter().min_by_key(|account| {
+            inventories
+                .get(&account.steam_id)
+                .map(|inv| inv.values().map(|item| item.count.on_hold as i32).sum::<i32>())
+                .unwrap_or(0)
+        })
+    }
+}
+
+/// Extracts the partner account id and the token from a Steam trade url
+///
+/// - The url is checked by `SteamId::from_trade_url`
+/// - Returns (partner, token)
+pub fn parse_trade_url(url: &str) -> Result<(String, String), String> {
+    let (partner, token) = SteamId::from_trade_url(url)
+        .map_err(|e| format!("steam.rs | parse_trade_url(url={}) | Error occured when parsing the trade url. E: {}", url, e))?;
+
+    Ok((partner.account_id().to_string(), token))
+}
+
+/// Retrieves the active sent and received trade offers of the default account
+pub async fn get_active_offers() -> Result<Vec<TradeOffer>, String> {
+    get_active_offers_for(&default_account()?).await
+}
+
+/// Retrieves the active sent and received trade offers of the account
+pub async fn get_active_offers_for(account: &SteamAccount) -> Result<Vec<TradeOffer>, String> {
+    let res = steam_api::get_trade_offers(account, true, true, true)
+        .await
+        .map_err(|e| format!("steam.rs | get_active_offers() | Error occured when sending the api request. E: {:?}", e))?;
+
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!("steam.rs | get_active_offers() | Error occured when parsing the data into json. E: {:?}", e))?;
+
+    let mut offers: Vec<TradeOffer> = Vec::new();
+    // Steam leaves out the lists that are empty
+    for list in ["trade_offers_sent", "trade_offers_received"] {
+        if parsed_data["response"][list].is_null() {
+            continue;
+        }
+
+        let offer_data: Vec<TradeOfferListReturn> = serde_json::from_value(parsed_data["response"][list].clone())
+            .map_err(|e| format!(
+                "steam.rs | get_active_offers() | Error occured when parsing {} into the data structre. E: {:?}",
+                list, e
+            ))?;
+
+        offers.extend(offer_data.into_iter().map(|offer| TradeOffer {
+            trade_offer_id: offer.tradeofferid,
+            partner: SteamId::from_account_id(offer.accountid_other),
+            items_to_give: offer.items_to_give.into_iter().map(|item| item.assetid).collect(),
+            items_to_receive: offer.items_to_receive.into_iter().map(|item| item.assetid).collect(),
+            state: TradeOfferState::from(offer.trade_offer_state),
+            is_our_offer: offer.is_our_offer,
+        }));
+    }
+
+    Ok(offers)
+}
+
+/// Waits until an active offer that gives us the asset arrives, returns its trade offer id
+///
+/// - Checks the active offers of the default account every TRADE_OFFER_POLL_SECS
+/// - A failed check is logged and retried, only the timeout ends the polling
+/// - Returns an error after timeout_secs or when the shutdown starts
+pub async fn poll_for_trade_offer(asset_id: &str, timeout_secs: u64) -> Result<String, String> {
+    let account = default_account()?;
+    let deadline = Instant::now() + Duration::from_secs(timeout_secs);
+    let token = control::shutdown_token();
+
+    loop {
+        match get_active_offers_for(&account).await {
+            Ok(offers) => {
+                let offer = offers.iter().find(|offer| {
+                    offer.state == TradeOfferState::Active && offer.items_to_receive.iter().any(|id| id == asset_id)
+                });
+                if let Some(offer) = offer {
+                    return Ok(offer.trade_offer_id.clone());
+                }
+            }
+            Err(err_str) => log_functions::log_err(&err_str),
+        }
+
+        if Instant::now() + Duration::from_secs(TRADE_OFFER_POLL_SECS) > deadline {
+            return Err(format!(
+                "steam.rs | poll_for_trade_offer(asset_id={}, timeout_secs={}) | Error occured, no trade offer with the asset arrived in time.",
+                asset_id, timeout_secs
+            ));
+        }
+        tokio::select! {
+            _ = token.cancelled() => {
+                return Err(format!(
+                    "steam.rs | poll_for_trade_offer(asset_id={}, timeout_secs={}) | Error occured, the shutdown started while polling.",
+                    asset_id, timeout_secs
+                ));
+            }
+            _ = sleep(Duration::from_secs(TRADE_OFFER_POLL_SECS)) => {}
+        }
+    }
+}
+
+/// Classifies a trade offer by the items that move and who created it
+pub fn classify_offer(offer: &TradeOffer) -> TradeOfferClass {
+    match (offer.items_to_give.is_empty(), offer.items_to_receive.is_empty()) {
+        (true, true) => TradeOfferClass::Empty,
+        (true, false) => TradeOfferClass::Incoming,
+        (false, false) => TradeOfferClass::Exchange,
+        (false, true) if offer.is_our_offer => TradeOfferClass::Outgoing,
+        (false, true) => TradeOfferClass::PossibleHijack,
+    }
+}
+
+/// Checks if an offer can be accepted by the accept loops, possible hijacks never are
+pub fn is_safe_to_accept(offer: &TradeOffer) -> bool {
+    offer.state == TradeOfferState::Active && classify_offer(offer) != TradeOfferClass::PossibleHijack
+}
+
+/// Checks if an offer is a market withdrawal, we give nothing and receive exactly one item
+pub fn is_single_item_withdrawal(offer: &TradeOffer) -> bool {
+    is_safe_to_accept(offer) && offer.items_to_give.is_empty() && offer.items_to_receive.len() == 1
+}
+
+/// Accepts a trade offer of the default account and retrieves the received item's asset ID
+pub async fn accept_trade_offer_get_asset_id(trade_offer_id: String) -> Result<String, String> {
+    accept_trade_offer_get_asset_id_for(&default_account()?, trade_offer_id).await
+}
+
+/// Accepts a trade offer and retrieves the received item's asset ID
+///
+/// - Returns the asset ID of the first received item in our inventory, see `accept_trade_offer_get_asset_ids_for`
+pub async fn accept_trade_offer_get_asset_id_for(account: &SteamAccount, trade_offer_id: String) -> Result<String, String> {
+    let received = accept_trade_offer_get_asset_ids_for(account, trade_offer_id).await?;
+    Ok(received[0].1.clone())
+}
+
+/// Accepts a trade offer of the default account and retrieves the asset IDs of every received item
+pub async fn accept_trade_offer_get_asset_ids(trade_offer_id: String) -> Result<Vec<(String, String)>, String> {
+    accept_trade_offer_get_asset_ids_for(&default_account()?, trade_offer_id).await
+}
+
+/// Accepts a trade offer and retrieves the asset IDs of every received item
+///
+/// - Fetches trade offer details to identify incoming items
+/// - Accepts the trade offer
+/// - Returns (asset ID in the offer, asset ID in our inventory) for every received item
+/// - The new asset IDs come from the status of the accepted trade, when Steam doesn't have it yet
+///   (e.g. the trade is in escrow) the asset IDs of the offer are returned for both
+pub async fn accept_trade_offer_get_asset_ids_for(account: &SteamAccount, trade_offer_id: String) -> Result<Vec<(String, String)>, String> {
+    // Get trade offer details to identify the items being received
+    let offer_data = get_trade_offer_data(account, &trade_offer_id).await?;
+
+    if offer_data.items_to_receive.is_empty() {
+        return Err(format!(
+            "steam.rs | accept_trade_offer_get_asset_ids(tradeofferid={}) | Error occured while the items_to_receive array is empty.", 
+            trade_offer_id
+        ));
+    }
+    // Receiving items never needs giving any
+    guard_items_to_give(&offer_data, &[])?;
+
+    // Accept the trade offer
+    send_accept(account, &trade_offer_id).await?;
+
+    let received = match get_received_asset_ids(account, &trade_offer_id).await {
+        Ok(received) => received,
+        Err(err_str) => {
+            log_functions::log_structured("Warning", &err_str);
+            Vec::new()
+        }
+    };
+
+    Ok(offer_data
+        .items_to_receive
+        .iter()
+        .map(|item| {
+            let new_asset_id = received
+                .iter()
+                .find(|asset| asset.assetid == item.assetid)
+                .map(|asset| asset.new_assetid.clone())
+                .unwrap_or(item.assetid.clone());
+            (item.assetid.clone(), new_asset_id)
+        })
+        .collect())
+}
+
+/// Retrieves and parses the details of a trade offer
+async fn get_trade_offer_data(account: &SteamAccount, trade_offer_id: &str) -> Result<SteamTradeOfferData, String> {
+    let res = steam_api::get_trade_offer(account, trade_offer_id.to_string())
+        .await
+        .map_err(|e| format!(
+            "steam.rs | get_trade_offer_data(tradeofferid={}) | Error occured when getting the trade offer. | {:?}", 
+            trade_offer_id, e
+        ))?;
+
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!(
+            "steam.rs | get_trade_offer_data(tradeofferid={}) | Error occured when parsing the data into json. | {:?}", 
+            trade_offer_id, e
+        ))?;
+
+    serde_json::from_value(parsed_data["response"]["offer"].clone())
+        .map_err(|e| format!(
+            "steam.rs | get_trade_offer_data(tradeofferid={}) | Error occured when parsing the data into the data structre. | {:?}", 
+            trade_offer_id, e
+        ))
+}
+
+/// Retrieves the assets received in the trade of an accepted trade offer with their new asset IDs
+async fn get_received_asset_ids(account: &SteamAccount, trade_offer_id: &str) -> Result<Vec<TradeStatusAssetReturn>, String> {
+    // The trade id is only set once the offer is accepted
+    let trade_id = get_trade_offer_data(account, trade_offer_id)
+        .await?
+        .tradeid
+        .ok_or(format!(
+            "steam.rs | get_received_asset_ids(tradeofferid={}) | Error occured, the accepted offer has no trade id yet.",
+            trade_offer_id
+        ))?;
+
+    let res = steam_api::get_trade_status(account, &trade_id)
+        .await
+        .map_err(|e| format!(
+            "steam.rs | get_received_asset_ids(tradeofferid={}) | Error occured when getting the trade status. | {:?}",
+            trade_offer_id, e
+        ))?;
+
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!(
+            "steam.rs | get_received_asset_ids(tradeofferid={}) | Error occured when parsing the data into json. | {:?}",
+            trade_offer_id, e
+        ))?;
+
+    // A trade without received assets has no assets_received array
+    match &parsed_data["response"]["trades"][0]["assets_received"] {
+        Value::Null => Ok(Vec::new()),
+        assets => serde_json::from_value(assets.clone())
+            .map_err(|e| format!(
+                "steam.rs | get_received_asset_ids(tradeofferid={}) | Error occured when parsing the data into the data structre. | {:?}",
+                trade_offer_id, e
+            )),
+    }
+}
+
+/// Accepts a Steam trade offer of the default account, see `accept_trade_offer_for`
+pub async fn accept_trade_offer(trade_offer_id: String, allowed_give: &[String]) -> Result<(), String> {
+    accept_trade_offer_for(&default_account()?, trade_offer_id, allowed_give).await
+}
+
+/// Accepts a Steam trade offer after checking what it takes from us
+///
+/// - The offer is fetched first, an offer that gives away any asset that isn't in allowed_give
+///   is refused with a notification, pass an empty slice for the offers that should only give us items
+pub async fn accept_trade_offer_for(account: &SteamAccount, trade_offer_id: String, allowed_give: &[String]) -> Result<(), String> {
+    let offer_data = get_trade_offer_data(account, &trade_offer_id).await?;
+    guard_items_to_give(&offer_data, allowed_give)?;
+
+    send_accept(account, &trade_offer_id).await
+}
+
+/// Checks that the offer gives away only the expected assets
+///
+/// - Every asset in items_to_give has to be in allowed_give, an empty allowed_give allows no giving at all
+/// - Returns the asset ids that are given away without being expected, empty if the offer is safe
+pub fn unexpected_items_to_give(items_to_give: &[String], allowed_give: &[String]) -> Vec<String> {
+    items_to_give
+        .iter()
+        .filter(|asset_id| !allowed_give.contains(asset_id))
+        .cloned()
+        .collect()
+}
+
+/// Refuses the offer with a notification if it gives away an asset that isn't expected
+fn guard_items_to_give(offer_data: &SteamTradeOfferData, allowed_give: &[String]) -> Result<(), String> {
+    let unexpected = unexpected_items_to_give(&offer_data.give_asset_ids(), allowed_give);
+    if unexpected.is_empty() {
+        return Ok(());
+    }
+
+    let err_str = format!(
+        "steam.rs | guard_items_to_give(tradeofferid={}) | Error occured, refused the offer, it gives away the unexpected assets {:?}.",
+        offer_data.tradeofferid, unexpected
+    );
+    log_functions::log_structured("Error", &err_str);
+    notifications::notify(format!(
+        "🚨 Refused trade offer {}\nIt takes {} item(s) we didn't agree to give: {:?}\nCheck the account, it may be compromised",
+        offer_data.tradeofferid,
+        unexpected.len(),
+        unexpected
+    ));
+    Err(err_str)
+}
+
+/// Sends the accept of a trade offer whose items were checked
+async fn send_accept(account: &SteamAccount, trade_offer_id: &str) -> Result<(), String> {
+    let _critical = control::begin_critical(CriticalOp::TradeAccept, trade_offer_id);
+    let res = steam_api::accept_trade_offer(account, trade_offer_id)
+        .await
+        .map_err(|e| format!("Steam accept trade api error: {:?}", e))?;
+    
+    let status = res.status();
+
+    if status == 403 {
+        notifications::notify_auth_error("steam.rs | accept_trade_offer()", "The steam authentication is not working.");
+        return Err("The steam authentication is not working.".to_string());
+    }
+
+    if status == 200 {
+        return Ok(());
+    } 
+
+    Err(format!("The steam accept trade returned an error status: {}", status))
+}
+
+/// Retrieves the Steam Web API token of the default account
+pub async fn get_webapi() -> Result<String, String> {
+    get_webapi_for(&default_account()?).await
+}
+
+/// Retrieves the Steam Web API token needed for API operations
+pub async fn get_webapi_for(account: &SteamAccount) -> Result<String, String> {
+    let res = steam_api::get_steam_webapi(account)
+        .await
+        .map_err(|e| format!("steam.rs | get_webapi() | Error occured when sending the api request. E: {:?}", e))?;
+
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!("steam.rs | get_webapi() | Error occured while parsing the api request. E: {:?}", e))?;
+
+    if let Value::String(webapi) = &parsed_data["data"]["webapi_token"] {
+        return Ok(webapi.to_string());
+    }
+    
+    Err("steam.rs | get_webapi() | The cookie is not valid to get the token.".to_string())
+}
+
+/// Sends a trade offer from the default account, see `send_trade_offer_for`
+pub async fn send_trade_offer(partner: SteamId, partner_token: &str, message: &str, items: &str) -> Result<TradeOfferId, SendTradeOfferError> {
+    send_trade_offer_for(&default_account()?, partner, partner_token, message, items).await
+}
+
+/// Sends a trade offer that gives the items (a json array of assets) to the partner, returns the id of the created offer
+///
+/// - Steam answers some refusals with a 200 status and the reason in strError, they are mapped with `parse_send_trade_offer_response`
+pub async fn send_trade_offer_for(
+    account: &SteamAccount,
+    partner: SteamId,
+    partner_token: &str,
+    message: &str,
+    items: &str,
+) -> Result<TradeOfferId, SendTradeOfferError> {
+    let res = steam_api::send_trade_offer(account, partner, partner_token, message, items)
+        .await
+        .map_err(|e| format!("steam.rs | send_trade_offer(partner={}) | Error occured when sending the api request. E: {:?}", partner, e))?;
+
+    let status = res.status();
+    if status == 401 || status == 403 {
+        notifications::notify_auth_error("steam.rs | send_trade_offer()", "The steam authentication is not working.");
+        return Err(SendTradeOfferError::AuthenticationFailed);
+    }
+
+    let body = res.text()
+        .await
+        .map_err(|e| format!("steam.rs | send_trade_offer(partner={}) | Error occured when reading the response body. E: {:?}", partner, e))?;
+
+    parse_send_trade_offer_response(&body)
+}
+
+/// Parses the response of a sent trade offer into the id of the created offer
+///
+/// - A body with a tradeofferid is a created offer, a pending mobile or email confirmation doesn't change that
+/// - The strError of Steam is mapped to the inventory privacy, trade ban and escrow errors, the rest is `Rejected`
+pub fn parse_send_trade_offer_response(body: &str) -> Result<TradeOfferId, SendTradeOfferError> {
+    let parsed_data: Value = serde_json::from_str(body)
+        .map_err(|e| format!("steam.rs | parse_send_trade_offer_response() | Error occured when parsing the body into json. E: {:?}. Body: {:?}", e, body))?;
+
+    if let Some(err_str) = parsed_data["strError"].as_str() {
+        let lower = err_str.to_lowercase();
+        return Err(if lower.contains("inventory is private") || lower.contains("privacy") {
+            SendTradeOfferError::InventoryPrivate
+        } else if lower.contains("trade ban") || lower.contains("not allowed to trade") || lower.contains("trade restriction") {
+            SendTradeOfferError::TradeBan(err_str.to_string())
+        } else if lower.contains("escrow") || lower.contains("will be held") || lower.contains("mobile authenticator") {
+            SendTradeOfferError::Escrow(err_str.to_string())
+        } else {
+            SendTradeOfferError::Rejected(err_str.to_string())
+        });
+    }
+
+    let trade_offer_id = match &parsed_data["tradeofferid"] {
+        Value::String(trade_offer_id) => trade_offer_id.parse::<TradeOfferId>().ok(),
+        Value::Number(trade_offer_id) => trade_offer_id.as_i64(),
+        _ => None,
+    };
+    trade_offer_id.ok_or(SendTradeOfferError::Failed(format!(
+        "steam.rs | parse_send_trade_offer_response() | Error occured, the trade offer was not created. Parsed data: {:?}",
+        parsed_data
+    )))
+}
+
+/// Cancels an outgoing Steam trade offer of the default account
+pub async fn cancel_trade_offer(trade_offer_id: String) -> Result<(), String> {
+    cancel_trade_offer_for(&default_account()?, trade_offer_id).await
+}
+
+/// Cancels an outgoing Steam trade offer
+pub async fn cancel_trade_offer_for(account: &SteamAccount, trade_offer_id: String) -> Result<(), String> {
+    let res = steam_api::cancel_trade_offer(account, &trade_offer_id)
+        .await
+        .map_err(|e| format!("Steam cancel trade api error: {:?}", e))?;
+
+    let status = res.status();
+
+    if status == 403 {
+        notifications::notify_auth_error("steam.rs | cancel_trade_offer()", "The steam authentication is not working.");
+        return Err("The steam authentication is not working.".to_string());
+    }
+
+    if status != 200 {
+        return Err(format!("The steam cancel trade returned an error status: {}", status));
+    }
+
+    // Steam answers a successful cancel with the id of the canceled offer
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!(
+            "steam.rs | cancel_trade_offer(tradeofferid={}) | Error occured when parsing the data into json. | {:?}",
+            trade_offer_id, e
+        ))?;
+
+    if let Value::String(canceled_id) = &parsed_data["tradeofferid"] {
+        if *canceled_id == trade_offer_id {
+            return Ok(());
+        }
+    }
+
+    Err(format!(
+        "steam.rs | cancel_trade_offer(tradeofferid={}) | Error occured, the trade offer was not canceled. Parsed data: {:?}",
+        trade_offer_id, parsed_data
+    ))
+}
+
+/// Cancels the Steam trade of an item that was already sent to a buyer
+///
+/// - Used by `ItemDataFunctions::remove_sell` when the sell trade is already out
+/// - Returns a `SellTradeCanceled` ticket which moves the item back to `Available`
+pub async fn cancel_sell_trade(item: &ItemData) -> Result<ItemStatusChangeTicket, String> {
+    if item.status != ItemStatus::OnSellOfferWaitingTrade {
+        return Err(format!(
+            "steam.rs | cancel_sell_trade(asset_id={}) | Error occured, the item has no sent sell trade. Status: {:?}",
+            item.asset_id, item.status
+        ));
+    }
+
+    cancel_trade_offer(item.trade_offer_id.clone()).await?;
+
+    Ok(ItemStatusChangeTicket{
+        market_ids: item.market_ids.clone(),
+        asset_id: item.asset_id.clone(),
+        change: ItemStatusChanges::SellTradeCanceled
+    })
+}
+
+/// Parses a Steam money string like "$1,234.56" or "1.234,56€" into dollars
+fn parse_steam_money(money_str: &str) -> Option<f32> {
+    let cleaned: String = money_str.chars().filter(|c| c.is_ascii_digit() || *c == '.' || *c == ',').collect();
+
+    // The last separator followed by two digits is the decimal one, the rest group the thousands
+    let normalized = match cleaned.rfind(|c: char| c == '.' || c == ',') {
+        Some(pos) if cleaned.len() - pos - 1 == 2 => {
+            let (whole, fraction) = cleaned.split_at(pos);
+            format!("{}.{}", whole.replace(['.', ','], ""), &fraction[1..])
+        }
+        _ => cleaned.replace(['.', ','], ""),
+    };
+    normalized.parse::<f32>().ok()
+}
+
+/// Parses the Steam volume string like "1,234" into a count
+fn parse_steam_volume(volume_str: &str) -> Option<u32> {
+    let digits: String = volume_str.chars().filter(|c| c.is_ascii_digit()).collect();
+    digits.parse::<u32>().ok()
+}
+
+/// Retrieves the Steam Community Market price of a CS item
+///
+/// - The items can't be sold out of Steam, the price is a liquidity signal and a sanity check of the other markets
+/// - `price_buy` is the lowest listing, `price_sell` the median sale price (the lowest listing if there were no sales)
+/// - The seller gets the price without the Steam and game fees (about 13% of the buyer price, 15% on top of the seller price)
+/// - Returns an error if Steam rate limits the request, the proxies and the retry backoff should keep it rare
+pub async fn get_item_price(market_hash_name: String) -> Result<Price, String> {
+    let res = steam_api::get_market_price(&market_hash_name, STEAM_CURRENCY_USD)
+        .await
+        .map_err(|e| format!(
+            "steam.rs | get_item_price(market_hash_name={}) | Error occured when sending the api request. E: {:?}",
+            market_hash_name, e
+        ))?;
+
+    if res.status() == 429 {
+        return Err(format!(
+            "steam.rs | get_item_price(market_hash_name={}) | Error occured, Steam rate limited the request. Retry-After: {:?}",
+            market_hash_name, res.headers().get(RETRY_AFTER)
+        ));
+    }
+
+    let overview: PriceOverviewReturn = res.json()
+        .await
+        .map_err(|e| format!(
+            "steam.rs | get_item_price(market_hash_name={}) | Error occured when parsing the api request to data structre. E: {:?}",
+            market_hash_name, e
+        ))?;
+
+    if !overview.success {
+        return Err(format!(
+            "steam.rs | get_item_price(market_hash_name={}) | Error occured, Steam has no price for the item. Overview: {:?}",
+            market_hash_name, overview
+        ));
+    }
+
+    let price = overview
+        .lowest_price
+        .as_deref()
+        .and_then(parse_steam_money)
+        .ok_or(format!(
+            "steam.rs | get_item_price(market_hash_name={}) | Error occured, the item has no lowest price. Overview: {:?}",
+            market_hash_name, overview
+        ))?;
+    let price_sell = overview.median_price.as_deref().and_then(parse_steam_money).unwrap_or(price);
+
+    let comms = data::get_market_commisions(Market::Steam, "")
+        .map_err(|e| format!(
+            "steam.rs | get_item_price(market_hash_name={}) | Error occured when trying to get the commisions of the market. E: {:?}",
+            market_hash_name, e
+        ))?;
+
+    // The Steam fees are added on top of what the seller gets, so the buyer price is divided by them
+    let price_buy_w_comm: f32 = ((price / ((100 - comms.0) as f32 / 100.0)) * 100.0).ceil() / 100.0;
+    let price_sell_w_comm: f32 = ((price_sell / (1.0 + ((comms.1 + comms.2) as f32 / 100.0))) * 100.0).floor() / 100.0;
+
+    Ok(Price {
+        market: Market::Steam,
+        commision: comms.1 + comms.2,
+        price_buy: price,
+        price_buy_trade: (price, price, price),
+        price_buy_w_comm,
+        price_sell_w_comm,
+        price_buy_trade_w_comm: (price_buy_w_comm, price_buy_w_comm, price_buy_w_comm),
+        price_sell,
+        sale_stats: None,
+        fetched_at_unix: chrono::Utc::now().timestamp(),
+        volume_24h: overview.volume.as_deref().and_then(parse_steam_volume),
+        // Requested with STEAM_CURRENCY_USD
+        currency: Currency::USD,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const REDLINE: &str = "AK-47 | Redline (Field-Tested)";
+    const ASIIMOV: &str = "AWP | Asiimov (Field-Tested)";
+    const SOUVENIR: &str = "Paris 2023 Mirage Souvenir Package";
+
+    fn owner() -> SteamId {
+        SteamId::from_account_id(1)
+    }
+
+    // Two inventory pages, the held Asiimov is sent again at the start of the second one
+    fn fixture_pages() -> (Value, Value) {
+        let descriptions = json!([
+            {"classid": "1", "instanceid": "0", "market_name": REDLINE, "tradable": 1},
+            {"classid": "2", "instanceid": "0", "market_name": ASIIMOV, "tradable": 0, "cache_expiration": "2030-01-01T00:00:00Z"},
+            {"classid": "3", "instanceid": "0", "market_name": SOUVENIR, "tradable": 0},
+        ]);
+        let asset = |asset_id: &str, class_id: &str| json!({
+            "amount": "1", "appid": 730, "assetid": asset_id, "classid": class_id, "contextid": "2", "instanceid": "0"
+        });
+
+        let first = json!({
+            "assets": [asset("101", "1"), asset("102", "2")],
+            "descriptions": descriptions,
+            "total_inventory_count": 3,
+            "more_items": 1,
+            "last_assetid": "102",
+            "success": 1,
+        });
+        let second = json!({
+            "assets": [asset("102", "2"), asset("103", "3")],
+            "descriptions": descriptions,
+            "total_inventory_count": 3,
+            "success": 1,
+        });
+        (first, second)
+    }
+
+    fn fixture_inventory() -> InventoryRequestReturn {
+        let (first, second) = fixture_pages();
+        let mut temp_data = InventoryRequestReturn::new();
+        let mut seen_asset_ids = HashSet::new();
+        temp_data.append_page(parse_inventory_page(owner(), &first).unwrap(), &mut seen_asset_ids);
+        temp_data.append_page(parse_inventory_page(owner(), &second).unwrap(), &mut seen_asset_ids);
+        temp_data
+    }
+
+    fn tracked(name: &str, assets: &[(&str, ItemStatus)]) -> Item {
+        Item {
+            name: name.to_string(),
+            count: ItemCount { total: assets.len() as i16, available: 0, on_offer: 0, on_hold: 0, max_count: 0 },
+            data: assets
+                .iter()
+                .map(|(asset_id, status)| ItemData::builder().asset_id(asset_id.to_string()).market(Market::Steam).status(status.clone()).build())
+                .collect(),
+            price: Vec::new(),
+            history: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn pages_are_joined_without_the_repeated_asset() {
+        let temp_data = fixture_inventory();
+
+        let asset_ids: Vec<&str> = temp_data.id_data.iter().map(|entry| entry.assetid.as_str()).collect();
+        assert_eq!(asset_ids, vec!["101", "102", "103"]);
+        assert_eq!(temp_data.names, vec![REDLINE, ASIIMOV, SOUVENIR]);
+        assert_eq!(temp_data.tradable, vec![true, false, false]);
+        assert_eq!(temp_data.untradable, vec![false, false, true]);
+        assert_eq!(temp_data.unlock_unix[1], Some(1893456000));
+        assert_eq!(temp_data.total_count, 3);
+    }
+
+    #[test]
+    fn next_page_follows_last_assetid_and_stops_without_more_items() {
+        let (first, second) = fixture_pages();
+
+        assert_eq!(next_inventory_page(&first, 2), Some("102".to_string()));
+        // No more_items and the fetched assets already reach the total count less the storage containers
+        assert_eq!(next_inventory_page(&second, 4), None);
+    }
+
+    #[test]
+    fn page_with_an_unknown_class_is_a_parse_error() {
+        let (mut first, _) = fixture_pages();
+        first["assets"][0]["classid"] = json!("9");
+
+        assert!(matches!(parse_inventory_page(owner(), &first), Err(InventoryFetchError::ParseError(_))));
+    }
+
+    #[test]
+    fn trade_lock_only_unlocks_the_held_assets_of_a_mixed_inventory() {
+        let now = 1_700_000_000;
+        let mut held_done = tracked(ASIIMOV, &[("102", ItemStatus::OnHold)]);
+        held_done.data[0].timestamp_unix = Some(now - 60);
+        let mut held_later = tracked(ASIIMOV, &[("105", ItemStatus::OnHold)]);
+        held_later.data[0].timestamp_unix = Some(now + 3600);
+        held_done.data.push(held_later.data.remove(0));
+
+        let inv = HashMap::from([
+            (ASIIMOV.to_string(), held_done),
+            // Tradable in Steam but not held, listed or untradable, no ticket for them
+            (REDLINE.to_string(), tracked(REDLINE, &[("101", ItemStatus::Available), ("106", ItemStatus::OnSellOfferWaitingBuyer)])),
+            (SOUVENIR.to_string(), tracked(SOUVENIR, &[("103", ItemStatus::Untradable)])),
+        ]);
+
+        let (on_hold, still_locked) = held_assets(&inv, now);
+        assert_eq!(on_hold, HashSet::from(["102"]));
+        assert_eq!(still_locked, vec!["105".to_string()]);
+
+        let mut temp_data = fixture_inventory();
+        // The hold of 102 ended since the fixture was taken
+        temp_data.tradable[1] = true;
+        let (unlocked, locked) = split_unlocked(&temp_data, &on_hold);
+        assert_eq!(unlocked, vec!["102".to_string()]);
+        assert!(locked.is_empty());
+
+        temp_data.tradable[1] = false;
+        let (unlocked, locked) = split_unlocked(&temp_data, &on_hold);
+        assert!(unlocked.is_empty());
+        assert_eq!(locked, vec!["102".to_string()]);
+    }
+
+    #[test]
+    fn fixture_diff_finds_new_vanished_and_flipped_assets() {
+        let temp_data = fixture_inventory();
+        let current: HashMap<String, (String, ItemStatus)> = (0..temp_data.names.len())
+            .map(|i| {
+                let status = live_status(temp_data.tradable[i], temp_data.untradable[i]);
+                (temp_data.id_data[i].assetid.clone(), (temp_data.names[i].clone(), status))
+            })
+            .collect();
+
+        let previous = HashMap::from([
+            // Tracked as held, Steam says it is still held
+            (ASIIMOV.to_string(), tracked(ASIIMOV, &[("102", ItemStatus::OnHold)])),
+            // 100 was sold outside the bot
+            (REDLINE.to_string(), tracked(REDLINE, &[("101", ItemStatus::Available), ("100", ItemStatus::Available)])),
+        ]);
+
+        let diff = compute_inventory_diff(&previous, &current);
+
+        // The souvenir package can never be traded, it is not a trade hold
+        assert_eq!(diff.new_assets, vec![("103".to_string(), SOUVENIR.to_string(), ItemStatus::Untradable)]);
+        assert_eq!(diff.vanished_assets, vec![("100".to_string(), REDLINE.to_string())]);
+        assert!(diff.tradable_flipped.is_empty());
+    }
+
+    #[test]
+    fn reconcile_applies_the_diff_to_the_tracked_inventory() {
+        let mut inv = HashMap::from([(ASIIMOV.to_string(), tracked(ASIIMOV, &[("102", ItemStatus::OnHold), ("104", ItemStatus::Available)]))]);
+        inv.get_mut(ASIIMOV).unwrap().count = ItemCount { total: 2, available: 1, on_offer: 0, on_hold: 1, max_count: 0 };
+
+        let diff = InventoryDiff {
+            new_assets: vec![
+                ("101".to_string(), REDLINE.to_string(), ItemStatus::Available),
+                ("103".to_string(), SOUVENIR.to_string(), ItemStatus::Untradable),
+                ("105".to_string(), ASIIMOV.to_string(), ItemStatus::OnHold),
+            ],
+            vanished_assets: vec![("104".to_string(), ASIIMOV.to_string())],
+            tradable_flipped: vec![("102".to_string(), ASIIMOV.to_string(), true)],
+        };
+        let report = reconcile_inventory(&mut inv, &diff);
+
+        assert_eq!(inv[REDLINE].count.available, 1);
+        assert_eq!(inv[REDLINE].data[0].status, ItemStatus::Available);
+        // Untradable is counted in the total only, like get_inventory does
+        let souvenir_count = &inv[SOUVENIR].count;
+        assert_eq!((souvenir_count.total, souvenir_count.available, souvenir_count.on_hold), (1, 0, 0));
+        assert_eq!(inv[SOUVENIR].data[0].status, ItemStatus::Untradable);
+        assert_eq!(inv[ASIIMOV].count.total, 2);
+        assert_eq!(inv[ASIIMOV].count.available, 0);
+        assert_eq!(inv[ASIIMOV].count.on_hold, 2);
+        assert_eq!(report.missing_items.len(), 1);
+        assert_eq!(report.missing_items[0].1.asset_id, "104");
+        assert_eq!(report.tickets.len(), 1);
+        assert_eq!(report.tickets[0].asset_id, "102");
+        assert_eq!(report.tickets[0].change, ItemStatusChanges::TradeLockDone);
+    }
+
+    #[test]
+    fn trade_url_gives_the_partner_and_token() {
+        let url = "https://steamcommunity.com/tradeoffer/new/?token=Ab3dE6gH&partner=12345678";
+        assert_eq!(parse_trade_url(url), Ok(("12345678".to_string(), "Ab3dE6gH".to_string())));
+
+        for url in [
+            "ftp://steamcommunity.com/tradeoffer/new/?partner=12345678&token=Ab3dE6gH",
+            "https://steamcommunity.com/tradeoffer/new/?partner=12345678",
+            "https://steamcommunity.com/tradeoffer/new/?partner=abc&token=Ab3dE6gH",
+            "https://steamcommunity.com/tradeoffer/new/?partner=12345678&token=short",
+        ] {
+            let err = parse_trade_url(url).unwrap_err();
+            assert!(err.starts_with(&format!("steam.rs | parse_trade_url(url={}) |", url)), "{}", err);
+        }
+    }
+
+    #[test]
+    fn created_offer_bodies_give_the_offer_id() {
+        assert_eq!(parse_send_trade_offer_response(r#"{"tradeofferid":"6523478190"}"#), Ok(6523478190));
+        // Waiting for the mobile confirmation is still a created offer
+        let pending = r#"{"tradeofferid":"6523478191","needs_mobile_confirmation":true,"needs_email_confirmation":false,"email_domain":"gmail.com"}"#;
+        assert_eq!(parse_send_trade_offer_response(pending), Ok(6523478191));
+        assert_eq!(parse_send_trade_offer_response(r#"{"tradeofferid":6523478192}"#), Ok(6523478192));
+    }
+
+    #[test]
+    fn refused_offer_bodies_give_the_typed_errors() {
+        let cases = [
+            (
+                "There was an error sending your trade offer.  Please try again later. (15)<br><br>This user's inventory is private.",
+                SendTradeOfferError::InventoryPrivate,
+            ),
+            (
+                "You are not allowed to trade with this user because they have a trade ban.",
+                SendTradeOfferError::TradeBan("You are not allowed to trade with this user because they have a trade ban.".to_string()),
+            ),
+            (
+                "The items in this trade will be held for 15 days because you have not had the Steam Guard Mobile Authenticator enabled.",
+                SendTradeOfferError::Escrow("The items in this trade will be held for 15 days because you have not had the Steam Guard Mobile Authenticator enabled.".to_string()),
+            ),
+            (
+                "There was an error sending your trade offer.  Please try again later. (26)",
+                SendTradeOfferError::Rejected("There was an error sending your trade offer.  Please try again later. (26)".to_string()),
+            ),
+        ];
+
+        for (err_str, err) in cases {
+            let body = serde_json::json!({ "strError": err_str }).to_string();
+            assert_eq!(parse_send_trade_offer_response(&body), Err(err), "{}", err_str);
+        }
+    }
+
+    #[test]
+    fn unexpected_offer_bodies_are_failures() {
+        for body in ["<html>Steam is down</html>", "{}", r#"{"tradeofferid":"abc"}"#, "null"] {
+            assert!(matches!(parse_send_trade_offer_response(body), Err(SendTradeOfferError::Failed(_))), "{}", body);
+        }
+    }
+
+    // A GetTradeOffer body, Steam leaves out the side of the offer that has no items
+    fn offer_body(give: &[&str], receive: &[&str]) -> Value {
+        let assets = |ids: &[&str]| -> Vec<Value> {
+            ids.iter()
+                .map(|id| json!({"appid": 730, "contextid": "2", "assetid": id, "classid": "1", "instanceid": "0", "amount": "1"}))
+                .collect()
+        };
+        let mut offer = json!({"tradeofferid": "6523478190", "accountid_other": 124123917, "trade_offer_state": 2, "is_our_offer": false});
+        if !give.is_empty() {
+            offer["items_to_give"] = json!(assets(give));
+        }
+        if !receive.is_empty() {
+            offer["items_to_receive"] = json!(assets(receive));
+        }
+        json!({"response": {"offer": offer}})
+    }
+
+    fn parse_offer(body: &Value) -> SteamTradeOfferData {
+        serde_json::from_value(body["response"]["offer"].clone()).unwrap()
+    }
+
+    #[test]
+    fn an_offer_that_only_gives_us_items_is_safe() {
+        let offer = parse_offer(&offer_body(&[], &["201"]));
+
+        assert!(offer.give_asset_ids().is_empty());
+        assert!(unexpected_items_to_give(&offer.give_asset_ids(), &[]).is_empty());
+    }
+
+    #[test]
+    fn an_offer_that_gives_the_sold_items_is_safe() {
+        let offer = parse_offer(&offer_body(&["101", "102"], &[]));
+        let sold = vec!["101".to_string(), "102".to_string(), "103".to_string()];
+
+        assert!(unexpected_items_to_give(&offer.give_asset_ids(), &sold).is_empty());
+    }
+
+    #[test]
+    fn an_offer_that_takes_more_than_agreed_is_refused() {
+        // A hijacked withdrawal that also takes one of our items
+        let offer = parse_offer(&offer_body(&["104"], &["201"]));
+        assert_eq!(unexpected_items_to_give(&offer.give_asset_ids(), &[]), vec!["104".to_string()]);
+
+        // A sale offer with an extra item
+        let offer = parse_offer(&offer_body(&["101", "105"], &[]));
+        assert_eq!(unexpected_items_to_give(&offer.give_asset_ids(), &["101".to_string()]), vec!["105".to_string()]);
+    }
+
+    // Serves the pages by the asset id they start from, like the inventory endpoint
+    fn serve_pages(pages: Vec<Value>) -> HashMap<String, Value> {
+        let mut start = "".to_string();
+        let mut served = HashMap::new();
+        for page in pages {
+            let next = page["last_assetid"].as_str().unwrap_or("").to_string();
+            served.insert(start, page);
+            start = next;
+        }
+        served
+    }
+
+    fn run_pipeline(served: &HashMap<String, Value>, rate_limit_first: bool) -> InventoryRequestReturn {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+        let mut rate_limited = !rate_limit_first;
+
+        runtime
+            .block_on(pipeline_inventory_pages(owner(), |last_asset_id: String| {
+                let page = if !rate_limited {
+                    rate_limited = true;
+                    Err(InventoryFetchError::RateLimited { retry_after_secs: 0 })
+                } else {
+                    served.get(&last_asset_id).cloned().ok_or(InventoryFetchError::EndOfInventory)
+                };
+                async move { page }
+            }))
+            .unwrap()
+    }
+
+    #[test]
+    fn pipeline_matches_the_sequential_join() {
+        let (first, second) = fixture_pages();
+        let served = serve_pages(vec![first, second]);
+
+        let expected = format!("{:?}", fixture_inventory());
+        assert_eq!(format!("{:?}", run_pipeline(&served, false)), expected);
+        // A rate limit on the first page is waited out without changing the result
+        assert_eq!(format!("{:?}", run_pipeline(&served, true)), expected);
+    }
+
+    // Pages of per_page assets with a class per page, chained with more_items and last_assetid
+    fn large_pages(page_count: usize, per_page: usize) -> Vec<Value> {
+        (0..page_count)
+            .map(|page| {
+                let class_id = (page + 1).to_string();
+                let assets: Vec<Value> = (0..per_page)
+                    .map(|i| json!({
+                        "amount": "1", "appid": 730, "assetid": (page * per_page + i + 1000).to_string(),
+                        "classid": class_id, "contextid": "2", "instanceid": "0"
+                    }))
+                    .collect();
+                let last_asset_id = assets.last().unwrap()["assetid"].clone();
+                let mut json = json!({
+                    "assets": assets,
+                    "descriptions": [{"classid": class_id, "instanceid": "0", "market_name": REDLINE, "tradable": 1}],
+                    "total_inventory_count": page_count * per_page,
+                    "success": 1,
+                });
+                if page + 1 < page_count {
+                    json["more_items"] = json!(1);
+                    json["last_assetid"] = last_asset_id;
+                }
+                json
+            })
+            .collect()
+    }
+
+    // The before/after timing of the pipelined fetch with a simulated request latency, it only prints
+    // the timings so it runs with `cargo test inventory_pipeline_timing -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn inventory_pipeline_timing() {
+        let latency = Duration::from_millis(150);
+        let served = serve_pages(large_pages(10, 2000));
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+
+        // Before: every page is requested only after the last one was parsed
+        let start = std::time::Instant::now();
+        let sequential = runtime.block_on(async {
+            let mut temp_data = InventoryRequestReturn::new();
+            let mut seen_asset_ids = HashSet::new();
+            let mut last_asset_id = Some("".to_string());
+            while let Some(page) = last_asset_id.and_then(|asset_id| served.get(&asset_id)) {
+                sleep(latency).await;
+                temp_data.append_page(parse_inventory_page(owner(), page).unwrap(), &mut seen_asset_ids);
+                last_asset_id = page["last_assetid"].as_str().map(|asset_id| asset_id.to_string());
+            }
+            temp_data
+        });
+        let sequential_elapsed = start.elapsed();
+
+        // After: the next page is requested while the last one is parsed
+        let start = std::time::Instant::now();
+        let pipelined = runtime
+            .block_on(pipeline_inventory_pages(owner(), |last_asset_id: String| {
+                let page = served.get(&last_asset_id).cloned().ok_or(InventoryFetchError::EndOfInventory);
+                async move {
+                    sleep(latency).await;
+                    page
+                }
+            }))
+            .unwrap();
+        let pipelined_elapsed = start.elapsed();
+
+        println!("sequential: {:?}, pipelined: {:?}", sequential_elapsed, pipelined_elapsed);
+        assert_eq!(format!("{:?}", pipelined), format!("{:?}", sequential));
+        assert_eq!(pipelined.id_data.len(), 20000);
     }
-    
-    Err("steam.rs | get_webapi() | The cookie is not valid to get the token.".to_string())
 }
@@ -0,0 +1,323 @@
+// waxpeer.rs
+//
+// This module provides logic for WaxPeer marketplace operations,
+// building on top of the API layer to handle price discovery, listing items for sale,
+// price updates and sending the Steam trades of sold items.
+
+use super::{api::waxpeer_api, steam};
+use crate::{
+    circuit_breaker::{self, EndpointGroup}, config, data, log_functions,
+    price_functions::{from_thousandths, to_thousandths},
+    structs::{ItemData, ItemStatusChangeTicket, ItemStatusChanges, Market, Price, SteamId},
+};
+use chrono::Local;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Structure for parsing listing data from WaxPeer
+#[allow(dead_code)]
+#[derive(Deserialize, Clone, Debug)]
+struct ItemEntryResult {
+    item_id: String,
+    name: String,
+    price: i64,
+}
+
+/// Structure for parsing a sold item in a trade to send
+#[allow(dead_code)]
+#[derive(Deserialize, Clone, Debug)]
+struct TradeItemResult {
+    item_id: String,
+    name: String,
+    price: i64,
+}
+
+/// Structure for parsing a trade that has to be sent to the buyer
+#[allow(dead_code)]
+#[derive(Deserialize, Clone, Debug)]
+struct TradeToSendResult {
+    id: i64,
+    tradelink: String,
+    trade_message: Option<String>,
+    items: Vec<TradeItemResult>,
+}
+
+/// Retrieves the current WaxPeer price of a CS item
+///
+/// - Finds the lowest listing with an exact name match
+/// - Calculates buy/sell prices with marketplace commissions
+/// - WaxPeer listings have no trade hold so every hold bucket has the same price
+pub async fn get_item_price(market_hash_name: String) -> Result<Price, String> {
+    circuit_breaker::check(Market::WaxPeer, EndpointGroup::Prices)?;
+
+    let item_data = search_listings(&market_hash_name)
+        .await
+        .map_err(|e| format!("waxpeer.rs | get_item_price(market_hash_name={}) | {}", market_hash_name, e))?;
+
+    let lowest = item_data
+        .iter()
+        .filter(|item| item.name == market_hash_name)
+        .map(|item| item.price)
+        .min()
+        .ok_or(format!(
+            "waxpeer.rs | get_item_price(market_hash_name={}) | Error occured while the returned item price data vector is empty.",
+            market_hash_name
+        ))?;
+
+    let comms = data::get_market_commisions(Market::WaxPeer, "")
+        .map_err(|e| format!(
+            "waxpeer.rs | get_item_price(market_hash_name={}) | Error occured when trying to get the commisions of the market. E: {:?}",
+            market_hash_name, e
+        ))?;
+
+    let price = from_thousandths(lowest);
+    let price_buy_w_comm: f32 = ((price / ((100 - comms.0) as f32 / 100.0)) * 100.0).ceil() / 100.0;
+    let price_sell_w_comm: f32 = ((price * (1.0 - ((comms.1 + comms.2) as f32 / 100.0))) * 100.0).ceil() / 100.0;
+
+    Ok(Price {
+        market: Market::WaxPeer,
+        commision: comms.1 + comms.2,
+        price_buy: price,
+        price_buy_trade: (price, price, price),
+        price_buy_w_comm,
+        price_sell_w_comm,
+        price_buy_trade_w_comm: (price_buy_w_comm, price_buy_w_comm, price_buy_w_comm),
+        price_sell: price,
+        sale_stats: None,
+        fetched_at_unix: Local::now().timestamp(),
+        volume_24h: None,
+        currency: config::get_bot_config().get_market_currency(&Market::WaxPeer),
+    })
+}
+
+/// Retrieves the WaxPeer listings of a CS item as (asset id, price) pairs
+///
+/// - Only the listings with an exact name match are returned, the listings of the account included
+pub async fn get_listing_prices(market_hash_name: &str) -> Result<Vec<(String, f32)>, String> {
+    let item_data = search_listings(market_hash_name)
+        .await
+        .map_err(|e| format!("waxpeer.rs | get_listing_prices(market_hash_name={}) | {}", market_hash_name, e))?;
+
+    Ok(item_data
+        .into_iter()
+        .filter(|item| item.name == market_hash_name)
+        .map(|item| (item.item_id, from_thousandths(item.price)))
+        .collect())
+}
+
+/// Sends the search request of the item and parses the listings
+async fn search_listings(market_hash_name: &str) -> Result<Vec<ItemEntryResult>, String> {
+    let res = waxpeer_api::get_item_price(market_hash_name)
+        .await
+        .map_err(|e| format!("Error occured when sending the api request. E: {:?}", e))?;
+
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!("Error occured when parsing the api request. E: {:?}", e))?;
+
+    serde_json::from_value(parsed_data["items"].clone())
+        .map_err(|e| format!("Error occured when parsing the api request to data structre. E: {:?}", e))
+}
+
+/// Lists an item from the Steam inventory for sale on WaxPeer
+///
+/// - Returns a `SellOfferCreated(Market::WaxPeer)` ticket on success
+/// - In dry run mode nothing is listed and the same ticket is returned
+pub async fn list_item(item: &ItemData, price: f32) -> Result<ItemStatusChangeTicket, String> {
+    // Simulate the listing so the strategy can be evaluated without selling
+    if config::get_bot_config().dry_run {
+        log_functions::log_dry_run(&format!("Would list {} at {} on {:?}", item.asset_id, price, Market::WaxPeer));
+        return Ok(listed_ticket(item));
+    }
+
+    let res = waxpeer_api::list_item(&item.asset_id, to_thousandths(price))
+        .await
+        .map_err(|e| format!(
+            "waxpeer.rs | list_item(asset_id={}, price={}) | Error occured when sending the api request. E: {:?}",
+            item.asset_id, price, e
+        ))?;
+
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!(
+            "waxpeer.rs | list_item(asset_id={}, price={}) | Error occured when parsing the api request. E: {:?}",
+            item.asset_id, price, e
+        ))?;
+
+    if !is_in_list(&parsed_data["listed"], &item.asset_id) {
+        return Err(format!(
+            "waxpeer.rs | list_item(asset_id={}, price={}) | Error occured, the item was not listed. Parsed data: {:?}",
+            item.asset_id, price, parsed_data
+        ));
+    }
+
+    Ok(listed_ticket(item))
+}
+
+/// Builds the ticket of an item listed on WaxPeer
+fn listed_ticket(item: &ItemData) -> ItemStatusChangeTicket {
+    ItemStatusChangeTicket {
+        market_ids: item.market_ids.clone(),
+        asset_id: item.asset_id.clone(),
+        change: ItemStatusChanges::SellOfferCreated(Market::WaxPeer),
+    }
+}
+
+/// Updates the price of an item listed on WaxPeer
+pub async fn update_price(asset_id: &str, price: f32) -> Result<(), String> {
+    let res = waxpeer_api::edit_price(asset_id, to_thousandths(price))
+        .await
+        .map_err(|e| format!(
+            "waxpeer.rs | update_price(asset_id={}, price={}) | Error occured when sending the api request. E: {:?}",
+            asset_id, price, e
+        ))?;
+
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!(
+            "waxpeer.rs | update_price(asset_id={}, price={}) | Error occured when parsing the api request. E: {:?}",
+            asset_id, price, e
+        ))?;
+
+    if !is_in_list(&parsed_data["updated"], asset_id) {
+        return Err(format!(
+            "waxpeer.rs | update_price(asset_id={}, price={}) | Error occured, the price was not updated. Parsed data: {:?}",
+            asset_id, price, parsed_data
+        ));
+    }
+
+    Ok(())
+}
+
+/// Updates the prices of several items listed on WaxPeer in one request
+///
+/// - Returns the asset ids whose price was updated, the rest keep their old price
+pub async fn update_prices(items: &[(String, f32)]) -> Result<Vec<String>, String> {
+    let prices: Vec<(String, i64)> = items
+        .iter()
+        .map(|(asset_id, price)| (asset_id.clone(), to_thousandths(*price)))
+        .collect();
+
+    let res = waxpeer_api::edit_prices(&prices)
+        .await
+        .map_err(|e| format!(
+            "waxpeer.rs | update_prices(items={}) | Error occured when sending the api request. E: {:?}",
+            items.len(), e
+        ))?;
+
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!(
+            "waxpeer.rs | update_prices(items={}) | Error occured when parsing the api request. E: {:?}",
+            items.len(), e
+        ))?;
+
+    Ok(items
+        .iter()
+        .filter(|(asset_id, _)| is_in_list(&parsed_data["updated"], asset_id))
+        .map(|(asset_id, _)| asset_id.clone())
+        .collect())
+}
+
+/// Removes an item listed on WaxPeer from sale
+pub async fn remove_listing(asset_id: &str) -> Result<(), String> {
+    let res = waxpeer_api::remove_item(asset_id)
+        .await
+        .map_err(|e| format!(
+            "waxpeer.rs | remove_listing(asset_id={}) | Error occured when sending the api request. E: {:?}",
+            asset_id, e
+        ))?;
+
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!(
+            "waxpeer.rs | remove_listing(asset_id={}) | Error occured when parsing the api request. E: {:?}",
+            asset_id, e
+        ))?;
+
+    if parsed_data["success"] != Value::Bool(true) {
+        return Err(format!(
+            "waxpeer.rs | remove_listing(asset_id={}) | Error occured, the listing was not removed. Parsed data: {:?}",
+            asset_id, parsed_data
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks the sold items and sends their Steam trades to the buyers
+///
+/// - Sends a Steam trade offer through the buyer's trade link for every sold trade
+/// - Returns a `SellTradeSent` ticket for every item whose trade was sent
+/// - Returns a `SellOfferBought` ticket for the items whose trade could not be sent yet
+pub async fn check_sell_operations() -> Result<Vec<ItemStatusChangeTicket>, String> {
+    let mut tickets_vec: Vec<ItemStatusChangeTicket> = Vec::new();
+
+    let res = waxpeer_api::get_trades_to_send()
+        .await
+        .map_err(|e| format!(
+            "waxpeer.rs | check_sell_operations() | Error occured when sending the api request. E: {:?}",
+            e
+        ))?;
+
+    let parsed_data: serde_json::Value = res.json()
+        .await
+        .map_err(|e| format!(
+            "waxpeer.rs | check_sell_operations() | Error occured when parsing the api request. E: {:?}",
+            e
+        ))?;
+
+    let trades_data: Vec<TradeToSendResult> = serde_json::from_value(parsed_data["trades"].clone())
+        .map_err(|e| format!(
+            "waxpeer.rs | check_sell_operations() | Error occured when parsing the api request to data structre. E: {:?}",
+            e
+        ))?;
+
+    for trade in trades_data {
+        let sent = send_trade(&trade).await;
+
+        for item in trade.items.iter() {
+            let change = match &sent {
+                Ok(trade_offer_id) => ItemStatusChanges::SellTradeSent(Market::WaxPeer, *trade_offer_id),
+                Err(_) => ItemStatusChanges::SellOfferBought(Market::WaxPeer),
+            };
+            tickets_vec.push(ItemStatusChangeTicket::new(item.item_id.clone(), change));
+        }
+
+        if let Err(err_str) = sent {
+            log_functions::log_err(&format!("waxpeer.rs | check_sell_operations() | Error occured when sending the trade. E: {:?}", err_str));
+        }
+    }
+
+    Ok(tickets_vec)
+}
+
+/// Sends the Steam trade offer of a sold WaxPeer trade, returns the trade offer id
+async fn send_trade(trade: &TradeToSendResult) -> Result<i64, String> {
+    let (partner, partner_token) = SteamId::from_trade_url(&trade.tradelink)
+        .map_err(|e| format!("waxpeer.rs | send_trade(id={}) | Error occured when parsing the trade url. E: {}", trade.id, e))?;
+
+    let assets: Vec<String> = trade
+        .items
+        .iter()
+        .map(|item| format!(r#"{{"appid":730,"contextid":"2","amount":1,"assetid":"{}"}}"#, item.item_id))
+        .collect();
+    let items = format!("[{}]", assets.join(","));
+    let message = trade.trade_message.clone().unwrap_or("".to_string());
+
+    steam::send_trade_offer(partner, &partner_token, &message, &items)
+        .await
+        .map_err(|e| format!("waxpeer.rs | send_trade(id={}) | Error occured when sending the trade offer. E: {}", trade.id, e))
+}
+
+/// Checks if the asset id is in a WaxPeer result list
+fn is_in_list(list: &Value, asset_id: &str) -> bool {
+    if let Value::Array(entries) = list {
+        return entries.iter().any(|entry| match &entry["item_id"] {
+            Value::String(id) => id == asset_id,
+            Value::Number(id) => id.to_string() == asset_id,
+            _ => false,
+        });
+    }
+    false
+}
@@ -0,0 +1,236 @@
+// circuit_breaker.rs
+//
+// This module keeps a circuit breaker per endpoint group of every market, so a market that is down
+// or in maintenance fails fast instead of burning through the proxies and the retries for the whole window.
+
+use crate::config;
+use crate::log_functions;
+use crate::metrics;
+use crate::structs::Market;
+use reqwest::{header::RETRY_AFTER, StatusCode};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The circuit of every endpoint group that had a failure, a group without an entry is closed
+static CIRCUITS: Mutex<Option<HashMap<(Market, EndpointGroup), Circuit>>> = Mutex::new(None);
+
+/// How long a probe can run before another one is let through
+/// A probe whose result is never recorded (a dry run, an early return, a cancelled future) would block the circuit otherwise
+const PROBE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The endpoints of a market that fail together, e.g. the pricing api can be up while the trading api is in maintenance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EndpointGroup {
+    /// The prices, the listings and the sale stats
+    Prices,
+    /// The buys, the listings of the own items, the withdrawals and the trade offers
+    Trading,
+    /// The balance and the inventory
+    Account,
+}
+
+/// The state of the circuit of a market
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// The requests go out, the consecutive failures are counted
+    Closed,
+    /// The requests fail fast until the cooldown is over
+    Open,
+    /// The cooldown is over, one probe request decides if the circuit closes or opens again
+    HalfOpen,
+}
+
+/// The circuit of a market
+#[derive(Debug, Clone)]
+struct Circuit {
+    state: CircuitState,
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    probe_started: Option<Instant>,
+}
+
+impl Default for Circuit {
+    fn default() -> Self {
+        Circuit {
+            state: CircuitState::Closed,
+            consecutive_failures: 0,
+            open_until: None,
+            probe_started: None,
+        }
+    }
+}
+
+/// The error of a call to a market whose circuit is open, no request was sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketUnavailable {
+    pub market: Market,
+    pub group: EndpointGroup,
+    /// How long until the next probe is let through, 0 while a probe is running
+    pub retry_in_secs: u64,
+}
+
+impl std::fmt::Display for MarketUnavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} {:?} is unavailable, its circuit is open for {} more seconds.", self.market, self.group, self.retry_in_secs)
+    }
+}
+
+impl From<MarketUnavailable> for String {
+    fn from(err: MarketUnavailable) -> Self {
+        err.to_string()
+    }
+}
+
+/// Checks if a request to the endpoint group of the market can be sent
+///
+/// - A closed circuit lets every request through
+/// - An open circuit whose cooldown is over becomes half open and lets this request through as the probe
+/// - A half open circuit lets another probe through if the last one didn't record its result within PROBE_TIMEOUT
+/// - Every other request of an open or half open circuit gets `MarketUnavailable`
+pub fn check(market: Market, group: EndpointGroup) -> Result<(), MarketUnavailable> {
+    let mut circuits = CIRCUITS.lock().unwrap();
+    let circuit = match circuits.get_or_insert_with(HashMap::new).get_mut(&(market.clone(), group)) {
+        Some(circuit) => circuit,
+        None => return Ok(()),
+    };
+    let now = Instant::now();
+
+    match circuit.state {
+        CircuitState::Closed => Ok(()),
+        CircuitState::Open if circuit.open_until.is_some_and(|until| until <= now) => {
+            circuit.state = CircuitState::HalfOpen;
+            circuit.probe_started = Some(now);
+            log_functions::log_structured("Info", &format!("The circuit of {:?} {:?} is half open, sending a probe request.", market, group));
+            Ok(())
+        }
+        CircuitState::HalfOpen if circuit.probe_started.is_none_or(|started| now.duration_since(started) >= PROBE_TIMEOUT) => {
+            circuit.probe_started = Some(now);
+            Ok(())
+        }
+        _ => Err(MarketUnavailable {
+            market,
+            group,
+            retry_in_secs: circuit.open_until.map(|until| until.saturating_duration_since(now).as_secs()).unwrap_or(0),
+        }),
+    }
+}
+
+/// Checks if the endpoint group of the market can be used right now without letting a probe through
+/// Used by the scans to skip the markets whose pricing circuit is open
+pub fn is_available(market: &Market, group: EndpointGroup) -> bool {
+    state(market, group) == CircuitState::Closed
+}
+
+/// Returns the state of the circuit of the endpoint group of the market
+pub fn state(market: &Market, group: EndpointGroup) -> CircuitState {
+    let circuits = CIRCUITS.lock().unwrap();
+    circuits
+        .as_ref()
+        .and_then(|circuits| circuits.get(&(market.clone(), group)))
+        .map(|circuit| circuit.state)
+        .unwrap_or(CircuitState::Closed)
+}
+
+/// Records the result of a request to the endpoint group of the market
+///
+/// - A 503 opens the circuit right away, it is how the markets answer during maintenance
+/// - Request errors (connection errors, timeouts), other 5xx and 429 responses are failures,
+///   failure_threshold of them in a row open the circuit
+/// - The cooldown is the Retry-After of the response if it has one, circuit_cooldown_secs of the config if not
+/// - Other 4xx responses say nothing about the health of the market, they neither close nor open the circuit
+///   but a probe that got one lets the next request probe again
+/// - A 2xx or 3xx response closes the circuit
+pub fn record<E>(market: Market, group: EndpointGroup, result: &Result<reqwest::Response, E>) {
+    match result {
+        Ok(response) if response.status() == StatusCode::SERVICE_UNAVAILABLE => open(market, group, retry_after(response), "503 Service Unavailable"),
+        Ok(response) if response.status().is_server_error() || response.status() == StatusCode::TOO_MANY_REQUESTS => {
+            failure(market, group, retry_after(response), &format!("status {}", response.status()))
+        }
+        Ok(response) if response.status().is_client_error() => end_probe(market, group),
+        Ok(_) => success(market, group),
+        Err(_) => failure(market, group, None, "request error"),
+    }
+}
+
+/// Opens the circuit of a market that answered with a maintenance page or message
+/// Used by the handlers that only see the maintenance in the body
+pub fn record_maintenance(market: Market, group: EndpointGroup, retry_after_secs: Option<u64>) {
+    open(market, group, retry_after_secs.map(Duration::from_secs), "maintenance");
+}
+
+/// Checks if a response body says the market is in maintenance
+pub fn is_maintenance_body(body: &str) -> bool {
+    body.to_lowercase().contains("maintenance")
+}
+
+/// Reads the Retry-After of a response in seconds
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+/// Counts a failure and opens the circuit when the threshold is reached or the probe failed
+fn failure(market: Market, group: EndpointGroup, retry_after: Option<Duration>, reason: &str) {
+    let threshold = config::get_bot_config().circuit_failure_threshold.max(1);
+    let should_open = {
+        let mut circuits = CIRCUITS.lock().unwrap();
+        let circuit = circuits.get_or_insert_with(HashMap::new).entry((market.clone(), group)).or_default();
+        circuit.consecutive_failures += 1;
+        circuit.state == CircuitState::HalfOpen || circuit.consecutive_failures >= threshold
+    };
+
+    if should_open {
+        open(market, group, retry_after, reason);
+    }
+}
+
+/// Opens the circuit for the cooldown
+fn open(market: Market, group: EndpointGroup, retry_after: Option<Duration>, reason: &str) {
+    let cooldown = retry_after.unwrap_or(Duration::from_secs(config::get_bot_config().circuit_cooldown_secs));
+    let was_open = {
+        let mut circuits = CIRCUITS.lock().unwrap();
+        let circuit = circuits.get_or_insert_with(HashMap::new).entry((market.clone(), group)).or_default();
+        let was_open = circuit.state == CircuitState::Open;
+        circuit.state = CircuitState::Open;
+        circuit.open_until = Some(Instant::now() + cooldown);
+        circuit.probe_started = None;
+        was_open
+    };
+
+    metrics::set_circuit_open(&market, group, true);
+    if !was_open {
+        log_functions::log_structured(
+            "Warning",
+            &format!("Opened the circuit of {:?} {:?} for {:?}. Reason: {}", market, group, cooldown, reason),
+        );
+    }
+}
+
+/// Lets the next request probe again, the last probe got an answer that doesn't decide the state
+fn end_probe(market: Market, group: EndpointGroup) {
+    let mut circuits = CIRCUITS.lock().unwrap();
+    if let Some(circuit) = circuits.get_or_insert_with(HashMap::new).get_mut(&(market, group)) {
+        circuit.probe_started = None;
+    }
+}
+
+/// Closes the circuit after a successful request
+fn success(market: Market, group: EndpointGroup) {
+    let was_closed = {
+        let mut circuits = CIRCUITS.lock().unwrap();
+        let circuit = circuits.get_or_insert_with(HashMap::new).entry((market.clone(), group)).or_default();
+        let was_closed = circuit.state == CircuitState::Closed;
+        *circuit = Circuit::default();
+        was_closed
+    };
+
+    if !was_closed {
+        metrics::set_circuit_open(&market, group, false);
+        log_functions::log_structured("Info", &format!("Closed the circuit of {:?} {:?}, the probe request succeeded.", market, group));
+    }
+}
@@ -0,0 +1,233 @@
+// key_pool.rs
+//
+// This module spreads the requests of a market over several api keys. Every key counts its
+// requests and errors in a sliding window, the least loaded healthy key is handed out,
+// a rate limited key rests until its Retry-After and a key that fails authentication is quarantined.
+
+use crate::config;
+use crate::log_functions;
+use crate::notifications;
+use reqwest::{header::RETRY_AFTER, StatusCode};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The pool of the BitSkins scrape keys, built from the config on the first use
+static BITSKINS_SCRAPE_KEYS: Mutex<Option<KeyPool>> = Mutex::new(None);
+
+/// The window the requests and the errors of a key are counted in
+const KEY_WINDOW: Duration = Duration::from_secs(60);
+
+/// How long a key rests after a 429 without a Retry-After
+const RATE_LIMIT_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// What happened to a request sent with a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyOutcome {
+    Success,
+    /// 429, the key rests for the Retry-After or RATE_LIMIT_COOLDOWN
+    RateLimited(Option<Duration>),
+    /// 401 or 403, the key was revoked and is quarantined until the pool is rebuilt
+    AuthError,
+    /// A request error or any other failed status, it only counts against the key
+    Error,
+}
+
+/// The state of a key that can be shown, the key itself is cut to its last 4 characters
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyStatus {
+    pub key_suffix: String,
+    pub requests_in_window: usize,
+    pub errors_in_window: usize,
+    /// How long until the rate limited key is handed out again
+    pub rate_limited_for_secs: Option<u64>,
+    pub quarantined: bool,
+}
+
+/// The recent requests of a key
+#[derive(Debug, Clone)]
+struct KeyState {
+    key: String,
+    requests: VecDeque<Instant>,
+    errors: VecDeque<Instant>,
+    rate_limited_until: Option<Instant>,
+    quarantined: bool,
+}
+
+impl KeyState {
+    fn new(key: String) -> Self {
+        KeyState {
+            key,
+            requests: VecDeque::new(),
+            errors: VecDeque::new(),
+            rate_limited_until: None,
+            quarantined: false,
+        }
+    }
+
+    /// Drops the requests and the errors that left the window
+    fn prune(&mut self, now: Instant, window: Duration) {
+        while self.requests.front().is_some_and(|t| now.duration_since(*t) > window) {
+            self.requests.pop_front();
+        }
+        while self.errors.front().is_some_and(|t| now.duration_since(*t) > window) {
+            self.errors.pop_front();
+        }
+    }
+
+    fn is_healthy(&self, now: Instant) -> bool {
+        !self.quarantined && self.rate_limited_until.is_none_or(|until| until <= now)
+    }
+}
+
+/// The keys of a market with their recent requests
+#[derive(Debug, Clone)]
+pub struct KeyPool {
+    name: String,
+    keys: Vec<KeyState>,
+    window: Duration,
+}
+
+impl KeyPool {
+    /// Builds the pool, the empty and the duplicated keys are left out
+    pub fn new(name: &str, keys: &[String], window: Duration) -> Self {
+        let mut states: Vec<KeyState> = Vec::new();
+        for key in keys.iter().filter(|key| !key.trim().is_empty()) {
+            if !states.iter().any(|state| state.key == *key) {
+                states.push(KeyState::new(key.clone()));
+            }
+        }
+
+        KeyPool {
+            name: name.to_string(),
+            keys: states,
+            window,
+        }
+    }
+
+    /// Hands out the healthy key with the least requests in the window, the errors break a tie
+    /// The request is counted right away, so parallel callers get different keys
+    /// None if every key is quarantined or rate limited
+    pub fn acquire(&mut self) -> Option<String> {
+        let now = Instant::now();
+        let window = self.window;
+        for state in self.keys.iter_mut() {
+            state.prune(now, window);
+        }
+
+        let state = self
+            .keys
+            .iter_mut()
+            .filter(|state| state.is_healthy(now))
+            .min_by_key(|state| (state.requests.len(), state.errors.len()))?;
+        state.requests.push_back(now);
+        Some(state.key.clone())
+    }
+
+    /// Records what happened to a request sent with the key
+    pub fn record(&mut self, key: &str, outcome: KeyOutcome) {
+        let now = Instant::now();
+        let name = self.name.clone();
+        let state = match self.keys.iter_mut().find(|state| state.key == key) {
+            Some(state) => state,
+            None => return,
+        };
+
+        match outcome {
+            KeyOutcome::Success => {}
+            KeyOutcome::RateLimited(retry_after) => {
+                state.errors.push_back(now);
+                state.rate_limited_until = Some(now + retry_after.unwrap_or(RATE_LIMIT_COOLDOWN));
+            }
+            KeyOutcome::AuthError => {
+                state.errors.push_back(now);
+                if !state.quarantined {
+                    state.quarantined = true;
+                    let msg = format!("The {} key ...{} failed authentication, it is quarantined.", name, key_suffix(key));
+                    log_functions::log_structured("Warning", &msg);
+                    notifications::notify(format!("🔑 {}", msg));
+                }
+            }
+            KeyOutcome::Error => state.errors.push_back(now),
+        }
+    }
+
+    /// Records the response of a request sent with the key
+    pub fn record_response<E>(&mut self, key: &str, result: &Result<reqwest::Response, E>) {
+        self.record(key, outcome_of(result));
+    }
+
+    /// Returns the state of every key
+    pub fn status(&mut self) -> Vec<KeyStatus> {
+        let now = Instant::now();
+        let window = self.window;
+
+        self.keys
+            .iter_mut()
+            .map(|state| {
+                state.prune(now, window);
+                KeyStatus {
+                    key_suffix: key_suffix(&state.key),
+                    requests_in_window: state.requests.len(),
+                    errors_in_window: state.errors.len(),
+                    rate_limited_for_secs: state
+                        .rate_limited_until
+                        .filter(|until| *until > now)
+                        .map(|until| until.saturating_duration_since(now).as_secs()),
+                    quarantined: state.quarantined,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Turns the response of a request into the outcome of its key
+pub fn outcome_of<E>(result: &Result<reqwest::Response, E>) -> KeyOutcome {
+    match result {
+        Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => KeyOutcome::RateLimited(
+            response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .map(Duration::from_secs),
+        ),
+        Ok(response) if response.status() == StatusCode::UNAUTHORIZED || response.status() == StatusCode::FORBIDDEN => KeyOutcome::AuthError,
+        Ok(response) if !response.status().is_success() => KeyOutcome::Error,
+        Ok(_) => KeyOutcome::Success,
+        Err(_) => KeyOutcome::Error,
+    }
+}
+
+/// Hands out a BitSkins scrape key of the bitskins_scrape_keys of the config
+pub fn acquire_bitskins_scrape_key() -> Option<String> {
+    let mut pool = BITSKINS_SCRAPE_KEYS.lock().unwrap();
+    pool.get_or_insert_with(bitskins_scrape_pool).acquire()
+}
+
+/// Records the response of a request sent with a BitSkins scrape key
+pub fn record_bitskins_scrape_key<E>(key: &str, result: &Result<reqwest::Response, E>) {
+    let mut pool = BITSKINS_SCRAPE_KEYS.lock().unwrap();
+    pool.get_or_insert_with(bitskins_scrape_pool).record_response(key, result);
+}
+
+/// Returns the state of every BitSkins scrape key
+pub fn bitskins_scrape_key_status() -> Vec<KeyStatus> {
+    let mut pool = BITSKINS_SCRAPE_KEYS.lock().unwrap();
+    pool.get_or_insert_with(bitskins_scrape_pool).status()
+}
+
+/// Rebuilds the BitSkins scrape key pool from the config, the counts and the quarantines are reset
+pub fn reload_bitskins_scrape_keys() {
+    *BITSKINS_SCRAPE_KEYS.lock().unwrap() = Some(bitskins_scrape_pool());
+}
+
+fn bitskins_scrape_pool() -> KeyPool {
+    KeyPool::new("BitSkins scrape", &config::get_bot_config().bitskins_scrape_keys, KEY_WINDOW)
+}
+
+/// The last 4 characters of a key, enough to tell the keys apart in the logs
+fn key_suffix(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    chars[chars.len().saturating_sub(4)..].iter().collect()
+}